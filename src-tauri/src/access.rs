@@ -0,0 +1,241 @@
+//! Library visibility per client.
+//!
+//! A library listed in the config's `library_access` is shown only to the
+//! clients its rule admits: those connecting from one of its IP ranges, or
+//! holding one of its access keys, sent as the `X-Eriri-Access-Key` header or
+//! kept in the cookie a browser gets by entering the key. Libraries without a
+//! rule are visible to everyone, and the Mac itself sees every library.
+//!
+//! The server resolves an `Access` per request and checks it wherever a
+//! library is exposed: catalog rows, book and comic endpoints, and the file
+//! allowlist, so a hidden library cannot be reached by guessing a path.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use tauri::{AppHandle, Runtime};
+
+use crate::auth::Caller;
+use crate::config;
+use crate::library::Catalog;
+use crate::models::LibraryAccess;
+
+/// Header carrying an access key.
+pub const HEADER: &str = "x-eriri-access-key";
+
+/// Cookie holding a browser's access key.
+pub const COOKIE: &str = "eriri_access";
+
+/// Cookie lifetime, matching the device token's.
+const COOKIE_MAX_AGE_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// The libraries hidden from one request, set by the auth middleware.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    /// Ids of the hidden libraries, with their canonical roots when they
+    /// still exist on disk.
+    hidden: Vec<(String, Option<PathBuf>)>,
+}
+
+impl Access {
+    /// Whether nothing is hidden, letting callers skip per-item checks.
+    pub fn sees_everything(&self) -> bool {
+        self.hidden.is_empty()
+    }
+
+    /// Ids of the hidden libraries.
+    pub fn hidden_ids(&self) -> impl Iterator<Item = &str> {
+        self.hidden.iter().map(|(id, _)| id.as_str())
+    }
+
+    pub fn sees_library(&self, id: &str) -> bool {
+        !self.hidden.iter().any(|(hidden, _)| hidden == id)
+    }
+
+    /// Whether the comic or book in `library_id` is visible. An item missing
+    /// from the catalog, with no library, is visible only when nothing is
+    /// hidden.
+    pub fn sees_item(&self, library_id: Option<&str>) -> bool {
+        library_id.map_or(self.sees_everything(), |id| self.sees_library(id))
+    }
+
+    /// Whether `path` lies outside every hidden library. The path is resolved
+    /// first, so `..` or a symlink cannot step into one.
+    pub fn sees_path(&self, path: &Path) -> bool {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        !self
+            .hidden
+            .iter()
+            .filter_map(|(_, root)| root.as_ref())
+            .any(|root| path.starts_with(root))
+    }
+
+    /// Drop the rows of hidden libraries.
+    pub fn filter_catalog(&self, catalog: &mut Catalog) {
+        catalog
+            .libraries
+            .retain(|library| self.sees_library(&library.id));
+        catalog
+            .comics
+            .retain(|comic| self.sees_library(&comic.library_id));
+        catalog
+            .authors
+            .retain(|author| self.sees_library(&author.library_id));
+        catalog
+            .books
+            .retain(|book| self.sees_library(&book.library_id));
+    }
+}
+
+#[cfg(test)]
+impl Access {
+    /// Access hiding the libraries `ids`, as if their roots were gone.
+    pub fn hiding(ids: &[&str]) -> Self {
+        Self {
+            hidden: ids.iter().map(|id| (id.to_string(), None)).collect(),
+        }
+    }
+
+    /// Access hiding libraries at the canonical `roots`.
+    pub fn hiding_roots(roots: &[&Path]) -> Self {
+        Self {
+            hidden: roots
+                .iter()
+                .map(|root| (root.display().to_string(), Some(root.to_path_buf())))
+                .collect(),
+        }
+    }
+}
+
+/// What the request from `caller` at `ip` with `headers` may see.
+pub fn resolve<R: Runtime>(
+    app: &AppHandle<R>,
+    caller: &Caller,
+    ip: IpAddr,
+    headers: &HeaderMap,
+) -> Access {
+    if *caller == Caller::Loopback {
+        return Access::default();
+    }
+    let rules = config::get(app).library_access;
+    if rules.is_empty() {
+        return Access::default();
+    }
+    let key = key(headers);
+    let hidden = rules
+        .iter()
+        .filter(|rule| !admits(rule, ip, key))
+        .map(|rule| {
+            let root = crate::library::library_path(app, &rule.library_id)
+                .and_then(|path| Path::new(&path).canonicalize().ok());
+            (rule.library_id.clone(), root)
+        })
+        .collect();
+    Access { hidden }
+}
+
+/// The access key of a request: the header, or else the cookie.
+fn key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| crate::auth::cookie(headers, COOKIE))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+fn admits(rule: &LibraryAccess, ip: IpAddr, key: Option<&str>) -> bool {
+    let in_range = rule.ranges.iter().any(|range| in_range(range, ip));
+    in_range || key.is_some_and(|key| rule.keys.iter().any(|allowed| allowed == key))
+}
+
+/// Whether `ip` is in `range`, a CIDR range or a single address. A range that
+/// does not parse admits no one.
+pub fn in_range(range: &str, ip: IpAddr) -> bool {
+    let range = range.trim();
+    match range.parse::<IpNet>() {
+        Ok(net) => net.contains(&ip),
+        Err(_) => range.parse::<IpAddr>().is_ok_and(|single| single == ip),
+    }
+}
+
+/// Whether some library's rule accepts `key`.
+pub fn is_known_key(app: &AppHandle, key: &str) -> bool {
+    config::get(app)
+        .library_access
+        .iter()
+        .any(|rule| rule.keys.iter().any(|allowed| allowed == key))
+}
+
+/// `Set-Cookie` value storing an access key in the browser.
+pub fn key_cookie(key: &str) -> String {
+    format!("{COOKIE}={key}; Path=/; Max-Age={COOKIE_MAX_AGE_SECS}; HttpOnly; SameSite=Strict")
+}
+
+/// `Set-Cookie` value removing the access key from the browser.
+pub fn cleared_key_cookie() -> String {
+    format!("{COOKIE}=; Path=/; Max-Age=0; HttpOnly; SameSite=Strict")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn rule(ranges: &[&str], keys: &[&str]) -> LibraryAccess {
+        LibraryAccess {
+            library_id: "kids".to_string(),
+            ranges: ranges.iter().map(|range| range.to_string()).collect(),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn rules_admit_clients_by_range_or_key() {
+        let rule = rule(&["192.168.1.0/24", "10.0.0.7", "not a range"], &["secret"]);
+        let ip = |octets: [u8; 4]| IpAddr::from(octets);
+
+        assert!(admits(&rule, ip([192, 168, 1, 30]), None));
+        assert!(admits(&rule, ip([10, 0, 0, 7]), None));
+        assert!(!admits(&rule, ip([10, 0, 0, 8]), None));
+        assert!(!admits(&rule, ip([10, 0, 0, 8]), Some("guess")));
+        assert!(admits(&rule, ip([10, 0, 0, 8]), Some("secret")));
+    }
+
+    #[test]
+    fn hidden_libraries_cover_their_rows_and_every_path_inside_them() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let hidden_root = dir.path().join("hidden");
+        std::fs::create_dir_all(hidden_root.join("Comic")).expect("create hidden comic");
+        std::fs::create_dir_all(dir.path().join("open")).expect("create open library");
+        let access = Access {
+            hidden: vec![(
+                "kids".to_string(),
+                Some(hidden_root.canonicalize().expect("resolve hidden root")),
+            )],
+        };
+
+        assert!(!access.sees_library("kids"));
+        assert!(access.sees_library("family"));
+        assert!(!access.sees_path(&hidden_root.join("Comic")));
+        assert!(!access.sees_path(&dir.path().join("open/../hidden/Comic")));
+        assert!(access.sees_path(&dir.path().join("open")));
+    }
+
+    #[test]
+    fn reads_the_key_from_the_header_or_the_cookie() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(key(&headers), None);
+
+        headers.insert(
+            axum::http::header::COOKIE,
+            HeaderValue::from_static("eriri_access=from-cookie"),
+        );
+        assert_eq!(key(&headers), Some("from-cookie"));
+
+        headers.insert(HEADER, HeaderValue::from_static("from-header"));
+        assert_eq!(key(&headers), Some("from-header"));
+    }
+}
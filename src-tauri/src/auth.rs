@@ -0,0 +1,740 @@
+//! Device pairing and token authentication for the LAN server.
+//!
+//! A new client pairs once with the PIN shown in the tray and receives a
+//! long-lived device token, which browsers keep as an HTTP-only cookie and
+//! other clients send as a bearer token. Only a hash of each token is stored,
+//! in `auth.db`, beside the device's name, user agent, and when and from where
+//! it was first and last seen; revoking (forgetting) a device deletes its row.
+//! A device marked as a guest may only read (see `policy`).
+//! Loopback clients are trusted unless the config sets `loopback_auth`.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, header};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, Runtime};
+use uuid::Uuid;
+
+use crate::config;
+
+/// Cookie holding a browser's device token.
+pub const COOKIE: &str = "eriri_token";
+
+/// How long an address waits after its first wrong PIN; each further wrong
+/// PIN doubles the wait, up to `MAX_PIN_DELAY`.
+const PIN_DELAY: Duration = Duration::from_secs(1);
+const MAX_PIN_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// Wrong PINs from all addresses within `PIN_LOCKOUT` before pairing pauses
+/// for everyone for `PIN_LOCKOUT`, so many addresses cannot share the work of
+/// guessing.
+const MAX_PIN_FAILURES: u32 = 20;
+const PIN_LOCKOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Cookie lifetime: a paired browser stays paired until it is revoked.
+const COOKIE_MAX_AGE_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// A device's last-seen time is rewritten at most this often, so streaming
+/// pages does not write to the DB on every request.
+const SEEN_RESOLUTION_MS: i64 = 60 * 1000;
+
+const AUTH_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS devices (
+        id         TEXT PRIMARY KEY,
+        name       TEXT NOT NULL,
+        token_hash TEXT NOT NULL UNIQUE,
+        created_at INTEGER NOT NULL,
+        user_agent TEXT,
+        last_seen  INTEGER,
+        last_ip    TEXT,
+        guest      INTEGER NOT NULL DEFAULT 0
+    );";
+
+pub struct Auth {
+    db: Mutex<Connection>,
+    pairing: Mutex<Pairing>,
+}
+
+/// The PIN currently shown in the tray, and the wrong guesses at it.
+struct Pairing {
+    pin: String,
+    /// Recent wrong guesses per address.
+    backoff: HashMap<IpAddr, Backoff>,
+    /// Wrong guesses from every address since `window_start`.
+    failures: u32,
+    window_start: Instant,
+    /// Pairing is paused for everyone until then.
+    locked_until: Option<Instant>,
+}
+
+struct Backoff {
+    failures: u32,
+    /// When the address may guess again.
+    until: Instant,
+}
+
+/// The outcome of entering a PIN.
+#[derive(Debug, PartialEq, Eq)]
+enum PinCheck {
+    Accepted,
+    Wrong,
+    /// Too many wrong guesses; try again after the delay.
+    Throttled(Duration),
+}
+
+/// The outcome of a pairing request.
+pub enum PairOutcome {
+    Paired(Paired),
+    WrongPin,
+    /// Too many wrong PINs; the client may retry after the delay.
+    Throttled(Duration),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub user_agent: Option<String>,
+    /// When the device paired.
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub last_ip: Option<String>,
+    /// Whether the device may only read.
+    pub guest: bool,
+    /// Whether this is the device asking.
+    pub current: bool,
+}
+
+/// Where a request comes from, as recorded for its device.
+#[derive(Debug, Clone)]
+pub struct Origin {
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+}
+
+/// A newly paired device and the token it authenticates with.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Paired {
+    pub device_id: String,
+    pub token: String,
+}
+
+/// Who an authorized request comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    /// A loopback client, trusted without a token.
+    Loopback,
+    /// The id of a paired device.
+    Device(String),
+}
+
+impl Caller {
+    /// Whether the caller, connecting from `ip`, is the Mac itself, which
+    /// alone may manage devices and profiles. With `loopback_auth` on, the
+    /// Mac's own browser is a device too.
+    pub fn is_owner(&self, ip: IpAddr) -> bool {
+        *self == Caller::Loopback || ip.is_loopback()
+    }
+
+    /// The paired device, if the caller is one.
+    pub fn device_id(&self) -> Option<&str> {
+        match self {
+            Caller::Loopback => None,
+            Caller::Device(id) => Some(id),
+        }
+    }
+}
+
+impl Origin {
+    pub fn new(ip: IpAddr, headers: &HeaderMap) -> Self {
+        Self {
+            ip,
+            user_agent: headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+}
+
+impl Pairing {
+    fn new() -> Self {
+        Self {
+            pin: new_pin(),
+            backoff: HashMap::new(),
+            failures: 0,
+            window_start: Instant::now(),
+            locked_until: None,
+        }
+    }
+
+    /// Check `pin`, entered from `ip` at `now`. A PIN pairs one device and is
+    /// then replaced. Wrong guesses slow down their address exponentially, and
+    /// too many from anywhere pause pairing altogether; a throttled guess is
+    /// refused without being compared, so guessing cannot go faster than the
+    /// delays allow.
+    fn attempt(&mut self, pin: &str, ip: IpAddr, now: Instant) -> PinCheck {
+        if let Some(until) = self.locked_until {
+            if now < until {
+                return PinCheck::Throttled(until - now);
+            }
+            self.locked_until = None;
+        }
+        self.backoff
+            .retain(|_, backoff| now < backoff.until + MAX_PIN_DELAY);
+        if let Some(backoff) = self.backoff.get(&ip)
+            && now < backoff.until
+        {
+            return PinCheck::Throttled(backoff.until - now);
+        }
+
+        if pin == self.pin {
+            self.pin = new_pin();
+            return PinCheck::Accepted;
+        }
+
+        let backoff = self.backoff.entry(ip).or_insert(Backoff {
+            failures: 0,
+            until: now,
+        });
+        backoff.failures += 1;
+        let delay = PIN_DELAY
+            .saturating_mul(1 << (backoff.failures - 1).min(20))
+            .min(MAX_PIN_DELAY);
+        backoff.until = now + delay;
+
+        if now.duration_since(self.window_start) >= PIN_LOCKOUT {
+            self.window_start = now;
+            self.failures = 0;
+        }
+        self.failures += 1;
+        if self.failures >= MAX_PIN_FAILURES {
+            self.locked_until = Some(now + PIN_LOCKOUT);
+            self.window_start = now;
+            self.failures = 0;
+        }
+        PinCheck::Wrong
+    }
+}
+
+#[cfg(test)]
+impl Auth {
+    /// Auth with an empty device list kept in memory.
+    pub fn in_memory() -> Self {
+        let conn = Connection::open_in_memory().expect("open in-memory auth db");
+        conn.execute_batch(AUTH_SCHEMA).expect("create auth schema");
+        Self {
+            db: Mutex::new(conn),
+            pairing: Mutex::new(Pairing::new()),
+        }
+    }
+
+    /// Pair a device without a PIN and return its token.
+    pub fn add_device(&self, name: &str, guest: bool) -> String {
+        let (id, token) = (Uuid::new_v4().to_string(), new_token());
+        let conn = self.db.lock().expect("lock auth db");
+        let origin = Origin {
+            ip: IpAddr::from([192, 168, 1, 20]),
+            user_agent: None,
+        };
+        insert_device(&conn, &id, name, &hash_token(&token), &origin, 0).expect("add device");
+        set_device_guest(&conn, &id, guest).expect("set guest");
+        token
+    }
+
+    /// Revoke the device holding `token`.
+    pub fn revoke_token(&self, token: &str) {
+        let conn = self.db.lock().expect("lock auth db");
+        let id = device_by_token_hash(&conn, &hash_token(token))
+            .expect("look up device")
+            .expect("device exists");
+        delete_device(&conn, &id).expect("revoke device");
+    }
+}
+
+// --- Setup ---
+
+pub fn init(app: &AppHandle) -> rusqlite::Result<()> {
+    let store_dir = config::get_store_dir(app);
+    let _ = std::fs::create_dir_all(&store_dir);
+
+    let conn = Connection::open(store_dir.join("auth.db"))?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.execute_batch(AUTH_SCHEMA)?;
+    // Databases created before devices were tracked between requests, or
+    // before guests.
+    for column in [
+        "user_agent TEXT",
+        "last_seen INTEGER",
+        "last_ip TEXT",
+        "guest INTEGER NOT NULL DEFAULT 0",
+    ] {
+        let _ = conn.execute(&format!("ALTER TABLE devices ADD COLUMN {column}"), []);
+    }
+
+    app.manage(Auth {
+        db: Mutex::new(conn),
+        pairing: Mutex::new(Pairing::new()),
+    });
+    Ok(())
+}
+
+// --- Pairing ---
+
+/// The PIN a new device enters to pair.
+pub fn pin(app: &AppHandle) -> Option<String> {
+    let auth = app.try_state::<Auth>()?;
+    let pairing = auth.pairing.lock().ok()?;
+    Some(pairing.pin.clone())
+}
+
+/// Replace the PIN shown in the tray. Wrong guesses stay counted.
+pub fn rotate_pin(app: &AppHandle) {
+    if let Some(auth) = app.try_state::<Auth>()
+        && let Ok(mut pairing) = auth.pairing.lock()
+    {
+        pairing.pin = new_pin();
+    }
+    crate::tray::rebuild(app);
+}
+
+/// Pair a device named `name` if `pin` is the current PIN. The PIN is then
+/// replaced, so the tray is rebuilt to show the new one.
+pub fn pair(
+    app: &AppHandle,
+    pin: &str,
+    name: &str,
+    origin: &Origin,
+) -> Result<PairOutcome, String> {
+    let outcome = pair_device(&app.state::<Auth>(), pin, name, origin)?;
+    if matches!(outcome, PairOutcome::Paired(_)) {
+        crate::tray::rebuild(app);
+    }
+    Ok(outcome)
+}
+
+fn pair_device(auth: &Auth, pin: &str, name: &str, origin: &Origin) -> Result<PairOutcome, String> {
+    let check = auth.pairing.lock().map_err(|e| e.to_string())?.attempt(
+        pin.trim(),
+        origin.ip,
+        Instant::now(),
+    );
+    match check {
+        PinCheck::Accepted => {}
+        PinCheck::Wrong => return Ok(PairOutcome::WrongPin),
+        PinCheck::Throttled(delay) => return Ok(PairOutcome::Throttled(delay)),
+    }
+
+    let paired = Paired {
+        device_id: Uuid::new_v4().to_string(),
+        token: new_token(),
+    };
+    let conn = auth.db.lock().map_err(|e| e.to_string())?;
+    insert_device(
+        &conn,
+        &paired.device_id,
+        name,
+        &hash_token(&paired.token),
+        origin,
+        crate::scanner::utils::current_time_millis() as i64,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(PairOutcome::Paired(paired))
+}
+
+// --- Authentication ---
+
+/// Who the request from `ip` with `headers` comes from, or `None` when it
+/// carries no valid token and loopback is not trusted.
+pub fn caller<R: Runtime>(app: &AppHandle<R>, ip: IpAddr, headers: &HeaderMap) -> Option<Caller> {
+    if let Some(token) = token(headers)
+        && let Some(id) = device_for_token(app, token)
+    {
+        return Some(Caller::Device(id));
+    }
+    (ip.is_loopback() && !config::get(app).loopback_auth).then_some(Caller::Loopback)
+}
+
+fn device_for_token<R: Runtime>(app: &AppHandle<R>, token: &str) -> Option<String> {
+    let auth = app.try_state::<Auth>()?;
+    let conn = auth.db.lock().ok()?;
+    device_by_token_hash(&conn, &hash_token(token)).ok()?
+}
+
+/// The device token of a request: a bearer token, or else the cookie.
+pub fn token(headers: &HeaderMap) -> Option<&str> {
+    if let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(token.trim());
+    }
+    cookie(headers, COOKIE)
+}
+
+/// The value of the cookie `name` sent with a request.
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+/// `Set-Cookie` value storing `token` in the browser.
+pub fn session_cookie(token: &str) -> String {
+    format!("{COOKIE}={token}; Path=/; Max-Age={COOKIE_MAX_AGE_SECS}; HttpOnly; SameSite=Strict")
+}
+
+/// `Set-Cookie` value removing the token from the browser.
+pub fn cleared_cookie() -> String {
+    format!("{COOKIE}=; Path=/; Max-Age=0; HttpOnly; SameSite=Strict")
+}
+
+// --- Devices ---
+
+/// Note that the device with `id` made a request from `origin`.
+pub fn seen<R: Runtime>(app: &AppHandle<R>, id: &str, origin: &Origin) {
+    if let Some(auth) = app.try_state::<Auth>()
+        && let Ok(conn) = auth.db.lock()
+    {
+        let now = crate::scanner::utils::current_time_millis() as i64;
+        let _ = touch_device(&conn, id, origin, now);
+    }
+}
+
+/// Paired devices, oldest first.
+pub fn devices(app: &AppHandle) -> Vec<Device> {
+    let Some(auth) = app.try_state::<Auth>() else {
+        return Vec::new();
+    };
+    let Ok(conn) = auth.db.lock() else {
+        return Vec::new();
+    };
+    list_devices(&conn).unwrap_or_default()
+}
+
+/// Rename a device; `false` when there is no such device.
+pub fn rename(app: &AppHandle, id: &str, name: &str) -> Result<bool, String> {
+    let auth = app.state::<Auth>();
+    let conn = auth.db.lock().map_err(|e| e.to_string())?;
+    let renamed = rename_device(&conn, id, name).map_err(|e| e.to_string())?;
+    drop(conn);
+    crate::tray::rebuild(app);
+    Ok(renamed)
+}
+
+/// Mark a device as a guest or not; `false` when there is no such device.
+pub fn set_guest(app: &AppHandle, id: &str, guest: bool) -> Result<bool, String> {
+    let auth = app.state::<Auth>();
+    let conn = auth.db.lock().map_err(|e| e.to_string())?;
+    set_device_guest(&conn, id, guest).map_err(|e| e.to_string())
+}
+
+/// Whether the device `id` is a guest. An unreadable DB counts as one, so a
+/// failure never grants write access.
+pub fn is_guest<R: Runtime>(app: &AppHandle<R>, id: &str) -> bool {
+    let Some(auth) = app.try_state::<Auth>() else {
+        return true;
+    };
+    let Ok(conn) = auth.db.lock() else {
+        return true;
+    };
+    device_is_guest(&conn, id).unwrap_or(true)
+}
+
+/// Revoke (forget) a device: its token stops working at once. `false` when
+/// there is no such device.
+pub fn revoke(app: &AppHandle, id: &str) -> Result<bool, String> {
+    let auth = app.state::<Auth>();
+    let conn = auth.db.lock().map_err(|e| e.to_string())?;
+    let removed = delete_device(&conn, id).map_err(|e| e.to_string())?;
+    drop(conn);
+    crate::tray::rebuild(app);
+    Ok(removed)
+}
+
+// --- Data access ---
+
+fn insert_device(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    token_hash: &str,
+    origin: &Origin,
+    now: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO devices (id, name, token_hash, created_at, user_agent, last_seen, last_ip)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?4, ?6)",
+        params![
+            id,
+            name,
+            token_hash,
+            now,
+            origin.user_agent,
+            origin.ip.to_string()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Record a request from `origin`, unless the row already says as much to
+/// within `SEEN_RESOLUTION_MS`.
+fn touch_device(conn: &Connection, id: &str, origin: &Origin, now: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE devices
+         SET last_seen = ?2, last_ip = ?3, user_agent = COALESCE(?4, user_agent)
+         WHERE id = ?1
+           AND (last_seen IS NULL OR last_seen <= ?2 - ?5 OR last_ip IS NOT ?3
+                OR (?4 IS NOT NULL AND user_agent IS NOT ?4))",
+        params![
+            id,
+            now,
+            origin.ip.to_string(),
+            origin.user_agent,
+            SEEN_RESOLUTION_MS
+        ],
+    )?;
+    Ok(())
+}
+
+fn device_by_token_hash(conn: &Connection, token_hash: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT id FROM devices WHERE token_hash = ?1",
+        params![token_hash],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn list_devices(conn: &Connection) -> rusqlite::Result<Vec<Device>> {
+    conn.prepare(
+        "SELECT id, name, user_agent, created_at, COALESCE(last_seen, created_at), last_ip,
+                guest
+         FROM devices ORDER BY created_at, id",
+    )?
+    .query_map([], |row| {
+        Ok(Device {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            user_agent: row.get(2)?,
+            first_seen: row.get(3)?,
+            last_seen: row.get(4)?,
+            last_ip: row.get(5)?,
+            guest: row.get(6)?,
+            current: false,
+        })
+    })?
+    .collect()
+}
+
+fn rename_device(conn: &Connection, id: &str, name: &str) -> rusqlite::Result<bool> {
+    let renamed = conn.execute(
+        "UPDATE devices SET name = ?2 WHERE id = ?1",
+        params![id, name],
+    )?;
+    Ok(renamed > 0)
+}
+
+fn set_device_guest(conn: &Connection, id: &str, guest: bool) -> rusqlite::Result<bool> {
+    let updated = conn.execute(
+        "UPDATE devices SET guest = ?2 WHERE id = ?1",
+        params![id, guest],
+    )?;
+    Ok(updated > 0)
+}
+
+/// A device that no longer exists is treated as a guest.
+fn device_is_guest(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT guest FROM devices WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(true))
+}
+
+fn delete_device(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    let removed = conn.execute("DELETE FROM devices WHERE id = ?1", params![id])?;
+    Ok(removed > 0)
+}
+
+fn new_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+fn new_pin() -> String {
+    format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().expect("open in-memory auth db");
+        conn.execute_batch(AUTH_SCHEMA).expect("create auth schema");
+        conn
+    }
+
+    #[test]
+    fn a_pin_pairs_once_and_stays_put_through_wrong_guesses() {
+        let mut pairing = Pairing::new();
+        assert_eq!(pairing.pin.len(), 6);
+        let ip = IpAddr::from([192, 168, 1, 20]);
+        let now = Instant::now();
+
+        let pin = pairing.pin.clone();
+        assert_eq!(pairing.attempt(&pin, ip, now), PinCheck::Accepted);
+        assert_ne!(pairing.pin, pin);
+        assert_eq!(pairing.attempt(&pin, ip, now), PinCheck::Wrong);
+
+        let pin = pairing.pin.clone();
+        assert_eq!(
+            pairing.attempt("wrong", ip, now + MAX_PIN_DELAY),
+            PinCheck::Wrong
+        );
+        assert_eq!(pairing.pin, pin);
+    }
+
+    #[test]
+    fn wrong_pins_slow_down_their_address_and_then_pause_pairing() {
+        let mut pairing = Pairing::new();
+        let pin = pairing.pin.clone();
+        let ip = IpAddr::from([192, 168, 1, 20]);
+        let other = IpAddr::from([192, 168, 1, 21]);
+        let start = Instant::now();
+
+        assert_eq!(pairing.attempt("wrong", ip, start), PinCheck::Wrong);
+        // Even the right PIN is refused until the delay is over.
+        assert_eq!(
+            pairing.attempt(&pin, ip, start),
+            PinCheck::Throttled(PIN_DELAY)
+        );
+        let later = start + PIN_DELAY;
+        assert_eq!(pairing.attempt("wrong", ip, later), PinCheck::Wrong);
+        assert_eq!(
+            pairing.attempt("wrong", ip, later + PIN_DELAY),
+            PinCheck::Throttled(PIN_DELAY)
+        );
+        assert_eq!(pairing.attempt(&pin, other, later), PinCheck::Accepted);
+
+        let pin = pairing.pin.clone();
+        let now = start + MAX_PIN_DELAY * 2;
+        for i in 0..MAX_PIN_FAILURES {
+            let guesser = IpAddr::from([10, 0, 0, i as u8]);
+            assert_eq!(pairing.attempt("wrong", guesser, now), PinCheck::Wrong);
+        }
+        assert_eq!(
+            pairing.attempt(&pin, other, now),
+            PinCheck::Throttled(PIN_LOCKOUT)
+        );
+        assert_eq!(
+            pairing.attempt(&pin, other, now + PIN_LOCKOUT),
+            PinCheck::Accepted
+        );
+    }
+
+    fn origin(ip: [u8; 4], user_agent: Option<&str>) -> Origin {
+        Origin {
+            ip: IpAddr::from(ip),
+            user_agent: user_agent.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn tokens_resolve_to_their_device_until_it_is_revoked() {
+        let conn = test_conn();
+        let token = new_token();
+        let ipad = origin([192, 168, 1, 20], Some("Safari"));
+        insert_device(&conn, "device-1", "iPad", &hash_token(&token), &ipad, 1)
+            .expect("insert device");
+
+        assert_eq!(
+            device_by_token_hash(&conn, &hash_token(&token)).expect("look up token"),
+            Some("device-1".to_string())
+        );
+        assert_eq!(
+            device_by_token_hash(&conn, &hash_token("other")).expect("look up other token"),
+            None
+        );
+        assert_eq!(list_devices(&conn).expect("list devices")[0].name, "iPad");
+
+        assert!(delete_device(&conn, "device-1").expect("revoke device"));
+        assert!(!delete_device(&conn, "device-1").expect("revoke missing device"));
+        assert_eq!(
+            device_by_token_hash(&conn, &hash_token(&token)).expect("look up revoked token"),
+            None
+        );
+    }
+
+    #[test]
+    fn devices_record_their_last_visit_and_can_be_renamed_or_made_guests() {
+        let conn = test_conn();
+        let ipad = origin([192, 168, 1, 20], Some("Safari"));
+        insert_device(&conn, "device-1", "iPad", "hash", &ipad, 1_000).expect("insert device");
+
+        // Within the resolution nothing is written; a new address always is.
+        touch_device(&conn, "device-1", &ipad, 1_000 + SEEN_RESOLUTION_MS - 1)
+            .expect("touch device soon after");
+        assert_eq!(
+            list_devices(&conn).expect("list devices")[0].last_seen,
+            1_000
+        );
+        let moved = origin([10, 0, 0, 5], None);
+        touch_device(&conn, "device-1", &moved, 2_000).expect("touch from new ip");
+
+        let device = &list_devices(&conn).expect("list devices")[0];
+        assert_eq!(device.first_seen, 1_000);
+        assert_eq!(device.last_seen, 2_000);
+        assert_eq!(device.last_ip.as_deref(), Some("10.0.0.5"));
+        assert_eq!(device.user_agent.as_deref(), Some("Safari"));
+
+        assert!(rename_device(&conn, "device-1", "Kids' iPad").expect("rename device"));
+        assert!(!rename_device(&conn, "missing", "Other").expect("rename missing device"));
+        assert_eq!(
+            list_devices(&conn).expect("list devices")[0].name,
+            "Kids' iPad"
+        );
+
+        assert!(!device_is_guest(&conn, "device-1").expect("read guest flag"));
+        assert!(set_device_guest(&conn, "device-1", true).expect("make guest"));
+        assert!(device_is_guest(&conn, "device-1").expect("read guest flag"));
+        assert!(list_devices(&conn).expect("list devices")[0].guest);
+        assert!(device_is_guest(&conn, "missing").expect("read missing device"));
+    }
+
+    #[test]
+    fn reads_the_token_from_a_bearer_header_or_the_cookie() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(token(&headers), None);
+
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; eriri_token=abc; other=1"),
+        );
+        assert_eq!(token(&headers), Some("abc"));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer xyz"),
+        );
+        assert_eq!(token(&headers), Some("xyz"));
+    }
+}
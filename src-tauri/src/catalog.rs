@@ -0,0 +1,467 @@
+//! Filtered, sorted and paginated catalog queries.
+//!
+//! The full `Catalog` snapshot takes seconds for a phone to hydrate once a
+//! library holds tens of thousands of comics. A query narrows the rows in SQL,
+//! joins reading progress from progress.db, sorts titles in natural order and
+//! returns one page with a cursor for the next.
+//!
+//! The cursor is the sort fields of the page's last item, so a page boundary
+//! stays put when items are added or removed before it.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::access::Access;
+use crate::library::{self, LibraryDb};
+use crate::models::{Book, Comic};
+use crate::progress::{self, ProgressDb, ReadMark};
+use crate::scanner::book_search::fold_text;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    #[default]
+    Comic,
+    Book,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadState {
+    /// No progress recorded.
+    Unread,
+    Reading,
+    /// Progress reached 100%.
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortKey {
+    #[default]
+    Title,
+    CreatedAt,
+    /// File size; comics have none and fall back to title order.
+    Size,
+    /// Unread items sort before any read one.
+    LastRead,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CatalogQuery {
+    pub kind: ItemKind,
+    pub library_id: Option<String>,
+    /// Title substring, matched like the in-book search.
+    pub title: Option<String>,
+    pub starred: Option<bool>,
+    pub deleted: Option<bool>,
+    pub read: Option<ReadState>,
+    /// Earliest `createdAt` (ms), inclusive.
+    pub created_from: Option<u64>,
+    /// Latest `createdAt` (ms), exclusive.
+    pub created_to: Option<u64>,
+    pub sort: SortKey,
+    pub order: SortOrder,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Items {
+    Comics(Vec<Comic>),
+    Books(Vec<Book>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogPage {
+    pub items: Items,
+    /// Pass back as `cursor` for the next page; absent on the last one.
+    #[serde(rename = "nextCursor", skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// What an item sorts by; also the content of a cursor.
+#[derive(Debug, Serialize, Deserialize)]
+struct SortFields {
+    id: String,
+    title: String,
+    created_at: u64,
+    size: u64,
+    last_read: Option<i64>,
+}
+
+trait Item {
+    fn fields(&self, marks: &HashMap<String, ReadMark>) -> SortFields;
+}
+
+impl Item for Comic {
+    fn fields(&self, marks: &HashMap<String, ReadMark>) -> SortFields {
+        SortFields {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            created_at: self.created_at,
+            size: 0,
+            last_read: marks.get(&self.id).map(|mark| mark.last_read),
+        }
+    }
+}
+
+impl Item for Book {
+    fn fields(&self, marks: &HashMap<String, ReadMark>) -> SortFields {
+        SortFields {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            created_at: self.created_at,
+            size: self.size,
+            last_read: marks.get(&self.id).map(|mark| mark.last_read),
+        }
+    }
+}
+
+pub fn validate_cursor(cursor: &str) -> Result<(), String> {
+    decode_cursor(cursor).map(|_| ())
+}
+
+/// A page of the catalog, with last-read times from `profile`'s progress and
+/// without the libraries hidden from the client.
+pub fn query(
+    app: &AppHandle,
+    profile: &str,
+    access: &Access,
+    q: &CatalogQuery,
+) -> Result<CatalogPage, String> {
+    let library_state = app.state::<LibraryDb>();
+    let library_conn = library_state.0.lock().map_err(|e| e.to_string())?;
+    let progress_state = app.state::<ProgressDb>();
+    let progress_conn = progress_state.0.lock().map_err(|e| e.to_string())?;
+    query_conn(&library_conn, &progress_conn, profile, access, q)
+}
+
+fn query_conn(
+    library_conn: &Connection,
+    progress_conn: &Connection,
+    profile: &str,
+    access: &Access,
+    q: &CatalogQuery,
+) -> Result<CatalogPage, String> {
+    let cursor = q.cursor.as_deref().map(decode_cursor).transpose()?;
+    let (items, next_cursor) = match q.kind {
+        ItemKind::Comic => {
+            let mut comics = load(library_conn, "comics", library::COMIC_COLUMNS, q, |row| {
+                library::comic_from_row(row)
+            })
+            .map_err(|e| e.to_string())?;
+            comics.retain(|comic| access.sees_library(&comic.library_id));
+            let marks =
+                progress::comic_read_marks(progress_conn, profile).map_err(|e| e.to_string())?;
+            let (page, next) = page(comics, &marks, q, cursor.as_ref());
+            (Items::Comics(page), next)
+        }
+        ItemKind::Book => {
+            let mut books = load(library_conn, "books", library::BOOK_COLUMNS, q, |row| {
+                library::book_from_row(row)
+            })
+            .map_err(|e| e.to_string())?;
+            books.retain(|book| access.sees_library(&book.library_id));
+            let marks =
+                progress::book_read_marks(progress_conn, profile).map_err(|e| e.to_string())?;
+            let (page, next) = page(books, &marks, q, cursor.as_ref());
+            (Items::Books(page), next)
+        }
+    };
+    Ok(CatalogPage { items, next_cursor })
+}
+
+/// Rows of `table` passing the column filters of `q`.
+fn load<T>(
+    conn: &Connection,
+    table: &str,
+    columns: &str,
+    q: &CatalogQuery,
+    from_row: impl FnMut(&rusqlite::Row) -> rusqlite::Result<T>,
+) -> rusqlite::Result<Vec<T>> {
+    conn.prepare(&format!(
+        "SELECT {columns} FROM {table}
+         WHERE (?1 IS NULL OR library_id = ?1)
+           AND (?2 IS NULL OR starred = ?2)
+           AND (?3 IS NULL OR deleted = ?3)
+           AND (?4 IS NULL OR created_at >= ?4)
+           AND (?5 IS NULL OR created_at < ?5)"
+    ))?
+    .query_map(
+        params![
+            q.library_id,
+            q.starred,
+            q.deleted,
+            q.created_from.map(|ms| ms as i64),
+            q.created_to.map(|ms| ms as i64),
+        ],
+        from_row,
+    )?
+    .collect()
+}
+
+/// Apply the title and read filters, sort, and cut the page after `cursor`.
+fn page<T: Item>(
+    items: Vec<T>,
+    marks: &HashMap<String, ReadMark>,
+    q: &CatalogQuery,
+    cursor: Option<&SortFields>,
+) -> (Vec<T>, Option<String>) {
+    let title = q.title.as_deref().map(fold_text);
+    let mut entries: Vec<(SortFields, T)> = items
+        .into_iter()
+        .map(|item| (item.fields(marks), item))
+        .filter(|(fields, _)| {
+            title
+                .as_ref()
+                .is_none_or(|title| fold_text(&fields.title).contains(title.as_str()))
+        })
+        .filter(|(fields, _)| {
+            q.read
+                .is_none_or(|state| read_state(marks.get(&fields.id)) == state)
+        })
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| compare(a, b, q));
+
+    let start = cursor.map_or(0, |cursor| {
+        entries.partition_point(|(fields, _)| compare(fields, cursor, q) != Ordering::Greater)
+    });
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let end = entries.len().min(start + limit);
+    let next_cursor = (end < entries.len()).then(|| encode_cursor(&entries[end - 1].0));
+
+    let page = entries.drain(start..end).map(|(_, item)| item).collect();
+    (page, next_cursor)
+}
+
+fn read_state(mark: Option<&ReadMark>) -> ReadState {
+    match mark {
+        None => ReadState::Unread,
+        Some(mark) if mark.percent >= 100.0 => ReadState::Finished,
+        Some(_) => ReadState::Reading,
+    }
+}
+
+/// Order by the sort key, then by natural title order and id so every item
+/// has one place.
+fn compare(a: &SortFields, b: &SortFields, q: &CatalogQuery) -> Ordering {
+    let by_key = match q.sort {
+        SortKey::Title => Ordering::Equal,
+        SortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+        SortKey::Size => a.size.cmp(&b.size),
+        SortKey::LastRead => a.last_read.cmp(&b.last_read),
+    };
+    let ordering = by_key
+        .then_with(|| natord::compare(&a.title, &b.title))
+        .then_with(|| a.id.cmp(&b.id));
+    match q.order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
+
+fn encode_cursor(fields: &SortFields) -> String {
+    hex::encode(serde_json::to_vec(fields).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str) -> Result<SortFields, String> {
+    hex::decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| "Invalid catalog cursor".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::DEFAULT_PROFILE;
+
+    fn test_conns() -> (Connection, Connection) {
+        let library_conn = Connection::open_in_memory().expect("open in-memory library db");
+        library::create_schema(&library_conn).expect("create library schema");
+        let progress_conn = Connection::open_in_memory().expect("open in-memory progress db");
+        progress::create_schema(&progress_conn).expect("create progress schema");
+
+        for (id, title, library_id, created_at, starred, size) in [
+            ("c1", "第10话", "lib-1", 10, true, 0),
+            ("c2", "第2话", "lib-1", 30, false, 0),
+            ("c3", "第1话", "lib-1", 20, false, 0),
+            ("c4", "ＯＮＥ Piece", "lib-2", 40, true, 0),
+        ] {
+            library_conn
+                .execute(
+                    "INSERT INTO comics (id, title, path, cover, library_id, created_at, starred, deleted)
+                     VALUES (?1, ?2, '/p', '', ?3, ?4, ?5, 0)",
+                    params![id, title, library_id, created_at, starred],
+                )
+                .expect("insert comic");
+            library_conn
+                .execute(
+                    "INSERT INTO books (id, title, path, author_id, library_id, size, created_at, starred, deleted)
+                     VALUES (?1, ?2, '/p', 'a', ?3, ?4, ?5, 0, ?6)",
+                    params![format!("b{id}"), title, library_id, size + created_at, created_at, id == "c4"],
+                )
+                .expect("insert book");
+        }
+        for (id, percent, last_read) in [("c1", 100.0, 5), ("c2", 40.0, 9)] {
+            progress_conn
+                .execute(
+                    "INSERT INTO comic_progress (comic_id, current, total, percent, last_read)
+                     VALUES (?1, 1, 2, ?2, ?3)",
+                    params![id, percent, last_read],
+                )
+                .expect("insert progress");
+        }
+        (library_conn, progress_conn)
+    }
+
+    fn comic_ids(conns: &(Connection, Connection), q: &CatalogQuery) -> Vec<String> {
+        match query_conn(&conns.0, &conns.1, DEFAULT_PROFILE, &Access::default(), q)
+            .expect("query catalog")
+            .items
+        {
+            Items::Comics(comics) => comics.into_iter().map(|comic| comic.id).collect(),
+            Items::Books(_) => panic!("expected comics"),
+        }
+    }
+
+    #[test]
+    fn filters_by_columns_title_and_read_state() {
+        let conns = test_conns();
+        let ids = |q: CatalogQuery| comic_ids(&conns, &q);
+
+        assert_eq!(ids(CatalogQuery::default()), vec!["c3", "c2", "c1", "c4"]);
+        assert_eq!(
+            ids(CatalogQuery {
+                library_id: Some("lib-2".to_string()),
+                ..CatalogQuery::default()
+            }),
+            vec!["c4"]
+        );
+        assert_eq!(
+            ids(CatalogQuery {
+                title: Some("one ｐｉｅｃｅ".to_string()),
+                ..CatalogQuery::default()
+            }),
+            vec!["c4"]
+        );
+        assert_eq!(
+            ids(CatalogQuery {
+                starred: Some(true),
+                created_from: Some(10),
+                created_to: Some(40),
+                ..CatalogQuery::default()
+            }),
+            vec!["c1"]
+        );
+        let by_state = |read| {
+            ids(CatalogQuery {
+                read: Some(read),
+                ..CatalogQuery::default()
+            })
+        };
+        assert_eq!(by_state(ReadState::Unread), vec!["c3", "c4"]);
+        assert_eq!(by_state(ReadState::Reading), vec!["c2"]);
+        assert_eq!(by_state(ReadState::Finished), vec!["c1"]);
+
+        let books = query_conn(
+            &conns.0,
+            &conns.1,
+            DEFAULT_PROFILE,
+            &Access::default(),
+            &CatalogQuery {
+                kind: ItemKind::Book,
+                deleted: Some(true),
+                ..CatalogQuery::default()
+            },
+        )
+        .expect("query books");
+        assert!(matches!(books.items, Items::Books(ref books) if books.len() == 1));
+    }
+
+    #[test]
+    fn sorts_by_each_key_and_pages_with_cursors() {
+        let conns = test_conns();
+        let sorted = |sort, order| {
+            comic_ids(
+                &conns,
+                &CatalogQuery {
+                    sort,
+                    order,
+                    ..CatalogQuery::default()
+                },
+            )
+        };
+
+        assert_eq!(
+            sorted(SortKey::CreatedAt, SortOrder::Desc),
+            vec!["c4", "c2", "c3", "c1"]
+        );
+        assert_eq!(
+            sorted(SortKey::LastRead, SortOrder::Asc),
+            vec!["c3", "c4", "c1", "c2"]
+        );
+        assert_eq!(
+            sorted(SortKey::Size, SortOrder::Desc),
+            vec!["c4", "c1", "c2", "c3"]
+        );
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = query_conn(
+                &conns.0,
+                &conns.1,
+                DEFAULT_PROFILE,
+                &Access::default(),
+                &CatalogQuery {
+                    kind: ItemKind::Book,
+                    sort: SortKey::Size,
+                    cursor: cursor.take(),
+                    limit: Some(3),
+                    ..CatalogQuery::default()
+                },
+            )
+            .expect("query page");
+            let Items::Books(books) = page.items else {
+                panic!("expected books");
+            };
+            seen.extend(books.into_iter().map(|book| book.id));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!["bc1", "bc3", "bc2", "bc4"]);
+
+        assert!(validate_cursor("not a cursor").is_err());
+        assert!(
+            validate_cursor(&encode_cursor(&SortFields {
+                id: "c1".to_string(),
+                title: String::new(),
+                created_at: 0,
+                size: 0,
+                last_read: None,
+            }))
+            .is_ok()
+        );
+    }
+}
@@ -119,8 +119,13 @@ pub fn save_config<R: Runtime>(app: &AppHandle<R>, config: &Config) -> Result<()
 }
 
 pub fn read_store_data<R: Runtime>(app: AppHandle<R>, key: String) -> Option<String> {
-    let store_dir = get_store_dir(&app);
-    let file_path = store_file_path(&store_dir, &key, "json").ok()?;
+    read_store_file(&get_store_dir(&app), &key)
+}
+
+/// Read the blob `key` from `store_dir`, which may be a profile's (see
+/// `profile::store_dir`).
+pub fn read_store_file(store_dir: &Path, key: &str) -> Option<String> {
+    let file_path = store_file_path(store_dir, key, "json").ok()?;
     fs::read_to_string(file_path).ok()
 }
 
@@ -132,14 +137,16 @@ pub fn write_store_data<R: Runtime>(
     key: String,
     data: String,
 ) -> Result<(), String> {
-    let store_dir = get_store_dir(&app);
-    let file_path = store_file_path(&store_dir, &key, "json")?;
-    write_file_atomically(&file_path, &data)
+    write_store_file(&get_store_dir(&app), &key, &data)
+}
+
+pub fn write_store_file(store_dir: &Path, key: &str, data: &str) -> Result<(), String> {
+    let file_path = store_file_path(store_dir, key, "json")?;
+    write_file_atomically(&file_path, data)
 }
 
-pub fn remove_store_data<R: Runtime>(app: AppHandle<R>, key: String) -> Result<(), String> {
-    let store_dir = get_store_dir(&app);
-    let file_path = store_file_path(&store_dir, &key, "json")?;
+pub fn remove_store_file(store_dir: &Path, key: &str) -> Result<(), String> {
+    let file_path = store_file_path(store_dir, key, "json")?;
     if file_path.exists() {
         fs::remove_file(file_path).map_err(|e| e.to_string())?;
     }
@@ -158,6 +165,7 @@ mod tests {
         let app = tauri::test::mock_app();
         app.manage(ConfigState(Mutex::new(Config {
             cache_dir: Some(cache_dir.to_string_lossy().into_owned()),
+            ..Config::default()
         })));
         app
     }
@@ -285,6 +293,7 @@ mod tests {
         let config_path = reset_config_file(app.handle());
         let config = Config {
             cache_dir: Some(cache_dir.path().to_string_lossy().into_owned()),
+            ..Config::default()
         };
 
         save_config(app.handle(), &config).expect("save config");
@@ -323,7 +332,7 @@ mod tests {
             r#"{"page":42}"#
         );
 
-        remove_store_data(handle.clone(), "reader".into()).expect("remove store data");
+        remove_store_file(&get_store_dir(&handle), "reader").expect("remove store data");
         assert_eq!(read_store_data(handle, "reader".into()), None);
     }
 
@@ -335,7 +344,7 @@ mod tests {
 
         assert_eq!(read_store_data(handle.clone(), "../escape".into()), None);
         assert!(write_store_data(handle.clone(), "../escape".into(), "data".into()).is_err());
-        assert!(remove_store_data(handle, "../escape".into()).is_err());
+        assert!(remove_store_file(&get_store_dir(&handle), "../escape").is_err());
         assert!(!cache_dir.path().join("escape.json").exists());
     }
 }
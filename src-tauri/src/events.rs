@@ -0,0 +1,237 @@
+//! Live change events for clients.
+//!
+//! Catalog, tag and progress writes publish an `Event` here, and every client
+//! holding `/api/events` open receives it as a Server-Sent Event, so a second
+//! device follows along without re-fetching. Progress events only reach the
+//! clients reading under the same profile, and library, tag and progress
+//! events only the clients that may see the library. Events are not stored:
+//! a client that connects later, or falls behind, re-fetches what it shows.
+
+use std::convert::Infallible;
+
+use axum::response::sse;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::access::Access;
+use crate::models::FileTags;
+use crate::progress::{BookProgress, ComicProgress};
+
+/// Events buffered for a slow client before it skips ahead to `Lagged`.
+const CAPACITY: usize = 256;
+
+pub struct Events(broadcast::Sender<Event>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Event {
+    LibraryImported {
+        library_id: String,
+    },
+    /// A refresh, or a watched entry update, changed the library's content.
+    LibraryRefreshed {
+        library_id: String,
+    },
+    LibraryRemoved {
+        library_id: String,
+    },
+    /// Only the tags that were set.
+    ComicTagsChanged {
+        #[serde(skip)]
+        library_id: String,
+        comic_id: String,
+        tags: FileTags,
+    },
+    BookTagsChanged {
+        #[serde(skip)]
+        library_id: String,
+        book_id: String,
+        tags: FileTags,
+    },
+    /// `None` when the progress was cleared.
+    ComicProgressUpdated {
+        /// `None` for a comic missing from the catalog.
+        #[serde(skip)]
+        library_id: Option<String>,
+        #[serde(skip)]
+        profile_id: String,
+        comic_id: String,
+        progress: Option<ComicProgress>,
+    },
+    BookProgressUpdated {
+        #[serde(skip)]
+        library_id: Option<String>,
+        #[serde(skip)]
+        profile_id: String,
+        book_id: String,
+        progress: Option<BookProgress>,
+    },
+    /// The book's favorite chapter lines, empty when cleared.
+    FavoritesUpdated {
+        #[serde(skip)]
+        library_id: Option<String>,
+        #[serde(skip)]
+        profile_id: String,
+        book_id: String,
+        lines: Vec<i64>,
+    },
+    /// Sent instead of the events a client missed; it should re-fetch.
+    Lagged,
+}
+
+impl Event {
+    /// Whether a client reading under `profile` with `access` should receive
+    /// this event.
+    fn reaches(&self, profile: &str, access: &Access) -> bool {
+        match self {
+            Event::LibraryImported { library_id }
+            | Event::LibraryRefreshed { library_id }
+            | Event::LibraryRemoved { library_id }
+            | Event::ComicTagsChanged { library_id, .. }
+            | Event::BookTagsChanged { library_id, .. } => access.sees_library(library_id),
+            Event::ComicProgressUpdated {
+                library_id,
+                profile_id,
+                ..
+            }
+            | Event::BookProgressUpdated {
+                library_id,
+                profile_id,
+                ..
+            }
+            | Event::FavoritesUpdated {
+                library_id,
+                profile_id,
+                ..
+            } => profile_id == profile && access.sees_item(library_id.as_deref()),
+            Event::Lagged => true,
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(Events(broadcast::channel(CAPACITY).0));
+}
+
+/// Send `event` to every connected client.
+pub fn publish(app: &AppHandle, event: Event) {
+    if let Some(events) = app.try_state::<Events>() {
+        // Fails only when no client is listening.
+        let _ = events.0.send(event);
+    }
+}
+
+/// The events published from now on for a client reading under `profile`
+/// with `access`, as an SSE stream.
+pub fn stream(
+    app: &AppHandle,
+    profile: String,
+    access: Access,
+) -> impl Stream<Item = Result<sse::Event, Infallible>> + use<> {
+    let receiver = app.state::<Events>().0.subscribe();
+    BroadcastStream::new(receiver).filter_map(move |event| {
+        let event = event.unwrap_or(Event::Lagged);
+        if !event.reaches(&profile, &access) {
+            return None;
+        }
+        serde_json::to_string(&event)
+            .ok()
+            .map(|json| Ok(sse::Event::default().data(json)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_serialize_with_their_type_and_camel_case_fields() {
+        let event = Event::ComicTagsChanged {
+            library_id: "library-1".to_string(),
+            comic_id: "comic-1".to_string(),
+            tags: FileTags {
+                starred: Some(true),
+                deleted: None,
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&event).expect("serialize event"),
+            serde_json::json!({
+                "type": "comicTagsChanged",
+                "comicId": "comic-1",
+                "tags": { "starred": true },
+            })
+        );
+
+        let event = Event::BookProgressUpdated {
+            library_id: Some("library-1".to_string()),
+            profile_id: "default".to_string(),
+            book_id: "book-1".to_string(),
+            progress: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).expect("serialize event"),
+            serde_json::json!({
+                "type": "bookProgressUpdated",
+                "bookId": "book-1",
+                "progress": null,
+            })
+        );
+    }
+
+    #[test]
+    fn progress_events_reach_only_their_profile() {
+        let everything = Access::default();
+        let event = Event::FavoritesUpdated {
+            library_id: None,
+            profile_id: "kid".to_string(),
+            book_id: "book-1".to_string(),
+            lines: vec![3],
+        };
+        assert!(event.reaches("kid", &everything));
+        assert!(!event.reaches("default", &everything));
+        assert!(
+            Event::LibraryRefreshed {
+                library_id: "library-1".to_string()
+            }
+            .reaches("default", &everything)
+        );
+    }
+
+    #[test]
+    fn library_tag_and_progress_events_skip_clients_the_library_is_hidden_from() {
+        let access = Access::hiding(&["kids"]);
+        let tagged = |library_id: &str| Event::BookTagsChanged {
+            library_id: library_id.to_string(),
+            book_id: "book-1".to_string(),
+            tags: FileTags {
+                starred: Some(true),
+                deleted: None,
+            },
+        };
+        assert!(!tagged("kids").reaches("default", &access));
+        assert!(tagged("family").reaches("default", &access));
+        assert!(
+            !Event::LibraryRemoved {
+                library_id: "kids".to_string()
+            }
+            .reaches("default", &access)
+        );
+        let read = |library_id: &str| Event::ComicProgressUpdated {
+            library_id: Some(library_id.to_string()),
+            profile_id: "default".to_string(),
+            comic_id: "comic-1".to_string(),
+            progress: None,
+        };
+        assert!(!read("kids").reaches("default", &access));
+        assert!(read("family").reaches("default", &access));
+        assert!(Event::Lagged.reaches("default", &access));
+    }
+}
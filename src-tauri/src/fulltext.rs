@@ -0,0 +1,561 @@
+//! Library-wide full-text search over book text.
+//!
+//! Book titles, chapter titles and lines live in the contentless FTS5 table
+//! `book_text` in library.db. FTS5's tokenizers only split on spaces and
+//! punctuation, which leaves a Chinese paragraph as one token, so text is
+//! pre-split here: every CJK run becomes its overlapping bigrams followed by
+//! its last character, after the same folding as the in-book search.
+//!
+//! A row id packs the book's `book_text_sources.seq` into its high 32 bits and
+//! the row's position into the low 32: a line index, `CHAPTER_FLAG` plus a
+//! chapter index, or `TITLE_POSITION`. The text itself is not stored; hits are
+//! read back from the parsed-book cache.
+
+use std::collections::HashMap;
+use std::fs;
+
+use rusqlite::{Connection, params};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::access::Access;
+use crate::library::{self, LibraryDb};
+use crate::models::{BookContent, LibraryHitKind, LibrarySearchHit};
+use crate::scanner::ScanProgress;
+use crate::scanner::book_index::{self, BookIndex};
+use crate::scanner::book_search::{self, fold_text};
+use crate::thumbnail::get_thumbnail_hash;
+
+/// Most hits one search returns.
+const MAX_HITS: usize = 100;
+/// Position bits of a chapter title row: this flag plus the chapter index.
+const CHAPTER_FLAG: i64 = 1 << 31;
+/// Position bits of the book title row.
+const TITLE_POSITION: i64 = POSITION_MASK;
+const POSITION_MASK: i64 = (1 << 32) - 1;
+
+struct RankedRow {
+    book_id: String,
+    title: String,
+    path: String,
+    position: i64,
+}
+
+/// Bring the index up to date for every book of a library, re-reading only
+/// books whose file or parse options changed since they were indexed. Stops
+/// between books once `progress` is cancelled.
+pub fn index_library(
+    app: &AppHandle,
+    library_id: &str,
+    progress: &ScanProgress,
+) -> Result<(), String> {
+    let state = app.state::<LibraryDb>();
+    let books = {
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        prune(&conn).map_err(|e| e.to_string())?;
+        library_books(&conn, library_id).map_err(|e| e.to_string())?
+    };
+
+    let cache_dir = book_index::cache_dir(app);
+    let mut indexed = 0;
+    for (id, title, path) in books {
+        progress.check()?;
+        let (options, indexed_key) = {
+            let conn = state.0.lock().map_err(|e| e.to_string())?;
+            (
+                library::book_parse_options(&conn, &path).map_err(|e| e.to_string())?,
+                source_key(&conn, &id).map_err(|e| e.to_string())?,
+            )
+        };
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        let key = format!(
+            "{}:{}",
+            get_thumbnail_hash(&metadata),
+            book_index::options_hash(&options)
+        );
+        if indexed_key.as_deref() == Some(key.as_str()) {
+            continue;
+        }
+
+        let content =
+            match book_index::open(&cache_dir, &path, &options).and_then(BookIndex::content) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!(path = %path, error = %e, "Failed to index book text");
+                    continue;
+                }
+            };
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        write_book(&conn, &id, &key, &title, &content).map_err(|e| e.to_string())?;
+        indexed += 1;
+    }
+
+    if indexed > 0 {
+        info!(library = %library_id, books = indexed, "Indexed book text");
+    }
+    Ok(())
+}
+
+/// Drop the rows of books no longer in the catalog.
+pub fn prune(conn: &Connection) -> rusqlite::Result<()> {
+    let seqs = conn
+        .prepare("SELECT seq FROM book_text_sources WHERE book_id NOT IN (SELECT id FROM books)")?
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for seq in seqs {
+        delete_rows(conn, seq)?;
+        conn.execute("DELETE FROM book_text_sources WHERE seq = ?1", params![seq])?;
+    }
+    Ok(())
+}
+
+/// Best hits for `query` across the libraries `access` sees, each read back
+/// from its book.
+pub fn search(
+    app: &AppHandle,
+    query: &str,
+    access: &Access,
+) -> Result<Vec<LibrarySearchHit>, String> {
+    let state = app.state::<LibraryDb>();
+    let hidden: Vec<&str> = access.hidden_ids().collect();
+    let rows = {
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        ranked_rows(&conn, query, MAX_HITS, &hidden).map_err(|e| e.to_string())?
+    };
+
+    let cache_dir = book_index::cache_dir(app);
+    let mut books: HashMap<String, Option<BookIndex>> = HashMap::new();
+    let mut hits = Vec::new();
+    for row in rows {
+        if row.position == TITLE_POSITION {
+            let (snippet, highlights) = book_search::highlight(&row.title, query);
+            hits.push(LibrarySearchHit {
+                book_id: row.book_id,
+                book_title: row.title,
+                kind: LibraryHitKind::Book,
+                line_index: None,
+                chapter: None,
+                snippet,
+                highlights,
+            });
+            continue;
+        }
+
+        if !books.contains_key(&row.book_id) {
+            let options = {
+                let conn = state.0.lock().map_err(|e| e.to_string())?;
+                library::book_parse_options(&conn, &row.path).map_err(|e| e.to_string())?
+            };
+            let index = book_index::open(&cache_dir, &row.path, &options).ok();
+            books.insert(row.book_id.clone(), index);
+        }
+        if let Some(Some(index)) = books.get(&row.book_id)
+            && let Some(hit) = read_hit(index, row, query)?
+        {
+            hits.push(hit);
+        }
+    }
+    Ok(hits)
+}
+
+/// A chapter or line hit, or `None` when the book changed since it was
+/// indexed and the position is gone.
+fn read_hit(
+    index: &BookIndex,
+    row: RankedRow,
+    query: &str,
+) -> Result<Option<LibrarySearchHit>, String> {
+    let chapters = &index.toc().chapters;
+    let (kind, line_index, chapter, text) = if row.position & CHAPTER_FLAG != 0 {
+        let Some(chapter) = chapters.get((row.position & !CHAPTER_FLAG) as usize) else {
+            return Ok(None);
+        };
+        let title = chapter.title.clone();
+        (
+            LibraryHitKind::Chapter,
+            chapter.line_index,
+            Some(title.clone()),
+            title,
+        )
+    } else {
+        let line_index = row.position as usize;
+        let lines = index.lines(line_index, line_index + 1)?.lines;
+        let Some(line) = lines.into_iter().next() else {
+            return Ok(None);
+        };
+        (
+            LibraryHitKind::Line,
+            line_index,
+            book_search::chapter_title(chapters, line_index),
+            line,
+        )
+    };
+
+    let (snippet, highlights) = book_search::highlight(&text, query);
+    Ok(Some(LibrarySearchHit {
+        book_id: row.book_id,
+        book_title: row.title,
+        kind,
+        line_index: Some(line_index),
+        chapter,
+        snippet,
+        highlights,
+    }))
+}
+
+/// The best-ranked rows for `query` outside the `hidden` libraries, with their
+/// book's id, title and path.
+fn ranked_rows(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+    hidden: &[&str],
+) -> rusqlite::Result<Vec<RankedRow>> {
+    let Some(expression) = match_expression(query) else {
+        return Ok(Vec::new());
+    };
+    let hidden = serde_json::to_string(hidden).unwrap_or_default();
+    conn.prepare(
+        "SELECT b.id, b.title, b.path, t.rowid & ?3
+         FROM book_text t
+         JOIN book_text_sources s ON s.seq = t.rowid >> 32
+         JOIN books b ON b.id = s.book_id
+         WHERE t.book_text MATCH ?1
+           AND b.library_id NOT IN (SELECT value FROM json_each(?4))
+         ORDER BY t.rank LIMIT ?2",
+    )?
+    .query_map(
+        params![expression, limit as i64, POSITION_MASK, hidden],
+        |row| {
+            Ok(RankedRow {
+                book_id: row.get(0)?,
+                title: row.get(1)?,
+                path: row.get(2)?,
+                position: row.get(3)?,
+            })
+        },
+    )?
+    .collect()
+}
+
+fn library_books(
+    conn: &Connection,
+    library_id: &str,
+) -> rusqlite::Result<Vec<(String, String, String)>> {
+    conn.prepare("SELECT id, title, path FROM books WHERE library_id = ?1")?
+        .query_map(params![library_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect()
+}
+
+fn source_key(conn: &Connection, book_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT key FROM book_text_sources WHERE book_id = ?1",
+        params![book_id],
+        |r| r.get(0),
+    )
+    .map(Some)
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+/// Replace the rows of one book: its title, chapter titles and non-empty
+/// lines. `key` records the file identity and options they were read with.
+fn write_book(
+    conn: &Connection,
+    book_id: &str,
+    key: &str,
+    title: &str,
+    content: &BookContent,
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let seq: i64 = tx.query_row(
+        "INSERT INTO book_text_sources (book_id, key) VALUES (?1, ?2)
+         ON CONFLICT(book_id) DO UPDATE SET key = excluded.key
+         RETURNING seq",
+        params![book_id, key],
+        |r| r.get(0),
+    )?;
+    delete_rows(&tx, seq)?;
+
+    let base = seq << 32;
+    {
+        let mut stmt = tx.prepare_cached("INSERT INTO book_text (rowid, text) VALUES (?1, ?2)")?;
+        stmt.execute(params![base | TITLE_POSITION, index_text(title)])?;
+        for (i, chapter) in content.chapters.iter().enumerate() {
+            stmt.execute(params![
+                base | CHAPTER_FLAG | i as i64,
+                index_text(&chapter.title)
+            ])?;
+        }
+        for (i, line) in content.lines.iter().enumerate() {
+            if !line.trim().is_empty() {
+                stmt.execute(params![base | i as i64, index_text(line)])?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+fn delete_rows(conn: &Connection, seq: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM book_text WHERE rowid BETWEEN ?1 AND ?2",
+        params![seq << 32, (seq << 32) | POSITION_MASK],
+    )?;
+    Ok(())
+}
+
+fn index_text(text: &str) -> String {
+    tokens(text).0.join(" ")
+}
+
+/// FTS5 expression for `query`: every whitespace-separated term as a prefix
+/// phrase. A term ending in a CJK run drops the run's last-character token,
+/// since the indexed run may go on. `None` when nothing is searchable.
+fn match_expression(query: &str) -> Option<String> {
+    let phrases: Vec<String> = query
+        .split_whitespace()
+        .filter_map(|term| {
+            let (mut tokens, trailing_run) = tokens(term);
+            if trailing_run > 1 {
+                tokens.pop();
+            }
+            (!tokens.is_empty()).then(|| format!("\"{}\"*", tokens.join(" ")))
+        })
+        .collect();
+    (!phrases.is_empty()).then(|| phrases.join(" AND "))
+}
+
+/// Folded words of `text`, with every CJK run as its overlapping bigrams and
+/// then its last character; plus the length of a CJK run ending the text.
+fn tokens(text: &str) -> (Vec<String>, usize) {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut run: Vec<char> = Vec::new();
+    let mut trailing_run = 0;
+    for c in fold_text(text).chars().chain([' ']) {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            run.push(c);
+            continue;
+        }
+        if let Some(&last) = run.last() {
+            tokens.extend(run.windows(2).map(|pair| pair.iter().collect::<String>()));
+            tokens.push(last.to_string());
+            trailing_run = run.len();
+            run.clear();
+        }
+        if c.is_alphanumeric() {
+            word.push(c);
+            trailing_run = 0;
+        } else if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+    }
+    (tokens, trailing_run)
+}
+
+/// Han, kana and Hangul letters: scripts written without spaces between words.
+fn is_cjk(c: char) -> bool {
+    c.is_alphanumeric()
+        && matches!(c,
+            '\u{3040}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{20000}'..='\u{2FFFF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chapter;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().expect("open in-memory library db");
+        library::create_schema(&conn).expect("create library schema");
+        for (id, title) in [("book-1", "修仙传"), ("book-2", "Eriri")] {
+            conn.execute(
+                "INSERT INTO books (id, title, path, author_id, library_id, size,
+                                    created_at, starred, deleted)
+                 VALUES (?1, ?2, '/library/' || ?1, 'author-1', 'library-1', 1, 1, 0, 0)",
+                params![id, title],
+            )
+            .expect("insert book");
+        }
+        conn
+    }
+
+    fn content(lines: &[&str], chapters: &[(&str, usize)]) -> BookContent {
+        BookContent {
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            chapters: chapters
+                .iter()
+                .map(|&(title, line_index)| Chapter {
+                    title: title.to_string(),
+                    line_index,
+                    confidence: None,
+                    level: 0,
+                    parent: None,
+                })
+                .collect(),
+            encoding: "UTF-8".to_string(),
+        }
+    }
+
+    fn hits(conn: &Connection, query: &str) -> Vec<(String, i64)> {
+        ranked_rows(conn, query, MAX_HITS, &[])
+            .expect("search index")
+            .into_iter()
+            .map(|row| (row.book_id, row.position))
+            .collect()
+    }
+
+    #[test]
+    fn splits_cjk_runs_into_bigrams_and_terms_into_prefix_phrases() {
+        assert_eq!(
+            index_text("修仙传 Ｅｒｉｒｉ３ 第1章。ア・イ"),
+            "修仙 仙传 传 eriri3 第 1 章 ア イ"
+        );
+        assert_eq!(
+            match_expression("修仙 ｶﾞｲ 仙 eri"),
+            Some("\"修仙\"* AND \"ガイ\"* AND \"仙\"* AND \"eri\"*".to_string())
+        );
+        assert_eq!(
+            match_expression("修仙a传说"),
+            Some("\"修仙 仙 a 传说\"*".to_string())
+        );
+        assert_eq!(match_expression("。 ！"), None);
+    }
+
+    #[test]
+    fn finds_titles_chapters_and_lines_by_cjk_substrings() {
+        let conn = test_conn();
+        write_book(
+            &conn,
+            "book-1",
+            "key-1",
+            "修仙传",
+            &content(
+                &["第一章 入门", "他开始修仙。", "", "仙人指路"],
+                &[("第一章 入门", 0)],
+            ),
+        )
+        .expect("index book");
+        write_book(
+            &conn,
+            "book-2",
+            "key-2",
+            "Eriri",
+            &content(&["ERIRI met a 仙人"], &[]),
+        )
+        .expect("index book");
+
+        let mut found = hits(&conn, "修仙");
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("book-1".to_string(), 1),
+                ("book-1".to_string(), TITLE_POSITION)
+            ]
+        );
+        let mut found = hits(&conn, "仙人");
+        found.sort();
+        assert_eq!(
+            found,
+            vec![("book-1".to_string(), 3), ("book-2".to_string(), 0)]
+        );
+        let mut found = hits(&conn, "入门");
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("book-1".to_string(), 0),
+                ("book-1".to_string(), CHAPTER_FLAG)
+            ]
+        );
+        assert_eq!(hits(&conn, "eri 仙").len(), 1);
+        assert!(hits(&conn, "仙修").is_empty());
+        assert_eq!(
+            source_key(&conn, "book-1").expect("read key").as_deref(),
+            Some("key-1")
+        );
+    }
+
+    #[test]
+    fn leaves_hidden_libraries_out_before_the_limit() {
+        let conn = test_conn();
+        conn.execute(
+            "INSERT INTO books (id, title, path, author_id, library_id, size,
+                                created_at, starred, deleted)
+             VALUES ('book-3', '修仙', '/kids/book-3', 'author-2', 'kids', 1, 1, 0, 0)",
+            [],
+        )
+        .expect("insert hidden book");
+        write_book(
+            &conn,
+            "book-3",
+            "key-3",
+            "修仙",
+            &content(&["修仙"; 3], &[]),
+        )
+        .expect("index hidden book");
+        write_book(&conn, "book-1", "key-1", "修仙传", &content(&[], &[])).expect("index book");
+
+        let found: Vec<_> = ranked_rows(&conn, "修仙", 1, &["kids"])
+            .expect("search index")
+            .into_iter()
+            .map(|row| (row.book_id, row.position))
+            .collect();
+        assert_eq!(found, vec![("book-1".to_string(), TITLE_POSITION)]);
+        assert_eq!(hits(&conn, "修仙").len(), 5);
+    }
+
+    #[test]
+    fn rewrites_and_prunes_rows_with_their_books() {
+        let conn = test_conn();
+        write_book(
+            &conn,
+            "book-1",
+            "key-1",
+            "修仙传",
+            &content(&["旧文本"], &[]),
+        )
+        .expect("index book");
+        write_book(
+            &conn,
+            "book-1",
+            "key-2",
+            "修仙传",
+            &content(&["新文本"], &[]),
+        )
+        .expect("reindex book");
+
+        assert!(hits(&conn, "旧文").is_empty());
+        assert_eq!(hits(&conn, "新文"), vec![("book-1".to_string(), 0)]);
+        assert_eq!(
+            source_key(&conn, "book-1").expect("read key").as_deref(),
+            Some("key-2")
+        );
+
+        conn.execute("DELETE FROM books WHERE id = 'book-1'", [])
+            .expect("remove book");
+        prune(&conn).expect("prune index");
+        assert!(hits(&conn, "新文").is_empty());
+        assert_eq!(source_key(&conn, "book-1").expect("read key"), None);
+        assert_eq!(
+            conn.query_row("SELECT COUNT(*) FROM book_text", [], |r| r.get::<_, i64>(0))
+                .expect("count rows"),
+            0
+        );
+    }
+}
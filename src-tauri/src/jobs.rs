@@ -0,0 +1,546 @@
+//! Background jobs: library imports, refreshes, watched-entry updates and
+//! cache cleaning.
+//!
+//! A job runs on the blocking pool and reports its progress through a shared
+//! `ScanProgress`, which is also how it is cancelled. Jobs of one library run
+//! one at a time, in submission order, on a single worker; cache cleaning has
+//! a lane of its own. Submitting work that an active job of the library
+//! already covers returns that job instead of queueing another. Jobs live in
+//! memory only, and only the latest finished ones are kept.
+
+use std::any::Any;
+use std::collections::{BTreeSet, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::scanner::ScanProgress;
+use crate::scanner::utils::{current_time_millis, generate_uuid};
+
+/// Finished jobs kept for inspection; older ones are dropped.
+const FINISHED_KEPT: usize = 50;
+
+pub struct Jobs(Mutex<JobTable>);
+
+/// The work a job does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Task {
+    /// Import the library at a path.
+    Import(String),
+    /// Rescan a whole library.
+    Refresh(String),
+    /// Rescan some entries directly under a library's root.
+    Update {
+        library_id: String,
+        entries: BTreeSet<PathBuf>,
+    },
+    /// Clear the thumbnail and parsed-book caches.
+    CleanCache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    Import,
+    Refresh,
+    Update,
+    CleanCache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// A snapshot of one job, as served by the API.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    /// `None` for cache cleaning.
+    pub library_id: Option<String>,
+    pub state: JobState,
+    /// Comics and books looked at so far.
+    pub scanned: u64,
+    /// Cover thumbnails written so far.
+    pub thumbnails: u64,
+    /// Why a failed job failed.
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+}
+
+impl Task {
+    fn kind(&self) -> JobKind {
+        match self {
+            Task::Import(_) => JobKind::Import,
+            Task::Refresh(_) => JobKind::Refresh,
+            Task::Update { .. } => JobKind::Update,
+            Task::CleanCache => JobKind::CleanCache,
+        }
+    }
+
+    /// The library the task works on, which is also its lane. An imported
+    /// library's id derives from its path.
+    fn library_id(&self) -> Option<String> {
+        match self {
+            Task::Import(path) => Some(generate_uuid(path)),
+            Task::Refresh(id) | Task::Update { library_id: id, .. } => Some(id.clone()),
+            Task::CleanCache => None,
+        }
+    }
+
+    /// Widen this queued task to also cover `other`, from the same lane.
+    /// False when they do different work.
+    fn absorb(&mut self, other: &Task) -> bool {
+        match (&mut *self, other) {
+            (this, other) if this == other => true,
+            (Task::Refresh(_), Task::Update { .. }) => true,
+            (Task::Update { entries, .. }, Task::Update { entries: more, .. }) => {
+                entries.extend(more.iter().cloned());
+                true
+            }
+            (Task::Update { .. }, Task::Refresh(_)) => {
+                *self = other.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Entry {
+    id: u64,
+    task: Task,
+    state: JobState,
+    error: Option<String>,
+    created_at: u64,
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    progress: Arc<ScanProgress>,
+}
+
+impl Entry {
+    fn job(&self) -> Job {
+        use std::sync::atomic::Ordering;
+
+        Job {
+            id: self.id,
+            kind: self.task.kind(),
+            library_id: self.task.library_id(),
+            state: self.state,
+            scanned: self.progress.scanned.load(Ordering::Relaxed),
+            thumbnails: self.progress.thumbnails.load(Ordering::Relaxed),
+            error: self.error.clone(),
+            created_at: self.created_at,
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(self.state, JobState::Queued | JobState::Running)
+    }
+}
+
+/// Every job still kept, oldest first.
+#[derive(Default)]
+struct JobTable {
+    last_id: u64,
+    jobs: Vec<Entry>,
+    /// Lanes with a worker running, which keeps claiming until its lane is
+    /// empty. Tracked apart from job states so a job submitted between one
+    /// job finishing and the next being claimed does not start a second one.
+    workers: HashSet<Option<String>>,
+}
+
+impl JobTable {
+    /// Queue `task`, or return the active job that already covers it. The
+    /// flag is true when the lane has no worker yet and one must be started.
+    fn submit(&mut self, task: Task, now: u64) -> (Job, bool) {
+        let lane = task.library_id();
+        for entry in &mut self.jobs {
+            if entry.task.library_id() != lane {
+                continue;
+            }
+            let covered = match entry.state {
+                JobState::Queued => entry.task.absorb(&task),
+                JobState::Running => entry.task == task,
+                _ => false,
+            };
+            if covered {
+                return (entry.job(), false);
+            }
+        }
+
+        let start = self.workers.insert(lane);
+        self.last_id += 1;
+        let entry = Entry {
+            id: self.last_id,
+            task,
+            state: JobState::Queued,
+            error: None,
+            created_at: now,
+            started_at: None,
+            finished_at: None,
+            progress: Arc::default(),
+        };
+        let job = entry.job();
+        self.jobs.push(entry);
+        (job, start)
+    }
+
+    /// Start the lane's next queued job. With none left the lane's worker
+    /// is done, and the next submission starts a new one.
+    fn claim(&mut self, lane: Option<&str>, now: u64) -> Option<(u64, Task, Arc<ScanProgress>)> {
+        let Some(entry) = self.jobs.iter_mut().find(|entry| {
+            entry.state == JobState::Queued && entry.task.library_id().as_deref() == lane
+        }) else {
+            self.workers.remove(&lane.map(str::to_string));
+            return None;
+        };
+        entry.state = JobState::Running;
+        entry.started_at = Some(now);
+        Some((entry.id, entry.task.clone(), entry.progress.clone()))
+    }
+
+    /// Record how a running job ended. A job that failed after being
+    /// cancelled counts as cancelled.
+    fn finish(&mut self, id: u64, result: Result<(), String>, now: u64) -> Option<Job> {
+        let entry = self.jobs.iter_mut().find(|entry| entry.id == id)?;
+        entry.state = match result {
+            Ok(()) => JobState::Succeeded,
+            Err(_) if entry.progress.is_cancelled() => JobState::Cancelled,
+            Err(e) => {
+                entry.error = Some(e);
+                JobState::Failed
+            }
+        };
+        entry.finished_at = Some(now);
+        let job = entry.job();
+
+        let finished = self.jobs.iter().filter(|entry| !entry.is_active()).count();
+        let mut excess = finished.saturating_sub(FINISHED_KEPT);
+        self.jobs.retain(|entry| {
+            if excess > 0 && !entry.is_active() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+        Some(job)
+    }
+
+    /// Drop a queued job, or ask a running one to stop at its next check.
+    /// Finished jobs are returned unchanged.
+    fn cancel(&mut self, id: u64, now: u64) -> Option<Job> {
+        let entry = self.jobs.iter_mut().find(|entry| entry.id == id)?;
+        match entry.state {
+            JobState::Queued => {
+                entry.progress.cancel();
+                entry.state = JobState::Cancelled;
+                entry.finished_at = Some(now);
+            }
+            JobState::Running => entry.progress.cancel(),
+            _ => {}
+        }
+        Some(entry.job())
+    }
+
+    /// Cancel every active job of the library `library_id`.
+    fn cancel_library(&mut self, library_id: &str, now: u64) {
+        let ids: Vec<u64> = self
+            .jobs
+            .iter()
+            .filter(|entry| {
+                entry.is_active() && entry.task.library_id().as_deref() == Some(library_id)
+            })
+            .map(|entry| entry.id)
+            .collect();
+        for id in ids {
+            self.cancel(id, now);
+        }
+    }
+
+    fn get(&self, id: u64) -> Option<Job> {
+        self.jobs
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(Entry::job)
+    }
+
+    /// Newest first.
+    fn list(&self) -> Vec<Job> {
+        self.jobs.iter().rev().map(Entry::job).collect()
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(Jobs(Mutex::new(JobTable::default())));
+}
+
+/// Run `task` in the background and return its job, or the active job that
+/// already covers it.
+pub fn submit(app: &AppHandle, task: Task) -> Result<Job, String> {
+    let lane = task.library_id();
+    let (job, start) = {
+        let state = app.state::<Jobs>();
+        let mut table = state.0.lock().map_err(|e| e.to_string())?;
+        table.submit(task, current_time_millis())
+    };
+    if start {
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || work(&app, lane.as_deref()));
+    }
+    Ok(job)
+}
+
+pub fn list(app: &AppHandle) -> Result<Vec<Job>, String> {
+    let state = app.state::<Jobs>();
+    let table = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(table.list())
+}
+
+pub fn get(app: &AppHandle, id: u64) -> Result<Option<Job>, String> {
+    let state = app.state::<Jobs>();
+    let table = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(table.get(id))
+}
+
+pub fn cancel(app: &AppHandle, id: u64) -> Result<Option<Job>, String> {
+    let state = app.state::<Jobs>();
+    let mut table = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(table.cancel(id, current_time_millis()))
+}
+
+/// Cancel the jobs of a library that is being removed, so none of them writes
+/// its rows back afterwards.
+pub fn cancel_library(app: &AppHandle, library_id: &str) -> Result<(), String> {
+    let state = app.state::<Jobs>();
+    let mut table = state.0.lock().map_err(|e| e.to_string())?;
+    table.cancel_library(library_id, current_time_millis());
+    Ok(())
+}
+
+/// Run the lane's queued jobs one after another until none is left. A job
+/// that panics fails like any other, and the lane moves on.
+fn work(app: &AppHandle, lane: Option<&str>) {
+    let state = app.state::<Jobs>();
+    loop {
+        let Some((id, task, progress)) = state
+            .0
+            .lock()
+            .ok()
+            .and_then(|mut table| table.claim(lane, current_time_millis()))
+        else {
+            return;
+        };
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(app, &task, &progress)))
+            .unwrap_or_else(|panic| Err(panic_message(panic.as_ref())));
+        if let Err(e) = &result
+            && !progress.is_cancelled()
+        {
+            warn!(job = id, task = ?task.kind(), error = %e, "Job failed");
+        }
+        let Ok(mut table) = state.0.lock() else {
+            return;
+        };
+        if let Some(job) = table.finish(id, result, current_time_millis()) {
+            info!(
+                job = id,
+                task = ?job.kind,
+                state = ?job.state,
+                scanned = job.scanned,
+                thumbnails = job.thumbnails,
+                "Job finished"
+            );
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    let detail = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("Job panicked: {detail}")
+}
+
+fn run(app: &AppHandle, task: &Task, progress: &ScanProgress) -> Result<(), String> {
+    match task {
+        Task::Import(path) => {
+            if crate::library::import(app, path, progress)?.created {
+                crate::server::rebuild_allowed_roots(app);
+                crate::watcher::rebuild(app);
+                crate::tray::rebuild(app);
+            }
+            Ok(())
+        }
+        Task::Refresh(id) => crate::library::refresh(app, id, progress),
+        Task::Update {
+            library_id,
+            entries,
+        } => crate::library::update_entries(
+            app,
+            library_id,
+            &entries.iter().cloned().collect::<Vec<_>>(),
+            progress,
+        ),
+        Task::CleanCache => {
+            let books = crate::scanner::book_index::cache_dir(app);
+            // (0, 0) => no age/size threshold => clear everything.
+            crate::thumbnail::clean_thumbnail_cache(app.clone(), Some(0), Some(0))?;
+            crate::scanner::book_index::clear(&books)?;
+            crate::tray::rebuild(app);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(library_id: &str, entries: &[&str]) -> Task {
+        Task::Update {
+            library_id: library_id.to_string(),
+            entries: entries.iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    #[test]
+    fn jobs_of_a_library_are_deduplicated_and_run_one_at_a_time() {
+        let mut table = JobTable::default();
+
+        let (refresh, start) = table.submit(Task::Refresh("lib".into()), 1);
+        assert!(start);
+        let (again, start) = table.submit(Task::Refresh("lib".into()), 2);
+        assert_eq!((again.id, start), (refresh.id, false));
+
+        let (id, task, _) = table.claim(Some("lib"), 3).expect("claim refresh");
+        assert_eq!((id, task), (refresh.id, Task::Refresh("lib".into())));
+
+        // A refresh already running returns itself; entry updates queue
+        // behind it and merge while they wait.
+        let (running, start) = table.submit(Task::Refresh("lib".into()), 4);
+        assert_eq!(
+            (running.id, running.state, start),
+            (refresh.id, JobState::Running, false)
+        );
+        let (first, start) = table.submit(update("lib", &["/lib/a"]), 5);
+        assert!(!start);
+        let (second, _) = table.submit(update("lib", &["/lib/b"]), 6);
+        assert_eq!(second.id, first.id);
+
+        // Other libraries have lanes of their own.
+        let (other, start) = table.submit(Task::Refresh("other".into()), 7);
+        assert!(start);
+        assert_ne!(other.id, first.id);
+
+        let finished = table.finish(refresh.id, Ok(()), 8).expect("finish refresh");
+        assert_eq!(finished.state, JobState::Succeeded);
+        let (_, task, _) = table.claim(Some("lib"), 9).expect("claim update");
+        assert_eq!(task, update("lib", &["/lib/a", "/lib/b"]));
+    }
+
+    #[test]
+    fn a_lane_keeps_its_one_worker_until_nothing_is_queued() {
+        let mut table = JobTable::default();
+        let (refresh, start) = table.submit(Task::Refresh("lib".into()), 1);
+        assert!(start);
+        table.claim(Some("lib"), 2).expect("claim refresh");
+        table.finish(refresh.id, Ok(()), 3).expect("finish refresh");
+
+        // Submitted after the refresh finished but before the worker looks
+        // for more: the worker picks it up, so no second one starts.
+        let (update, start) = table.submit(update("lib", &["/lib/a"]), 4);
+        assert!(!start);
+        let (id, _, _) = table.claim(Some("lib"), 5).expect("claim update");
+        assert_eq!(id, update.id);
+        table.finish(update.id, Ok(()), 6).expect("finish update");
+
+        assert!(table.claim(Some("lib"), 7).is_none());
+        let (_, start) = table.submit(Task::Refresh("lib".into()), 8);
+        assert!(start);
+    }
+
+    #[test]
+    fn a_queued_update_becomes_a_refresh_and_cancelled_jobs_end_as_cancelled() {
+        let mut table = JobTable::default();
+        let (running, _) = table.submit(update("lib", &["/lib/a"]), 1);
+        table.claim(Some("lib"), 2).expect("claim update");
+        let (queued, _) = table.submit(update("lib", &["/lib/b"]), 3);
+        let (refresh, _) = table.submit(Task::Refresh("lib".into()), 4);
+        assert_eq!((refresh.id, refresh.kind), (queued.id, JobKind::Refresh));
+
+        let cancelled = table.cancel(queued.id, 5).expect("cancel queued job");
+        assert_eq!(cancelled.state, JobState::Cancelled);
+        let stopping = table.cancel(running.id, 5).expect("cancel running job");
+        assert_eq!(stopping.state, JobState::Running);
+        let stopped = table
+            .finish(running.id, Err("Cancelled".into()), 6)
+            .expect("finish cancelled job");
+        assert_eq!((stopped.state, stopped.error), (JobState::Cancelled, None));
+        assert!(table.claim(Some("lib"), 7).is_none());
+
+        let (failed, _) = table.submit(Task::CleanCache, 8);
+        table.claim(None, 9).expect("claim clean");
+        let failed = table
+            .finish(failed.id, Err("disk full".into()), 10)
+            .expect("finish failed job");
+        assert_eq!(
+            (failed.state, failed.error.as_deref()),
+            (JobState::Failed, Some("disk full"))
+        );
+        assert_eq!(
+            table.list().iter().map(|job| job.id).collect::<Vec<_>>(),
+            vec![failed.id, queued.id, running.id]
+        );
+    }
+
+    #[test]
+    fn cancelling_a_library_stops_its_lane_only() {
+        let mut table = JobTable::default();
+        let (running, _) = table.submit(Task::Refresh("lib".into()), 1);
+        table.claim(Some("lib"), 2).expect("claim refresh");
+        let (queued, _) = table.submit(update("lib", &["/lib/a"]), 3);
+        let (other, _) = table.submit(Task::Refresh("other".into()), 4);
+
+        table.cancel_library("lib", 5);
+        let state = |id| table.get(id).map(|job| job.state);
+        assert_eq!(state(queued.id), Some(JobState::Cancelled));
+        assert_eq!(state(other.id), Some(JobState::Queued));
+        let stopped = table
+            .finish(running.id, Err("Cancelled".into()), 6)
+            .expect("finish cancelled refresh");
+        assert_eq!(stopped.state, JobState::Cancelled);
+    }
+
+    #[test]
+    fn a_panicking_job_reports_the_panic_as_its_error() {
+        let panic = panic::catch_unwind(|| panic!("page out of range")).expect_err("job panics");
+        assert_eq!(
+            panic_message(panic.as_ref()),
+            "Job panicked: page out of range"
+        );
+        let panic =
+            panic::catch_unwind(|| panic!("page {} out of range", 3)).expect_err("job panics");
+        assert_eq!(
+            panic_message(panic.as_ref()),
+            "Job panicked: page 3 out of range"
+        );
+    }
+}
@@ -1,12 +1,22 @@
+mod access;
+mod auth;
+mod catalog;
 mod config;
+mod events;
+mod fulltext;
+mod jobs;
 mod library;
 mod models;
+mod policy;
+mod profile;
 mod progress;
+mod revision;
 mod scanner;
 mod server;
 mod tags;
 mod thumbnail;
 mod tray;
+mod watcher;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -17,9 +27,13 @@ pub fn run() {
             config::init(app)?;
             thumbnail::init(app)?;
             progress::init(app.handle())?;
+            auth::init(app.handle())?;
             library::init(app.handle())?;
+            jobs::init(app.handle());
+            events::init(app.handle());
             server::init_allowed_roots(app.handle());
             server::init(app.handle());
+            watcher::init(app.handle());
             tray::setup(app)?;
 
             // Run as a menu-bar accessory: no Dock icon, no window on launch.
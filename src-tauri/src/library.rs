@@ -5,15 +5,24 @@
 //! (starred / deleted) stay sourced from macOS file xattr — the DB only mirrors
 //! them.
 
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 use rusqlite::{Connection, params};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, Runtime};
 use tracing::{info, warn};
 
+use crate::access::Access;
 use crate::config;
+use crate::events::{self, Event};
 use crate::models::{Author, Book, Comic, FileTags};
+use crate::revision;
+use crate::scanner::book::ParseOptions;
+use crate::scanner::chapter::{ChapterRule, default_rules};
+use crate::scanner::paragraph::ParagraphOptions;
+use crate::scanner::{LibraryScan, ScanProgress, Stamped};
 
 pub struct LibraryDb(pub Mutex<Connection>);
 
@@ -28,6 +37,9 @@ pub struct Library {
     pub created_at: i64,
     #[serde(rename = "sortOrder")]
     pub sort_order: i64,
+    /// Revision of the last write to this row; a refresh always writes one.
+    #[serde(default)]
+    pub revision: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -50,19 +62,49 @@ pub struct AuthorRow {
 
 #[derive(Debug, Serialize)]
 pub struct Catalog {
+    /// The catalog revision these rows are current at.
+    pub revision: i64,
     pub libraries: Vec<Library>,
     pub comics: Vec<Comic>,
     pub authors: Vec<AuthorRow>,
     pub books: Vec<Book>,
 }
 
+/// Catalog rows written after a revision, and the ids of rows deleted since.
+#[derive(Debug, Serialize)]
+pub struct CatalogChanges {
+    #[serde(flatten)]
+    pub catalog: Catalog,
+    pub removed: RemovedRows,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RemovedRows {
+    pub libraries: Vec<String>,
+    pub comics: Vec<String>,
+    pub authors: Vec<String>,
+    pub books: Vec<String>,
+}
+
+/// Tables whose rows make up the catalog, each keyed by `id`.
+const CATALOG_TABLES: [&str; 4] = ["libraries", "comics", "authors", "books"];
+
+/// The column naming the library of each catalog table's rows.
+const CATALOG_LIBRARY_COLUMNS: [(&str, &str); 4] = [
+    ("libraries", "id"),
+    ("comics", "library_id"),
+    ("authors", "library_id"),
+    ("books", "library_id"),
+];
+
 const LIBRARY_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS libraries (
         id         TEXT PRIMARY KEY,
         name       TEXT NOT NULL,
         path       TEXT NOT NULL,
         type       TEXT NOT NULL,
         created_at INTEGER NOT NULL,
-        sort_order INTEGER NOT NULL
+        sort_order INTEGER NOT NULL,
+        revision   INTEGER NOT NULL DEFAULT 0
     );
     CREATE TABLE IF NOT EXISTS comics (
         id         TEXT PRIMARY KEY,
@@ -72,14 +114,18 @@ const LIBRARY_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS libraries (
         library_id TEXT NOT NULL,
         created_at INTEGER NOT NULL,
         starred    INTEGER NOT NULL,
-        deleted    INTEGER NOT NULL
+        deleted    INTEGER NOT NULL,
+        mtime      INTEGER,
+        revision   INTEGER NOT NULL DEFAULT 0
     );
     CREATE TABLE IF NOT EXISTS authors (
         id         TEXT PRIMARY KEY,
         name       TEXT NOT NULL,
         path       TEXT NOT NULL,
         library_id TEXT NOT NULL,
-        book_count INTEGER NOT NULL
+        book_count INTEGER NOT NULL,
+        mtime      INTEGER,
+        revision   INTEGER NOT NULL DEFAULT 0
     );
     CREATE TABLE IF NOT EXISTS books (
         id         TEXT PRIMARY KEY,
@@ -90,7 +136,26 @@ const LIBRARY_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS libraries (
         size       INTEGER NOT NULL,
         created_at INTEGER NOT NULL,
         starred    INTEGER NOT NULL,
-        deleted    INTEGER NOT NULL
+        deleted    INTEGER NOT NULL,
+        encoding   TEXT,
+        mtime      INTEGER,
+        revision   INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS chapter_rules (
+        library_id TEXT PRIMARY KEY,
+        rules      TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS paragraph_options (
+        library_id TEXT PRIMARY KEY,
+        options    TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS book_text_sources (
+        seq     INTEGER PRIMARY KEY,
+        book_id TEXT NOT NULL UNIQUE,
+        key     TEXT NOT NULL
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS book_text USING fts5(
+        text, content = '', contentless_delete = 1
     );
     CREATE INDEX IF NOT EXISTS idx_comics_library ON comics(library_id);
     CREATE INDEX IF NOT EXISTS idx_authors_library ON authors(library_id);
@@ -98,33 +163,41 @@ const LIBRARY_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS libraries (
     CREATE INDEX IF NOT EXISTS idx_books_library ON books(library_id);";
 
 const UPSERT_LIBRARY_SQL: &str =
-    "INSERT INTO libraries (id, name, path, type, created_at, sort_order)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+    "INSERT INTO libraries (id, name, path, type, created_at, sort_order, revision)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
          ON CONFLICT(id) DO UPDATE SET
             name = excluded.name, path = excluded.path, type = excluded.type,
-            created_at = excluded.created_at, sort_order = excluded.sort_order";
+            created_at = excluded.created_at, sort_order = excluded.sort_order,
+            revision = excluded.revision";
 
-const UPSERT_COMIC_SQL: &str =
-    "INSERT INTO comics (id, title, path, cover, library_id, created_at, starred, deleted)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+const UPSERT_COMIC_SQL: &str = "INSERT INTO comics
+            (id, title, path, cover, library_id, created_at, starred, deleted, mtime, revision)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
          ON CONFLICT(id) DO UPDATE SET
             title = excluded.title, path = excluded.path, cover = excluded.cover,
             library_id = excluded.library_id, created_at = excluded.created_at,
-            starred = excluded.starred, deleted = excluded.deleted";
+            starred = excluded.starred, deleted = excluded.deleted, mtime = excluded.mtime,
+            revision = excluded.revision";
 
-const UPSERT_AUTHOR_SQL: &str = "INSERT INTO authors (id, name, path, library_id, book_count)
-         VALUES (?1, ?2, ?3, ?4, ?5)
+const UPSERT_AUTHOR_SQL: &str =
+    "INSERT INTO authors (id, name, path, library_id, book_count, mtime, revision)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
          ON CONFLICT(id) DO UPDATE SET
-            name = excluded.name, path = excluded.path,
-            library_id = excluded.library_id, book_count = excluded.book_count";
-
-const UPSERT_BOOK_SQL: &str =
-    "INSERT INTO books (id, title, path, author_id, library_id, size, created_at, starred, deleted)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            name = excluded.name, path = excluded.path, library_id = excluded.library_id,
+            book_count = excluded.book_count, mtime = excluded.mtime,
+            revision = excluded.revision";
+
+// A detected encoding survives the update while the book's size is unchanged.
+const UPSERT_BOOK_SQL: &str = "INSERT INTO books
+            (id, title, path, author_id, library_id, size, created_at, starred, deleted, mtime,
+             revision)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
          ON CONFLICT(id) DO UPDATE SET
             title = excluded.title, path = excluded.path, author_id = excluded.author_id,
             library_id = excluded.library_id, size = excluded.size,
-            created_at = excluded.created_at, starred = excluded.starred, deleted = excluded.deleted";
+            created_at = excluded.created_at, starred = excluded.starred,
+            deleted = excluded.deleted, mtime = excluded.mtime, revision = excluded.revision,
+            encoding = CASE WHEN books.size = excluded.size THEN books.encoding END";
 
 // --- Setup ---
 
@@ -155,7 +228,7 @@ fn open_db(app: &AppHandle) -> rusqlite::Result<Connection> {
 
     let conn = Connection::open(&db_path)?;
     conn.pragma_update(None, "journal_mode", "WAL")?;
-    conn.execute_batch(LIBRARY_SCHEMA)?;
+    create_schema(&conn)?;
 
     // Migrate legacy `asset://localhost/<enc>` covers to `/file?path=<enc>`
     // (the encoded path is identical, only the prefix changed).
@@ -165,11 +238,39 @@ fn open_db(app: &AppHandle) -> rusqlite::Result<Connection> {
         [],
     );
 
+    // Databases created before books remembered their text encoding.
+    let _ = conn.execute("ALTER TABLE books ADD COLUMN encoding TEXT", []);
+    // Databases created before refreshes compared modification times; their
+    // rows are read again on the next refresh.
+    for table in ["comics", "authors", "books"] {
+        let _ = conn.execute(&format!("ALTER TABLE {table} ADD COLUMN mtime INTEGER"), []);
+    }
+    // Databases created before revisions; their rows all count as revision 0.
+    for table in CATALOG_TABLES {
+        let _ = conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN revision INTEGER NOT NULL DEFAULT 0"),
+            [],
+        );
+    }
+
     migrate_from_json(app, &conn);
 
     Ok(conn)
 }
 
+/// Create the catalog tables, the revision counter and the triggers that leave
+/// tombstones for deleted catalog rows.
+pub(crate) fn create_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(LIBRARY_SCHEMA)?;
+    conn.execute_batch(revision::REVISION_SCHEMA)?;
+    // Databases created before tombstones kept their library.
+    let _ = conn.execute("ALTER TABLE tombstones ADD COLUMN library_id TEXT", []);
+    for (table, library) in CATALOG_LIBRARY_COLUMNS {
+        conn.execute_batch(&revision::tombstone_trigger(table, "id", library))?;
+    }
+    Ok(())
+}
+
 /// One-time import of the legacy `library.json` blob, only when the DB is empty.
 fn migrate_from_json(app: &AppHandle, conn: &Connection) {
     let count: i64 = conn
@@ -234,6 +335,8 @@ fn migrate_from_json(app: &AppHandle, conn: &Connection) {
 
 // --- Row helpers ---
 
+// Each helper write is a mutation of its own, at the next revision.
+
 fn upsert_library(conn: &Connection, lib: &Library) -> rusqlite::Result<()> {
     conn.execute(
         UPSERT_LIBRARY_SQL,
@@ -243,7 +346,8 @@ fn upsert_library(conn: &Connection, lib: &Library) -> rusqlite::Result<()> {
             lib.path,
             lib.type_,
             lib.created_at,
-            lib.sort_order
+            lib.sort_order,
+            revision::next(conn)?
         ],
     )?;
     Ok(())
@@ -270,7 +374,9 @@ fn upsert_comic(conn: &Connection, c: &Comic) -> rusqlite::Result<()> {
             c.library_id,
             c.created_at as i64,
             c.starred as i64,
-            c.deleted as i64
+            c.deleted as i64,
+            None::<i64>,
+            revision::next(conn)?
         ],
     )?;
     Ok(())
@@ -279,7 +385,15 @@ fn upsert_comic(conn: &Connection, c: &Comic) -> rusqlite::Result<()> {
 fn upsert_author(conn: &Connection, a: &Author) -> rusqlite::Result<()> {
     conn.execute(
         UPSERT_AUTHOR_SQL,
-        params![a.id, a.name, a.path, a.library_id, a.book_count as i64],
+        params![
+            a.id,
+            a.name,
+            a.path,
+            a.library_id,
+            a.book_count as i64,
+            None::<i64>,
+            revision::next(conn)?
+        ],
     )?;
     Ok(())
 }
@@ -296,37 +410,58 @@ fn upsert_book(conn: &Connection, b: &Book) -> rusqlite::Result<()> {
             b.size as i64,
             b.created_at as i64,
             b.starred as i64,
-            b.deleted as i64
+            b.deleted as i64,
+            None::<i64>,
+            revision::next(conn)?
         ],
     )?;
     Ok(())
 }
 
-/// Replace all scanned content for one library (comics + authors + books).
-fn replace_library_content(
+/// Rows written, rewritten and deleted by one `sync_library_content`.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SyncCounts {
+    added: usize,
+    updated: usize,
+    removed: usize,
+}
+
+impl std::ops::AddAssign for SyncCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.added += other.added;
+        self.updated += other.updated;
+        self.removed += other.removed;
+    }
+}
+
+/// Bring one library's stored content in line with `scan`: insert new
+/// entries, rewrite the ones that differ from their row and delete the ones no
+/// longer found, all at one new revision. Untouched rows are left alone. With a
+/// `scope`, `scan` covers only those top-level entries, and rows outside them
+/// are never deleted.
+fn sync_library_content(
     conn: &Connection,
     library_id: &str,
-    comics: &[Comic],
-    authors: &[Author],
-) -> rusqlite::Result<()> {
+    scan: &LibraryScan,
+    scope: Option<&[PathBuf]>,
+) -> rusqlite::Result<SyncCounts> {
     let tx = conn.unchecked_transaction()?;
-    tx.execute(
-        "DELETE FROM comics WHERE library_id = ?1",
-        params![library_id],
-    )?;
-    tx.execute(
-        "DELETE FROM books WHERE library_id = ?1",
-        params![library_id],
-    )?;
-    tx.execute(
-        "DELETE FROM authors WHERE library_id = ?1",
-        params![library_id],
-    )?;
-
-    {
-        let mut stmt = tx.prepare_cached(UPSERT_COMIC_SQL)?;
-        for c in comics {
-            stmt.execute(params![
+    // The library was removed while it was being scanned.
+    if !library_exists(&tx, library_id)? {
+        return Ok(SyncCounts::default());
+    }
+    let stored = stored_scan(&tx, library_id)?;
+    let revision = revision::next(&tx)?;
+
+    let mut counts = sync_table(
+        &tx,
+        "comics",
+        library_id,
+        scope,
+        &stored.comics,
+        &scan.comics,
+        |Stamped { entry: c, mtime }| {
+            tx.prepare_cached(UPSERT_COMIC_SQL)?.execute(params![
                 c.id,
                 c.title,
                 c.path,
@@ -334,50 +469,263 @@ fn replace_library_content(
                 c.library_id,
                 c.created_at as i64,
                 c.starred as i64,
-                c.deleted as i64
-            ])?;
-        }
-    }
-
-    {
-        let mut author_stmt = tx.prepare_cached(UPSERT_AUTHOR_SQL)?;
-        let mut book_stmt = tx.prepare_cached(UPSERT_BOOK_SQL)?;
-        for a in authors {
-            author_stmt.execute(params![
+                c.deleted as i64,
+                *mtime as i64,
+                revision
+            ])
+        },
+    )?;
+    counts += sync_table(
+        &tx,
+        "authors",
+        library_id,
+        scope,
+        &stored.authors,
+        &scan.authors,
+        |Stamped { entry: a, mtime }| {
+            tx.prepare_cached(UPSERT_AUTHOR_SQL)?.execute(params![
                 a.id,
                 a.name,
                 a.path,
                 a.library_id,
-                a.book_count as i64
-            ])?;
-            for b in &a.books {
-                book_stmt.execute(params![
-                    b.id,
-                    b.title,
-                    b.path,
-                    b.author_id,
-                    b.library_id,
-                    b.size as i64,
-                    b.created_at as i64,
-                    b.starred as i64,
-                    b.deleted as i64
-                ])?;
-            }
+                a.book_count as i64,
+                *mtime as i64,
+                revision
+            ])
+        },
+    )?;
+    counts += sync_table(
+        &tx,
+        "books",
+        library_id,
+        scope,
+        &stored.books,
+        &scan.books,
+        |Stamped { entry: b, mtime }| {
+            tx.prepare_cached(UPSERT_BOOK_SQL)?.execute(params![
+                b.id,
+                b.title,
+                b.path,
+                b.author_id,
+                b.library_id,
+                b.size as i64,
+                b.created_at as i64,
+                b.starred as i64,
+                b.deleted as i64,
+                *mtime as i64,
+                revision
+            ])
+        },
+    )?;
+
+    tx.commit()?;
+    Ok(counts)
+}
+
+/// A catalog row kept in step with scans by `sync_table`.
+trait Row: PartialEq {
+    fn id(&self) -> &str;
+}
+
+impl Row for Comic {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Row for Author {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Row for Book {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+fn sync_table<T: Row>(
+    tx: &Connection,
+    table: &str,
+    library_id: &str,
+    scope: Option<&[PathBuf]>,
+    stored: &[Stamped<T>],
+    scanned: &[Stamped<T>],
+    mut write: impl FnMut(&Stamped<T>) -> rusqlite::Result<usize>,
+) -> rusqlite::Result<SyncCounts> {
+    let stored: HashMap<&str, &Stamped<T>> =
+        stored.iter().map(|row| (row.entry.id(), row)).collect();
+    let mut gone = HashSet::new();
+    let mut stmt = tx.prepare(&format!(
+        "SELECT id, path FROM {table} WHERE library_id = ?1"
+    ))?;
+    let mut rows = stmt.query(params![library_id])?;
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(1)?;
+        if scope.is_none_or(|entries| {
+            entries
+                .iter()
+                .any(|entry| Path::new(&path).starts_with(entry))
+        }) {
+            gone.insert(row.get::<_, String>(0)?);
         }
     }
 
-    tx.commit()
+    let mut counts = SyncCounts::default();
+    for item in scanned {
+        let existed = gone.remove(item.entry.id());
+        if stored.get(item.entry.id()) == Some(&item) {
+            continue;
+        }
+        write(item)?;
+        if existed {
+            counts.updated += 1;
+        } else {
+            counts.added += 1;
+        }
+    }
+
+    let mut delete = tx.prepare(&format!("DELETE FROM {table} WHERE id = ?1"))?;
+    for gone_id in &gone {
+        delete.execute(params![gone_id])?;
+    }
+    counts.removed = gone.len();
+    Ok(counts)
+}
+
+/// One library's content as its last scan stored it, for the next scan to
+/// diff against. Rows stored before modification times were recorded are
+/// left out, so they are read again.
+fn stored_scan(conn: &Connection, library_id: &str) -> rusqlite::Result<LibraryScan> {
+    let comics = conn
+        .prepare(&format!(
+            "SELECT {COMIC_COLUMNS}, mtime FROM comics
+             WHERE library_id = ?1 AND mtime IS NOT NULL"
+        ))?
+        .query_map(params![library_id], |row| {
+            Ok(Stamped {
+                entry: comic_from_row(row)?,
+                mtime: row.get::<_, i64>(8)? as u64,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let authors = conn
+        .prepare(
+            "SELECT id, name, path, library_id, book_count, mtime FROM authors
+             WHERE library_id = ?1 AND mtime IS NOT NULL",
+        )?
+        .query_map(params![library_id], |row| {
+            Ok(Stamped {
+                entry: Author {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    path: row.get(2)?,
+                    library_id: row.get(3)?,
+                    book_count: row.get::<_, i64>(4)? as u32,
+                    books: Vec::new(),
+                },
+                mtime: row.get::<_, i64>(5)? as u64,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let books = conn
+        .prepare(&format!(
+            "SELECT {BOOK_COLUMNS}, mtime FROM books
+             WHERE library_id = ?1 AND mtime IS NOT NULL"
+        ))?
+        .query_map(params![library_id], |row| {
+            Ok(Stamped {
+                entry: book_from_row(row)?,
+                mtime: row.get::<_, i64>(9)? as u64,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(LibraryScan {
+        comics,
+        authors,
+        books,
+    })
 }
 
 // --- Catalog read ---
 
+/// Columns read by `comic_from_row`, in order.
+pub(crate) const COMIC_COLUMNS: &str =
+    "id, title, path, cover, library_id, created_at, starred, deleted";
+/// Columns read by `book_from_row`, in order.
+pub(crate) const BOOK_COLUMNS: &str =
+    "id, title, path, author_id, library_id, size, created_at, starred, deleted";
+
+pub(crate) fn comic_from_row(row: &rusqlite::Row) -> rusqlite::Result<Comic> {
+    Ok(Comic {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        path: row.get(2)?,
+        cover: row.get(3)?,
+        library_id: row.get(4)?,
+        created_at: row.get::<_, i64>(5)? as u64,
+        starred: row.get::<_, i64>(6)? != 0,
+        deleted: row.get::<_, i64>(7)? != 0,
+    })
+}
+
+pub(crate) fn book_from_row(row: &rusqlite::Row) -> rusqlite::Result<Book> {
+    Ok(Book {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        path: row.get(2)?,
+        author_id: row.get(3)?,
+        library_id: row.get(4)?,
+        size: row.get::<_, i64>(5)? as u64,
+        created_at: row.get::<_, i64>(6)? as u64,
+        starred: row.get::<_, i64>(7)? != 0,
+        deleted: row.get::<_, i64>(8)? != 0,
+    })
+}
+
 pub fn get_catalog(conn: &Connection) -> rusqlite::Result<Catalog> {
+    catalog_since(conn, None)
+}
+
+/// What changed in the catalog after revision `since`.
+pub fn catalog_changes(
+    conn: &Connection,
+    since: i64,
+    access: &Access,
+) -> rusqlite::Result<CatalogChanges> {
+    let mut catalog = catalog_since(conn, Some(since))?;
+    access.filter_catalog(&mut catalog);
+    let removed = |table| {
+        revision::removed_since(conn, table, "id", since).map(|removed| {
+            removed
+                .into_iter()
+                .filter(|(_, library_id)| access.sees_item(library_id.as_deref()))
+                .map(|(id, _)| id)
+                .collect()
+        })
+    };
+    Ok(CatalogChanges {
+        catalog,
+        removed: RemovedRows {
+            libraries: removed("libraries")?,
+            comics: removed("comics")?,
+            authors: removed("authors")?,
+            books: removed("books")?,
+        },
+    })
+}
+
+/// Rows written after revision `since`, or every row without one.
+fn catalog_since(conn: &Connection, since: Option<i64>) -> rusqlite::Result<Catalog> {
+    let revision = revision::current(conn)?;
+
     let libraries = {
         let mut stmt = conn.prepare(
-            "SELECT id, name, path, type, created_at, sort_order
-             FROM libraries ORDER BY sort_order",
+            "SELECT id, name, path, type, created_at, sort_order, revision
+             FROM libraries WHERE ?1 IS NULL OR revision > ?1 ORDER BY sort_order",
         )?;
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params![since], |row| {
             Ok(Library {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -385,34 +733,26 @@ pub fn get_catalog(conn: &Connection) -> rusqlite::Result<Catalog> {
                 type_: row.get(3)?,
                 created_at: row.get(4)?,
                 sort_order: row.get(5)?,
+                revision: row.get(6)?,
             })
         })?;
         rows.collect::<rusqlite::Result<Vec<_>>>()?
     };
 
     let comics = {
-        let mut stmt = conn.prepare(
-            "SELECT id, title, path, cover, library_id, created_at, starred, deleted FROM comics",
-        )?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Comic {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                path: row.get(2)?,
-                cover: row.get(3)?,
-                library_id: row.get(4)?,
-                created_at: row.get::<_, i64>(5)? as u64,
-                starred: row.get::<_, i64>(6)? != 0,
-                deleted: row.get::<_, i64>(7)? != 0,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COMIC_COLUMNS} FROM comics WHERE ?1 IS NULL OR revision > ?1"
+        ))?;
+        let rows = stmt.query_map(params![since], comic_from_row)?;
         rows.collect::<rusqlite::Result<Vec<_>>>()?
     };
 
     let authors = {
-        let mut stmt =
-            conn.prepare("SELECT id, name, path, library_id, book_count FROM authors")?;
-        let rows = stmt.query_map([], |row| {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, path, library_id, book_count FROM authors
+             WHERE ?1 IS NULL OR revision > ?1",
+        )?;
+        let rows = stmt.query_map(params![since], |row| {
             Ok(AuthorRow {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -425,26 +765,15 @@ pub fn get_catalog(conn: &Connection) -> rusqlite::Result<Catalog> {
     };
 
     let books = {
-        let mut stmt = conn.prepare(
-            "SELECT id, title, path, author_id, library_id, size, created_at, starred, deleted FROM books",
-        )?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Book {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                path: row.get(2)?,
-                author_id: row.get(3)?,
-                library_id: row.get(4)?,
-                size: row.get::<_, i64>(5)? as u64,
-                created_at: row.get::<_, i64>(6)? as u64,
-                starred: row.get::<_, i64>(7)? != 0,
-                deleted: row.get::<_, i64>(8)? != 0,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BOOK_COLUMNS} FROM books WHERE ?1 IS NULL OR revision > ?1"
+        ))?;
+        let rows = stmt.query_map(params![since], book_from_row)?;
         rows.collect::<rusqlite::Result<Vec<_>>>()?
     };
 
     Ok(Catalog {
+        revision,
         libraries,
         comics,
         authors,
@@ -454,8 +783,14 @@ pub fn get_catalog(conn: &Connection) -> rusqlite::Result<Catalog> {
 
 // --- Mutations (scan + persist). These do filesystem + DB work. ---
 
-/// Import a new library at `path`: detect type, scan, and persist.
-pub fn import(app: &AppHandle, path: &str) -> Result<ImportOutcome, String> {
+/// Import a new library at `path`: detect type, scan, persist, and index its
+/// book text for search. Nothing is written if `progress` is cancelled during
+/// the scan.
+pub fn import(
+    app: &AppHandle,
+    path: &str,
+    progress: &ScanProgress,
+) -> Result<ImportOutcome, String> {
     let id = crate::scanner::utils::generate_uuid(path);
 
     {
@@ -484,7 +819,8 @@ pub fn import(app: &AppHandle, path: &str) -> Result<ImportOutcome, String> {
         .unwrap_or(1)
     };
 
-    let (comics, authors) = scan(app, path, &id, &type_)?;
+    let content = scan(app, path, &id, &type_, &LibraryScan::default(), progress)?;
+    progress.check()?;
 
     let library = Library {
         id: id.clone(),
@@ -493,40 +829,131 @@ pub fn import(app: &AppHandle, path: &str) -> Result<ImportOutcome, String> {
         type_,
         created_at: crate::scanner::utils::current_time_millis() as i64,
         sort_order,
+        revision: 0,
     };
 
     let state = app.state::<LibraryDb>();
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     upsert_library(&conn, &library).map_err(|e| e.to_string())?;
-    replace_library_content(&conn, &id, &comics, &authors).map_err(|e| e.to_string())?;
+    sync_library_content(&conn, &id, &content, None).map_err(|e| e.to_string())?;
+    drop(conn);
+    events::publish(
+        app,
+        Event::LibraryImported {
+            library_id: id.clone(),
+        },
+    );
+
+    crate::fulltext::index_library(app, &id, progress)?;
     Ok(ImportOutcome { id, created: true })
 }
 
-/// Re-scan an existing library and replace its content. Bumps `created_at`
-/// so the frontend remounts the view.
-pub fn refresh(app: &AppHandle, id: &str) -> Result<(), String> {
-    let (path, type_) = {
-        let state = app.state::<LibraryDb>();
-        let conn = state.0.lock().map_err(|e| e.to_string())?;
-        conn.query_row(
-            "SELECT path, type FROM libraries WHERE id = ?1",
-            params![id],
-            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
-        )
-        .map_err(|e| e.to_string())?
-    };
-
-    let (comics, authors) = scan(app, &path, id, &type_)?;
+/// Re-scan an existing library against its stored content and write only what
+/// changed, re-indexing changed books for search. The library row itself is
+/// always written again, at a new revision, so the frontend remounts the
+/// view. Nothing is written if `progress` is cancelled during the scan.
+pub fn refresh(app: &AppHandle, id: &str, progress: &ScanProgress) -> Result<(), String> {
+    let (path, type_, previous) = stored_library(app, id)?;
+    let content = scan(app, &path, id, &type_, &previous, progress)?;
+    progress.check()?;
 
     let state = app.state::<LibraryDb>();
     let conn = state.0.lock().map_err(|e| e.to_string())?;
-    replace_library_content(&conn, id, &comics, &authors).map_err(|e| e.to_string())?;
+    let counts = sync_library_content(&conn, id, &content, None).map_err(|e| e.to_string())?;
+    // The library was removed while it was being scanned.
+    if !library_exists(&conn, id).map_err(|e| e.to_string())? {
+        return Ok(());
+    }
+    info!(
+        library_id = id,
+        added = counts.added,
+        updated = counts.updated,
+        removed = counts.removed,
+        "Refreshed library"
+    );
+    let revision = revision::next(&conn).map_err(|e| e.to_string())?;
     conn.execute(
-        "UPDATE libraries SET created_at = ?2 WHERE id = ?1",
-        params![id, crate::scanner::utils::current_time_millis() as i64],
+        "UPDATE libraries SET revision = ?2 WHERE id = ?1",
+        params![id, revision],
     )
     .map_err(|e| e.to_string())?;
-    Ok(())
+    drop(conn);
+    events::publish(
+        app,
+        Event::LibraryRefreshed {
+            library_id: id.to_string(),
+        },
+    );
+
+    crate::fulltext::index_library(app, id, progress)
+}
+
+/// Re-scan only `entries` of a library, comic folders or archives or author
+/// directories directly under its root, and write what changed under them.
+/// Entries that are gone lose their rows. Changed books are re-indexed for
+/// search.
+pub fn update_entries(
+    app: &AppHandle,
+    id: &str,
+    entries: &[PathBuf],
+    progress: &ScanProgress,
+) -> Result<(), String> {
+    let (_, type_, previous) = stored_library(app, id)?;
+    let content = if type_ == "book" {
+        crate::scanner::book::scan_book_authors(entries, id, &previous, progress)
+    } else {
+        LibraryScan {
+            comics: crate::scanner::comic::scan_comic_entries(
+                app,
+                entries,
+                id,
+                &previous.comics,
+                progress,
+            ),
+            ..LibraryScan::default()
+        }
+    };
+    progress.check()?;
+
+    let state = app.state::<LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    let counts =
+        sync_library_content(&conn, id, &content, Some(entries)).map_err(|e| e.to_string())?;
+    drop(conn);
+    if counts == SyncCounts::default() {
+        return Ok(());
+    }
+    info!(
+        library_id = id,
+        entries = entries.len(),
+        added = counts.added,
+        updated = counts.updated,
+        removed = counts.removed,
+        "Updated library entries"
+    );
+    events::publish(
+        app,
+        Event::LibraryRefreshed {
+            library_id: id.to_string(),
+        },
+    );
+
+    crate::fulltext::index_library(app, id, progress)
+}
+
+/// Root path, type and stored content of the library with `id`.
+fn stored_library(app: &AppHandle, id: &str) -> Result<(String, String, LibraryScan), String> {
+    let state = app.state::<LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    let (path, type_) = conn
+        .query_row(
+            "SELECT path, type FROM libraries WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let previous = stored_scan(&conn, id).map_err(|e| e.to_string())?;
+    Ok((path, type_, previous))
 }
 
 fn scan(
@@ -534,31 +961,89 @@ fn scan(
     path: &str,
     id: &str,
     type_: &str,
-) -> Result<(Vec<Comic>, Vec<Author>), String> {
+    previous: &LibraryScan,
+    progress: &ScanProgress,
+) -> Result<LibraryScan, String> {
     if type_ == "book" {
-        let authors = crate::scanner::book::scan_book_library(path, id)?;
-        Ok((Vec::new(), authors))
+        crate::scanner::book::scan_book_library(path, id, previous, progress)
     } else {
-        let comics = crate::scanner::comic::scan_comic_library(app.clone(), path, id)?;
-        Ok((comics, Vec::new()))
+        let comics = crate::scanner::comic::scan_comic_library(
+            app.clone(),
+            path,
+            id,
+            &previous.comics,
+            progress,
+        )?;
+        Ok(LibraryScan {
+            comics,
+            ..LibraryScan::default()
+        })
     }
 }
 
 pub fn remove(conn: &Connection, id: &str) -> rusqlite::Result<()> {
     let tx = conn.unchecked_transaction()?;
+    // The tombstones of the deleted rows carry this revision.
+    revision::next(&tx)?;
     tx.execute("DELETE FROM comics WHERE library_id = ?1", params![id])?;
     tx.execute("DELETE FROM books WHERE library_id = ?1", params![id])?;
     tx.execute("DELETE FROM authors WHERE library_id = ?1", params![id])?;
+    tx.execute(
+        "DELETE FROM chapter_rules WHERE library_id = ?1",
+        params![id],
+    )?;
+    tx.execute(
+        "DELETE FROM paragraph_options WHERE library_id = ?1",
+        params![id],
+    )?;
     tx.execute("DELETE FROM libraries WHERE id = ?1", params![id])?;
+    crate::fulltext::prune(&tx)?;
     tx.commit()
 }
 
 pub fn reorder(conn: &Connection, ordered_ids: &[String]) -> rusqlite::Result<()> {
     let tx = conn.unchecked_transaction()?;
+    let revision = revision::next(&tx)?;
     for (index, id) in ordered_ids.iter().enumerate() {
         tx.execute(
-            "UPDATE libraries SET sort_order = ?2 WHERE id = ?1",
-            params![id, index as i64],
+            "UPDATE libraries SET sort_order = ?2, revision = ?3 WHERE id = ?1",
+            params![id, index as i64, revision],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Reorder the libraries `ordered_ids` among the sort positions they already
+/// hold, leaving every other library in place. Unknown ids are skipped.
+pub fn reorder_within(conn: &Connection, ordered_ids: &[String]) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let mut ids: Vec<&String> = Vec::new();
+    let mut slots = Vec::new();
+    for id in ordered_ids {
+        let slot = tx
+            .query_row(
+                "SELECT sort_order FROM libraries WHERE id = ?1",
+                params![id],
+                |r| r.get::<_, i64>(0),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+        if let Some(slot) = slot
+            && !ids.contains(&id)
+        {
+            ids.push(id);
+            slots.push(slot);
+        }
+    }
+    slots.sort_unstable();
+    let revision = revision::next(&tx)?;
+    for (id, slot) in ids.into_iter().zip(slots) {
+        tx.execute(
+            "UPDATE libraries SET sort_order = ?2, revision = ?3 WHERE id = ?1",
+            params![id, slot, revision],
         )?;
     }
     tx.commit()
@@ -584,27 +1069,29 @@ fn update_catalog_tags(
     let sql = match table {
         CatalogTagTable::Comics => {
             "UPDATE comics
-             SET starred = COALESCE(?2, starred), deleted = COALESCE(?3, deleted)
+             SET starred = COALESCE(?2, starred), deleted = COALESCE(?3, deleted), revision = ?4
              WHERE id = ?1"
         }
         CatalogTagTable::Books => {
             "UPDATE books
-             SET starred = COALESCE(?2, starred), deleted = COALESCE(?3, deleted)
+             SET starred = COALESCE(?2, starred), deleted = COALESCE(?3, deleted), revision = ?4
              WHERE id = ?1"
         }
     };
-    conn.execute(sql, params![id, starred, deleted])?;
+    conn.execute(sql, params![id, starred, deleted, revision::next(conn)?])?;
     Ok(())
 }
 
 /// Write tags to the file (xattr) and mirror them on the catalog row.
 pub fn set_comic_tags(app: &AppHandle, id: &str, tags: &FileTags) -> Result<(), String> {
-    let path: String = {
+    let (path, library_id): (String, String) = {
         let state = app.state::<LibraryDb>();
         let conn = state.0.lock().map_err(|e| e.to_string())?;
-        conn.query_row("SELECT path FROM comics WHERE id = ?1", params![id], |r| {
-            r.get(0)
-        })
+        conn.query_row(
+            "SELECT path, library_id FROM comics WHERE id = ?1",
+            params![id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
         .map_err(|e| e.to_string())?
     };
 
@@ -614,16 +1101,27 @@ pub fn set_comic_tags(app: &AppHandle, id: &str, tags: &FileTags) -> Result<(),
     let state = app.state::<LibraryDb>();
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     update_catalog_tags(&conn, CatalogTagTable::Comics, id, tags).map_err(|e| e.to_string())?;
+    drop(conn);
+    events::publish(
+        app,
+        Event::ComicTagsChanged {
+            library_id,
+            comic_id: id.to_string(),
+            tags: *tags,
+        },
+    );
     Ok(())
 }
 
 pub fn set_book_tags(app: &AppHandle, id: &str, tags: &FileTags) -> Result<(), String> {
-    let path: String = {
+    let (path, library_id): (String, String) = {
         let state = app.state::<LibraryDb>();
         let conn = state.0.lock().map_err(|e| e.to_string())?;
-        conn.query_row("SELECT path FROM books WHERE id = ?1", params![id], |r| {
-            r.get(0)
-        })
+        conn.query_row(
+            "SELECT path, library_id FROM books WHERE id = ?1",
+            params![id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
         .map_err(|e| e.to_string())?
     };
 
@@ -633,6 +1131,15 @@ pub fn set_book_tags(app: &AppHandle, id: &str, tags: &FileTags) -> Result<(), S
     let state = app.state::<LibraryDb>();
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     update_catalog_tags(&conn, CatalogTagTable::Books, id, tags).map_err(|e| e.to_string())?;
+    drop(conn);
+    events::publish(
+        app,
+        Event::BookTagsChanged {
+            library_id,
+            book_id: id.to_string(),
+            tags: *tags,
+        },
+    );
     Ok(())
 }
 
@@ -659,7 +1166,178 @@ pub fn list_for_menu(app: &AppHandle) -> Vec<(String, String, String)> {
     .unwrap_or_default()
 }
 
-pub fn library_path(app: &AppHandle, id: &str) -> Option<String> {
+/// Path of the book with `id`, if it is in the catalog.
+pub fn book_path(conn: &Connection, id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT path FROM books WHERE id = ?1", params![id], |r| {
+        r.get(0)
+    })
+    .map(Some)
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+/// Path of the comic with `id`, if it is in the catalog.
+pub fn comic_path(conn: &Connection, id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT path FROM comics WHERE id = ?1", params![id], |r| {
+        r.get(0)
+    })
+    .map(Some)
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+/// Library of the comic with `id`, if it is in the catalog.
+pub fn comic_library(conn: &Connection, id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT library_id FROM comics WHERE id = ?1",
+        params![id],
+        |r| r.get(0),
+    )
+    .map(Some)
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+/// Library of the book with `id`, if it is in the catalog.
+pub fn book_library(conn: &Connection, id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT library_id FROM books WHERE id = ?1",
+        params![id],
+        |r| r.get(0),
+    )
+    .map(Some)
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+/// Encoding stored for the book at `path` by an earlier open, if any.
+pub fn book_encoding(conn: &Connection, path: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT encoding FROM books WHERE path = ?1",
+        params![path],
+        |r| r.get(0),
+    )
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+pub fn set_book_encoding(conn: &Connection, path: &str, encoding: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE books SET encoding = ?1 WHERE path = ?2",
+        params![encoding, path],
+    )?;
+    Ok(())
+}
+
+/// Chapter rules of a library, or the default preset when none were saved.
+pub fn chapter_rules(conn: &Connection, library_id: &str) -> rusqlite::Result<Vec<ChapterRule>> {
+    let rules = conn
+        .query_row(
+            "SELECT rules FROM chapter_rules WHERE library_id = ?1",
+            params![library_id],
+            |r| r.get::<_, String>(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e),
+        })?;
+    Ok(parse_chapter_rules(rules.as_deref()))
+}
+
+pub fn set_chapter_rules(
+    conn: &Connection,
+    library_id: &str,
+    rules: &[ChapterRule],
+) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(rules)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    conn.execute(
+        "INSERT INTO chapter_rules (library_id, rules) VALUES (?1, ?2)
+         ON CONFLICT(library_id) DO UPDATE SET rules = excluded.rules",
+        params![library_id, json],
+    )?;
+    Ok(())
+}
+
+/// Paragraph options of a library; all off when none were saved.
+pub fn paragraph_options(
+    conn: &Connection,
+    library_id: &str,
+) -> rusqlite::Result<ParagraphOptions> {
+    let options = conn
+        .query_row(
+            "SELECT options FROM paragraph_options WHERE library_id = ?1",
+            params![library_id],
+            |r| r.get::<_, String>(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e),
+        })?;
+    Ok(parse_paragraph_options(options.as_deref()))
+}
+
+pub fn set_paragraph_options(
+    conn: &Connection,
+    library_id: &str,
+    options: &ParagraphOptions,
+) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(options)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    conn.execute(
+        "INSERT INTO paragraph_options (library_id, options) VALUES (?1, ?2)
+         ON CONFLICT(library_id) DO UPDATE SET options = excluded.options",
+        params![library_id, json],
+    )?;
+    Ok(())
+}
+
+/// Stored encoding plus the library's chapter rules and paragraph options for
+/// the book at `path`. Books outside the catalog get the defaults.
+pub fn book_parse_options(conn: &Connection, path: &str) -> rusqlite::Result<ParseOptions> {
+    conn.query_row(
+        "SELECT b.encoding, r.rules, p.options FROM books b
+         LEFT JOIN chapter_rules r ON r.library_id = b.library_id
+         LEFT JOIN paragraph_options p ON p.library_id = b.library_id
+         WHERE b.path = ?1",
+        params![path],
+        |r| {
+            Ok(ParseOptions {
+                encoding: r.get(0)?,
+                chapter_rules: parse_chapter_rules(r.get::<_, Option<String>>(1)?.as_deref()),
+                paragraphs: parse_paragraph_options(r.get::<_, Option<String>>(2)?.as_deref()),
+            })
+        },
+    )
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(ParseOptions::default()),
+        e => Err(e),
+    })
+}
+
+fn parse_chapter_rules(json: Option<&str>) -> Vec<ChapterRule> {
+    json.and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_else(default_rules)
+}
+
+fn parse_paragraph_options(json: Option<&str>) -> ParagraphOptions {
+    json.and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+pub fn library_path<R: Runtime>(app: &AppHandle<R>, id: &str) -> Option<String> {
     let state = app.state::<LibraryDb>();
     let conn = state.0.lock().ok()?;
     conn.query_row(
@@ -677,8 +1355,26 @@ mod tests {
 
     fn test_conn() -> Connection {
         let conn = Connection::open_in_memory().expect("open in-memory library db");
-        conn.execute_batch(LIBRARY_SCHEMA)
-            .expect("create library schema");
+        create_schema(&conn).expect("create library schema");
+        conn
+    }
+
+    /// A database holding the empty book library `library-1`.
+    fn book_library_conn() -> Connection {
+        let conn = test_conn();
+        upsert_library(
+            &conn,
+            &Library {
+                id: "library-1".to_string(),
+                name: "Library".to_string(),
+                path: "/library".to_string(),
+                type_: "book".to_string(),
+                created_at: 1,
+                sort_order: 0,
+                revision: 0,
+            },
+        )
+        .expect("insert library");
         conn
     }
 
@@ -701,13 +1397,23 @@ mod tests {
             names,
             vec![
                 "authors",
+                "book_text",
+                "book_text_config",
+                "book_text_data",
+                "book_text_docsize",
+                "book_text_idx",
+                "book_text_sources",
                 "books",
+                "chapter_rules",
                 "comics",
                 "idx_authors_library",
                 "idx_books_author",
                 "idx_books_library",
                 "idx_comics_library",
                 "libraries",
+                "paragraph_options",
+                "revision",
+                "tombstones",
             ]
         );
     }
@@ -722,6 +1428,7 @@ mod tests {
             type_: "book".to_string(),
             created_at: 2,
             sort_order: 1,
+            revision: 0,
         };
         let earlier = Library {
             id: "library-1".to_string(),
@@ -730,6 +1437,7 @@ mod tests {
             type_: "comic".to_string(),
             created_at: 1,
             sort_order: 0,
+            revision: 0,
         };
         upsert_library(&conn, &later).expect("insert later library");
         upsert_library(&conn, &earlier).expect("insert earlier library");
@@ -805,6 +1513,7 @@ mod tests {
                     type_: "comic".to_string(),
                     created_at: 1,
                     sort_order,
+                    revision: 0,
                 },
             )
             .expect("insert library");
@@ -826,6 +1535,48 @@ mod tests {
         assert_eq!(sort_order("library-2"), 1);
     }
 
+    #[test]
+    fn reorder_within_swaps_libraries_among_their_own_positions() {
+        let conn = test_conn();
+        for (id, sort_order) in [("library-1", 0), ("hidden", 1), ("library-2", 2)] {
+            upsert_library(
+                &conn,
+                &Library {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    path: format!("/library/{id}"),
+                    type_: "comic".to_string(),
+                    created_at: 1,
+                    sort_order,
+                    revision: 0,
+                },
+            )
+            .expect("insert library");
+        }
+
+        reorder_within(
+            &conn,
+            &[
+                "library-2".to_string(),
+                "missing".to_string(),
+                "library-1".to_string(),
+            ],
+        )
+        .expect("reorder libraries");
+
+        let sort_order = |id: &str| {
+            conn.query_row(
+                "SELECT sort_order FROM libraries WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, i64>(0),
+            )
+            .expect("read library sort order")
+        };
+        assert_eq!(sort_order("library-2"), 0);
+        assert_eq!(sort_order("hidden"), 1);
+        assert_eq!(sort_order("library-1"), 2);
+    }
+
     #[test]
     fn library_exists_detects_existing_import_identity() {
         let conn = test_conn();
@@ -840,6 +1591,7 @@ mod tests {
                 type_: "comic".to_string(),
                 created_at: 1,
                 sort_order: 0,
+                revision: 0,
             },
         )
         .expect("insert library");
@@ -847,8 +1599,42 @@ mod tests {
         assert!(library_exists(&conn, "library-1").expect("check existing library"));
     }
 
+    fn stamped<T>(entry: T) -> Stamped<T> {
+        Stamped { entry, mtime: 1 }
+    }
+
+    fn book(id: &str, size: u64) -> Book {
+        Book {
+            id: id.to_string(),
+            title: id.to_string(),
+            path: format!("/library/Author/{id}.txt"),
+            author_id: "author-1".to_string(),
+            library_id: "library-1".to_string(),
+            size,
+            created_at: 1,
+            starred: false,
+            deleted: false,
+        }
+    }
+
+    /// One author holding `books`, as a book library scan reports it.
+    fn book_scan(books: Vec<Book>) -> LibraryScan {
+        LibraryScan {
+            authors: vec![stamped(Author {
+                id: "author-1".to_string(),
+                name: "Author".to_string(),
+                path: "/library/Author".to_string(),
+                library_id: "library-1".to_string(),
+                book_count: books.len() as u32,
+                books: Vec::new(),
+            })],
+            books: books.into_iter().map(stamped).collect(),
+            ..LibraryScan::default()
+        }
+    }
+
     #[test]
-    fn replacing_library_content_removes_stale_rows_and_persists_nested_books() {
+    fn syncing_library_content_removes_stale_rows_and_persists_books() {
         let conn = test_conn();
         let library = Library {
             id: "library-1".to_string(),
@@ -857,6 +1643,7 @@ mod tests {
             type_: "book".to_string(),
             created_at: 1,
             sort_order: 0,
+            revision: 0,
         };
         upsert_library(&conn, &library).expect("insert library");
         upsert_comic(
@@ -874,30 +1661,16 @@ mod tests {
         )
         .expect("insert stale comic");
 
-        replace_library_content(
+        sync_library_content(
             &conn,
             "library-1",
-            &[],
-            &[Author {
-                id: "author-1".to_string(),
-                name: "Author".to_string(),
-                path: "/library/Author".to_string(),
-                library_id: "library-1".to_string(),
-                book_count: 1,
-                books: vec![Book {
-                    id: "book-1".to_string(),
-                    title: "Book".to_string(),
-                    path: "/library/Author/Book.txt".to_string(),
-                    author_id: "author-1".to_string(),
-                    library_id: "library-1".to_string(),
-                    size: 10,
-                    created_at: 2,
-                    starred: true,
-                    deleted: false,
-                }],
-            }],
+            &book_scan(vec![Book {
+                starred: true,
+                ..book("book-1", 10)
+            }]),
+            None,
         )
-        .expect("replace library content");
+        .expect("sync library content");
 
         let catalog = get_catalog(&conn).expect("read catalog");
         assert!(catalog.comics.is_empty());
@@ -907,7 +1680,330 @@ mod tests {
     }
 
     #[test]
-    fn remove_deletes_library_and_all_catalog_rows_for_it() {
+    fn book_encodings_persist_and_survive_rescans_of_unchanged_books() {
+        let conn = book_library_conn();
+        sync_library_content(
+            &conn,
+            "library-1",
+            &book_scan(vec![book("same", 10), book("changed", 10)]),
+            None,
+        )
+        .expect("insert books");
+
+        assert_eq!(
+            book_encoding(&conn, "/library/Author/same.txt").expect("read unset encoding"),
+            None
+        );
+        set_book_encoding(&conn, "/library/Author/same.txt", "GBK").expect("store encoding");
+        set_book_encoding(&conn, "/library/Author/changed.txt", "Big5").expect("store encoding");
+        assert_eq!(
+            book_encoding(&conn, "/library/missing.txt").expect("read missing book"),
+            None
+        );
+
+        sync_library_content(
+            &conn,
+            "library-1",
+            &book_scan(vec![book("same", 10), book("changed", 11)]),
+            None,
+        )
+        .expect("rescan books");
+
+        assert_eq!(
+            book_encoding(&conn, "/library/Author/same.txt")
+                .expect("read kept encoding")
+                .as_deref(),
+            Some("GBK")
+        );
+        assert_eq!(
+            book_encoding(&conn, "/library/Author/changed.txt").expect("read reset encoding"),
+            None
+        );
+    }
+
+    #[test]
+    fn syncing_writes_only_changed_rows_and_feeds_the_next_scan() {
+        let conn = book_library_conn();
+        upsert_book(&conn, &book("legacy", 1)).expect("insert row without mtime");
+        let first = book_scan(vec![
+            book("same", 1),
+            book("changed", 1),
+            book("gone", 1),
+            book("legacy", 1),
+        ]);
+        assert_eq!(
+            sync_library_content(&conn, "library-1", &first, None).expect("sync first scan"),
+            SyncCounts {
+                added: 4,
+                updated: 1,
+                removed: 0,
+            }
+        );
+
+        update_catalog_tags(
+            &conn,
+            CatalogTagTable::Books,
+            "same",
+            &FileTags {
+                starred: Some(true),
+                deleted: None,
+            },
+        )
+        .expect("star book");
+        let mut next = stored_scan(&conn, "library-1").expect("read stored scan");
+        next.books.sort_by(|a, b| a.entry.id.cmp(&b.entry.id));
+        assert_eq!(
+            next.books
+                .iter()
+                .map(|book| (book.entry.id.as_str(), book.entry.starred))
+                .collect::<Vec<_>>(),
+            vec![
+                ("changed", false),
+                ("gone", false),
+                ("legacy", false),
+                ("same", true)
+            ]
+        );
+
+        // What a rescan reports: reused rows as stored, one changed book, one
+        // gone and one new.
+        next.books[0].mtime = 2;
+        next.books.remove(1);
+        next.books.push(stamped(book("new", 1)));
+        assert_eq!(
+            sync_library_content(&conn, "library-1", &next, None).expect("sync rescan"),
+            SyncCounts {
+                added: 1,
+                updated: 1,
+                removed: 1,
+            }
+        );
+        let catalog = get_catalog(&conn).expect("read catalog");
+        let mut ids = catalog
+            .books
+            .iter()
+            .map(|book| book.id.as_str())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        assert_eq!(ids, vec!["changed", "legacy", "new", "same"]);
+        assert!(
+            catalog
+                .books
+                .iter()
+                .any(|book| book.id == "same" && book.starred)
+        );
+    }
+
+    #[test]
+    fn scoped_sync_only_deletes_rows_under_its_entries() {
+        let conn = book_library_conn();
+        let mut scan = book_scan(vec![book("gone", 1)]);
+        scan.authors.push(stamped(Author {
+            id: "author-2".to_string(),
+            path: "/library/Author 2".to_string(),
+            ..scan.authors[0].entry.clone()
+        }));
+        scan.books.push(stamped(Book {
+            author_id: "author-2".to_string(),
+            path: "/library/Author 2/kept.txt".to_string(),
+            ..book("kept", 1)
+        }));
+        sync_library_content(&conn, "library-1", &scan, None).expect("sync library");
+
+        let counts = sync_library_content(
+            &conn,
+            "library-1",
+            &LibraryScan::default(),
+            Some(&[PathBuf::from("/library/Author")]),
+        )
+        .expect("sync removed author");
+
+        assert_eq!(counts.removed, 2);
+        let catalog = get_catalog(&conn).expect("read catalog");
+        assert_eq!(catalog.authors.len(), 1);
+        assert_eq!(catalog.authors[0].id, "author-2");
+        assert_eq!(catalog.books.len(), 1);
+        assert_eq!(catalog.books[0].id, "kept");
+    }
+
+    #[test]
+    fn catalog_changes_report_rows_written_and_deleted_after_a_revision() {
+        let conn = test_conn();
+        upsert_library(
+            &conn,
+            &Library {
+                id: "library-1".to_string(),
+                name: "Library".to_string(),
+                path: "/library".to_string(),
+                type_: "book".to_string(),
+                created_at: 1,
+                sort_order: 0,
+                revision: 0,
+            },
+        )
+        .expect("insert library");
+        sync_library_content(
+            &conn,
+            "library-1",
+            &book_scan(vec![book("kept", 1), book("gone", 1)]),
+            None,
+        )
+        .expect("sync first scan");
+        let since = get_catalog(&conn).expect("read catalog").revision;
+
+        let changes =
+            catalog_changes(&conn, since, &Access::default()).expect("read unchanged catalog");
+        assert_eq!(changes.catalog.revision, since);
+        assert!(changes.catalog.books.is_empty());
+        assert!(changes.removed.books.is_empty());
+
+        sync_library_content(
+            &conn,
+            "library-1",
+            &book_scan(vec![book("kept", 1), book("new", 1)]),
+            None,
+        )
+        .expect("sync rescan");
+        let changes =
+            catalog_changes(&conn, since, &Access::default()).expect("read changed catalog");
+        assert!(changes.catalog.revision > since);
+        assert_eq!(
+            changes
+                .catalog
+                .books
+                .iter()
+                .map(|book| book.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["new"]
+        );
+        assert!(changes.catalog.libraries.is_empty());
+        assert_eq!(changes.removed.books, vec!["gone"]);
+
+        // A row written back is no longer reported as removed.
+        sync_library_content(
+            &conn,
+            "library-1",
+            &book_scan(vec![book("kept", 1), book("new", 1), book("gone", 1)]),
+            None,
+        )
+        .expect("sync restored book");
+        let changes =
+            catalog_changes(&conn, since, &Access::default()).expect("read restored catalog");
+        assert!(changes.removed.books.is_empty());
+
+        reorder(&conn, &["library-1".to_string()]).expect("reorder libraries");
+        let changes =
+            catalog_changes(&conn, since, &Access::default()).expect("read reordered catalog");
+        assert_eq!(changes.catalog.libraries[0].id, "library-1");
+
+        remove(&conn, "library-1").expect("remove library");
+        let changes =
+            catalog_changes(&conn, since, &Access::default()).expect("read catalog after remove");
+        assert!(changes.catalog.libraries.is_empty());
+        assert_eq!(changes.removed.libraries, vec!["library-1"]);
+        assert_eq!(changes.removed.authors, vec!["author-1"]);
+        assert_eq!(changes.removed.books, vec!["gone", "kept", "new"]);
+
+        // Nor are the removals of a library hidden from the client.
+        let changes = catalog_changes(&conn, since, &Access::hiding(&["library-1"]))
+            .expect("read hidden removals");
+        assert!(changes.removed.libraries.is_empty());
+        assert!(changes.removed.authors.is_empty());
+        assert!(changes.removed.books.is_empty());
+    }
+
+    #[test]
+    fn book_parsing_settings_default_per_library_and_reach_parse_options() {
+        use crate::scanner::chapter::ChapterPreset;
+
+        let conn = book_library_conn();
+        sync_library_content(
+            &conn,
+            "library-1",
+            &book_scan(vec![Book {
+                path: "/library/Author/book.txt".to_string(),
+                ..book("book-1", 1)
+            }]),
+            None,
+        )
+        .expect("insert book content");
+        assert_eq!(
+            chapter_rules(&conn, "library-1").expect("read default rules"),
+            default_rules()
+        );
+
+        let rules = vec![
+            ChapterRule::Preset {
+                preset: ChapterPreset::English,
+                priority: 1,
+            },
+            ChapterRule::Regex {
+                pattern: "^Act \\d+".to_string(),
+                priority: 0,
+                volume: false,
+            },
+        ];
+        set_chapter_rules(&conn, "library-1", &rules).expect("store rules");
+        set_chapter_rules(&conn, "library-1", &rules[..1]).expect("replace rules");
+        set_book_encoding(&conn, "/library/Author/book.txt", "GBK").expect("store encoding");
+        assert_eq!(
+            chapter_rules(&conn, "library-1").expect("read stored rules"),
+            rules[..1]
+        );
+        assert_eq!(
+            paragraph_options(&conn, "library-1").expect("read default options"),
+            ParagraphOptions::default()
+        );
+        let paragraphs = ParagraphOptions {
+            rejoin_wrapped: true,
+            strip_indent: true,
+            ..ParagraphOptions::default()
+        };
+        set_paragraph_options(&conn, "library-1", &ParagraphOptions::default())
+            .expect("store options");
+        set_paragraph_options(&conn, "library-1", &paragraphs).expect("replace options");
+
+        assert_eq!(
+            book_path(&conn, "book-1")
+                .expect("read book path")
+                .as_deref(),
+            Some("/library/Author/book.txt")
+        );
+        assert_eq!(book_path(&conn, "book-2").expect("read missing path"), None);
+        let options =
+            book_parse_options(&conn, "/library/Author/book.txt").expect("read book options");
+        assert_eq!(options.encoding.as_deref(), Some("GBK"));
+        assert_eq!(options.chapter_rules, rules[..1]);
+        assert_eq!(options.paragraphs, paragraphs);
+        let outside = book_parse_options(&conn, "/elsewhere.txt").expect("read outside options");
+        assert_eq!(outside.encoding, None);
+        assert_eq!(outside.chapter_rules, default_rules());
+        assert_eq!(outside.paragraphs, ParagraphOptions::default());
+
+        conn.execute(
+            "UPDATE chapter_rules SET rules = 'not json' WHERE library_id = 'library-1'",
+            [],
+        )
+        .expect("corrupt rules");
+        assert_eq!(
+            chapter_rules(&conn, "library-1").expect("read corrupt rules"),
+            default_rules()
+        );
+
+        remove(&conn, "library-1").expect("remove library");
+        let remaining: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM chapter_rules)
+                      + (SELECT COUNT(*) FROM paragraph_options)",
+                [],
+                |r| r.get(0),
+            )
+            .expect("count settings");
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn remove_deletes_library_and_all_catalog_rows_and_stops_late_scans() {
         let conn = test_conn();
         let library = Library {
             id: "library-1".to_string(),
@@ -916,12 +2012,11 @@ mod tests {
             type_: "comic".to_string(),
             created_at: 1,
             sort_order: 0,
+            revision: 0,
         };
         upsert_library(&conn, &library).expect("insert library");
-        replace_library_content(
-            &conn,
-            "library-1",
-            &[Comic {
+        let scan = LibraryScan {
+            comics: vec![stamped(Comic {
                 id: "comic-1".to_string(),
                 title: "Comic".to_string(),
                 path: "/library/Comic".to_string(),
@@ -930,16 +2025,23 @@ mod tests {
                 created_at: 1,
                 starred: false,
                 deleted: true,
-            }],
-            &[],
-        )
-        .expect("insert comic content");
+            })],
+            ..LibraryScan::default()
+        };
+        sync_library_content(&conn, "library-1", &scan, None).expect("insert comic content");
 
         remove(&conn, "library-1").expect("remove library");
 
         let catalog = get_catalog(&conn).expect("read catalog after remove");
         assert!(catalog.libraries.is_empty());
         assert!(catalog.comics.is_empty());
+
+        // A scan that was running when the library went away writes nothing.
+        assert_eq!(
+            sync_library_content(&conn, "library-1", &scan, None).expect("sync removed library"),
+            SyncCounts::default()
+        );
+        assert!(get_catalog(&conn).expect("read catalog").comics.is_empty());
     }
 
     #[test]
@@ -952,6 +2054,7 @@ mod tests {
             type_: "comic".to_string(),
             created_at: 1,
             sort_order: 0,
+            revision: 0,
         };
         upsert_library(&conn, &library).expect("insert library");
         library.name = "New".to_string();
@@ -975,6 +2078,7 @@ mod tests {
             type_: "comic".to_string(),
             created_at: 1,
             sort_order: 0,
+            revision: 0,
         };
         upsert_library(&conn, &library).expect("insert library");
         upsert_comic(
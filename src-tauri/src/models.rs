@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Book {
     pub id: String,
     pub title: String,
@@ -16,7 +16,7 @@ pub struct Book {
     pub deleted: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Author {
     pub id: String,
     pub name: String,
@@ -35,15 +35,94 @@ pub struct Chapter {
     pub title: String,
     #[serde(rename = "lineIndex")]
     pub line_index: usize,
+    /// How sure the heuristic fallback is that this line is a heading, from 0
+    /// to 1. Absent for chapters found by rules or a book's own TOC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// Depth in the table of contents: 0 for top-level entries, 1 for a
+    /// chapter inside a volume, and so on.
+    #[serde(default)]
+    pub level: u8,
+    /// Index in `BookContent::chapters` of the enclosing entry, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BookContent {
     pub lines: Vec<String>,
     pub chapters: Vec<Chapter>,
+    /// Encoding label the text was decoded from, stored so later opens skip
+    /// detection.
+    pub encoding: String,
+}
+
+/// Everything about a book except its lines, which are fetched in ranges.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookToc {
+    #[serde(rename = "lineCount")]
+    pub line_count: usize,
+    pub chapters: Vec<Chapter>,
+    pub encoding: String,
+}
+
+/// Lines `start..start + lines.len()` of a book.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookLines {
+    pub start: usize,
+    pub lines: Vec<String>,
+}
+
+/// Lines of one book matching a search, in reading order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookSearch {
+    pub hits: Vec<SearchHit>,
+    /// More lines matched than were returned.
+    pub truncated: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(rename = "lineIndex")]
+    pub line_index: usize,
+    pub snippet: String,
+    /// `[start, end)` of each match in `snippet`, in characters (code points).
+    pub highlights: Vec<(usize, usize)>,
+    /// Title of the chapter the line belongs to.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub chapter: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LibraryHitKind {
+    /// The book's title matched.
+    Book,
+    /// A chapter title matched.
+    Chapter,
+    /// A line of text matched.
+    Line,
+}
+
+/// One match of a library-wide search; hits come best first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibrarySearchHit {
+    #[serde(rename = "bookId")]
+    pub book_id: String,
+    #[serde(rename = "bookTitle")]
+    pub book_title: String,
+    pub kind: LibraryHitKind,
+    #[serde(rename = "lineIndex", skip_serializing_if = "Option::is_none", default)]
+    pub line_index: Option<usize>,
+    /// The matching chapter, or the chapter a matching line belongs to.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub chapter: Option<String>,
+    pub snippet: String,
+    /// `[start, end)` of each match in `snippet`, in characters (code points).
+    pub highlights: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Comic {
     pub id: String,
     pub title: String,
@@ -70,15 +149,43 @@ pub struct ComicImage {
     pub index: u32,
 }
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct FileTags {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub starred: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct Config {
     pub cache_dir: Option<String>,
+    /// Require a paired device from loopback clients too, not just the LAN.
+    #[serde(default)]
+    pub loopback_auth: bool,
+    /// Libraries shown only to some clients; the others see every library.
+    #[serde(default)]
+    pub library_access: Vec<LibraryAccess>,
+    /// Ranges the server accepts connections from, in CIDR notation or single
+    /// addresses; empty accepts any. The Mac itself is always accepted.
+    #[serde(default)]
+    pub allowed_ranges: Vec<String>,
+    /// Ranges whose devices may change things; when set, devices connecting
+    /// from elsewhere are read-only, like guests.
+    #[serde(default)]
+    pub writer_ranges: Vec<String>,
+}
+
+/// Restricts one library to the clients in `ranges` or holding one of `keys`.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct LibraryAccess {
+    pub library_id: String,
+    /// IP ranges in CIDR notation, or single addresses.
+    #[serde(default)]
+    pub ranges: Vec<String>,
+    /// Access keys a client may send instead of connecting from a range.
+    #[serde(default)]
+    pub keys: Vec<String>,
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
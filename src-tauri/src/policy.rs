@@ -0,0 +1,95 @@
+//! Read-only clients and the connection allowlist.
+//!
+//! The config's `allowed_ranges` lists the ranges the server accepts
+//! connections from; anything else is turned away before pairing. Every
+//! request is also classified as a read or a write: guest devices, and devices
+//! outside `writer_ranges` when it is set, may only read, so they cannot tag,
+//! reorder or remove libraries, write store blobs or progress, or reveal
+//! files. The Mac itself is never restricted.
+
+use std::net::IpAddr;
+
+use axum::http::Method;
+use tauri::{AppHandle, Runtime};
+
+use crate::access;
+use crate::auth::{self, Caller};
+use crate::config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Read,
+    Write,
+}
+
+/// Whether a request reads or writes. Safe methods read, as do the requests
+/// that only change the caller's own session: pairing, logging out, picking a
+/// profile and entering an access key. Everything else writes.
+pub fn classify(method: &Method, path: &str) -> Mode {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Mode::Read;
+    }
+    let session = matches!(
+        path,
+        "/api/auth/pair" | "/api/auth/logout" | "/api/access-key"
+    ) || (path.starts_with("/api/profiles/") && path.ends_with("/select"));
+    if session { Mode::Read } else { Mode::Write }
+}
+
+/// Whether the server accepts a connection from `ip`.
+pub fn admits<R: Runtime>(app: &AppHandle<R>, ip: IpAddr) -> bool {
+    ip.is_loopback() || listed(&config::get(app).allowed_ranges, ip)
+}
+
+/// Whether `caller`, connecting from `ip`, may make writes.
+pub fn may_write<R: Runtime>(app: &AppHandle<R>, caller: &Caller, ip: IpAddr) -> bool {
+    if ip.is_loopback() {
+        return true;
+    }
+    match caller {
+        Caller::Loopback => true,
+        Caller::Device(id) => {
+            listed(&config::get(app).writer_ranges, ip) && !auth::is_guest(app, id)
+        }
+    }
+}
+
+/// Whether `ip` is in one of `ranges`; an empty list admits everyone.
+fn listed(ranges: &[String], ip: IpAddr) -> bool {
+    ranges.is_empty() || ranges.iter().any(|range| access::in_range(range, ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_session_requests_among_mutating_methods_count_as_reads() {
+        assert_eq!(classify(&Method::GET, "/api/libraries"), Mode::Read);
+        assert_eq!(classify(&Method::HEAD, "/file"), Mode::Read);
+        assert_eq!(classify(&Method::POST, "/api/auth/pair"), Mode::Read);
+        assert_eq!(
+            classify(&Method::POST, "/api/profiles/kid/select"),
+            Mode::Read
+        );
+        assert_eq!(classify(&Method::DELETE, "/api/access-key"), Mode::Read);
+
+        assert_eq!(classify(&Method::POST, "/api/tag"), Mode::Write);
+        assert_eq!(classify(&Method::PUT, "/api/libraries/order"), Mode::Write);
+        assert_eq!(classify(&Method::DELETE, "/api/library/lib-1"), Mode::Write);
+        assert_eq!(classify(&Method::PUT, "/api/store/theme"), Mode::Write);
+        assert_eq!(classify(&Method::POST, "/api/reveal"), Mode::Write);
+        assert_eq!(classify(&Method::PUT, "/api/profiles/kid"), Mode::Write);
+    }
+
+    #[test]
+    fn an_empty_range_list_admits_everyone() {
+        let home = IpAddr::from([192, 168, 1, 30]);
+        let outside = IpAddr::from([203, 0, 113, 9]);
+        assert!(listed(&[], outside));
+
+        let ranges = vec!["192.168.1.0/24".to_string()];
+        assert!(listed(&ranges, home));
+        assert!(!listed(&ranges, outside));
+    }
+}
@@ -0,0 +1,235 @@
+//! Reader profiles.
+//!
+//! Everyone in a household reads under their own profile, with separate
+//! progress, favorite chapters and `/api/store` blobs; file tags stay shared.
+//! A request's profile is named by the `X-Eriri-Profile` header, else by the
+//! cookie a browser gets when it picks one, else it is the default profile,
+//! which also owns everything saved before profiles existed. The profile list
+//! lives in `progress.db`, beside the progress it scopes.
+
+use std::path::PathBuf;
+
+use axum::http::HeaderMap;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime};
+use uuid::Uuid;
+
+use crate::config;
+use crate::progress::{self, ProgressDb};
+
+/// The profile requests fall back to. Progress tables default their
+/// `profile_id` to this literal.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Header naming the profile of one request.
+pub const HEADER: &str = "x-eriri-profile";
+
+/// Cookie holding a browser's chosen profile.
+pub const COOKIE: &str = "eriri_profile";
+
+/// Cookie lifetime, matching the device token's.
+const COOKIE_MAX_AGE_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+pub(crate) const PROFILE_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS profiles (
+        id         TEXT PRIMARY KEY,
+        name       TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    INSERT OR IGNORE INTO profiles (id, name, created_at) VALUES ('default', '默认', 0);";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    /// Whether requests from the caller use this profile.
+    pub current: bool,
+}
+
+/// The profile a request is scoped to, set by the auth middleware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileId(pub String);
+
+/// The profile of a request with `headers`, or `None` when its header names
+/// a profile that does not exist. A stale cookie falls back to the default.
+pub fn resolve<R: Runtime>(app: &AppHandle<R>, headers: &HeaderMap) -> Option<String> {
+    let named = headers
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim);
+    if let Some(id) = named {
+        return exists(app, id).then(|| id.to_string());
+    }
+    match crate::auth::cookie(headers, COOKIE) {
+        Some(id) if exists(app, id) => Some(id.to_string()),
+        _ => Some(DEFAULT_PROFILE.to_string()),
+    }
+}
+
+fn exists<R: Runtime>(app: &AppHandle<R>, id: &str) -> bool {
+    with_db(app, |conn| profile_exists(conn, id)).unwrap_or(false)
+}
+
+/// `Set-Cookie` value making `id` the browser's profile.
+pub fn selection_cookie(id: &str) -> String {
+    format!("{COOKIE}={id}; Path=/; Max-Age={COOKIE_MAX_AGE_SECS}; HttpOnly; SameSite=Strict")
+}
+
+/// Where a profile's store blobs live: the store dir itself for the default
+/// profile, a subdirectory for the others.
+pub fn store_dir<R: Runtime>(app: &AppHandle<R>, id: &str) -> PathBuf {
+    let store_dir = config::get_store_dir(app);
+    if id == DEFAULT_PROFILE {
+        store_dir
+    } else {
+        store_dir.join("profiles").join(id)
+    }
+}
+
+// --- Profiles ---
+
+/// All profiles, the default first, then oldest first.
+pub fn list(app: &AppHandle) -> Result<Vec<Profile>, String> {
+    with_db(app, list_profiles)
+}
+
+pub fn create(app: &AppHandle, name: &str) -> Result<Profile, String> {
+    let profile = Profile {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        created_at: crate::scanner::utils::current_time_millis() as i64,
+        current: false,
+    };
+    with_db(app, |conn| {
+        insert_profile(conn, &profile.id, &profile.name, profile.created_at)
+    })?;
+    Ok(profile)
+}
+
+/// Rename a profile; `false` when there is no such profile.
+pub fn rename(app: &AppHandle, id: &str, name: &str) -> Result<bool, String> {
+    with_db(app, |conn| rename_profile(conn, id, name))
+}
+
+/// Remove a profile with its progress and store blobs; `false` when there is
+/// no such profile. The default profile cannot be removed.
+pub fn remove(app: &AppHandle, id: &str) -> Result<bool, String> {
+    if id == DEFAULT_PROFILE {
+        return Err("The default profile cannot be removed".to_string());
+    }
+    if !with_db(app, |conn| delete_profile(conn, id))? {
+        return Ok(false);
+    }
+    let dir = store_dir(app, id);
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    Ok(true)
+}
+
+fn with_db<T, R: Runtime>(
+    app: &AppHandle<R>,
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let state = app.state::<ProgressDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    f(&conn).map_err(|e| e.to_string())
+}
+
+// --- Data access ---
+
+fn list_profiles(conn: &Connection) -> rusqlite::Result<Vec<Profile>> {
+    conn.prepare(
+        "SELECT id, name, created_at FROM profiles
+         ORDER BY id != 'default', created_at, id",
+    )?
+    .query_map([], |row| {
+        Ok(Profile {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+            current: false,
+        })
+    })?
+    .collect()
+}
+
+fn profile_exists(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    Ok(conn
+        .query_row("SELECT 1 FROM profiles WHERE id = ?1", params![id], |_| {
+            Ok(())
+        })
+        .optional()?
+        .is_some())
+}
+
+fn insert_profile(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    created_at: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO profiles (id, name, created_at) VALUES (?1, ?2, ?3)",
+        params![id, name, created_at],
+    )?;
+    Ok(())
+}
+
+fn rename_profile(conn: &Connection, id: &str, name: &str) -> rusqlite::Result<bool> {
+    let renamed = conn.execute(
+        "UPDATE profiles SET name = ?2 WHERE id = ?1",
+        params![id, name],
+    )?;
+    Ok(renamed > 0)
+}
+
+/// Delete a profile and its progress.
+fn delete_profile(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    let tx = conn.unchecked_transaction()?;
+    progress::clear_profile(&tx, id)?;
+    let removed = tx.execute("DELETE FROM profiles WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(removed > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().expect("open in-memory progress db");
+        progress::create_schema(&conn).expect("create progress schema");
+        conn
+    }
+
+    #[test]
+    fn the_default_profile_exists_and_removing_another_clears_its_progress() {
+        let conn = test_conn();
+        insert_profile(&conn, "kid", "Kid", 5).expect("insert profile");
+        let names: Vec<_> = list_profiles(&conn)
+            .expect("list profiles")
+            .into_iter()
+            .map(|profile| profile.id)
+            .collect();
+        assert_eq!(names, vec![DEFAULT_PROFILE, "kid"]);
+
+        progress::set_favorites(&conn, "kid", "book-1", &[3]).expect("set kid favorites");
+        progress::set_favorites(&conn, DEFAULT_PROFILE, "book-1", &[7])
+            .expect("set default favorites");
+
+        assert!(delete_profile(&conn, "kid").expect("delete profile"));
+        assert!(!profile_exists(&conn, "kid").expect("look up deleted profile"));
+        assert!(!delete_profile(&conn, "kid").expect("delete missing profile"));
+        let snapshot = progress::get_snapshot(&conn, DEFAULT_PROFILE).expect("read snapshot");
+        assert_eq!(snapshot.favorite_chapters["book-1"], vec![7]);
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM favorite_chapters", [], |row| {
+                row.get(0)
+            })
+            .expect("count favorites");
+        assert_eq!(count, 1);
+    }
+}
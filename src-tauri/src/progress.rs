@@ -2,7 +2,9 @@
 //!
 //! Comic/book progress and favorite chapters live in SQLite, so every browser
 //! client shares one source of truth with field-level updates (no whole-blob
-//! clobbering across devices). The legacy `progress.json` is migrated once.
+//! clobbering across devices). Every entry belongs to a reader profile (see
+//! `profile`). The legacy `progress.json` is migrated once, into the default
+//! profile.
 
 use std::collections::HashMap;
 use std::sync::Mutex;
@@ -13,6 +15,8 @@ use tauri::{AppHandle, Manager};
 use tracing::{info, warn};
 
 use crate::config;
+use crate::profile::{self, DEFAULT_PROFILE};
+use crate::revision;
 
 pub struct ProgressDb(pub Mutex<Connection>);
 
@@ -23,6 +27,9 @@ pub struct ComicProgress {
     pub percent: f64,
     #[serde(rename = "lastRead")]
     pub last_read: i64,
+    /// The paired device that wrote this; set by the server, not the client.
+    #[serde(rename = "deviceId", default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,35 +41,74 @@ pub struct BookProgress {
     pub last_read: i64,
     #[serde(rename = "currentChapterTitle", default)]
     pub current_chapter_title: Option<String>,
+    /// The paired device that wrote this; set by the server, not the client.
+    #[serde(rename = "deviceId", default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct Snapshot {
+    /// The progress revision these entries are current at.
+    pub revision: i64,
     pub comics: HashMap<String, ComicProgress>,
     pub books: HashMap<String, BookProgress>,
     #[serde(rename = "favoriteChapters")]
     pub favorite_chapters: HashMap<String, Vec<i64>>,
 }
 
+/// Progress written after a revision, and the ids cleared since.
+#[derive(Debug, Serialize)]
+pub struct ProgressChanges {
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+    pub removed: RemovedProgress,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemovedProgress {
+    pub comics: Vec<String>,
+    pub books: Vec<String>,
+    /// Books left with no favorite chapters.
+    #[serde(rename = "favoriteChapters")]
+    pub favorite_chapters: Vec<String>,
+}
+
+/// Progress tables and the key each is tombstoned by.
+const PROGRESS_TABLES: [(&str, &str); 3] = [
+    ("comic_progress", "comic_id"),
+    ("book_progress", "book_id"),
+    ("favorite_chapters", "book_id"),
+];
+
 const PROGRESS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS comic_progress (
-        comic_id   TEXT PRIMARY KEY,
+        profile_id TEXT    NOT NULL DEFAULT 'default',
+        comic_id   TEXT    NOT NULL,
         current    INTEGER NOT NULL,
         total      INTEGER NOT NULL,
         percent    REAL    NOT NULL,
-        last_read  INTEGER NOT NULL
+        last_read  INTEGER NOT NULL,
+        revision   INTEGER NOT NULL DEFAULT 0,
+        device_id  TEXT,
+        PRIMARY KEY (profile_id, comic_id)
     );
     CREATE TABLE IF NOT EXISTS book_progress (
-        book_id               TEXT PRIMARY KEY,
+        profile_id            TEXT    NOT NULL DEFAULT 'default',
+        book_id               TEXT    NOT NULL,
         current               INTEGER NOT NULL,
         total                 INTEGER NOT NULL,
         percent               REAL    NOT NULL,
         last_read             INTEGER NOT NULL,
-        current_chapter_title TEXT
+        current_chapter_title TEXT,
+        revision              INTEGER NOT NULL DEFAULT 0,
+        device_id             TEXT,
+        PRIMARY KEY (profile_id, book_id)
     );
     CREATE TABLE IF NOT EXISTS favorite_chapters (
-        book_id    TEXT NOT NULL,
+        profile_id TEXT    NOT NULL DEFAULT 'default',
+        book_id    TEXT    NOT NULL,
         line_index INTEGER NOT NULL,
-        PRIMARY KEY (book_id, line_index)
+        revision   INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (profile_id, book_id, line_index)
     );";
 
 // --- Setup ---
@@ -74,7 +120,22 @@ pub fn init(app: &AppHandle) -> rusqlite::Result<()> {
 
     let conn = Connection::open(&db_path)?;
     conn.pragma_update(None, "journal_mode", "WAL")?;
-    conn.execute_batch(PROGRESS_SCHEMA)?;
+    create_schema(&conn)?;
+    // Databases created before revisions; their rows all count as revision 0.
+    for (table, _) in PROGRESS_TABLES {
+        let _ = conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN revision INTEGER NOT NULL DEFAULT 0"),
+            [],
+        );
+    }
+    // ...and before progress was attributed to devices.
+    for table in ["comic_progress", "book_progress"] {
+        let _ = conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN device_id TEXT"),
+            [],
+        );
+    }
+    migrate_to_profiles(&conn)?;
 
     migrate_from_json(app, &conn);
 
@@ -82,6 +143,62 @@ pub fn init(app: &AppHandle) -> rusqlite::Result<()> {
     Ok(())
 }
 
+/// Create the progress and profile tables, the revision counter and the
+/// triggers that leave tombstones for cleared progress.
+pub(crate) fn create_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(PROGRESS_SCHEMA)?;
+    conn.execute_batch(profile::PROFILE_SCHEMA)?;
+    conn.execute_batch(revision::REVISION_SCHEMA)?;
+    for (table, key) in PROGRESS_TABLES {
+        conn.execute_batch(&revision::scoped_tombstone_trigger(
+            table,
+            "profile_id",
+            key,
+        ))?;
+    }
+    Ok(())
+}
+
+/// Databases created before profiles key entries by id alone. Rebuild their
+/// tables keyed by profile too, keeping the entries as the default profile's.
+fn migrate_to_profiles(conn: &Connection) -> rusqlite::Result<()> {
+    let has_profiles: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('comic_progress')
+         WHERE name = 'profile_id'",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_profiles {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for (table, _) in PROGRESS_TABLES {
+        tx.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {table}_tombstone;
+             ALTER TABLE {table} RENAME TO {table}_old;"
+        ))?;
+    }
+    tx.execute_batch(PROGRESS_SCHEMA)?;
+    for (table, _) in PROGRESS_TABLES {
+        let columns = tx
+            .prepare(&format!(
+                "SELECT name FROM pragma_table_info('{table}_old')"
+            ))?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .join(", ");
+        tx.execute_batch(&format!(
+            "INSERT INTO {table} ({columns}) SELECT {columns} FROM {table}_old;
+             DROP TABLE {table}_old;"
+        ))?;
+    }
+    create_schema(&tx)?;
+    tx.commit()?;
+    info!("Moved reading progress into the default profile");
+    Ok(())
+}
+
 /// One-time import of the legacy `progress.json` blob, only when the DB is empty.
 fn migrate_from_json(app: &AppHandle, conn: &Connection) {
     let count: i64 = conn
@@ -109,7 +226,7 @@ fn migrate_from_json(app: &AppHandle, conn: &Connection) {
     if let Some(comics) = state.get("comics").and_then(|v| v.as_object()) {
         for (id, p) in comics {
             if let Ok(progress) = serde_json::from_value::<ComicProgress>(p.clone())
-                && upsert_comic(conn, id, &progress).is_ok()
+                && upsert_comic(conn, DEFAULT_PROFILE, id, &progress).is_ok()
             {
                 imported += 1;
             }
@@ -118,7 +235,7 @@ fn migrate_from_json(app: &AppHandle, conn: &Connection) {
     if let Some(books) = state.get("books").and_then(|v| v.as_object()) {
         for (id, p) in books {
             if let Ok(progress) = serde_json::from_value::<BookProgress>(p.clone())
-                && upsert_book(conn, id, &progress).is_ok()
+                && upsert_book(conn, DEFAULT_PROFILE, id, &progress).is_ok()
             {
                 imported += 1;
             }
@@ -127,7 +244,7 @@ fn migrate_from_json(app: &AppHandle, conn: &Connection) {
     if let Some(favs) = state.get("favoriteChapters").and_then(|v| v.as_object()) {
         for (id, lines) in favs {
             if let Ok(list) = serde_json::from_value::<Vec<i64>>(lines.clone()) {
-                let _ = set_favorites(conn, id, &list);
+                let _ = set_favorites(conn, DEFAULT_PROFILE, id, &list);
             }
         }
     }
@@ -144,12 +261,46 @@ fn migrate_from_json(app: &AppHandle, conn: &Connection) {
 
 // --- Data access (operate on a borrowed connection) ---
 
-pub fn get_snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
+pub fn get_snapshot(conn: &Connection, profile: &str) -> rusqlite::Result<Snapshot> {
+    snapshot_since(conn, profile, None)
+}
+
+/// What changed in `profile`'s reading progress after revision `since`.
+pub fn progress_changes(
+    conn: &Connection,
+    profile: &str,
+    since: i64,
+) -> rusqlite::Result<ProgressChanges> {
+    let snapshot = snapshot_since(conn, profile, Some(since))?;
+    let [comics, books, favorite_chapters] = PROGRESS_TABLES.map(|(table, key)| {
+        revision::removed_in_scope(conn, table, "profile_id", key, profile, since)
+    });
+    Ok(ProgressChanges {
+        snapshot,
+        removed: RemovedProgress {
+            comics: comics?,
+            books: books?,
+            favorite_chapters: favorite_chapters?,
+        },
+    })
+}
+
+/// Entries written after revision `since`, or every entry without one. A book
+/// whose favorites changed is listed with its full set.
+fn snapshot_since(
+    conn: &Connection,
+    profile: &str,
+    since: Option<i64>,
+) -> rusqlite::Result<Snapshot> {
+    let revision = revision::current(conn)?;
+
     let mut comics = HashMap::new();
     {
-        let mut stmt = conn
-            .prepare("SELECT comic_id, current, total, percent, last_read FROM comic_progress")?;
-        let rows = stmt.query_map([], |row| {
+        let mut stmt = conn.prepare(
+            "SELECT comic_id, current, total, percent, last_read, device_id FROM comic_progress
+             WHERE profile_id = ?1 AND (?2 IS NULL OR revision > ?2)",
+        )?;
+        let rows = stmt.query_map(params![profile, since], |row| {
             Ok((
                 row.get::<_, String>(0)?,
                 ComicProgress {
@@ -157,6 +308,7 @@ pub fn get_snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
                     total: row.get(2)?,
                     percent: row.get(3)?,
                     last_read: row.get(4)?,
+                    device_id: row.get(5)?,
                 },
             ))
         })?;
@@ -169,9 +321,11 @@ pub fn get_snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
     let mut books = HashMap::new();
     {
         let mut stmt = conn.prepare(
-            "SELECT book_id, current, total, percent, last_read, current_chapter_title FROM book_progress",
+            "SELECT book_id, current, total, percent, last_read, current_chapter_title,
+                    device_id
+             FROM book_progress WHERE profile_id = ?1 AND (?2 IS NULL OR revision > ?2)",
         )?;
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params![profile, since], |row| {
             Ok((
                 row.get::<_, String>(0)?,
                 BookProgress {
@@ -180,6 +334,7 @@ pub fn get_snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
                     percent: row.get(3)?,
                     last_read: row.get(4)?,
                     current_chapter_title: row.get(5)?,
+                    device_id: row.get(6)?,
                 },
             ))
         })?;
@@ -192,9 +347,13 @@ pub fn get_snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
     let mut favorite_chapters: HashMap<String, Vec<i64>> = HashMap::new();
     {
         let mut stmt = conn.prepare(
-            "SELECT book_id, line_index FROM favorite_chapters ORDER BY book_id, line_index",
+            "SELECT book_id, line_index FROM favorite_chapters
+             WHERE profile_id = ?1 AND (?2 IS NULL OR book_id IN
+                (SELECT book_id FROM favorite_chapters
+                 WHERE profile_id = ?1 AND revision > ?2))
+             ORDER BY book_id, line_index",
         )?;
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params![profile, since], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
         })?;
         for row in rows {
@@ -204,93 +363,194 @@ pub fn get_snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
     }
 
     Ok(Snapshot {
+        revision,
         comics,
         books,
         favorite_chapters,
     })
 }
 
-pub fn upsert_comic(conn: &Connection, id: &str, p: &ComicProgress) -> rusqlite::Result<()> {
+/// How far one comic or book has been read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadMark {
+    pub percent: f64,
+    pub last_read: i64,
+}
+
+pub fn comic_read_marks(
+    conn: &Connection,
+    profile: &str,
+) -> rusqlite::Result<HashMap<String, ReadMark>> {
+    read_marks(
+        conn,
+        profile,
+        "SELECT comic_id, percent, last_read FROM comic_progress WHERE profile_id = ?1",
+    )
+}
+
+pub fn book_read_marks(
+    conn: &Connection,
+    profile: &str,
+) -> rusqlite::Result<HashMap<String, ReadMark>> {
+    read_marks(
+        conn,
+        profile,
+        "SELECT book_id, percent, last_read FROM book_progress WHERE profile_id = ?1",
+    )
+}
+
+fn read_marks(
+    conn: &Connection,
+    profile: &str,
+    sql: &str,
+) -> rusqlite::Result<HashMap<String, ReadMark>> {
+    conn.prepare(sql)?
+        .query_map(params![profile], |row| {
+            Ok((
+                row.get(0)?,
+                ReadMark {
+                    percent: row.get(1)?,
+                    last_read: row.get(2)?,
+                },
+            ))
+        })?
+        .collect()
+}
+
+pub fn upsert_comic(
+    conn: &Connection,
+    profile: &str,
+    id: &str,
+    p: &ComicProgress,
+) -> rusqlite::Result<()> {
     conn.execute(
-        "INSERT INTO comic_progress (comic_id, current, total, percent, last_read)
-         VALUES (?1, ?2, ?3, ?4, ?5)
-         ON CONFLICT(comic_id) DO UPDATE SET
+        "INSERT INTO comic_progress
+            (profile_id, comic_id, current, total, percent, last_read, revision, device_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(profile_id, comic_id) DO UPDATE SET
             current = excluded.current,
             total = excluded.total,
             percent = excluded.percent,
-            last_read = excluded.last_read",
-        params![id, p.current, p.total, p.percent, p.last_read],
+            last_read = excluded.last_read,
+            revision = excluded.revision,
+            device_id = excluded.device_id",
+        params![
+            profile,
+            id,
+            p.current,
+            p.total,
+            p.percent,
+            p.last_read,
+            revision::next(conn)?,
+            p.device_id
+        ],
     )?;
     Ok(())
 }
 
-pub fn delete_comic(conn: &Connection, id: &str) -> rusqlite::Result<()> {
+pub fn delete_comic(conn: &Connection, profile: &str, id: &str) -> rusqlite::Result<()> {
+    revision::next(conn)?;
     conn.execute(
-        "DELETE FROM comic_progress WHERE comic_id = ?1",
-        params![id],
+        "DELETE FROM comic_progress WHERE profile_id = ?1 AND comic_id = ?2",
+        params![profile, id],
     )?;
     Ok(())
 }
 
-pub fn upsert_book(conn: &Connection, id: &str, p: &BookProgress) -> rusqlite::Result<()> {
+pub fn upsert_book(
+    conn: &Connection,
+    profile: &str,
+    id: &str,
+    p: &BookProgress,
+) -> rusqlite::Result<()> {
     conn.execute(
-        "INSERT INTO book_progress (book_id, current, total, percent, last_read, current_chapter_title)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-         ON CONFLICT(book_id) DO UPDATE SET
+        "INSERT INTO book_progress
+            (profile_id, book_id, current, total, percent, last_read, current_chapter_title,
+             revision, device_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT(profile_id, book_id) DO UPDATE SET
             current = excluded.current,
             total = excluded.total,
             percent = excluded.percent,
             last_read = excluded.last_read,
-            current_chapter_title = excluded.current_chapter_title",
+            current_chapter_title = excluded.current_chapter_title,
+            revision = excluded.revision,
+            device_id = excluded.device_id",
         params![
+            profile,
             id,
             p.current,
             p.total,
             p.percent,
             p.last_read,
-            p.current_chapter_title
+            p.current_chapter_title,
+            revision::next(conn)?,
+            p.device_id
         ],
     )?;
     Ok(())
 }
 
-pub fn delete_book(conn: &Connection, id: &str) -> rusqlite::Result<()> {
-    conn.execute("DELETE FROM book_progress WHERE book_id = ?1", params![id])?;
+pub fn delete_book(conn: &Connection, profile: &str, id: &str) -> rusqlite::Result<()> {
+    revision::next(conn)?;
+    conn.execute(
+        "DELETE FROM book_progress WHERE profile_id = ?1 AND book_id = ?2",
+        params![profile, id],
+    )?;
     Ok(())
 }
 
 /// Replace the full favorite-chapter set for a book (idempotent).
-pub fn set_favorites(conn: &Connection, book_id: &str, lines: &[i64]) -> rusqlite::Result<()> {
+pub fn set_favorites(
+    conn: &Connection,
+    profile: &str,
+    book_id: &str,
+    lines: &[i64],
+) -> rusqlite::Result<()> {
     let tx = conn.unchecked_transaction()?;
+    let revision = revision::next(&tx)?;
     tx.execute(
-        "DELETE FROM favorite_chapters WHERE book_id = ?1",
-        params![book_id],
+        "DELETE FROM favorite_chapters WHERE profile_id = ?1 AND book_id = ?2",
+        params![profile, book_id],
     )?;
     for &line in lines {
         tx.execute(
-            "INSERT OR IGNORE INTO favorite_chapters (book_id, line_index) VALUES (?1, ?2)",
-            params![book_id, line],
+            "INSERT OR IGNORE INTO favorite_chapters (profile_id, book_id, line_index, revision)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![profile, book_id, line, revision],
         )?;
     }
     tx.commit()
 }
 
-pub fn delete_favorites(conn: &Connection, book_id: &str) -> rusqlite::Result<()> {
+pub fn delete_favorites(conn: &Connection, profile: &str, book_id: &str) -> rusqlite::Result<()> {
+    revision::next(conn)?;
     conn.execute(
-        "DELETE FROM favorite_chapters WHERE book_id = ?1",
-        params![book_id],
+        "DELETE FROM favorite_chapters WHERE profile_id = ?1 AND book_id = ?2",
+        params![profile, book_id],
     )?;
     Ok(())
 }
 
+/// Delete every entry of `profile`, when the profile itself is removed.
+pub fn clear_profile(conn: &Connection, profile: &str) -> rusqlite::Result<()> {
+    revision::next(conn)?;
+    for (table, _) in PROGRESS_TABLES {
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE profile_id = ?1"),
+            params![profile],
+        )?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn test_conn() -> Connection {
         let conn = Connection::open_in_memory().expect("open in-memory progress db");
-        conn.execute_batch(PROGRESS_SCHEMA)
-            .expect("create progress schema");
+        create_schema(&conn).expect("create progress schema");
         conn
     }
 
@@ -311,7 +571,14 @@ mod tests {
 
         assert_eq!(
             names,
-            vec!["book_progress", "comic_progress", "favorite_chapters"]
+            vec![
+                "book_progress",
+                "comic_progress",
+                "favorite_chapters",
+                "profiles",
+                "revision",
+                "tombstones"
+            ]
         );
     }
 
@@ -323,6 +590,7 @@ mod tests {
             total: 5,
             percent: 50.0,
             last_read: 100,
+            device_id: None,
         };
         let book = BookProgress {
             current: 10,
@@ -330,20 +598,33 @@ mod tests {
             percent: 10.0,
             last_read: 200,
             current_chapter_title: Some("第一章".to_string()),
+            device_id: None,
         };
 
-        upsert_comic(&conn, "comic-1", &comic).expect("upsert comic progress");
-        upsert_book(&conn, "book-1", &book).expect("upsert book progress");
+        upsert_comic(&conn, DEFAULT_PROFILE, "comic-1", &comic).expect("upsert comic progress");
+        upsert_book(&conn, DEFAULT_PROFILE, "book-1", &book).expect("upsert book progress");
 
-        let snapshot = get_snapshot(&conn).expect("read progress snapshot");
+        let snapshot = get_snapshot(&conn, DEFAULT_PROFILE).expect("read progress snapshot");
         assert_eq!(snapshot.comics["comic-1"].current, 2);
         assert_eq!(
             snapshot.books["book-1"].current_chapter_title.as_deref(),
             Some("第一章")
         );
+        assert_eq!(
+            comic_read_marks(&conn, DEFAULT_PROFILE).expect("read comic marks")["comic-1"],
+            ReadMark {
+                percent: 50.0,
+                last_read: 100
+            }
+        );
+        assert_eq!(
+            book_read_marks(&conn, DEFAULT_PROFILE).expect("read book marks")["book-1"].last_read,
+            200
+        );
 
-        delete_comic(&conn, "comic-1").expect("delete comic progress");
-        let snapshot = get_snapshot(&conn).expect("read progress snapshot after delete");
+        delete_comic(&conn, DEFAULT_PROFILE, "comic-1").expect("delete comic progress");
+        let snapshot =
+            get_snapshot(&conn, DEFAULT_PROFILE).expect("read progress snapshot after delete");
         assert!(!snapshot.comics.contains_key("comic-1"));
         assert!(snapshot.books.contains_key("book-1"));
     }
@@ -352,14 +633,15 @@ mod tests {
     fn favorite_chapters_replace_previous_values_and_snapshot_sorted() {
         let conn = test_conn();
 
-        set_favorites(&conn, "book-1", &[20, 10, 10]).expect("set favorites");
-        set_favorites(&conn, "book-1", &[30, 0]).expect("replace favorites");
+        set_favorites(&conn, DEFAULT_PROFILE, "book-1", &[20, 10, 10]).expect("set favorites");
+        set_favorites(&conn, DEFAULT_PROFILE, "book-1", &[30, 0]).expect("replace favorites");
 
-        let snapshot = get_snapshot(&conn).expect("read progress snapshot");
+        let snapshot = get_snapshot(&conn, DEFAULT_PROFILE).expect("read progress snapshot");
         assert_eq!(snapshot.favorite_chapters["book-1"], vec![0, 30]);
 
-        delete_favorites(&conn, "book-1").expect("delete favorites");
-        let snapshot = get_snapshot(&conn).expect("read progress snapshot after delete");
+        delete_favorites(&conn, DEFAULT_PROFILE, "book-1").expect("delete favorites");
+        let snapshot =
+            get_snapshot(&conn, DEFAULT_PROFILE).expect("read progress snapshot after delete");
         assert!(!snapshot.favorite_chapters.contains_key("book-1"));
     }
 
@@ -367,13 +649,14 @@ mod tests {
     fn snapshot_starts_empty_and_book_progress_can_be_deleted() {
         let conn = test_conn();
 
-        let snapshot = get_snapshot(&conn).expect("read empty progress snapshot");
+        let snapshot = get_snapshot(&conn, DEFAULT_PROFILE).expect("read empty progress snapshot");
         assert!(snapshot.comics.is_empty());
         assert!(snapshot.books.is_empty());
         assert!(snapshot.favorite_chapters.is_empty());
 
         upsert_book(
             &conn,
+            DEFAULT_PROFILE,
             "book-1",
             &BookProgress {
                 current: 1,
@@ -381,44 +664,186 @@ mod tests {
                 percent: 10.0,
                 last_read: 100,
                 current_chapter_title: None,
+                device_id: None,
             },
         )
         .expect("upsert book progress without chapter");
-        delete_book(&conn, "book-1").expect("delete book progress");
+        delete_book(&conn, DEFAULT_PROFILE, "book-1").expect("delete book progress");
 
-        let snapshot = get_snapshot(&conn).expect("read progress snapshot after book delete");
+        let snapshot =
+            get_snapshot(&conn, DEFAULT_PROFILE).expect("read progress snapshot after book delete");
         assert!(!snapshot.books.contains_key("book-1"));
     }
 
+    #[test]
+    fn progress_changes_report_entries_written_and_cleared_after_a_revision() {
+        let conn = test_conn();
+        let comic = ComicProgress {
+            current: 1,
+            total: 5,
+            percent: 25.0,
+            last_read: 100,
+            device_id: None,
+        };
+        upsert_comic(&conn, DEFAULT_PROFILE, "comic-1", &comic).expect("upsert first comic");
+        upsert_comic(&conn, DEFAULT_PROFILE, "comic-2", &comic).expect("upsert second comic");
+        set_favorites(&conn, DEFAULT_PROFILE, "book-1", &[10, 20]).expect("set first favorites");
+        set_favorites(&conn, DEFAULT_PROFILE, "book-2", &[5]).expect("set second favorites");
+        let since = get_snapshot(&conn, DEFAULT_PROFILE)
+            .expect("read snapshot")
+            .revision;
+
+        upsert_comic(&conn, DEFAULT_PROFILE, "comic-2", &comic).expect("rewrite comic");
+        delete_comic(&conn, DEFAULT_PROFILE, "comic-1").expect("delete comic");
+        set_favorites(&conn, DEFAULT_PROFILE, "book-1", &[20]).expect("replace favorites");
+        delete_favorites(&conn, DEFAULT_PROFILE, "book-2").expect("delete favorites");
+
+        let changes =
+            progress_changes(&conn, DEFAULT_PROFILE, since).expect("read progress changes");
+        assert!(changes.snapshot.revision > since);
+        assert_eq!(
+            changes.snapshot.comics.keys().collect::<Vec<_>>(),
+            vec!["comic-2"]
+        );
+        assert_eq!(changes.snapshot.favorite_chapters["book-1"], vec![20]);
+        assert!(!changes.snapshot.favorite_chapters.contains_key("book-2"));
+        assert_eq!(changes.removed.comics, vec!["comic-1"]);
+        assert!(changes.removed.books.is_empty());
+        assert_eq!(changes.removed.favorite_chapters, vec!["book-2"]);
+
+        let latest = progress_changes(&conn, DEFAULT_PROFILE, changes.snapshot.revision)
+            .expect("read progress changes at latest revision");
+        assert!(latest.snapshot.comics.is_empty());
+        assert!(latest.snapshot.favorite_chapters.is_empty());
+        assert!(latest.removed.comics.is_empty());
+    }
+
     #[test]
     fn upserts_replace_existing_progress_fields() {
         let conn = test_conn();
 
         upsert_comic(
             &conn,
+            DEFAULT_PROFILE,
             "comic-1",
             &ComicProgress {
                 current: 1,
                 total: 5,
                 percent: 25.0,
                 last_read: 100,
+                device_id: Some("ipad".to_string()),
             },
         )
         .expect("insert comic progress");
         upsert_comic(
             &conn,
+            DEFAULT_PROFILE,
             "comic-1",
             &ComicProgress {
                 current: 4,
                 total: 5,
                 percent: 100.0,
                 last_read: 200,
+                device_id: Some("phone".to_string()),
             },
         )
         .expect("replace comic progress");
 
-        let snapshot = get_snapshot(&conn).expect("read replaced progress snapshot");
+        let snapshot =
+            get_snapshot(&conn, DEFAULT_PROFILE).expect("read replaced progress snapshot");
         assert_eq!(snapshot.comics["comic-1"].current, 4);
         assert_eq!(snapshot.comics["comic-1"].last_read, 200);
+        assert_eq!(
+            snapshot.comics["comic-1"].device_id.as_deref(),
+            Some("phone")
+        );
+    }
+    #[test]
+    fn profiles_keep_separate_progress_and_changes() {
+        let conn = test_conn();
+        let comic = |current| ComicProgress {
+            current,
+            total: 10,
+            percent: current as f64 * 10.0,
+            last_read: 100,
+            device_id: None,
+        };
+        upsert_comic(&conn, DEFAULT_PROFILE, "comic-1", &comic(2)).expect("upsert default comic");
+        upsert_comic(&conn, "kid", "comic-1", &comic(7)).expect("upsert kid comic");
+        let since = get_snapshot(&conn, "kid")
+            .expect("read kid snapshot")
+            .revision;
+
+        delete_comic(&conn, DEFAULT_PROFILE, "comic-1").expect("delete default comic");
+
+        assert!(
+            get_snapshot(&conn, DEFAULT_PROFILE)
+                .expect("read default snapshot")
+                .comics
+                .is_empty()
+        );
+        assert_eq!(
+            get_snapshot(&conn, "kid")
+                .expect("read kid snapshot")
+                .comics["comic-1"]
+                .current,
+            7
+        );
+        let kid = progress_changes(&conn, "kid", since).expect("read kid changes");
+        assert!(kid.removed.comics.is_empty());
+        let default =
+            progress_changes(&conn, DEFAULT_PROFILE, since).expect("read default changes");
+        assert_eq!(default.removed.comics, vec!["comic-1"]);
+    }
+
+    #[test]
+    fn entries_from_before_profiles_move_to_the_default_profile() {
+        let conn = Connection::open_in_memory().expect("open in-memory progress db");
+        conn.execute_batch(
+            "CREATE TABLE comic_progress (
+                comic_id TEXT PRIMARY KEY, current INTEGER NOT NULL, total INTEGER NOT NULL,
+                percent REAL NOT NULL, last_read INTEGER NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 0, device_id TEXT
+             );
+             CREATE TABLE book_progress (
+                book_id TEXT PRIMARY KEY, current INTEGER NOT NULL, total INTEGER NOT NULL,
+                percent REAL NOT NULL, last_read INTEGER NOT NULL, current_chapter_title TEXT,
+                revision INTEGER NOT NULL DEFAULT 0, device_id TEXT
+             );
+             CREATE TABLE favorite_chapters (
+                book_id TEXT NOT NULL, line_index INTEGER NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 0, PRIMARY KEY (book_id, line_index)
+             );
+             INSERT INTO comic_progress VALUES ('comic-1', 3, 10, 30.0, 100, 0, NULL);
+             INSERT INTO favorite_chapters VALUES ('book-1', 12, 0);",
+        )
+        .expect("create pre-profile schema");
+        for (table, key) in PROGRESS_TABLES {
+            conn.execute_batch(&format!(
+                "CREATE TRIGGER {table}_tombstone AFTER DELETE ON {table} BEGIN
+                    INSERT OR REPLACE INTO tombstones (kind, id, revision)
+                        SELECT '{table}', OLD.{key}, value FROM revision;
+                END;"
+            ))
+            .expect("create pre-profile trigger");
+        }
+        create_schema(&conn).expect("create progress schema over old tables");
+
+        migrate_to_profiles(&conn).expect("migrate to profiles");
+        migrate_to_profiles(&conn).expect("migrate again as a no-op");
+
+        let snapshot = get_snapshot(&conn, DEFAULT_PROFILE).expect("read default snapshot");
+        assert_eq!(snapshot.comics["comic-1"].current, 3);
+        assert_eq!(snapshot.favorite_chapters["book-1"], vec![12]);
+        upsert_comic(&conn, "kid", "comic-1", &snapshot.comics["comic-1"])
+            .expect("upsert the same comic for another profile");
+        delete_comic(&conn, DEFAULT_PROFILE, "comic-1").expect("delete default comic");
+        assert_eq!(
+            progress_changes(&conn, DEFAULT_PROFILE, 0)
+                .expect("read default changes")
+                .removed
+                .comics,
+            vec!["comic-1"]
+        );
     }
 }
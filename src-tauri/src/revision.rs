@@ -0,0 +1,98 @@
+//! Revision numbers for incremental sync.
+//!
+//! library.db and progress.db each keep one counter. A mutation takes the
+//! next value before it writes and stamps it on every row it inserts or
+//! updates; a row it deletes leaves a tombstone carrying that value, written
+//! by the table's `AFTER DELETE` trigger. A client that has seen revision N
+//! then asks for the rows and tombstones above N.
+
+use rusqlite::{Connection, params};
+
+/// The counter and tombstones, created alongside each database's tables.
+pub(crate) const REVISION_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS revision (
+        value INTEGER NOT NULL
+    );
+    INSERT INTO revision (value) SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM revision);
+    CREATE TABLE IF NOT EXISTS tombstones (
+        kind     TEXT NOT NULL,
+        id       TEXT NOT NULL,
+        revision INTEGER NOT NULL,
+        PRIMARY KEY (kind, id)
+    );";
+
+/// A trigger that buries rows deleted from `table`, keyed by `key`, at the
+/// current revision, keeping their `library` column in the tombstone's
+/// `library_id` so hidden libraries' removals can be left out. Replaces an
+/// older trigger on `table`.
+pub(crate) fn tombstone_trigger(table: &str, key: &str, library: &str) -> String {
+    format!(
+        "DROP TRIGGER IF EXISTS {table}_tombstone;
+        CREATE TRIGGER {table}_tombstone AFTER DELETE ON {table} BEGIN
+            INSERT OR REPLACE INTO tombstones (kind, id, revision, library_id)
+                SELECT '{table}', OLD.{key}, value, OLD.{library} FROM revision;
+        END;"
+    )
+}
+
+/// Like `tombstone_trigger`, for a table whose rows belong to scopes (such as
+/// reader profiles): the tombstone id is `{scope}/{key}`.
+pub(crate) fn scoped_tombstone_trigger(table: &str, scope: &str, key: &str) -> String {
+    format!(
+        "CREATE TRIGGER IF NOT EXISTS {table}_tombstone AFTER DELETE ON {table} BEGIN
+            INSERT OR REPLACE INTO tombstones (kind, id, revision)
+                SELECT '{table}', OLD.{scope} || '/' || OLD.{key}, value FROM revision;
+        END;"
+    )
+}
+
+/// Advance the counter for one mutation and return the new revision.
+pub fn next(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "UPDATE revision SET value = value + 1 RETURNING value",
+        [],
+        |row| row.get(0),
+    )
+}
+
+pub fn current(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("SELECT value FROM revision", [], |row| row.get(0))
+}
+
+/// Keys deleted from `table` after revision `since` that were not written
+/// back since, with the library each belonged to (`None` for tombstones
+/// buried before they kept it).
+pub fn removed_since(
+    conn: &Connection,
+    table: &str,
+    key: &str,
+    since: i64,
+) -> rusqlite::Result<Vec<(String, Option<String>)>> {
+    conn.prepare(&format!(
+        "SELECT id, library_id FROM tombstones
+         WHERE kind = ?1 AND revision > ?2 AND id NOT IN (SELECT {key} FROM {table})
+         ORDER BY id"
+    ))?
+    .query_map(params![table, since], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect()
+}
+
+/// Keys deleted from `table` in scope `scope_id` after revision `since` that
+/// were not written back since, for tables buried by `scoped_tombstone_trigger`.
+pub fn removed_in_scope(
+    conn: &Connection,
+    table: &str,
+    scope: &str,
+    key: &str,
+    scope_id: &str,
+    since: i64,
+) -> rusqlite::Result<Vec<String>> {
+    conn.prepare(&format!(
+        "SELECT substr(id, length(?3) + 2) AS key FROM tombstones
+         WHERE kind = ?1 AND revision > ?2 AND substr(id, 1, length(?3) + 1) = ?3 || '/'
+           AND substr(id, length(?3) + 2) NOT IN
+               (SELECT {key} FROM {table} WHERE {scope} = ?3)
+         ORDER BY key"
+    ))?
+    .query_map(params![table, since, scope_id], |row| row.get(0))?
+    .collect()
+}
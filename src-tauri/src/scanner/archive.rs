@@ -0,0 +1,945 @@
+//! Comic archives (CBZ/ZIP, CBR/RAR, CB7/7z, CBT/tar) read in place.
+//!
+//! An archive stands in for a comic directory: its image entries are the
+//! pages, listed in natural order and read one at a time on demand, so nothing
+//! is ever extracted to disk.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use sevenz_rust2::{ArchiveReader, Password};
+use zip::ZipArchive;
+
+use crate::thumbnail::is_image_file;
+
+use super::utils::is_hidden;
+
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["cbz", "zip", "cbr", "rar", "cb7", "7z", "cbt", "tar"];
+
+const ZIP_MAGIC: &[u8] = b"PK";
+const RAR_MAGIC: &[u8] = b"Rar!\x1a\x07";
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+/// POSIX tar has no leading magic; `ustar` sits at byte 257 of the first header.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Most bytes reserved up front for one entry. Entry sizes come from the
+/// archive's own headers, so a larger claim just grows the buffer as it reads.
+const MAX_PREALLOC_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Decoded pages of a solid 7z or RAR kept in memory at most. Larger archives
+/// are read entry by entry instead, each read decoding from the block start.
+const SOLID_CACHE_MAX_BYTES: u64 = 512 * 1024 * 1024;
+
+/// How long the solid cache outlives the last page read from it. A reader
+/// turns pages every few seconds; once it stops, the memory is given back.
+const SOLID_CACHE_IDLE: Duration = Duration::from_secs(120);
+
+/// Pages of the solid archive most recently read. A solid block can only be
+/// decoded from its start, so reading a reader's pages one request at a time
+/// would be quadratic; decoding them all once keeps page turns cheap.
+static SOLID_CACHE: Mutex<SolidCache> = Mutex::new(SolidCache {
+    entry: None,
+    used: None,
+    sweeping: false,
+});
+
+/// Page names and bytes, in archive order.
+type SolidPages = Vec<(String, Vec<u8>)>;
+type SolidEntry = (SolidKey, Arc<SolidPages>);
+
+#[derive(Default)]
+struct SolidCache {
+    entry: Option<SolidEntry>,
+    used: Option<Instant>,
+    /// Whether a thread is waiting to drop the pages once they go idle.
+    sweeping: bool,
+}
+
+impl SolidCache {
+    /// The cached pages of `key`, marked as used at `now`.
+    fn get(&mut self, key: &SolidKey, now: Instant) -> Option<Arc<SolidPages>> {
+        let (cached_key, pages) = self.entry.as_ref()?;
+        if cached_key != key {
+            return None;
+        }
+        self.used = Some(now);
+        Some(Arc::clone(pages))
+    }
+
+    fn put(&mut self, key: SolidKey, pages: Arc<SolidPages>, now: Instant) {
+        self.entry = Some((key, pages));
+        self.used = Some(now);
+    }
+
+    /// Drop the pages once they have gone `SOLID_CACHE_IDLE` unused; otherwise
+    /// how long until they will have.
+    fn expire(&mut self, now: Instant) -> Option<Duration> {
+        let idle = self
+            .used
+            .map_or(SOLID_CACHE_IDLE, |used| now.saturating_duration_since(used));
+        if idle < SOLID_CACHE_IDLE {
+            return Some(SOLID_CACHE_IDLE - idle);
+        }
+        self.entry = None;
+        self.used = None;
+        None
+    }
+}
+
+/// Identifies one version of an archive file, so a rewritten archive is never
+/// served from stale cached pages.
+#[derive(Clone, PartialEq)]
+struct SolidKey {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+pub fn is_comic_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext_str| {
+            ARCHIVE_EXTENSIONS
+                .iter()
+                .any(|&x| x.eq_ignore_ascii_case(ext_str))
+        })
+}
+
+/// An open comic archive. Opening parses the entry index once; keep the
+/// handle around to read several pages without re-parsing it.
+pub struct ComicArchive {
+    backend: Backend,
+}
+
+enum Backend {
+    Zip(ZipArchive<BufReader<File>>),
+    /// unrar walks the archive from the start for every read, so only the
+    /// path and the entry list are kept; a solid one is read through
+    /// `SOLID_CACHE` like a solid 7z.
+    Rar {
+        path: PathBuf,
+        entries: Vec<RarEntry>,
+        solid: bool,
+        key: SolidKey,
+    },
+    SevenZip {
+        reader: Box<ArchiveReader<File>>,
+        key: SolidKey,
+    },
+    /// Tar stores entries uncompressed, so each page is a byte range of the
+    /// file recorded while listing.
+    Tar {
+        file: File,
+        entries: Vec<TarEntry>,
+    },
+}
+
+struct RarEntry {
+    name: String,
+    size: u64,
+}
+
+struct TarEntry {
+    name: String,
+    offset: u64,
+    size: u64,
+}
+
+impl ComicArchive {
+    /// Open by content rather than extension: scanners often ship RAR data
+    /// under a `.cbz` name and vice versa.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open archive: {e}"))?;
+        let mut magic = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+        (&file)
+            .take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+            .read_to_end(&mut magic)
+            .map_err(|e| format!("Failed to open archive: {e}"))?;
+        let is_tar = magic
+            .get(TAR_MAGIC_OFFSET..)
+            .is_some_and(|m| m == TAR_MAGIC)
+            || path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("cbt") || ext.eq_ignore_ascii_case("tar")
+                });
+
+        let metadata = file
+            .metadata()
+            .map_err(|e| format!("Failed to open archive: {e}"))?;
+        let key = SolidKey {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        };
+
+        let backend = if magic.starts_with(RAR_MAGIC) {
+            let (entries, solid) = list_rar_entries(path)?;
+            Backend::Rar {
+                path: path.to_path_buf(),
+                entries,
+                solid,
+                key,
+            }
+        } else if magic.starts_with(SEVEN_ZIP_MAGIC) {
+            let reader = ArchiveReader::new(file, Password::empty())
+                .map_err(|e| format!("Failed to read archive: {e}"))?;
+            Backend::SevenZip {
+                reader: Box::new(reader),
+                key,
+            }
+        } else if magic.starts_with(ZIP_MAGIC) {
+            Backend::Zip(
+                ZipArchive::new(BufReader::new(file))
+                    .map_err(|e| format!("Failed to read archive: {e}"))?,
+            )
+        } else if is_tar {
+            Backend::Tar {
+                entries: list_tar_entries(&file)?,
+                file,
+            }
+        } else {
+            return Err("Unsupported archive format".to_string());
+        };
+        Ok(Self { backend })
+    }
+
+    /// Image entry names in natural reading order. Directories, hidden files
+    /// (including macOS `__MACOSX/._*` forks) and non-images are skipped.
+    pub fn pages(&self) -> Vec<String> {
+        let mut pages: Vec<String> = match &self.backend {
+            Backend::Zip(zip) => zip
+                .file_names()
+                .flatten()
+                .filter(|name| is_page_entry(name))
+                .map(|name| name.into_owned())
+                .collect(),
+            Backend::Rar { entries, .. } => entries
+                .iter()
+                .filter(|entry| is_page_entry(&entry.name))
+                .map(|entry| entry.name.clone())
+                .collect(),
+            Backend::SevenZip { reader, .. } => reader
+                .archive()
+                .files
+                .iter()
+                .filter(|file| !file.is_directory() && is_page_entry(file.name()))
+                .map(|file| file.name().to_string())
+                .collect(),
+            Backend::Tar { entries, .. } => entries
+                .iter()
+                .filter(|entry| is_page_entry(&entry.name))
+                .map(|entry| entry.name.clone())
+                .collect(),
+        };
+        pages.sort_by(|a, b| natord::compare(a, b));
+        pages
+    }
+
+    /// Whether pages can only be reached by walking the archive from the
+    /// start. Such archives are scanned in one pass with `for_each_page`
+    /// rather than page by page.
+    pub fn is_sequential(&self) -> bool {
+        match &self.backend {
+            Backend::Rar { .. } => true,
+            Backend::SevenZip { reader, .. } => reader.archive().is_solid,
+            Backend::Zip(_) | Backend::Tar { .. } => false,
+        }
+    }
+
+    /// Read a whole entry into memory.
+    pub fn read(&mut self, entry: &str) -> Result<Vec<u8>, String> {
+        self.read_prefix(entry, u64::MAX)
+    }
+
+    /// Read at most `limit` bytes from the start of an entry — enough to parse
+    /// an image header without inflating the full page. Sequential archives
+    /// still decode the whole entry.
+    pub fn read_prefix(&mut self, entry: &str, limit: u64) -> Result<Vec<u8>, String> {
+        match &mut self.backend {
+            Backend::Zip(zip) => {
+                let file = zip
+                    .by_name(entry)
+                    .map_err(|e| format!("Failed to find archive entry: {e}"))?;
+                let mut data = entry_buffer(file.size().min(limit));
+                file.take(limit)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+                Ok(data)
+            }
+            Backend::Rar {
+                path,
+                entries,
+                solid,
+                key,
+            } => {
+                let mut data = if *solid && let Some(pages) = solid_rar_pages(path, entries, key)? {
+                    solid_page(&pages, entry)?
+                } else {
+                    let mut data = None;
+                    walk_rar(path, |name| name == entry, |_, bytes| data = Some(bytes))?;
+                    data.ok_or_else(|| "Failed to find archive entry".to_string())?
+                };
+                data.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+                Ok(data)
+            }
+            Backend::SevenZip { reader, key } => {
+                let mut data = if reader.archive().is_solid
+                    && let Some(pages) = solid_7z_pages(reader, key)?
+                {
+                    solid_page(&pages, entry)?
+                } else {
+                    reader
+                        .read_file(entry)
+                        .map_err(|e| format!("Failed to find archive entry: {e}"))?
+                };
+                data.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+                Ok(data)
+            }
+            Backend::Tar { file, entries } => {
+                let entry = entries
+                    .iter()
+                    .find(|candidate| candidate.name == entry)
+                    .ok_or_else(|| "Failed to find archive entry".to_string())?;
+                let len = entry.size.min(limit);
+                file.seek(SeekFrom::Start(entry.offset))
+                    .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+                let mut data = entry_buffer(len);
+                file.take(len)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+                Ok(data)
+            }
+        }
+    }
+
+    /// Decode every page once, in archive order, handing each to `visit`.
+    pub fn for_each_page(&mut self, mut visit: impl FnMut(&str, Vec<u8>)) -> Result<(), String> {
+        match &mut self.backend {
+            Backend::Zip(zip) => {
+                for index in 0..zip.len() {
+                    let mut file = zip
+                        .by_index(index)
+                        .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+                    let Ok(name) = file.name().map(|name| name.into_owned()) else {
+                        continue;
+                    };
+                    if !is_page_entry(&name) {
+                        continue;
+                    }
+                    let mut data = Vec::new();
+                    file.read_to_end(&mut data)
+                        .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+                    visit(&name, data);
+                }
+                Ok(())
+            }
+            Backend::Rar { path, .. } => walk_rar(path, is_page_entry, visit),
+            // One pass decodes each block once, solid or not, so a scan never
+            // needs the solid cache.
+            Backend::SevenZip { reader, .. } => reader
+                .for_each_entries(|file, data| {
+                    if !file.is_directory() && is_page_entry(file.name()) {
+                        let mut page = entry_buffer(file.size());
+                        data.read_to_end(&mut page)?;
+                        visit(file.name(), page);
+                    } else {
+                        skip_entry(data)?;
+                    }
+                    Ok(true)
+                })
+                .map_err(|e| format!("Failed to read archive entry: {e}")),
+            Backend::Tar { .. } => {
+                for entry in self.pages() {
+                    let data = self.read(&entry)?;
+                    visit(&entry, data);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn is_page_entry(name: &str) -> bool {
+    !name.ends_with('/')
+        && !name.split('/').any(|part| part == "__MACOSX")
+        && !is_hidden(Path::new(name))
+        && is_image_file(Path::new(name))
+}
+
+fn rar_entry_name(filename: &Path) -> String {
+    filename.to_string_lossy().replace('\\', "/")
+}
+
+/// The file entries of a RAR archive, and whether it is solid.
+fn list_rar_entries(path: &Path) -> Result<(Vec<RarEntry>, bool), String> {
+    let listing = unrar::Archive::new(path)
+        .open_for_listing()
+        .map_err(|e| format!("Failed to read archive: {e}"))?;
+    let solid = listing.is_solid();
+    let mut entries = Vec::new();
+    for header in listing {
+        let header = header.map_err(|e| format!("Failed to read archive: {e}"))?;
+        if header.is_file() {
+            entries.push(RarEntry {
+                name: rar_entry_name(&header.filename),
+                size: header.unpacked_size,
+            });
+        }
+    }
+    Ok((entries, solid))
+}
+
+/// Walk a RAR archive front to back, decoding only the entries `wanted`
+/// selects and skipping the rest.
+fn walk_rar(
+    path: &Path,
+    wanted: impl Fn(&str) -> bool,
+    mut visit: impl FnMut(&str, Vec<u8>),
+) -> Result<(), String> {
+    let mut cursor = unrar::Archive::new(path)
+        .open_for_processing()
+        .map_err(|e| format!("Failed to read archive: {e}"))?;
+    while let Some(header) = cursor
+        .read_header()
+        .map_err(|e| format!("Failed to read archive: {e}"))?
+    {
+        let name = rar_entry_name(&header.entry().filename);
+        cursor = if header.entry().is_file() && wanted(&name) {
+            let (data, next) = header
+                .read()
+                .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+            visit(&name, data);
+            next
+        } else {
+            header
+                .skip()
+                .map_err(|e| format!("Failed to read archive: {e}"))?
+        };
+    }
+    Ok(())
+}
+
+fn list_tar_entries(mut file: &File) -> Result<Vec<TarEntry>, String> {
+    file.rewind()
+        .map_err(|e| format!("Failed to read archive: {e}"))?;
+    let mut archive = tar::Archive::new(file);
+    let mut entries = Vec::new();
+    for entry in archive
+        .entries()
+        .map_err(|e| format!("Failed to read archive: {e}"))?
+    {
+        let entry = entry.map_err(|e| format!("Failed to read archive: {e}"))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| format!("Failed to read archive: {e}"))?
+            .to_string_lossy()
+            .into_owned();
+        entries.push(TarEntry {
+            name,
+            offset: entry.raw_file_position(),
+            size: entry.size(),
+        });
+    }
+    Ok(entries)
+}
+
+/// All pages of a solid 7z, decoded once and shared through `SOLID_CACHE`.
+/// `None` when the pages would not fit the cache budget.
+fn solid_7z_pages(
+    reader: &mut ArchiveReader<File>,
+    key: &SolidKey,
+) -> Result<Option<Arc<SolidPages>>, String> {
+    let total: u64 = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| !file.is_directory() && is_page_entry(file.name()))
+        .map(|file| file.size())
+        .fold(0, u64::saturating_add);
+    cached_solid_pages(key, total, || {
+        let mut pages = SolidPages::new();
+        reader
+            .for_each_entries(|file, data| {
+                if !file.is_directory() && is_page_entry(file.name()) {
+                    let mut page = entry_buffer(file.size());
+                    data.read_to_end(&mut page)?;
+                    pages.push((file.name().to_string(), page));
+                } else {
+                    skip_entry(data)?;
+                }
+                Ok(true)
+            })
+            .map_err(|e| format!("Failed to read archive entry: {e}"))?;
+        Ok(pages)
+    })
+}
+
+/// All pages of a solid RAR, like `solid_7z_pages`.
+fn solid_rar_pages(
+    path: &Path,
+    entries: &[RarEntry],
+    key: &SolidKey,
+) -> Result<Option<Arc<SolidPages>>, String> {
+    let total: u64 = entries
+        .iter()
+        .filter(|entry| is_page_entry(&entry.name))
+        .map(|entry| entry.size)
+        .fold(0, u64::saturating_add);
+    cached_solid_pages(key, total, || {
+        let mut pages = SolidPages::new();
+        walk_rar(path, is_page_entry, |name, page| {
+            pages.push((name.to_string(), page));
+        })?;
+        Ok(pages)
+    })
+}
+
+/// The pages of `key` from `SOLID_CACHE`, or else the ones `decode` returns,
+/// cached in their place when their `total` size fits the budget.
+fn cached_solid_pages(
+    key: &SolidKey,
+    total: u64,
+    decode: impl FnOnce() -> Result<SolidPages, String>,
+) -> Result<Option<Arc<SolidPages>>, String> {
+    if let Ok(mut cache) = SOLID_CACHE.lock()
+        && let Some(pages) = cache.get(key, Instant::now())
+    {
+        return Ok(Some(pages));
+    }
+    if total > SOLID_CACHE_MAX_BYTES {
+        return Ok(None);
+    }
+
+    let pages = Arc::new(decode()?);
+    if let Ok(mut cache) = SOLID_CACHE.lock() {
+        cache.put(key.clone(), Arc::clone(&pages), Instant::now());
+        if !cache.sweeping {
+            cache.sweeping = true;
+            thread::spawn(sweep_solid_cache);
+        }
+    }
+    Ok(Some(pages))
+}
+
+/// The bytes of `entry` among decoded solid `pages`.
+fn solid_page(pages: &SolidPages, entry: &str) -> Result<Vec<u8>, String> {
+    pages
+        .iter()
+        .find(|(name, _)| name == entry)
+        .map(|(_, data)| data.clone())
+        .ok_or_else(|| "Failed to find archive entry".to_string())
+}
+
+/// Wait out the solid cache's idle time, then drop its pages.
+fn sweep_solid_cache() {
+    loop {
+        let wait = match SOLID_CACHE.lock() {
+            Ok(mut cache) => match cache.expire(Instant::now()) {
+                Some(wait) => wait,
+                None => {
+                    cache.sweeping = false;
+                    return;
+                }
+            },
+            Err(_) => return,
+        };
+        thread::sleep(wait);
+    }
+}
+
+/// An empty buffer for an entry whose header claims `size` bytes.
+fn entry_buffer(size: u64) -> Vec<u8> {
+    Vec::with_capacity(usize::try_from(size.min(MAX_PREALLOC_BYTES)).unwrap_or(0))
+}
+
+/// Consume an entry passed over in a 7z block. Entries share one decoder, so
+/// the next one only starts once this one has been read through.
+fn skip_entry(data: &mut dyn Read) -> std::io::Result<()> {
+    std::io::copy(data, &mut std::io::sink()).map(|_| ())
+}
+
+/// Read one page out of the archive at `path`, rejecting non-page entries.
+pub fn read_page(path: &Path, entry: &str) -> Result<Vec<u8>, String> {
+    if !is_page_entry(entry) {
+        return Err("Not a comic page".to_string());
+    }
+    ComicArchive::open(path)?.read(entry)
+}
+
+/// Content-Type for a page entry, from its extension.
+pub fn page_content_type(entry: &str) -> &'static str {
+    let ext = Path::new(entry)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "jxl" => "image/jxl",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    pub(crate) fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(File::create(path).expect("create zip"));
+        for (name, data) in entries {
+            if name.ends_with('/') {
+                zip.add_directory(*name, SimpleFileOptions::default())
+                    .expect("add zip directory");
+            } else {
+                zip.start_file(*name, SimpleFileOptions::default())
+                    .expect("start zip entry");
+                zip.write_all(data).expect("write zip entry");
+            }
+        }
+        zip.finish().expect("finish zip");
+    }
+
+    /// Write a RAR 4 archive with every entry stored uncompressed, flagged
+    /// solid when `solid` is set. No RAR encoder is available as a crate, and
+    /// stored blocks are simple enough to lay out by hand.
+    pub(crate) fn write_rar(path: &Path, entries: &[(&str, &[u8])], solid: bool) {
+        fn block(head_type: u8, flags: u16, body: &[u8]) -> Vec<u8> {
+            let size = u16::try_from(7 + body.len()).expect("rar header fits u16");
+            let mut header = vec![head_type];
+            header.extend_from_slice(&flags.to_le_bytes());
+            header.extend_from_slice(&size.to_le_bytes());
+            header.extend_from_slice(body);
+            let crc = (crc32fast::hash(&header) & 0xFFFF) as u16;
+            let mut out = crc.to_le_bytes().to_vec();
+            out.extend_from_slice(&header);
+            out
+        }
+
+        let mut out = b"Rar!\x1a\x07\x00".to_vec();
+        out.extend(block(0x73, if solid { 0x0008 } else { 0 }, &[0; 6]));
+        for (index, (name, data)) in entries.iter().enumerate() {
+            let size = u32::try_from(data.len()).expect("rar entry fits u32");
+            let name_len = u16::try_from(name.len()).expect("rar name fits u16");
+            let mut body = Vec::new();
+            body.extend_from_slice(&size.to_le_bytes()); // packed size
+            body.extend_from_slice(&size.to_le_bytes()); // unpacked size
+            body.push(3); // host OS: Unix
+            body.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+            body.extend_from_slice(&0x5A21_0000u32.to_le_bytes()); // DOS time
+            body.push(20); // unpack version
+            body.push(0x30); // method: store
+            body.extend_from_slice(&name_len.to_le_bytes());
+            body.extend_from_slice(&0o100_644u32.to_le_bytes());
+            body.extend_from_slice(name.as_bytes());
+            // Entries after the first continue the solid stream.
+            let flags = if solid && index > 0 { 0x8010 } else { 0x8000 };
+            out.extend(block(0x74, flags, &body));
+            out.extend_from_slice(data);
+        }
+        out.extend(block(0x7B, 0x4000, &[]));
+        std::fs::write(path, out).expect("write rar");
+    }
+
+    /// Write a 7z archive, packing every entry into one solid block when
+    /// `solid` is set and into one block per entry otherwise.
+    pub(crate) fn write_7z(path: &Path, entries: &[(&str, &[u8])], solid: bool) {
+        use sevenz_rust2::{ArchiveEntry, ArchiveWriter, SourceReader};
+
+        let mut writer = ArchiveWriter::create(path).expect("create 7z");
+        if solid {
+            writer
+                .push_archive_entries(
+                    entries
+                        .iter()
+                        .map(|(name, _)| ArchiveEntry::new_file(name))
+                        .collect(),
+                    entries
+                        .iter()
+                        .map(|(_, data)| SourceReader::new(*data))
+                        .collect(),
+                )
+                .expect("write solid 7z block");
+        } else {
+            for (name, data) in entries {
+                writer
+                    .push_archive_entry(ArchiveEntry::new_file(name), Some(*data))
+                    .expect("write 7z entry");
+            }
+        }
+        writer.finish().expect("finish 7z");
+    }
+
+    pub(crate) fn write_tar(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut tar = tar::Builder::new(File::create(path).expect("create tar"));
+        for (name, data) in entries {
+            let mut header = tar::Header::new_ustar();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, name, *data)
+                .expect("write tar entry");
+        }
+        tar.finish().expect("finish tar");
+    }
+
+    #[test]
+    fn recognizes_comic_archive_extensions() {
+        assert!(is_comic_archive(Path::new("Comic.cbz")));
+        assert!(is_comic_archive(Path::new("Comic.ZIP")));
+        assert!(is_comic_archive(Path::new("Comic.cbr")));
+        assert!(is_comic_archive(Path::new("Comic.cb7")));
+        assert!(is_comic_archive(Path::new("Comic.CBT")));
+        assert!(!is_comic_archive(Path::new("Comic.txt")));
+        assert!(!is_comic_archive(Path::new("Comic")));
+    }
+
+    #[test]
+    fn lists_image_pages_naturally_and_reads_entries() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let path = dir.path().join("comic.cbz");
+        write_zip(
+            &path,
+            &[
+                ("10.jpg", b"ten"),
+                ("chapter/", b""),
+                ("chapter/2.png", b"two"),
+                ("1.jpg", b"one"),
+                ("notes.txt", b"skip"),
+                (".hidden.jpg", b"skip"),
+                ("__MACOSX/._1.jpg", b"skip"),
+            ],
+        );
+
+        let mut archive = ComicArchive::open(&path).expect("open archive");
+        assert!(!archive.is_sequential());
+        assert_eq!(archive.pages(), vec!["1.jpg", "10.jpg", "chapter/2.png"]);
+        assert_eq!(archive.read("10.jpg").expect("read entry"), b"ten");
+        assert_eq!(
+            archive
+                .read_prefix("chapter/2.png", 2)
+                .expect("read prefix"),
+            b"tw"
+        );
+        assert!(archive.read("missing.jpg").is_err());
+
+        let mut visited = Vec::new();
+        archive
+            .for_each_page(|name, data| visited.push((name.to_string(), data)))
+            .expect("visit pages");
+        assert_eq!(visited.len(), 3);
+        assert!(visited.contains(&("chapter/2.png".to_string(), b"two".to_vec())));
+
+        assert_eq!(read_page(&path, "1.jpg").expect("read page"), b"one");
+        assert!(read_page(&path, "notes.txt").is_err());
+    }
+
+    #[test]
+    fn reads_rar_pages_without_extracting() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let path = dir.path().join("comic.cbr");
+        write_rar(
+            &path,
+            &[
+                ("10.jpg", b"ten"),
+                ("vol/2.png", b"two"),
+                ("notes.txt", b"skip"),
+            ],
+            false,
+        );
+
+        let mut archive = ComicArchive::open(&path).expect("open rar archive");
+        assert!(archive.is_sequential());
+        assert_eq!(archive.pages(), vec!["10.jpg", "vol/2.png"]);
+        assert_eq!(archive.read("vol/2.png").expect("read rar entry"), b"two");
+        assert_eq!(
+            archive.read_prefix("10.jpg", 1).expect("read rar prefix"),
+            b"t"
+        );
+        assert!(archive.read("missing.jpg").is_err());
+
+        let mut visited = Vec::new();
+        archive
+            .for_each_page(|name, data| visited.push((name.to_string(), data)))
+            .expect("visit rar pages");
+        assert_eq!(
+            visited,
+            vec![
+                ("10.jpg".to_string(), b"ten".to_vec()),
+                ("vol/2.png".to_string(), b"two".to_vec()),
+            ]
+        );
+
+        // Content decides the backend, not the extension.
+        let misnamed = dir.path().join("misnamed.cbz");
+        std::fs::copy(&path, &misnamed).expect("copy rar archive");
+        assert_eq!(read_page(&misnamed, "10.jpg").expect("read page"), b"ten");
+    }
+
+    #[test]
+    fn reads_7z_pages_from_solid_and_non_solid_archives() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let entries: &[(&str, &[u8])] = &[
+            ("vol/2.png", b"two"),
+            ("notes.txt", b"skip"),
+            ("10.jpg", b"ten"),
+        ];
+
+        for solid in [false, true] {
+            let path = dir.path().join(format!("comic-{solid}.cb7"));
+            write_7z(&path, entries, solid);
+
+            let mut archive = ComicArchive::open(&path).expect("open 7z archive");
+            assert_eq!(archive.is_sequential(), solid);
+            assert_eq!(archive.pages(), vec!["10.jpg", "vol/2.png"]);
+            assert_eq!(archive.read("vol/2.png").expect("read 7z entry"), b"two");
+            assert_eq!(
+                archive.read_prefix("10.jpg", 2).expect("read prefix"),
+                b"te"
+            );
+            assert!(archive.read("missing.jpg").is_err());
+
+            let mut visited = Vec::new();
+            archive
+                .for_each_page(|name, data| visited.push((name.to_string(), data)))
+                .expect("visit 7z pages");
+            assert_eq!(
+                visited,
+                vec![
+                    ("vol/2.png".to_string(), b"two".to_vec()),
+                    ("10.jpg".to_string(), b"ten".to_vec()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn reads_solid_rar_pages_through_the_solid_cache() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let path = dir.path().join("comic.cbr");
+        write_rar(
+            &path,
+            &[("1.jpg", b"one"), ("notes.txt", b"skip"), ("2.jpg", b"two")],
+            true,
+        );
+
+        let mut archive = ComicArchive::open(&path).expect("open rar archive");
+        assert_eq!(archive.read("2.jpg").expect("read rar entry"), b"two");
+        assert_eq!(
+            archive.read_prefix("1.jpg", 2).expect("read rar prefix"),
+            b"on"
+        );
+        // Only pages are decoded into the cache, so other entries are gone.
+        assert!(archive.read("notes.txt").is_err());
+    }
+
+    #[test]
+    fn solid_7z_cache_is_invalidated_when_the_archive_changes() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let path = dir.path().join("comic.cb7");
+        write_7z(&path, &[("1.jpg", b"old")], true);
+        assert_eq!(read_page(&path, "1.jpg").expect("read old page"), b"old");
+
+        write_7z(&path, &[("1.jpg", b"newer")], true);
+        assert_eq!(read_page(&path, "1.jpg").expect("read new page"), b"newer");
+    }
+
+    #[test]
+    fn header_entry_sizes_reserve_at_most_the_cap() {
+        assert!(entry_buffer(3).capacity() >= 3);
+        let claimed = entry_buffer(u64::MAX);
+        assert!(claimed.capacity() <= MAX_PREALLOC_BYTES as usize);
+    }
+
+    #[test]
+    fn solid_7z_cache_is_dropped_after_going_unused() {
+        let key = SolidKey {
+            path: PathBuf::from("/comics/a.cb7"),
+            len: 3,
+            modified: None,
+        };
+        let start = Instant::now();
+        let mut cache = SolidCache::default();
+        cache.put(key.clone(), Arc::new(SolidPages::new()), start);
+
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            cache.expire(start + minute),
+            Some(SOLID_CACHE_IDLE - minute)
+        );
+        assert!(cache.get(&key, start + minute).is_some());
+        assert_eq!(
+            cache.expire(start + SOLID_CACHE_IDLE),
+            Some(minute),
+            "a read restarts the idle clock"
+        );
+
+        assert_eq!(cache.expire(start + minute + SOLID_CACHE_IDLE), None);
+        assert!(cache.get(&key, start + minute + SOLID_CACHE_IDLE).is_none());
+    }
+
+    #[test]
+    fn reads_tar_pages_by_offset() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let path = dir.path().join("comic.cbt");
+        write_tar(
+            &path,
+            &[
+                ("10.jpg", b"ten"),
+                ("vol/2.png", b"two"),
+                ("notes.txt", b"skip"),
+            ],
+        );
+
+        let mut archive = ComicArchive::open(&path).expect("open tar archive");
+        assert!(!archive.is_sequential());
+        assert_eq!(archive.pages(), vec!["10.jpg", "vol/2.png"]);
+        assert_eq!(archive.read("vol/2.png").expect("read tar entry"), b"two");
+        assert_eq!(archive.read_prefix("10.jpg", 1).expect("read prefix"), b"t");
+        assert!(archive.read("missing.jpg").is_err());
+
+        let mut visited = Vec::new();
+        archive
+            .for_each_page(|name, _| visited.push(name.to_string()))
+            .expect("visit tar pages");
+        assert_eq!(visited, vec!["10.jpg", "vol/2.png"]);
+    }
+
+    #[test]
+    fn rejects_missing_or_corrupt_archives() {
+        let dir = tempfile::tempdir().expect("create archive dir");
+        let corrupt = dir.path().join("corrupt.cbz");
+        std::fs::write(&corrupt, "not a zip").expect("write corrupt archive");
+        let truncated = dir.path().join("truncated.cbz");
+        std::fs::write(&truncated, "PK\x03\x04").expect("write truncated archive");
+
+        assert!(ComicArchive::open(&dir.path().join("missing.cbz")).is_err());
+        assert!(ComicArchive::open(&corrupt).is_err());
+        assert!(ComicArchive::open(&truncated).is_err());
+    }
+
+    #[test]
+    fn maps_page_extensions_to_content_types() {
+        assert_eq!(page_content_type("a/1.JPG"), "image/jpeg");
+        assert_eq!(page_content_type("1.png"), "image/png");
+        assert_eq!(page_content_type("1.WebP"), "image/webp");
+        assert_eq!(page_content_type("1.jxl"), "image/jxl");
+        assert_eq!(page_content_type("1"), "application/octet-stream");
+    }
+}
@@ -1,168 +1,237 @@
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 #[cfg(not(coverage))]
 use tracing::info;
 
 use crate::models::{Author, Book, BookContent, Chapter};
 use crate::tags::get_file_tags;
 
+use super::chapter::{ChapterMatcher, ChapterRule, default_rules, link_parents};
+use super::encoding::decode;
+use super::epub::{is_epub_file, parse_epub, read_title};
+use super::heuristic::with_suggestions;
+use super::paragraph::{ParagraphOptions, paragraphs};
 use super::utils::{
-    current_time_millis, generate_uuid, get_created_time, is_book_file, is_hidden, remove_extension,
+    current_time_millis, generate_uuid, get_created_time, get_modified_time, is_book_file,
+    is_hidden, list_entries, remove_extension,
 };
+use super::{LibraryScan, ScanProgress, Stamped};
+
+pub fn scan_book_library(
+    library_path: &str,
+    library_id: &str,
+    previous: &LibraryScan,
+    progress: &ScanProgress,
+) -> Result<LibraryScan, String> {
+    #[cfg(not(coverage))]
+    let start = std::time::Instant::now();
+    let scan = scan_book_authors(
+        &list_entries(Path::new(library_path))?,
+        library_id,
+        previous,
+        progress,
+    );
 
-fn extract_chapter_title(line: &str) -> Option<String> {
-    let trimmed = line.trim();
+    #[cfg(not(coverage))]
+    info!(
+        authors = scan.authors.len(),
+        duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        "Scanned book library"
+    );
 
-    const SPECIAL_CHAPTERS: &[&str] = &["序章", "终章", "番外", "后记", "尾声"];
-    for &prefix in SPECIAL_CHAPTERS {
-        if trimmed.starts_with(prefix) {
-            return Some(trimmed.to_string());
-        }
-    }
+    Ok(scan)
+}
 
-    if !trimmed.starts_with('第') {
-        return None;
+/// Every directory in `author_paths` becomes an `Author` and every book file
+/// in it a `Book`; paths that are gone or are not directories are left out.
+/// An author directory whose modification time matches `previous` keeps its
+/// earlier listing, and a book whose size and modification time match is kept
+/// as it was, without reading its title or tags again. A cancelled `progress`
+/// stops the scan after the current author.
+pub fn scan_book_authors(
+    author_paths: &[PathBuf],
+    library_id: &str,
+    previous: &LibraryScan,
+    progress: &ScanProgress,
+) -> LibraryScan {
+    let previous_authors: HashMap<&str, u64> = previous
+        .authors
+        .iter()
+        .map(|author| (author.entry.path.as_str(), author.mtime))
+        .collect();
+    let previous_books: HashMap<&str, &Stamped<Book>> = previous
+        .books
+        .iter()
+        .map(|book| (book.entry.path.as_str(), book))
+        .collect();
+    let mut listings: HashMap<&str, Vec<PathBuf>> = HashMap::new();
+    for book in &previous.books {
+        listings
+            .entry(book.entry.author_id.as_str())
+            .or_default()
+            .push(PathBuf::from(&book.entry.path));
     }
 
-    let mut chars = trimmed.chars();
-    let _ = chars.next();
-    let mut has_number = false;
-    const CHAPTER_SUFFIXES: &[char] = &['章', '回', '节', '卷', '集', '幕'];
+    let mut scan = LibraryScan::default();
 
-    for c in chars {
-        if is_chapter_number_char(c) {
-            has_number = true;
-            continue;
+    for author_path in author_paths {
+        if progress.is_cancelled() {
+            break;
         }
-
-        return (has_number && CHAPTER_SUFFIXES.contains(&c)).then(|| trimmed.to_string());
-    }
-
-    None
-}
-
-fn is_chapter_number_char(c: char) -> bool {
-    c.is_ascii_digit()
-        || matches!(
-            c,
-            '０' | '１'
-                | '２'
-                | '３'
-                | '４'
-                | '５'
-                | '６'
-                | '７'
-                | '８'
-                | '９'
-                | '一'
-                | '二'
-                | '三'
-                | '四'
-                | '五'
-                | '六'
-                | '七'
-                | '八'
-                | '九'
-                | '十'
-                | '百'
-                | '千'
-        )
-}
-
-pub fn scan_book_library(library_path: &str, library_id: &str) -> Result<Vec<Author>, String> {
-    #[cfg(not(coverage))]
-    let start = std::time::Instant::now();
-    let path = Path::new(library_path);
-    let mut authors = Vec::new();
-
-    let entries = fs::read_dir(path).map_err(|e| e.to_string())?;
-
-    for entry in entries.flatten() {
-        let author_path = entry.path();
-        if is_hidden(&author_path) || !entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+        // Like `DirEntry`, entries are not followed through symlinks.
+        let Ok(metadata) = fs::symlink_metadata(author_path) else {
+            continue;
+        };
+        if !metadata.is_dir() {
             continue;
         }
 
-        let author_name = entry.file_name().to_string_lossy().into_owned();
-        let author_id = generate_uuid(&author_path.to_string_lossy());
-
-        let mut books = Vec::new();
-
-        if let Ok(author_entries) = fs::read_dir(&author_path) {
-            for book_entry in author_entries.flatten() {
-                let book_path = book_entry.path();
-                if is_hidden(&book_path) || !is_book_file(&book_path) {
-                    continue;
-                }
-
-                let book_id = generate_uuid(&book_path.to_string_lossy());
-                let title = remove_extension(&book_entry.file_name().to_string_lossy());
+        let author_name = author_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let author_path_str = author_path.to_string_lossy().into_owned();
+        let author_id = generate_uuid(&author_path_str);
+        let author_mtime = get_modified_time(&metadata);
+
+        let book_paths = if previous_authors.get(author_path_str.as_str()) == Some(&author_mtime) {
+            listings.remove(author_id.as_str()).unwrap_or_default()
+        } else {
+            fs::read_dir(author_path)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .map(|book_entry| book_entry.path())
+                        .filter(|book_path| !is_hidden(book_path) && is_book_file(book_path))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let book_count = book_paths.len();
+        for book_path in book_paths {
+            progress.count_scanned();
+            let (size, created_at, mtime) = fs::metadata(&book_path)
+                .map(|m| (m.len(), get_created_time(&m), get_modified_time(&m)))
+                .unwrap_or_else(|_| {
+                    let now = current_time_millis();
+                    (0, now, now)
+                });
+            let path_str = book_path.to_string_lossy().into_owned();
+            if let Some(&known) = previous_books.get(path_str.as_str())
+                && known.mtime == mtime
+                && known.entry.size == size
+            {
+                scan.books.push(known.clone());
+                continue;
+            }
 
-                let (size, created_at) = book_entry
-                    .metadata()
-                    .map(|m| (m.len(), get_created_time(&m)))
-                    .unwrap_or((0, current_time_millis()));
+            let file_title =
+                remove_extension(&book_path.file_name().unwrap_or_default().to_string_lossy());
+            let title = if is_epub_file(&book_path) {
+                read_title(&book_path).unwrap_or(file_title)
+            } else {
+                file_title
+            };
 
-                let (starred, deleted) = get_file_tags(&book_path);
+            let (starred, deleted) = get_file_tags(&book_path);
 
-                books.push(Book {
-                    id: book_id,
+            scan.books.push(Stamped {
+                entry: Book {
+                    id: generate_uuid(&path_str),
                     title,
-                    path: book_path.to_string_lossy().into_owned(),
+                    path: path_str,
                     author_id: author_id.clone(),
                     library_id: library_id.to_string(),
                     size,
                     created_at,
                     starred,
                     deleted,
-                });
-            }
+                },
+                mtime,
+            });
         }
 
-        authors.push(Author {
-            id: author_id,
-            name: author_name,
-            path: author_path.to_string_lossy().into_owned(),
-            library_id: library_id.to_string(),
-            book_count: u32::try_from(books.len()).unwrap_or(u32::MAX),
-            books,
+        scan.authors.push(Stamped {
+            entry: Author {
+                id: author_id,
+                name: author_name,
+                path: author_path_str,
+                library_id: library_id.to_string(),
+                book_count: u32::try_from(book_count).unwrap_or(u32::MAX),
+                books: Vec::new(),
+            },
+            mtime: author_mtime,
         });
     }
 
-    #[cfg(not(coverage))]
-    info!(
-        authors = authors.len(),
-        duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
-        "Scanned book library"
-    );
-
-    Ok(authors)
+    scan
 }
 
-pub fn parse_book(path: &str) -> Result<BookContent, String> {
-    let file = File::open(path).map_err(|e| format!("Failed to open file: {e}"))?;
-    let reader = BufReader::new(file);
-    let mut lines = Vec::new();
-    let mut chapters = Vec::new();
-
-    for line in reader.lines() {
-        let line = line.map_err(|e| format!("Failed to read file: {e}"))?;
-        if line.trim().is_empty() {
-            continue;
-        }
+/// How a text book is read: its stored encoding, if any, and the chapter rules
+/// and paragraph options of its library.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub encoding: Option<String>,
+    pub chapter_rules: Vec<ChapterRule>,
+    pub paragraphs: ParagraphOptions,
+}
 
-        if let Some(title) = extract_chapter_title(&line) {
-            chapters.push(Chapter {
-                title,
-                line_index: lines.len(),
-            });
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            encoding: None,
+            chapter_rules: default_rules(),
+            paragraphs: ParagraphOptions::default(),
         }
+    }
+}
 
-        lines.push(line);
+/// Parse a book into reader lines and chapters. Text books are decoded with
+/// the stored encoding when there is one, else detected, and normalised into
+/// paragraphs before chapters are found by the library's rules, with
+/// suggested chapters filling in when the rules find too few. EPUB chapters
+/// come from the book's own TOC.
+pub fn parse_book(path: &str, options: &ParseOptions) -> Result<BookContent, String> {
+    if is_epub_file(Path::new(path)) {
+        return parse_epub(Path::new(path));
     }
 
-    Ok(BookContent { lines, chapters })
+    let data = fs::read(path).map_err(|e| format!("Failed to open file: {e}"))?;
+    let (text, encoding) = decode(&data, options.encoding.as_deref());
+    let paragraphs = paragraphs(&text, &options.paragraphs);
+    let matcher = ChapterMatcher::new(&options.chapter_rules);
+
+    let chapters = paragraphs
+        .iter()
+        .enumerate()
+        .filter(|(_, paragraph)| !paragraph.text.is_empty())
+        .filter_map(|(line_index, paragraph)| {
+            let heading = matcher.heading(&paragraph.text)?;
+            Some(Chapter {
+                title: heading.title,
+                line_index,
+                confidence: None,
+                level: u8::from(!heading.volume),
+                parent: None,
+            })
+        })
+        .collect();
+
+    let mut chapters = with_suggestions(chapters, &paragraphs);
+    link_parents(&mut chapters);
+    let lines = paragraphs
+        .into_iter()
+        .map(|paragraph| paragraph.text)
+        .collect();
+
+    Ok(BookContent {
+        lines,
+        chapters,
+        encoding: encoding.name().to_string(),
+    })
 }
 
 #[cfg(test)]
@@ -179,8 +248,11 @@ mod tests {
         )
         .expect("write temp book");
 
-        let content =
-            parse_book(file.path().to_str().expect("temp path is utf-8")).expect("parse temp book");
+        let content = parse_book(
+            file.path().to_str().expect("temp path is utf-8"),
+            &ParseOptions::default(),
+        )
+        .expect("parse temp book");
 
         assert_eq!(
             content.lines,
@@ -209,6 +281,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_book_normalises_paragraphs_before_finding_chapters() {
+        let mut file = tempfile::NamedTempFile::new().expect("create temp book");
+        write!(file, "　　第一章 开始\n\n　　正文\n\n\n第二章\n").expect("write temp book");
+
+        let content = parse_book(
+            file.path().to_str().expect("path is utf-8"),
+            &ParseOptions {
+                paragraphs: ParagraphOptions {
+                    strip_indent: true,
+                    keep_blank_lines: true,
+                    ..ParagraphOptions::default()
+                },
+                ..ParseOptions::default()
+            },
+        )
+        .expect("parse temp book");
+
+        assert_eq!(content.lines, vec!["第一章 开始", "", "正文", "", "第二章"]);
+        assert_eq!(
+            content
+                .chapters
+                .iter()
+                .map(|chapter| (chapter.title.as_str(), chapter.line_index))
+                .collect::<Vec<_>>(),
+            vec![("第一章 开始", 0), ("第二章", 4)]
+        );
+    }
+
+    #[test]
+    fn parse_book_nests_chapters_under_volumes() {
+        let mut file = tempfile::NamedTempFile::new().expect("create temp book");
+        write!(
+            file,
+            "序章\n第一卷 风起\n第一章\n正文\n第二章\n第二卷 云涌\n第三章\n正文\n"
+        )
+        .expect("write temp book");
+
+        let content = parse_book(
+            file.path().to_str().expect("path is utf-8"),
+            &ParseOptions::default(),
+        )
+        .expect("parse temp book");
+
+        assert_eq!(
+            content
+                .chapters
+                .iter()
+                .map(|chapter| (chapter.title.as_str(), chapter.level, chapter.parent))
+                .collect::<Vec<_>>(),
+            vec![
+                ("序章", 0, None),
+                ("第一卷 风起", 0, None),
+                ("第一章", 1, Some(1)),
+                ("第二章", 1, Some(1)),
+                ("第二卷 云涌", 0, None),
+                ("第三章", 1, Some(4)),
+            ]
+        );
+    }
+
     #[test]
     fn scan_book_library_discovers_authors_and_text_books() {
         let library_dir = tempfile::tempdir().expect("create temp library");
@@ -221,17 +354,73 @@ mod tests {
         fs::write(author_dir.join(".hidden.txt"), "hidden").expect("write hidden book");
         fs::write(author_dir.join("notes.md"), "not a book").expect("write non-book");
 
-        let authors = scan_book_library(
+        let scan = scan_book_library(
             library_dir.path().to_str().expect("library path is utf-8"),
             "library-1",
+            &LibraryScan::default(),
+            &ScanProgress::default(),
+        )
+        .expect("scan book library");
+
+        assert_eq!(scan.authors.len(), 1);
+        assert_eq!(scan.authors[0].entry.name, "Author 1");
+        assert_eq!(scan.authors[0].entry.book_count, 1);
+        assert_eq!(scan.books[0].entry.title, "Book 1");
+        assert_eq!(scan.books[0].entry.library_id, "library-1");
+        assert_eq!(scan.books[0].entry.author_id, scan.authors[0].entry.id);
+    }
+
+    #[test]
+    fn scan_book_library_reuses_unchanged_listings_and_books() {
+        let library_dir = tempfile::tempdir().expect("create temp library");
+        let author_dir = library_dir.path().join("Author 1");
+        fs::create_dir(&author_dir).expect("create author dir");
+        fs::write(author_dir.join("Same.txt"), "正文").expect("write book");
+        fs::write(author_dir.join("Grown.txt"), "正文").expect("write book");
+        let library_path = library_dir.path().to_str().expect("library path is utf-8");
+        let mut previous = scan_book_library(
+            library_path,
+            "library-1",
+            &LibraryScan::default(),
+            &ScanProgress::default(),
         )
         .expect("scan book library");
 
-        assert_eq!(authors.len(), 1);
-        assert_eq!(authors[0].name, "Author 1");
-        assert_eq!(authors[0].book_count, 1);
-        assert_eq!(authors[0].books[0].title, "Book 1");
-        assert_eq!(authors[0].books[0].library_id, "library-1");
+        // Marked titles show which books were reused rather than rescanned.
+        for book in &mut previous.books {
+            book.entry.title = format!("{} (kept)", book.entry.title);
+        }
+        fs::write(author_dir.join("Grown.txt"), "更长的正文").expect("grow book");
+        let rescan = scan_book_library(
+            library_path,
+            "library-1",
+            &previous,
+            &ScanProgress::default(),
+        )
+        .expect("rescan book library");
+
+        let mut titles = rescan
+            .books
+            .iter()
+            .map(|book| book.entry.title.as_str())
+            .collect::<Vec<_>>();
+        titles.sort_unstable();
+        assert_eq!(titles, vec!["Grown", "Same (kept)"]);
+
+        // An unchanged directory is not listed again, so a book missing from
+        // the earlier scan stays missing.
+        previous
+            .books
+            .retain(|book| book.entry.title == "Same (kept)");
+        let rescan = scan_book_library(
+            library_path,
+            "library-1",
+            &previous,
+            &ScanProgress::default(),
+        )
+        .expect("rescan book library");
+        assert_eq!(rescan.books.len(), 1);
+        assert_eq!(rescan.authors[0].entry.book_count, 1);
     }
 
     #[cfg(unix)]
@@ -254,18 +443,77 @@ mod tests {
             "test requires an unprivileged user that cannot read mode-000 directories"
         );
 
-        let authors = scan_book_library(
+        let scan = scan_book_library(
             library_dir.path().to_str().expect("library path is utf-8"),
             "library-1",
+            &LibraryScan::default(),
+            &ScanProgress::default(),
         )
         .expect("scan library with unreadable author");
 
         fs::set_permissions(&author_dir, original_permissions).expect("restore author dir");
 
-        assert_eq!(authors.len(), 1);
-        assert_eq!(authors[0].name, "Unreadable Author");
-        assert_eq!(authors[0].book_count, 0);
-        assert!(authors[0].books.is_empty());
+        assert_eq!(scan.authors.len(), 1);
+        assert_eq!(scan.authors[0].entry.name, "Unreadable Author");
+        assert_eq!(scan.authors[0].entry.book_count, 0);
+        assert!(scan.books.is_empty());
+    }
+
+    #[test]
+    fn scan_and_parse_epub_books_by_opf_title_and_toc() {
+        use crate::scanner::epub::tests::write_epub;
+
+        let library_dir = tempfile::tempdir().expect("create temp library");
+        let author_dir = library_dir.path().join("Author 1");
+        fs::create_dir(&author_dir).expect("create author dir");
+        write_epub(&author_dir.join("a.epub"), Some("元数据书名"), true);
+        write_epub(&author_dir.join("b.epub"), None, false);
+
+        let scan = scan_book_library(
+            library_dir.path().to_str().expect("library path is utf-8"),
+            "library-1",
+            &LibraryScan::default(),
+            &ScanProgress::default(),
+        )
+        .expect("scan book library");
+        let mut titles = scan
+            .books
+            .iter()
+            .map(|book| book.entry.title.as_str())
+            .collect::<Vec<_>>();
+        titles.sort_unstable();
+        assert_eq!(titles, vec!["b", "元数据书名"]);
+
+        let content = parse_book(
+            author_dir.join("a.epub").to_str().expect("path is utf-8"),
+            &ParseOptions::default(),
+        )
+        .expect("parse epub book");
+        assert_eq!(content.chapters[0].title, "第一章 开始");
+    }
+
+    #[test]
+    fn parse_book_transcodes_legacy_encodings_and_reports_them() {
+        let text = "第一章 开始\r\n他们在这里说话，我们没有看见那个人。\r\n".repeat(10);
+        let mut file = tempfile::NamedTempFile::new().expect("create temp book");
+        file.write_all(&encoding_rs::GBK.encode(&text).0)
+            .expect("write gbk book");
+        let path = file.path().to_str().expect("path is utf-8");
+
+        let detected = parse_book(path, &ParseOptions::default()).expect("parse gbk book");
+        assert_eq!(detected.encoding, "GBK");
+        assert_eq!(detected.lines[0], "第一章 开始");
+        assert_eq!(detected.chapters.len(), 10);
+
+        let stored = parse_book(
+            path,
+            &ParseOptions {
+                encoding: Some("GBK".to_string()),
+                ..ParseOptions::default()
+            },
+        )
+        .expect("parse with stored encoding");
+        assert_eq!(stored.lines, detected.lines);
     }
 
     #[test]
@@ -275,12 +523,46 @@ mod tests {
             .path()
             .join("missing.txt");
 
-        let error = parse_book(missing.to_str().expect("path is utf-8"))
-            .expect_err("missing book should fail");
+        let error = parse_book(
+            missing.to_str().expect("path is utf-8"),
+            &ParseOptions::default(),
+        )
+        .expect_err("missing book should fail");
 
         assert!(error.starts_with("Failed to open file:"));
     }
 
+    #[test]
+    fn parse_book_suggests_chapters_when_rules_find_none() {
+        let mut file = tempfile::NamedTempFile::new().expect("create temp book");
+        write!(
+            file,
+            "1.\n\n他走了。\n天黑了。\n\n2.\n\n她来了。\n天亮了。\n"
+        )
+        .expect("write temp book");
+
+        let content = parse_book(
+            file.path().to_str().expect("path is utf-8"),
+            &ParseOptions::default(),
+        )
+        .expect("parse temp book");
+
+        assert_eq!(
+            content
+                .chapters
+                .iter()
+                .map(|chapter| (chapter.title.as_str(), chapter.line_index))
+                .collect::<Vec<_>>(),
+            vec![("1.", 0), ("2.", 3)]
+        );
+        assert!(
+            content
+                .chapters
+                .iter()
+                .all(|chapter| chapter.confidence.is_some())
+        );
+    }
+
     #[test]
     fn parse_book_ignores_lines_that_are_not_chapter_titles() {
         let mut file = tempfile::NamedTempFile::new().expect("create temp book");
@@ -290,8 +572,11 @@ mod tests {
         )
         .expect("write temp book");
 
-        let content =
-            parse_book(file.path().to_str().expect("path is utf-8")).expect("parse temp book");
+        let content = parse_book(
+            file.path().to_str().expect("path is utf-8"),
+            &ParseOptions::default(),
+        )
+        .expect("parse temp book");
 
         assert_eq!(
             content
@@ -0,0 +1,393 @@
+//! Persistent parsed-book cache and line index for ranged reads.
+//!
+//! The first open of a book parses it once and writes its lines to
+//! `lines.txt`, their byte offsets to `offsets.bin` (little-endian `u64`, one
+//! per line plus the end) and the table of contents to `meta.json`. Later opens
+//! are a lookup, and range reads seek straight to the lines they need.
+//!
+//! Entries live under `<store>/books/`, keyed by file identity as in
+//! `thumbnail::get_thumbnail_hash`: a changed file misses the cache, and a
+//! change to the library's parse options rebuilds the entry in place. An entry
+//! is built in a directory of its own and swapped in whole, so its three files
+//! always come from the same parse. `paths/` remembers the entry last built for
+//! each book path, so the one a changed file leaves behind is removed.
+
+use std::fs::{self, File};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Runtime};
+
+use crate::config;
+use crate::models::{BookContent, BookLines, BookToc};
+use crate::thumbnail::get_thumbnail_hash;
+
+use super::book::{ParseOptions, parse_book};
+
+const LINES_FILE: &str = "lines.txt";
+const OFFSETS_FILE: &str = "offsets.bin";
+const META_FILE: &str = "meta.json";
+/// Directory of per-path files naming each book's current entry.
+const PATHS_DIR: &str = "paths";
+/// Longest range one `lines` call returns.
+pub const MAX_RANGE_LINES: usize = 2000;
+
+static INDEX_TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Deserialize)]
+struct IndexMeta {
+    options: String,
+    toc: BookToc,
+}
+
+/// An open line index; only the table of contents is held in memory.
+pub struct BookIndex {
+    dir: PathBuf,
+    meta: IndexMeta,
+}
+
+pub fn cache_dir<R: Runtime>(app: &AppHandle<R>) -> PathBuf {
+    config::get_store_dir(app).join("books")
+}
+
+/// Drop every cached book; each is re-parsed on its next open.
+pub fn clear(cache_dir: &Path) -> Result<(), String> {
+    match fs::remove_dir_all(cache_dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Open the cached index under `cache_dir` for the book at `path`, parsing the
+/// book first when the entry is missing or was built with other options.
+pub fn open(cache_dir: &Path, path: &str, options: &ParseOptions) -> Result<BookIndex, String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("Failed to open file: {e}"))?;
+    let key = get_thumbnail_hash(&metadata);
+    let index_dir = cache_dir.join(&key);
+    let options_hash = options_hash(options);
+
+    if let Some(meta) = read_meta(&index_dir).filter(|meta| meta.options == options_hash) {
+        return Ok(BookIndex {
+            dir: index_dir,
+            meta,
+        });
+    }
+
+    let content = parse_book(path, options)?;
+    let mut text = Vec::new();
+    let mut offsets = Vec::with_capacity((content.lines.len() + 1) * 8);
+    for line in &content.lines {
+        offsets.extend_from_slice(&(text.len() as u64).to_le_bytes());
+        text.extend_from_slice(line.as_bytes());
+        text.push(b'\n');
+    }
+    offsets.extend_from_slice(&(text.len() as u64).to_le_bytes());
+
+    let meta = IndexMeta {
+        options: options_hash,
+        toc: BookToc {
+            line_count: content.lines.len(),
+            chapters: content.chapters,
+            encoding: content.encoding,
+        },
+    };
+    let meta_json = serde_json::to_vec(&meta).map_err(|e| e.to_string())?;
+
+    publish(
+        &index_dir,
+        &[
+            (LINES_FILE, &text),
+            (OFFSETS_FILE, &offsets),
+            (META_FILE, &meta_json),
+        ],
+    )?;
+    replace_previous(cache_dir, path, &key);
+
+    Ok(BookIndex {
+        dir: index_dir,
+        meta,
+    })
+}
+
+impl BookIndex {
+    pub fn toc(&self) -> &BookToc {
+        &self.meta.toc
+    }
+
+    pub fn into_toc(self) -> BookToc {
+        self.meta.toc
+    }
+
+    /// The whole book, as `scanner::book::parse_book` returns it.
+    pub fn content(self) -> Result<BookContent, String> {
+        let lines = self.read(0, self.meta.toc.line_count)?.lines;
+        Ok(BookContent {
+            lines,
+            chapters: self.meta.toc.chapters,
+            encoding: self.meta.toc.encoding,
+        })
+    }
+
+    /// Lines `start..end`, clamped to the book and to `MAX_RANGE_LINES`.
+    pub fn lines(&self, start: usize, end: usize) -> Result<BookLines, String> {
+        let end = end
+            .min(self.meta.toc.line_count)
+            .min(start.saturating_add(MAX_RANGE_LINES));
+        self.read(start.min(end), end)
+    }
+
+    /// All lines of the chapter at `index` in the table of contents, up to the
+    /// next entry.
+    pub fn chapter(&self, index: usize) -> Result<Option<BookLines>, String> {
+        let chapters = &self.meta.toc.chapters;
+        let Some(chapter) = chapters.get(index) else {
+            return Ok(None);
+        };
+        let end = chapters
+            .get(index + 1)
+            .map_or(self.meta.toc.line_count, |next| next.line_index);
+        self.read(chapter.line_index, end.max(chapter.line_index))
+            .map(Some)
+    }
+
+    fn read(&self, start: usize, end: usize) -> Result<BookLines, String> {
+        let offsets = read_at(
+            &self.dir.join(OFFSETS_FILE),
+            start as u64 * 8,
+            (end - start + 1) * 8,
+        )?
+        .chunks_exact(8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+        .collect::<Vec<_>>();
+        let base = offsets[0];
+        let len = usize::try_from(offsets[offsets.len() - 1] - base).map_err(index_error)?;
+        let text = read_at(&self.dir.join(LINES_FILE), base, len)?;
+
+        let lines = offsets
+            .windows(2)
+            .map(|pair| {
+                let from = (pair[0] - base) as usize;
+                let to = (pair[1] - base) as usize - 1;
+                String::from_utf8_lossy(&text[from..to]).into_owned()
+            })
+            .collect();
+        Ok(BookLines { start, lines })
+    }
+}
+
+/// The options that shape the parse. The encoding is left out: it is only ever
+/// detected, never chosen.
+pub fn options_hash(options: &ParseOptions) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&options.chapter_rules).unwrap_or_default());
+    hasher.update(serde_json::to_vec(&options.paragraphs).unwrap_or_default());
+    hex::encode(hasher.finalize())
+}
+
+fn read_meta(index_dir: &Path) -> Option<IndexMeta> {
+    let data = fs::read(index_dir.join(META_FILE)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn read_at(path: &Path, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(index_error)?;
+    let mut buf = vec![0; len];
+    file.read_exact_at(&mut buf, offset).map_err(index_error)?;
+    Ok(buf)
+}
+
+/// Write `files` into a fresh directory beside `index_dir`, then rename it
+/// into place, so concurrent builds of one book never mix their files.
+fn publish(index_dir: &Path, files: &[(&str, &[u8])]) -> Result<(), String> {
+    let tmp = sibling(index_dir, "tmp");
+    let published = fs::create_dir_all(&tmp)
+        .and_then(|()| {
+            files
+                .iter()
+                .try_for_each(|(name, data)| fs::write(tmp.join(name), data))
+        })
+        .map_err(index_error)
+        .and_then(|()| swap_in(&tmp, index_dir));
+    if published.is_err() {
+        let _ = fs::remove_dir_all(&tmp);
+    }
+    published
+}
+
+/// Rename the built `tmp` to `index_dir`, moving any entry already there aside
+/// first. Losing the slot to a concurrent build of the same book is fine.
+fn swap_in(tmp: &Path, index_dir: &Path) -> Result<(), String> {
+    if fs::rename(tmp, index_dir).is_ok() {
+        return Ok(());
+    }
+    let old = sibling(index_dir, "old");
+    let _ = fs::rename(index_dir, &old);
+    let placed = fs::rename(tmp, index_dir);
+    let _ = fs::remove_dir_all(&old);
+    match placed {
+        Err(_) if index_dir.join(META_FILE).exists() => {
+            let _ = fs::remove_dir_all(tmp);
+            Ok(())
+        }
+        placed => placed.map_err(index_error),
+    }
+}
+
+/// Record `key` as the entry of the book at `path`, removing the entry built
+/// for an earlier version of the file.
+fn replace_previous(cache_dir: &Path, path: &str, key: &str) {
+    let paths_dir = cache_dir.join(PATHS_DIR);
+    let pointer = paths_dir.join(hex::encode(Sha256::digest(path.as_bytes())));
+    if let Ok(previous) = fs::read_to_string(&pointer)
+        && previous != key
+        && !previous.is_empty()
+        && previous.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        let _ = fs::remove_dir_all(cache_dir.join(previous));
+    }
+    let _ = fs::create_dir_all(&paths_dir).and_then(|()| fs::write(&pointer, key));
+}
+
+/// A unique path next to `index_dir` for a build or a replaced entry.
+fn sibling(index_dir: &Path, kind: &str) -> PathBuf {
+    let seq = INDEX_TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    index_dir.with_extension(format!("{}.{seq}.{kind}", std::process::id()))
+}
+
+fn index_error(e: impl std::fmt::Display) -> String {
+    format!("Failed to read book index: {e}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::paragraph::ParagraphOptions;
+
+    /// The entries in `cache_dir`, leaving out the path pointers.
+    fn entry_count(cache_dir: &Path) -> usize {
+        fs::read_dir(cache_dir)
+            .expect("list cache")
+            .filter(|entry| entry.as_ref().expect("read cache entry").file_name() != PATHS_DIR)
+            .count()
+    }
+
+    fn write_book(dir: &Path, text: &str) -> String {
+        let path = dir.join("book.txt");
+        fs::write(&path, text).expect("write book");
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn reads_ranges_and_chapters_from_the_index() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = write_book(dir.path(), "第一章 开始\n甲\n\n乙\n第二章 继续\n丙 é\n");
+        let cache_dir = dir.path().join("books");
+
+        let index = open(&cache_dir, &path, &ParseOptions::default()).expect("build index");
+        assert_eq!(index.toc().line_count, 5);
+        assert_eq!(index.toc().chapters.len(), 2);
+        assert_eq!(index.toc().encoding, "UTF-8");
+
+        let range = index.lines(1, 3).expect("read range");
+        assert_eq!(
+            (range.start, range.lines),
+            (1, vec!["甲".into(), "乙".into()])
+        );
+        assert_eq!(index.lines(4, 99).expect("read tail").lines, vec!["丙 é"]);
+        let past_end = index.lines(9, 12).expect("read past end");
+        assert_eq!((past_end.start, past_end.lines.len()), (5, 0));
+
+        let second = index
+            .chapter(1)
+            .expect("read chapter")
+            .expect("chapter exists");
+        assert_eq!(second.start, 3);
+        assert_eq!(second.lines, vec!["第二章 继续", "丙 é"]);
+        assert_eq!(
+            index
+                .chapter(0)
+                .expect("read chapter")
+                .expect("chapter exists")
+                .lines
+                .len(),
+            3
+        );
+        assert!(index.chapter(2).expect("read missing chapter").is_none());
+    }
+
+    #[test]
+    fn reuses_the_cache_until_the_file_or_options_change() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = write_book(dir.path(), "第一章\n甲\n");
+        let cache_dir = dir.path().join("books");
+        open(&cache_dir, &path, &ParseOptions::default()).expect("build index");
+
+        // A stale meta file proves reuse: a rebuild would overwrite it.
+        let key = get_thumbnail_hash(&fs::metadata(&path).expect("stat book"));
+        let meta_path = cache_dir.join(key).join(META_FILE);
+        let meta = fs::read_to_string(&meta_path).expect("read meta");
+        fs::write(
+            &meta_path,
+            meta.replace("\"lineCount\":2", "\"lineCount\":1"),
+        )
+        .expect("edit meta");
+        let reused = open(&cache_dir, &path, &ParseOptions::default()).expect("reuse index");
+        assert_eq!(reused.toc().line_count, 1);
+        let stray = meta_path.with_file_name("stray");
+        fs::write(&stray, "").expect("write stray file");
+
+        let options = ParseOptions {
+            paragraphs: ParagraphOptions {
+                keep_blank_lines: true,
+                ..ParagraphOptions::default()
+            },
+            ..ParseOptions::default()
+        };
+        let rebuilt = open(&cache_dir, &path, &options).expect("rebuild for options");
+        assert_eq!(rebuilt.toc().line_count, 2);
+        assert_eq!(entry_count(&cache_dir), 1);
+        assert!(!stray.exists(), "the rebuild replaces the whole entry");
+
+        fs::write(&path, "第一章\n甲\n乙\n").expect("change book");
+        let rebuilt = open(&cache_dir, &path, &options).expect("rebuild for file");
+        assert_eq!(
+            entry_count(&cache_dir),
+            1,
+            "the old version's entry is removed"
+        );
+        let content = rebuilt.content().expect("read content");
+        assert_eq!(content.lines, vec!["第一章", "甲", "乙"]);
+        assert_eq!(content.chapters.len(), 1);
+
+        clear(&cache_dir).expect("clear cache");
+        assert!(!cache_dir.exists());
+        clear(&cache_dir).expect("clear missing cache");
+    }
+
+    #[test]
+    fn caps_ranges_and_reports_missing_books() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = write_book(dir.path(), &"行\n".repeat(MAX_RANGE_LINES + 10));
+        let index =
+            open(&dir.path().join("books"), &path, &ParseOptions::default()).expect("build index");
+        assert_eq!(
+            index.lines(5, usize::MAX).expect("read capped").lines.len(),
+            MAX_RANGE_LINES
+        );
+
+        let missing = open(
+            &dir.path().join("books"),
+            &dir.path().join("missing.txt").to_string_lossy(),
+            &ParseOptions::default(),
+        );
+        assert!(
+            missing
+                .err()
+                .expect("missing book")
+                .starts_with("Failed to open file:")
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Full-text search inside one book.
+//!
+//! Matching runs over folded text: case is ignored, full-width ASCII and the
+//! ideographic space match their half-width forms, and half-width katakana
+//! (with separate sound marks) match full-width kana. Every folded character
+//! remembers the source characters it came from, so highlights land on the
+//! original text.
+
+use crate::models::{BookSearch, Chapter, SearchHit};
+
+use super::book_index::{BookIndex, MAX_RANGE_LINES};
+
+/// Most lines one search returns.
+const MAX_HITS: usize = 500;
+/// Characters of context kept before the first match of a line.
+const SNIPPET_BEFORE: usize = 30;
+/// Longest snippet, in characters, unless the match itself is longer.
+const SNIPPET_CHARS: usize = 120;
+
+/// Full-width forms of U+FF61..=U+FF9F.
+const HALF_WIDTH_KANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+const VOICEABLE: &str = "カキクケコサシスセソタチツテトハヒフヘホ";
+const SEMI_VOICEABLE: &str = "ハヒフヘホ";
+
+struct Folded {
+    ch: char,
+    /// Source characters `start..end` this one was folded from.
+    start: usize,
+    end: usize,
+}
+
+pub fn validate_query(query: &str) -> Result<(), String> {
+    if query.trim().is_empty() {
+        return Err("Empty search query".to_string());
+    }
+    Ok(())
+}
+
+/// Lines of the book containing `query`, each with a snippet around its first
+/// match and the title of its chapter.
+pub fn search(index: &BookIndex, query: &str) -> Result<BookSearch, String> {
+    let needle = needle(query);
+    let toc = index.toc();
+
+    let mut hits = Vec::new();
+    for start in (0..toc.line_count).step_by(MAX_RANGE_LINES) {
+        let chunk = index.lines(start, start + MAX_RANGE_LINES)?;
+        for (offset, line) in chunk.lines.iter().enumerate() {
+            let matches = find(line, &needle);
+            if matches.is_empty() {
+                continue;
+            }
+            if hits.len() == MAX_HITS {
+                return Ok(BookSearch {
+                    hits,
+                    truncated: true,
+                });
+            }
+            let line_index = start + offset;
+            let (snippet, highlights) = snippet(line, &matches);
+            hits.push(SearchHit {
+                line_index,
+                snippet,
+                highlights,
+                chapter: chapter_title(&toc.chapters, line_index),
+            });
+        }
+    }
+    Ok(BookSearch {
+        hits,
+        truncated: false,
+    })
+}
+
+/// A snippet of `text` around the first match of any whitespace-separated
+/// term of `query`, with every match in it; the start of `text` when none
+/// matches.
+pub fn highlight(text: &str, query: &str) -> (String, Vec<(usize, usize)>) {
+    let mut matches: Vec<_> = query
+        .split_whitespace()
+        .flat_map(|term| find(text, &needle(term)))
+        .collect();
+    matches.sort_unstable();
+    snippet(text, &matches)
+}
+
+/// `text` folded for matching, as the in-book search compares it.
+pub fn fold_text(text: &str) -> String {
+    fold(text).into_iter().map(|f| f.ch).collect()
+}
+
+fn needle(query: &str) -> Vec<char> {
+    fold(query.trim()).into_iter().map(|f| f.ch).collect()
+}
+
+/// Source character ranges of the non-overlapping matches of `needle`.
+fn find(line: &str, needle: &[char]) -> Vec<(usize, usize)> {
+    let folded = fold(line);
+    let mut found = Vec::new();
+    let mut i = 0;
+    while !needle.is_empty() && i + needle.len() <= folded.len() {
+        let window = &folded[i..i + needle.len()];
+        if window.iter().map(|f| f.ch).eq(needle.iter().copied()) {
+            found.push((window[0].start, window[needle.len() - 1].end));
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+/// Up to `SNIPPET_CHARS` of `line` around its first match, marked with an
+/// ellipsis where cut, and the matches that fall inside it.
+fn snippet(line: &str, matches: &[(usize, usize)]) -> (String, Vec<(usize, usize)>) {
+    let chars: Vec<char> = line.chars().collect();
+    let (first_start, first_end) = matches.first().copied().unwrap_or_default();
+    let from = first_start.saturating_sub(SNIPPET_BEFORE);
+    let to = chars.len().min((from + SNIPPET_CHARS).max(first_end));
+
+    let mut snippet = String::new();
+    let lead = usize::from(from > 0);
+    if from > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(&chars[from..to]);
+    if to < chars.len() {
+        snippet.push('…');
+    }
+
+    let highlights = matches
+        .iter()
+        .filter(|&&(start, end)| start >= from && end <= to)
+        .map(|&(start, end)| (start - from + lead, end - from + lead))
+        .collect();
+    (snippet, highlights)
+}
+
+/// Title of the last chapter starting at or before `line_index`.
+pub fn chapter_title(chapters: &[Chapter], line_index: usize) -> Option<String> {
+    let count = chapters.partition_point(|chapter| chapter.line_index <= line_index);
+    count.checked_sub(1).map(|i| chapters[i].title.clone())
+}
+
+fn fold(text: &str) -> Vec<Folded> {
+    let mut out: Vec<Folded> = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let c = fold_width(c);
+        if let Some(last) = out.last_mut()
+            && let Some(voiced) = voice(last.ch, c)
+        {
+            last.ch = voiced;
+            last.end = i + 1;
+            continue;
+        }
+        out.extend(c.to_lowercase().map(|ch| Folded {
+            ch,
+            start: i,
+            end: i + 1,
+        }));
+    }
+    out
+}
+
+fn fold_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{FF61}'..='\u{FF9F}' => HALF_WIDTH_KANA
+            .chars()
+            .nth(c as usize - 0xFF61)
+            .unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// A kana followed by a separate sound mark, as one voiced kana.
+fn voice(kana: char, mark: char) -> Option<char> {
+    let offset = match mark {
+        '゛' if kana == 'ウ' => return Some('ヴ'),
+        '゛' if VOICEABLE.contains(kana) => 1,
+        '゜' if SEMI_VOICEABLE.contains(kana) => 2,
+        _ => return None,
+    };
+    char::from_u32(kana as u32 + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::scanner::book::ParseOptions;
+    use crate::scanner::book_index;
+
+    fn search_book(text: &str, query: &str) -> BookSearch {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("book.txt");
+        fs::write(&path, text).expect("write book");
+        let index = book_index::open(
+            &dir.path().join("books"),
+            &path.to_string_lossy(),
+            &ParseOptions::default(),
+        )
+        .expect("build index");
+        search(&index, query).expect("search book")
+    }
+
+    #[test]
+    fn folds_case_width_and_half_width_kana() {
+        assert_eq!(fold_text("ＡＢｃ１２　Eriri"), "abc12 eriri");
+        assert_eq!(fold_text("ｶﾞｲﾄﾞﾌﾞｯｸ"), "ガイドブック");
+        assert_eq!(fold_text("ﾊﾟｳﾞｧ｡"), "パヴァ。");
+        assert_eq!(find("前ｶﾞｲﾄﾞ後", &['ガ', 'イ', 'ド']), vec![(1, 6)]);
+    }
+
+    #[test]
+    fn finds_lines_with_snippets_and_chapter_titles() {
+        let long = format!("{}Ｅｒｉｒｉ{}", "前".repeat(40), "后".repeat(200));
+        let text =
+            format!("序言 eriri\n第一章 开始\n正文ERIRI与Eriri\n{long}\n第二章 继续\n无关\n");
+
+        let result = search_book(&text, " eriri ");
+
+        assert!(!result.truncated);
+        let lines = result
+            .hits
+            .iter()
+            .map(|hit| hit.line_index)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![0, 2, 3]);
+        assert_eq!(result.hits[0].chapter, None);
+        assert_eq!(result.hits[1].chapter.as_deref(), Some("第一章 开始"));
+        assert_eq!(result.hits[1].highlights, vec![(2, 7), (8, 13)]);
+
+        let cut = &result.hits[2];
+        assert!(cut.snippet.starts_with('…') && cut.snippet.ends_with('…'));
+        assert_eq!(cut.snippet.chars().count(), SNIPPET_CHARS + 2);
+        let (start, end) = cut.highlights[0];
+        let marked: String = cut.snippet.chars().skip(start).take(end - start).collect();
+        assert_eq!((start, marked.as_str()), (SNIPPET_BEFORE + 1, "Ｅｒｉｒｉ"));
+    }
+
+    #[test]
+    fn highlights_every_query_term_or_falls_back_to_the_start() {
+        let (snippet, highlights) = highlight("甲乙丙 ABC 乙", "ａｂｃ 乙");
+        assert_eq!(snippet, "甲乙丙 ABC 乙");
+        assert_eq!(highlights, vec![(1, 2), (4, 7), (8, 9)]);
+
+        let long = "字".repeat(SNIPPET_CHARS + 1);
+        let (snippet, highlights) = highlight(&long, "无");
+        assert_eq!(snippet.chars().count(), SNIPPET_CHARS + 1);
+        assert!(snippet.ends_with('…') && highlights.is_empty());
+    }
+
+    #[test]
+    fn caps_hits_and_rejects_blank_queries() {
+        let result = search_book(&"一行\n".repeat(MAX_HITS + 1), "一");
+
+        assert!(result.truncated);
+        assert_eq!(result.hits.len(), MAX_HITS);
+        assert!(validate_query("　 ").is_err());
+        assert!(validate_query("行").is_ok());
+    }
+}
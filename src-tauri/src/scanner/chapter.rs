@@ -0,0 +1,408 @@
+//! Chapter heading rules for text books.
+//!
+//! Each library keeps an ordered rule set: built-in presets for common heading
+//! styles plus user regexes. Rules are tried from the highest priority down and
+//! the first match names the chapter. Volume headings (`第X卷`, "Part IV",
+//! `第一部`) open a level that the chapters after them nest under.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Chapter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterPreset {
+    /// `第…章/回/节/卷/集/幕` plus 序章, 终章, 番外, 后记 and 尾声. `卷` is a
+    /// volume.
+    Chinese,
+    /// "Chapter 12", "Part IV", "Prologue", "Epilogue: …". Parts, books and
+    /// volumes are volumes.
+    English,
+    /// `第…話/章/節/幕/部/巻` plus プロローグ, エピローグ, 幕間 and the like.
+    /// `部` and `巻` are volumes.
+    Japanese,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ChapterRule {
+    Preset {
+        preset: ChapterPreset,
+        priority: i32,
+    },
+    /// A heading regex matched against the trimmed line. A named `title` group
+    /// becomes the chapter title; otherwise the whole line does. With `volume`
+    /// the matched lines contain the chapters that follow them.
+    Regex {
+        pattern: String,
+        priority: i32,
+        #[serde(default)]
+        volume: bool,
+    },
+}
+
+impl ChapterRule {
+    fn priority(&self) -> i32 {
+        match self {
+            ChapterRule::Preset { priority, .. } | ChapterRule::Regex { priority, .. } => *priority,
+        }
+    }
+}
+
+/// The rule set a library starts with: the Chinese headings the reader has
+/// always recognised.
+pub fn default_rules() -> Vec<ChapterRule> {
+    vec![ChapterRule::Preset {
+        preset: ChapterPreset::Chinese,
+        priority: 0,
+    }]
+}
+
+/// Reject rule sets with a regex that does not compile.
+pub fn validate_rules(rules: &[ChapterRule]) -> Result<(), String> {
+    for rule in rules {
+        if let ChapterRule::Regex { pattern, .. } = rule {
+            Regex::new(pattern).map_err(|e| format!("Invalid chapter pattern: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// A line matched by a chapter rule.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Heading {
+    pub title: String,
+    pub volume: bool,
+}
+
+enum Matcher {
+    Preset(ChapterPreset),
+    Regex(Regex, bool),
+}
+
+/// A compiled rule set, built once per parse.
+pub struct ChapterMatcher {
+    matchers: Vec<Matcher>,
+}
+
+impl ChapterMatcher {
+    /// Compile `rules` in priority order. Ties keep their listed order, and
+    /// patterns that fail to compile are skipped.
+    pub fn new(rules: &[ChapterRule]) -> Self {
+        let mut rules: Vec<&ChapterRule> = rules.iter().collect();
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority()));
+        let matchers = rules
+            .into_iter()
+            .filter_map(|rule| match rule {
+                ChapterRule::Preset { preset, .. } => Some(Matcher::Preset(*preset)),
+                ChapterRule::Regex {
+                    pattern, volume, ..
+                } => Regex::new(pattern)
+                    .ok()
+                    .map(|regex| Matcher::Regex(regex, *volume)),
+            })
+            .collect();
+        Self { matchers }
+    }
+
+    /// The heading `line` is under the first rule that matches, if any.
+    pub fn heading(&self, line: &str) -> Option<Heading> {
+        let trimmed = line.trim();
+        self.matchers.iter().find_map(|matcher| match matcher {
+            Matcher::Preset(ChapterPreset::Chinese) => chinese_heading(trimmed),
+            Matcher::Preset(ChapterPreset::English) => {
+                regex_heading(&ENGLISH_HEADINGS, trimmed, false)
+            }
+            Matcher::Preset(ChapterPreset::Japanese) => {
+                regex_heading(&JAPANESE_HEADINGS, trimmed, false)
+            }
+            Matcher::Regex(regex, volume) => regex_heading(regex, trimmed, *volume),
+        })
+    }
+}
+
+impl Default for ChapterMatcher {
+    fn default() -> Self {
+        Self::new(&default_rules())
+    }
+}
+
+const ENGLISH_NUMBER: &str = r"(?:\d+|[ivxlcdm]+|one|two|three|four|five|six|seven|eight|nine|ten|eleven|twelve|thirteen|fourteen|fifteen|sixteen|seventeen|eighteen|nineteen|twenty|thirty|forty|fifty)";
+
+static ENGLISH_HEADINGS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)^(?:(?:chapter|chap\.|(?P<volume>part|book|volume|vol\.))\s+{ENGLISH_NUMBER}(?:[-\s]{ENGLISH_NUMBER})?\b|(?:prologue|epilogue|interlude|afterword|foreword|preface)(?:$|\s*[:.\-–—]))"
+    ))
+    .expect("Invalid English chapter pattern")
+});
+
+static JAPANESE_HEADINGS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:第[0-9０-９一二三四五六七八九十百千〇零]+(?:[話章節幕]|(?P<volume>[部巻]))|(?:プロローグ|エピローグ|序章|終章|幕間|間章|番外編|あとがき)(?:$|[\s　:：・]))",
+    )
+    .expect("Invalid Japanese chapter pattern")
+});
+
+/// A regex match is a volume when the rule says so or the pattern has a
+/// matching `volume` group.
+fn regex_heading(regex: &Regex, trimmed: &str, volume: bool) -> Option<Heading> {
+    let captures = regex.captures(trimmed)?;
+    let title = captures
+        .name("title")
+        .map_or(trimmed, |title| title.as_str().trim());
+    (!title.is_empty()).then(|| Heading {
+        title: title.to_string(),
+        volume: volume || captures.name("volume").is_some(),
+    })
+}
+
+fn chinese_heading(trimmed: &str) -> Option<Heading> {
+    const SPECIAL_CHAPTERS: &[&str] = &["序章", "终章", "番外", "后记", "尾声"];
+    for &prefix in SPECIAL_CHAPTERS {
+        if trimmed.starts_with(prefix) {
+            return Some(Heading {
+                title: trimmed.to_string(),
+                volume: false,
+            });
+        }
+    }
+
+    if !trimmed.starts_with('第') {
+        return None;
+    }
+
+    let mut chars = trimmed.chars();
+    let _ = chars.next();
+    let mut has_number = false;
+    const CHAPTER_SUFFIXES: &[char] = &['章', '回', '节', '卷', '集', '幕'];
+
+    for c in chars {
+        if is_chapter_number_char(c) {
+            has_number = true;
+            continue;
+        }
+
+        return (has_number && CHAPTER_SUFFIXES.contains(&c)).then(|| Heading {
+            title: trimmed.to_string(),
+            volume: c == '卷',
+        });
+    }
+
+    None
+}
+
+/// Nest chapters by `level`: each entry's parent becomes the closest earlier
+/// entry with a lower level, and levels are renumbered to the actual depth so
+/// chapters before the first volume stay at the top.
+pub fn link_parents(chapters: &mut [Chapter]) {
+    let mut open: Vec<(usize, u8)> = Vec::new();
+    for (index, chapter) in chapters.iter_mut().enumerate() {
+        let level = chapter.level;
+        while open
+            .last()
+            .is_some_and(|&(_, open_level)| open_level >= level)
+        {
+            open.pop();
+        }
+        chapter.parent = open.last().map(|&(parent, _)| parent);
+        chapter.level = u8::try_from(open.len()).unwrap_or(u8::MAX);
+        open.push((index, level));
+    }
+}
+
+fn is_chapter_number_char(c: char) -> bool {
+    c.is_ascii_digit()
+        || matches!(
+            c,
+            '０' | '１'
+                | '２'
+                | '３'
+                | '４'
+                | '５'
+                | '６'
+                | '７'
+                | '８'
+                | '９'
+                | '一'
+                | '二'
+                | '三'
+                | '四'
+                | '五'
+                | '六'
+                | '七'
+                | '八'
+                | '九'
+                | '十'
+                | '百'
+                | '千'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(preset: ChapterPreset, priority: i32) -> ChapterRule {
+        ChapterRule::Preset { preset, priority }
+    }
+
+    fn title(matcher: &ChapterMatcher, line: &str) -> Option<String> {
+        matcher.heading(line).map(|heading| heading.title)
+    }
+
+    #[test]
+    fn default_rules_keep_the_chinese_headings() {
+        let matcher = ChapterMatcher::default();
+        assert_eq!(
+            title(&matcher, "  第十二章 开始 ").as_deref(),
+            Some("第十二章 开始")
+        );
+        assert_eq!(title(&matcher, "尾声").as_deref(), Some("尾声"));
+        assert_eq!(title(&matcher, "第章 缺少数字"), None);
+        assert_eq!(title(&matcher, "第一话 不支持后缀"), None);
+        assert_eq!(title(&matcher, "Chapter 1"), None);
+    }
+
+    #[test]
+    fn english_preset_matches_numbered_and_named_headings() {
+        let matcher = ChapterMatcher::new(&[preset(ChapterPreset::English, 0)]);
+        for heading in [
+            "Chapter 12",
+            "CHAPTER XIV: The Return",
+            "Chapter Twenty-One",
+            "Part IV",
+            "Book one",
+            "Prologue",
+            "Epilogue - After",
+        ] {
+            assert_eq!(
+                title(&matcher, heading).as_deref(),
+                Some(heading),
+                "{heading}"
+            );
+        }
+        for line in [
+            "Chapter and verse were quoted.",
+            "Prologues are optional.",
+            "Partly cloudy",
+        ] {
+            assert_eq!(title(&matcher, line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn japanese_preset_matches_episode_and_named_headings() {
+        let matcher = ChapterMatcher::new(&[preset(ChapterPreset::Japanese, 0)]);
+        assert_eq!(
+            title(&matcher, "第一話 はじまり").as_deref(),
+            Some("第一話 はじまり")
+        );
+        assert_eq!(title(&matcher, "第３章").as_deref(), Some("第３章"));
+        assert_eq!(title(&matcher, "プロローグ").as_deref(), Some("プロローグ"));
+        assert_eq!(
+            title(&matcher, "あとがき　感謝").as_deref(),
+            Some("あとがき　感謝")
+        );
+        assert_eq!(title(&matcher, "エピローグの後で"), None);
+    }
+
+    #[test]
+    fn higher_priority_rules_win_and_title_groups_rename_chapters() {
+        let rules = [
+            preset(ChapterPreset::Chinese, 0),
+            ChapterRule::Regex {
+                pattern: r"^第(?P<title>.+)章$".to_string(),
+                priority: 10,
+                volume: false,
+            },
+            ChapterRule::Regex {
+                pattern: r"^Vol\.\s*\d+".to_string(),
+                priority: -1,
+                volume: true,
+            },
+            ChapterRule::Regex {
+                pattern: "(".to_string(),
+                priority: 20,
+                volume: false,
+            },
+        ];
+        let matcher = ChapterMatcher::new(&rules);
+        assert_eq!(title(&matcher, "第一章").as_deref(), Some("一"));
+        assert_eq!(
+            title(&matcher, "第一章 开始").as_deref(),
+            Some("第一章 开始")
+        );
+        assert_eq!(title(&matcher, "Vol. 2").as_deref(), Some("Vol. 2"));
+
+        assert!(validate_rules(&rules[..3]).is_ok());
+        assert!(
+            validate_rules(&rules)
+                .expect_err("unbalanced pattern should fail")
+                .starts_with("Invalid chapter pattern:")
+        );
+    }
+
+    #[test]
+    fn volume_headings_are_flagged_and_nest_later_chapters() {
+        let matcher = ChapterMatcher::new(&[
+            preset(ChapterPreset::Chinese, 0),
+            preset(ChapterPreset::English, 0),
+            preset(ChapterPreset::Japanese, 0),
+        ]);
+        let volumes = ["第一卷 风起", "Part IV", "Book one", "第二部", "第３巻"]
+            .map(|line| matcher.heading(line).map(|heading| heading.volume));
+        assert_eq!(volumes, [Some(true); 5]);
+        let chapters = ["第一章", "Chapter 3", "第一話", "序章"]
+            .map(|line| matcher.heading(line).map(|heading| heading.volume));
+        assert_eq!(chapters, [Some(false); 4]);
+
+        let chapter = |level| Chapter {
+            title: String::new(),
+            line_index: 0,
+            confidence: None,
+            level,
+            parent: None,
+        };
+        let mut toc = [1, 0, 1, 1, 0, 1, 3, 2].map(chapter);
+        link_parents(&mut toc);
+        assert_eq!(
+            toc.iter()
+                .map(|chapter| (chapter.level, chapter.parent))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, None),
+                (0, None),
+                (1, Some(1)),
+                (1, Some(1)),
+                (0, None),
+                (1, Some(4)),
+                (2, Some(5)),
+                (2, Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_round_trip_through_json() {
+        let rules = vec![
+            preset(ChapterPreset::English, 5),
+            ChapterRule::Regex {
+                pattern: "^Act \\d+".to_string(),
+                priority: 1,
+                volume: false,
+            },
+        ];
+        let json = serde_json::to_string(&rules).expect("serialize rules");
+        assert_eq!(
+            json,
+            r#"[{"kind":"preset","preset":"english","priority":5},{"kind":"regex","pattern":"^Act \\d+","priority":1,"volume":false}]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<ChapterRule>>(&json).expect("deserialize rules"),
+            rules
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use fast_image_resize as fr;
+use percent_encoding::percent_decode_str;
 use rayon::prelude::*;
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
 use tauri::AppHandle;
 use tracing::{info, warn};
 use turbojpeg::Decompressor;
@@ -11,31 +14,90 @@ use turbojpeg::Decompressor;
 use crate::models::{Comic, ComicImage};
 use crate::tags::get_file_tags;
 use crate::thumbnail::{
-    THUMB_FALLBACK_HEIGHT, THUMB_WIDTH, add_stat, file_url, find_cover_image, get_thumbnail_dir,
+    THUMB_FALLBACK_HEIGHT, THUMB_WIDTH, add_stat, archive_entry_url, file_url, find_cover_entry,
+    find_cover_image, get_archive_entry_hash, get_image_dimensions_from_bytes, get_thumbnail_dir,
     get_thumbnail_hash, is_image_file, process_and_get_dimensions,
+    process_bytes_and_get_dimensions,
 };
 
-use super::utils::{current_time_millis, generate_uuid, get_created_time, is_hidden};
+use super::archive::{ComicArchive, is_comic_archive};
+use super::utils::{
+    generate_uuid, get_created_time, get_modified_time, is_hidden, list_entries, remove_extension,
+};
+use super::{ScanProgress, Stamped};
+
+/// Bytes read from an archive page to parse its header when the thumbnail is
+/// already cached, instead of inflating the whole page.
+const HEADER_PREFIX_BYTES: u64 = 64 * 1024;
+
+/// Decoded pages queued between a sequential archive reader and the
+/// thumbnail workers, bounding memory while the reader runs ahead.
+const SEQUENTIAL_PAGE_BUFFER: usize = 8;
 
 pub fn scan_comic_library(
     app: AppHandle,
     library_path: &str,
     library_id: &str,
-) -> Result<Vec<Comic>, String> {
+    previous: &[Stamped<Comic>],
+    progress: &ScanProgress,
+) -> Result<Vec<Stamped<Comic>>, String> {
     let start = std::time::Instant::now();
-    let path = Path::new(library_path);
+    let comics = scan_comic_entries(
+        &app,
+        &list_entries(Path::new(library_path))?,
+        library_id,
+        previous,
+        progress,
+    );
 
-    let thumb_dir = get_thumbnail_dir(&app);
+    info!(
+        count = comics.len(),
+        duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        "Scanned comic library"
+    );
+
+    Ok(comics)
+}
+
+/// Scan only `paths`, entries directly under a comic library; the ones that
+/// are gone or are not comics are left out. A cancelled `progress` leaves out
+/// the rest too.
+pub fn scan_comic_entries(
+    app: &AppHandle,
+    paths: &[PathBuf],
+    library_id: &str,
+    previous: &[Stamped<Comic>],
+    progress: &ScanProgress,
+) -> Vec<Stamped<Comic>> {
+    let thumb_dir = get_thumbnail_dir(app);
+    let (comics, total_new_count, total_new_bytes) =
+        scan_comic_entries_in(paths, library_id, &thumb_dir, previous, progress);
+    if total_new_count > 0 {
+        add_stat(app, total_new_count, total_new_bytes);
+    }
+    comics
+}
+
+/// Every directory or comic archive in `paths` becomes a `Comic` with the same
+/// identity rules: the id derives from the path and the title is the file
+/// name, without the extension for archives. Entries of `previous` whose
+/// modification time is unchanged are kept as they were, without looking for
+/// their cover or reading their tags again.
+fn scan_comic_entries_in(
+    paths: &[PathBuf],
+    library_id: &str,
+    thumb_dir: &Path,
+    previous: &[Stamped<Comic>],
+    progress: &ScanProgress,
+) -> (Vec<Stamped<Comic>>, usize, u64) {
     let new_count = AtomicUsize::new(0);
     let new_bytes = AtomicU64::new(0);
-
-    let entries = fs::read_dir(path).map_err(|e| e.to_string())?;
-    let entries_vec: Vec<_> = entries
-        .flatten()
-        .filter(|e| !is_hidden(&e.path()))
+    let previous: HashMap<&str, &Stamped<Comic>> = previous
+        .iter()
+        .map(|comic| (comic.entry.path.as_str(), comic))
         .collect();
 
-    let mut comics: Vec<Comic> = entries_vec
+    let mut comics: Vec<Stamped<Comic>> = paths
         .par_iter()
         .map_init(
             || {
@@ -43,81 +105,97 @@ pub fn scan_comic_library(
                 let resizer = fr::Resizer::new();
                 (decompressor, resizer)
             },
-            |(decompressor_opt, resizer), entry| {
-                if !entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+            |(decompressor_opt, resizer), comic_path| {
+                if progress.is_cancelled() {
+                    return None;
+                }
+                // Like `DirEntry`, entries are not followed through symlinks.
+                let metadata = fs::symlink_metadata(comic_path).ok()?;
+                let file_type = metadata.file_type();
+                let is_archive = file_type.is_file() && is_comic_archive(comic_path);
+                if !file_type.is_dir() && !is_archive {
                     return None;
                 }
 
-                let comic_path = entry.path();
                 let path_str = comic_path.to_string_lossy();
-                let comic_name = entry.file_name().to_string_lossy().into_owned();
-                let comic_id = generate_uuid(&path_str);
-
-                let created_at = entry
-                    .metadata()
-                    .map(|m| get_created_time(&m))
-                    .unwrap_or_else(|_| current_time_millis());
-
-                let cover = find_cover_image(&comic_path)
-                    .and_then(|cover_path| fs::metadata(&cover_path).ok().map(|m| (cover_path, m)))
-                    .map(|(cover_path, cover_meta)| {
-                        let hash = get_thumbnail_hash(&cover_meta);
-                        let thumb_path = thumb_dir.join(format!("{hash}.jpg"));
-
-                        if !thumb_path.exists()
-                            && let Some(decompressor) = decompressor_opt.as_mut()
-                            && let Ok((_, _, file_size)) = process_and_get_dimensions(
-                                &cover_path,
-                                &thumb_path,
-                                decompressor,
-                                resizer,
-                            )
-                            && file_size > 0
-                        {
-                            new_count.fetch_add(1, Ordering::Relaxed);
-                            new_bytes.fetch_add(file_size, Ordering::Relaxed);
-                        }
-
-                        if thumb_path.exists() {
-                            file_url(&thumb_path.to_string_lossy())
-                        } else {
-                            file_url(&cover_path.to_string_lossy())
-                        }
-                    })
-                    .unwrap_or_default();
+                let mtime = get_modified_time(&metadata);
+                if let Some(&known) = previous.get(path_str.as_ref())
+                    && known.mtime == mtime
+                    && cover_survives(&known.entry.cover)
+                {
+                    progress.count_scanned();
+                    return Some(known.clone());
+                }
 
-                let (starred, deleted) = get_file_tags(&comic_path);
+                let file_name = comic_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                let comic_name = if is_archive {
+                    remove_extension(&file_name)
+                } else {
+                    file_name
+                };
+                let comic_id = generate_uuid(&path_str);
 
-                Some(Comic {
-                    id: comic_id,
-                    title: comic_name,
-                    path: path_str.into_owned(),
-                    cover,
-                    library_id: library_id.to_string(),
-                    created_at,
-                    starred,
-                    deleted,
+                let created_at = get_created_time(&metadata);
+
+                let entry_count = AtomicUsize::new(0);
+                let cover = if is_archive {
+                    find_archive_cover(
+                        comic_path,
+                        thumb_dir,
+                        decompressor_opt,
+                        resizer,
+                        &entry_count,
+                        &new_bytes,
+                    )
+                } else {
+                    find_directory_cover(
+                        comic_path,
+                        thumb_dir,
+                        decompressor_opt,
+                        resizer,
+                        &entry_count,
+                        &new_bytes,
+                    )
+                }
+                .unwrap_or_default();
+                let entry_count = entry_count.into_inner();
+                new_count.fetch_add(entry_count, Ordering::Relaxed);
+                progress
+                    .thumbnails
+                    .fetch_add(entry_count as u64, Ordering::Relaxed);
+                progress.count_scanned();
+
+                let (starred, deleted) = get_file_tags(comic_path);
+
+                Some(Stamped {
+                    entry: Comic {
+                        id: comic_id,
+                        title: comic_name,
+                        path: path_str.into_owned(),
+                        cover,
+                        library_id: library_id.to_string(),
+                        created_at,
+                        starred,
+                        deleted,
+                    },
+                    mtime,
                 })
             },
         )
         .flatten()
         .collect();
 
-    let total_new_count = new_count.load(Ordering::Relaxed);
-    let total_new_bytes = new_bytes.load(Ordering::Relaxed);
-    if total_new_count > 0 {
-        add_stat(&app, total_new_count, total_new_bytes);
-    }
-
-    comics.sort_by(|a, b| natord::compare(&a.title, &b.title));
-
-    info!(
-        count = comics.len(),
-        duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
-        "Scanned comic library"
-    );
+    comics.sort_by(|a, b| natord::compare(&a.entry.title, &b.entry.title));
 
-    Ok(comics)
+    (
+        comics,
+        new_count.load(Ordering::Relaxed),
+        new_bytes.load(Ordering::Relaxed),
+    )
 }
 
 pub fn scan_comic_images(app: AppHandle, comic_path: &str) -> Result<Vec<ComicImage>, String> {
@@ -146,6 +224,10 @@ fn scan_comic_images_in(
     comic_path: &Path,
     thumb_dir: &Path,
 ) -> Result<(Vec<ComicImage>, usize, u64), String> {
+    if comic_path.is_file() && is_comic_archive(comic_path) {
+        return scan_archive_images_in(comic_path, thumb_dir);
+    }
+
     let new_count = AtomicUsize::new(0);
     let new_bytes = AtomicU64::new(0);
 
@@ -243,11 +325,267 @@ fn scan_comic_images_in(
     Ok((images, total_new_count, total_new_bytes))
 }
 
+/// Cover URL for a directory comic: the thumbnail of the image chosen by
+/// `find_cover_image`, falling back to streaming the image itself.
+fn find_directory_cover(
+    comic_path: &Path,
+    thumb_dir: &Path,
+    decompressor_opt: &mut Option<Decompressor>,
+    resizer: &mut fr::Resizer,
+    new_count: &AtomicUsize,
+    new_bytes: &AtomicU64,
+) -> Option<String> {
+    let cover_path = find_cover_image(comic_path)?;
+    let cover_meta = fs::metadata(&cover_path).ok()?;
+    let hash = get_thumbnail_hash(&cover_meta);
+    let thumb_path = thumb_dir.join(format!("{hash}.jpg"));
+
+    if !thumb_path.exists()
+        && let Some(decompressor) = decompressor_opt.as_mut()
+        && let Ok((_, _, file_size)) =
+            process_and_get_dimensions(&cover_path, &thumb_path, decompressor, resizer)
+        && file_size > 0
+    {
+        new_count.fetch_add(1, Ordering::Relaxed);
+        new_bytes.fetch_add(file_size, Ordering::Relaxed);
+    }
+
+    Some(if thumb_path.exists() {
+        file_url(&thumb_path.to_string_lossy())
+    } else {
+        file_url(&cover_path.to_string_lossy())
+    })
+}
+
+/// Whether a cover URL from an earlier scan still resolves; cleaning the cache
+/// deletes the thumbnails covers point at.
+fn cover_survives(cover: &str) -> bool {
+    cover.strip_prefix("/file?path=").is_none_or(|encoded| {
+        Path::new(percent_decode_str(encoded).decode_utf8_lossy().as_ref()).exists()
+    })
+}
+
+/// Cover URL for an archive comic: the thumbnail of the page chosen by
+/// `find_cover_entry`, generated from the entry in memory, falling back to
+/// streaming the page itself.
+fn find_archive_cover(
+    archive_path: &Path,
+    thumb_dir: &Path,
+    decompressor_opt: &mut Option<Decompressor>,
+    resizer: &mut fr::Resizer,
+    new_count: &AtomicUsize,
+    new_bytes: &AtomicU64,
+) -> Option<String> {
+    let metadata = fs::metadata(archive_path).ok()?;
+    let mut archive = ComicArchive::open(archive_path).ok()?;
+    let pages = archive.pages();
+    let names: Vec<&str> = pages.iter().map(String::as_str).collect();
+    let entry = find_cover_entry(&names)?;
+
+    let hash = get_archive_entry_hash(&metadata, entry);
+    let thumb_path = thumb_dir.join(format!("{hash}.jpg"));
+
+    if !thumb_path.exists()
+        && let Some(decompressor) = decompressor_opt.as_mut()
+        && let Ok(data) = archive.read(entry)
+        && let Ok((_, _, file_size)) =
+            process_bytes_and_get_dimensions(&data, &thumb_path, decompressor, resizer)
+        && file_size > 0
+    {
+        new_count.fetch_add(1, Ordering::Relaxed);
+        new_bytes.fetch_add(file_size, Ordering::Relaxed);
+    }
+
+    Some(if thumb_path.exists() {
+        file_url(&thumb_path.to_string_lossy())
+    } else {
+        archive_entry_url(&archive_path.to_string_lossy(), entry)
+    })
+}
+
+/// Archive counterpart of the directory scan: one `ComicImage` per page entry,
+/// served through `/archive` and thumbnailed from the entry bytes. Pages have
+/// no file of their own, so they carry no file tags.
+fn scan_archive_images_in(
+    archive_path: &Path,
+    thumb_dir: &Path,
+) -> Result<(Vec<ComicImage>, usize, u64), String> {
+    let new_count = AtomicUsize::new(0);
+    let new_bytes = AtomicU64::new(0);
+
+    let metadata = fs::metadata(archive_path).map_err(|e| e.to_string())?;
+    let mut archive = ComicArchive::open(archive_path)?;
+    let pages = archive.pages();
+    let archive_str = archive_path.to_string_lossy();
+
+    info!(count = pages.len(), "Found archive pages");
+
+    let record = |entry: &str,
+                  result: Result<(u32, u32, u64), Box<dyn std::error::Error>>|
+     -> (u32, u32) {
+        match result {
+            Ok((w, h, file_size)) => {
+                if file_size > 0 {
+                    new_count.fetch_add(1, Ordering::Relaxed);
+                    new_bytes.fetch_add(file_size, Ordering::Relaxed);
+                }
+                (w, h)
+            }
+            Err(e) => {
+                warn!(error = %e, archive = %archive_path.display(), entry = %entry, "Failed to process archive page");
+                (THUMB_WIDTH, THUMB_FALLBACK_HEIGHT)
+            }
+        }
+    };
+    let thumb_path_for = |entry: &str| {
+        let hash = get_archive_entry_hash(&metadata, entry);
+        thumb_dir.join(format!("{hash}.jpg"))
+    };
+
+    let dimensions: Vec<(u32, u32)> = if archive.is_sequential() {
+        // Solid RAR pages can only be decoded front to back, so one pass
+        // feeds every page to the thumbnail workers instead of each worker
+        // walking the archive again per page.
+        let mut decoded = HashMap::new();
+        let (tx, rx) = mpsc::sync_channel::<(String, Vec<u8>)>(SEQUENTIAL_PAGE_BUFFER);
+        std::thread::scope(|scope| {
+            let workers = scope.spawn(|| {
+                rx.into_iter()
+                    .par_bridge()
+                    .map_init(
+                        || (Decompressor::new().ok(), fr::Resizer::new()),
+                        |(decompressor_opt, resizer), (entry, data)| {
+                            let thumb_path = thumb_path_for(&entry);
+                            let size = match decompressor_opt.as_mut() {
+                                Some(decompressor) => record(
+                                    &entry,
+                                    process_bytes_and_get_dimensions(
+                                        &data,
+                                        &thumb_path,
+                                        decompressor,
+                                        resizer,
+                                    ),
+                                ),
+                                None => (THUMB_WIDTH, THUMB_FALLBACK_HEIGHT),
+                            };
+                            (entry, size)
+                        },
+                    )
+                    .collect::<Vec<_>>()
+            });
+            if let Err(e) = archive.for_each_page(|entry, data| {
+                let _ = tx.send((entry.to_string(), data));
+            }) {
+                warn!(error = %e, archive = %archive_path.display(), "Failed to read archive pages");
+            }
+            drop(tx);
+            if let Ok(results) = workers.join() {
+                decoded.extend(results);
+            }
+        });
+        pages
+            .iter()
+            .map(|entry| {
+                decoded
+                    .remove(entry)
+                    .unwrap_or((THUMB_WIDTH, THUMB_FALLBACK_HEIGHT))
+            })
+            .collect()
+    } else {
+        pages
+            .par_iter()
+            .map_init(
+                || {
+                    let archive = ComicArchive::open(archive_path).ok();
+                    let decompressor = Decompressor::new().ok();
+                    let resizer = fr::Resizer::new();
+                    (archive, decompressor, resizer)
+                },
+                |(archive_opt, decompressor_opt, resizer), entry| match (
+                    archive_opt.as_mut(),
+                    decompressor_opt.as_mut(),
+                ) {
+                    (Some(archive), Some(decompressor)) => record(
+                        entry,
+                        archive_page_dimensions(
+                            archive,
+                            entry,
+                            &thumb_path_for(entry),
+                            decompressor,
+                            resizer,
+                        ),
+                    ),
+                    _ => (THUMB_WIDTH, THUMB_FALLBACK_HEIGHT),
+                },
+            )
+            .collect()
+    };
+
+    // `pages` is already in natural order and `dimensions` follows it.
+    let images = pages
+        .iter()
+        .zip(dimensions)
+        .enumerate()
+        .map(|(i, (entry, (width, height)))| {
+            let thumb_path = thumb_path_for(entry);
+            let url = archive_entry_url(&archive_str, entry);
+            let thumbnail = if thumb_path.exists() {
+                file_url(&thumb_path.to_string_lossy())
+            } else {
+                url.clone()
+            };
+
+            ComicImage {
+                path: archive_path.join(entry).to_string_lossy().into_owned(),
+                filename: entry.rsplit('/').next().unwrap_or(entry).to_string(),
+                url,
+                thumbnail,
+                width,
+                height,
+                starred: false,
+                deleted: false,
+                index: i as u32,
+            }
+        })
+        .collect();
+
+    Ok((
+        images,
+        new_count.load(Ordering::Relaxed),
+        new_bytes.load(Ordering::Relaxed),
+    ))
+}
+
+/// Dimensions of one archive page, generating its thumbnail when missing.
+/// With a cached thumbnail only the page header is read.
+fn archive_page_dimensions(
+    archive: &mut ComicArchive,
+    entry: &str,
+    thumb_path: &Path,
+    decompressor: &mut Decompressor,
+    resizer: &mut fr::Resizer,
+) -> Result<(u32, u32, u64), Box<dyn std::error::Error>> {
+    if thumb_path.exists()
+        && let Ok(prefix) = archive.read_prefix(entry, HEADER_PREFIX_BYTES)
+        && let Ok((w, h)) = get_image_dimensions_from_bytes(&prefix)
+    {
+        return Ok((w, h, 0));
+    }
+
+    let data = archive.read(entry)?;
+    process_bytes_and_get_dimensions(&data, thumb_path, decompressor, resizer)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scanner::archive::tests::{write_7z, write_rar, write_tar, write_zip};
     use image::{Rgb, RgbImage};
 
+    fn entries(library: &Path) -> Vec<PathBuf> {
+        list_entries(library).expect("list library entries")
+    }
+
     fn write_image(path: &Path, width: u32, height: u32) {
         RgbImage::from_pixel(width, height, Rgb([20, 40, 60]))
             .save(path)
@@ -293,6 +631,288 @@ mod tests {
         assert_eq!((cached_count, cached_bytes), (0, 0));
     }
 
+    fn encode_image(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+        let mut data = std::io::Cursor::new(Vec::new());
+        RgbImage::from_pixel(width, height, Rgb([20, 40, 60]))
+            .write_to(&mut data, format)
+            .expect("encode comic image");
+        data.into_inner()
+    }
+
+    #[test]
+    fn scans_archive_pages_through_archive_urls_and_reuses_thumbnails() {
+        let dir = tempfile::tempdir().expect("create library dir");
+        let thumbnails = tempfile::tempdir().expect("create thumbnail dir");
+        let archive = dir.path().join("Comic.cbz");
+        let page_10 = encode_image(30, 15, image::ImageFormat::Jpeg);
+        let page_2 = encode_image(20, 40, image::ImageFormat::Png);
+        write_zip(
+            &archive,
+            &[
+                ("10.jpg", &page_10),
+                ("2.png", &page_2),
+                ("broken.jpg", b"not an image"),
+                ("notes.txt", b"ignore"),
+            ],
+        );
+
+        let (images, created_count, created_bytes) =
+            scan_comic_images_in(&archive, thumbnails.path()).expect("scan archive pages");
+
+        assert_eq!(
+            images
+                .iter()
+                .map(|image| (image.filename.as_str(), image.index))
+                .collect::<Vec<_>>(),
+            vec![("2.png", 0), ("10.jpg", 1), ("broken.jpg", 2)]
+        );
+        assert_eq!((images[0].width, images[0].height), (20, 40));
+        assert_eq!((images[1].width, images[1].height), (30, 15));
+        assert_eq!(
+            (images[2].width, images[2].height),
+            (THUMB_WIDTH, THUMB_FALLBACK_HEIGHT)
+        );
+        assert!(images[0].url.starts_with("/archive?path="));
+        assert!(images[0].url.ends_with("&entry=2.png"));
+        assert!(images[0].thumbnail.starts_with("/file?path="));
+        assert_eq!(images[2].thumbnail, images[2].url);
+        assert_eq!(images[0].path, archive.join("2.png").to_string_lossy());
+        assert_eq!(created_count, 2);
+        assert!(created_bytes > 0);
+
+        let (cached_images, cached_count, cached_bytes) =
+            scan_comic_images_in(&archive, thumbnails.path()).expect("rescan archive pages");
+        assert_eq!(cached_images.len(), 3);
+        assert_eq!((cached_images[1].width, cached_images[1].height), (30, 15));
+        assert_eq!((cached_count, cached_bytes), (0, 0));
+    }
+
+    #[test]
+    fn scans_rar_pages_in_one_pass_and_keeps_natural_order() {
+        let dir = tempfile::tempdir().expect("create library dir");
+        let thumbnails = tempfile::tempdir().expect("create thumbnail dir");
+        let archive = dir.path().join("Comic.cbr");
+        let page_10 = encode_image(30, 15, image::ImageFormat::Jpeg);
+        let page_2 = encode_image(20, 40, image::ImageFormat::Png);
+        write_rar(
+            &archive,
+            &[
+                ("10.jpg", &page_10),
+                ("broken.jpg", b"not an image"),
+                ("2.png", &page_2),
+            ],
+            false,
+        );
+
+        let (images, created_count, _) =
+            scan_comic_images_in(&archive, thumbnails.path()).expect("scan rar pages");
+
+        assert_eq!(
+            images
+                .iter()
+                .map(|image| (image.filename.as_str(), image.width, image.height))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2.png", 20, 40),
+                ("10.jpg", 30, 15),
+                ("broken.jpg", THUMB_WIDTH, THUMB_FALLBACK_HEIGHT),
+            ]
+        );
+        assert!(images[0].thumbnail.starts_with("/file?path="));
+        assert_eq!(created_count, 2);
+
+        let mut decompressor = Decompressor::new().ok();
+        let mut resizer = fr::Resizer::new();
+        let (count, bytes) = (AtomicUsize::new(0), AtomicU64::new(0));
+        let cover = find_archive_cover(
+            &archive,
+            thumbnails.path(),
+            &mut decompressor,
+            &mut resizer,
+            &count,
+            &bytes,
+        )
+        .expect("find rar cover");
+        assert_eq!(cover, images[0].thumbnail);
+    }
+
+    #[test]
+    fn library_scan_treats_archives_and_directories_alike() {
+        let library = tempfile::tempdir().expect("create library dir");
+        let thumbnails = tempfile::tempdir().expect("create thumbnail dir");
+        let page = encode_image(16, 32, image::ImageFormat::Png);
+        let folder = library.path().join("Comic 1");
+        fs::create_dir(&folder).expect("create comic dir");
+        fs::write(folder.join("1.png"), &page).expect("write comic page");
+        write_7z(
+            &library.path().join("Comic 2.cb7"),
+            &[("1.png", &page)],
+            true,
+        );
+        write_tar(&library.path().join("Comic 10.cbt"), &[("1.png", &page)]);
+        write_zip(&library.path().join("Comic 3.cbz"), &[("1.png", &page)]);
+        fs::write(library.path().join("notes.txt"), "ignore").expect("write non-comic file");
+
+        let progress = ScanProgress::default();
+        let (comics, created_count, _) = scan_comic_entries_in(
+            &entries(library.path()),
+            "library-1",
+            thumbnails.path(),
+            &[],
+            &progress,
+        );
+        let comics = comics
+            .into_iter()
+            .map(|comic| comic.entry)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            comics
+                .iter()
+                .map(|comic| comic.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Comic 1", "Comic 2", "Comic 3", "Comic 10"]
+        );
+        for comic in &comics {
+            assert_eq!(comic.id, generate_uuid(&comic.path));
+            assert_eq!(comic.library_id, "library-1");
+            assert!(comic.cover.starts_with("/file?path="));
+        }
+        assert_eq!(
+            comics[1].path,
+            library.path().join("Comic 2.cb7").to_string_lossy()
+        );
+        assert!(created_count >= 1);
+        assert_eq!(progress.scanned.load(Ordering::Relaxed), 4);
+        assert_eq!(
+            progress.thumbnails.load(Ordering::Relaxed),
+            created_count as u64
+        );
+
+        progress.cancel();
+        let (comics, _, _) = scan_comic_entries_in(
+            &entries(library.path()),
+            "library-1",
+            thumbnails.path(),
+            &[],
+            &progress,
+        );
+        assert!(comics.is_empty());
+
+        let (images, _, _) =
+            scan_comic_images_in(&library.path().join("Comic 2.cb7"), thumbnails.path())
+                .expect("scan solid 7z pages");
+        assert_eq!((images[0].width, images[0].height), (16, 32));
+    }
+
+    #[test]
+    fn library_rescan_reuses_untouched_entries_with_live_covers() {
+        let library = tempfile::tempdir().expect("create library dir");
+        let thumbnails = tempfile::tempdir().expect("create thumbnail dir");
+        let page = encode_image(16, 32, image::ImageFormat::Png);
+        for name in ["Comic 1", "Comic 2", "Comic 3"] {
+            let folder = library.path().join(name);
+            fs::create_dir(&folder).expect("create comic dir");
+            fs::write(folder.join("1.png"), &page).expect("write comic page");
+        }
+        let (mut previous, _, _) = scan_comic_entries_in(
+            &entries(library.path()),
+            "library-1",
+            thumbnails.path(),
+            &[],
+            &ScanProgress::default(),
+        );
+
+        // Marked titles show which entries were reused rather than rescanned.
+        for comic in &mut previous {
+            comic.entry.title = format!("{} (kept)", comic.entry.title);
+        }
+        previous[1].mtime -= 1;
+        let thumbnail = previous[2].entry.cover.clone();
+        fs::remove_file(
+            percent_decode_str(thumbnail.trim_start_matches("/file?path="))
+                .decode_utf8_lossy()
+                .as_ref(),
+        )
+        .expect("delete cover thumbnail");
+
+        let (comics, created_count, _) = scan_comic_entries_in(
+            &entries(library.path()),
+            "library-1",
+            thumbnails.path(),
+            &previous,
+            &ScanProgress::default(),
+        );
+
+        assert_eq!(
+            comics
+                .iter()
+                .map(|comic| comic.entry.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Comic 1 (kept)", "Comic 2", "Comic 3"]
+        );
+        assert_eq!(comics[1].mtime, previous[1].mtime + 1);
+        assert_eq!(comics[2].entry.cover, thumbnail);
+        assert_eq!(created_count, 1);
+    }
+
+    #[test]
+    fn archive_cover_prefers_explicit_cover_and_falls_back_to_page_url() {
+        let dir = tempfile::tempdir().expect("create library dir");
+        let thumbnails = tempfile::tempdir().expect("create thumbnail dir");
+        let archive = dir.path().join("Comic.zip");
+        let first = encode_image(40, 20, image::ImageFormat::Png);
+        let cover_page = encode_image(16, 32, image::ImageFormat::Png);
+        write_zip(&archive, &[("1.png", &first), ("1001.png", &cover_page)]);
+
+        let mut decompressor = Decompressor::new().ok();
+        let mut resizer = fr::Resizer::new();
+        let (count, bytes) = (AtomicUsize::new(0), AtomicU64::new(0));
+        let cover = find_archive_cover(
+            &archive,
+            thumbnails.path(),
+            &mut decompressor,
+            &mut resizer,
+            &count,
+            &bytes,
+        )
+        .expect("find archive cover");
+        let hash =
+            get_archive_entry_hash(&fs::metadata(&archive).expect("stat archive"), "1001.png");
+        let thumb = thumbnails.path().join(format!("{hash}.jpg"));
+        assert_eq!(cover, file_url(&thumb.to_string_lossy()));
+        let (width, height) = image::image_dimensions(&thumb).expect("read cover thumbnail");
+        assert!(height > width, "the thumbnail is of the portrait 1001.png");
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+
+        let broken = dir.path().join("Broken.cbz");
+        write_zip(&broken, &[("1.jpg", b"not an image")]);
+        let cover = find_archive_cover(
+            &broken,
+            thumbnails.path(),
+            &mut decompressor,
+            &mut resizer,
+            &count,
+            &bytes,
+        )
+        .expect("find broken archive cover");
+        assert!(cover.starts_with("/archive?path="));
+
+        let empty = dir.path().join("Empty.cbz");
+        write_zip(&empty, &[("notes.txt", b"no pages")]);
+        assert!(
+            find_archive_cover(
+                &empty,
+                thumbnails.path(),
+                &mut decompressor,
+                &mut resizer,
+                &count,
+                &bytes,
+            )
+            .is_none()
+        );
+    }
+
     #[test]
     fn reports_missing_comic_directories() {
         let dir = tempfile::tempdir().expect("create parent dir");
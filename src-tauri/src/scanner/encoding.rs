@@ -0,0 +1,151 @@
+//! Text encoding detection for books.
+//!
+//! Chinese TXT collections are mostly GBK/GB18030 or Big5, with some UTF-16.
+//! A BOM wins outright; otherwise UTF-16 is told by its zero-byte pattern,
+//! valid UTF-8 is kept as is, and everything else is left to chardetng.
+
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
+
+/// Bytes inspected when guessing. Enough for a confident guess on any novel
+/// without scanning multi-megabyte files twice.
+const SAMPLE_BYTES: usize = 1024 * 1024;
+
+/// Decode `data` to UTF-8. A BOM always wins; otherwise `known` (an encoding
+/// label stored from an earlier open) is used, else the encoding is detected.
+/// Returns the text together with the encoding that was used.
+pub fn decode(data: &[u8], known: Option<&str>) -> (String, &'static Encoding) {
+    let (encoding, bom_len) = Encoding::for_bom(data).unwrap_or_else(|| {
+        let encoding = known
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+            .unwrap_or_else(|| detect(data));
+        (encoding, 0)
+    });
+    let text = encoding.decode_without_bom_handling(&data[bom_len..]).0;
+    (text.into_owned(), encoding)
+}
+
+/// Guess the encoding of BOM-less text.
+pub fn detect(data: &[u8]) -> &'static Encoding {
+    let sample = &data[..data.len().min(SAMPLE_BYTES)];
+
+    // UTF-16 first: text that is all ASCII in UTF-16 is valid UTF-8 too.
+    if let Some(encoding) = detect_utf16(sample) {
+        return encoding;
+    }
+    if is_utf8_prefix(sample) {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(sample, sample.len() == data.len());
+    detector.guess(None, Utf8Detection::Deny)
+}
+
+/// Legacy CJK encodings never produce NUL bytes, while UTF-16 spells every
+/// newline and space with one. Counting those code units at even offsets in
+/// both byte orders tells UTF-16 apart and picks its endianness; ideographs
+/// such as U+4E00 also contain a zero byte, so only whitespace counts.
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let (mut le, mut be) = (0usize, 0usize);
+    for unit in sample.chunks_exact(2) {
+        match *unit {
+            [b'\n' | b'\r' | b' ', 0] => le += 1,
+            [0, b'\n' | b'\r' | b' '] => be += 1,
+            _ => {}
+        }
+    }
+
+    if le > 0 && be * 10 <= le {
+        Some(UTF_16LE)
+    } else if be > 0 && le * 10 <= be {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Valid UTF-8, allowing for a character cut off by the sample end (an error
+/// with no `error_len`).
+fn is_utf8_prefix(sample: &[u8]) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{BIG5, GBK};
+
+    const SIMPLIFIED: &str = "第一章 开始\n他们在这里说话，我们没有看见那个人。\
+        这是一个很长的故事，关于时间和记忆，还有那些已经过去的日子。\n";
+    const TRADITIONAL: &str = "第一章 開始\n他們在這裡說話，我們沒有看見那個人。\
+        這是一個很長的故事，關於時間和記憶，還有那些已經過去的日子。\n";
+
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_legacy_chinese_encodings_statistically() {
+        let gbk = GBK.encode(&SIMPLIFIED.repeat(20)).0.into_owned();
+        let (text, encoding) = decode(&gbk, None);
+        assert_eq!(encoding, GBK);
+        assert_eq!(text, SIMPLIFIED.repeat(20));
+
+        let big5 = BIG5.encode(&TRADITIONAL.repeat(20)).0.into_owned();
+        let (text, encoding) = decode(&big5, None);
+        assert_eq!(encoding, BIG5);
+        assert_eq!(text, TRADITIONAL.repeat(20));
+    }
+
+    #[test]
+    fn prefers_bom_then_known_label_then_utf8() {
+        let mut bom_le = vec![0xFF, 0xFE];
+        bom_le.extend(utf16(SIMPLIFIED, true));
+        assert_eq!(
+            decode(&bom_le, Some("GBK")),
+            (SIMPLIFIED.to_string(), UTF_16LE)
+        );
+
+        let mut bom_utf8 = vec![0xEF, 0xBB, 0xBF];
+        bom_utf8.extend_from_slice(SIMPLIFIED.as_bytes());
+        assert_eq!(decode(&bom_utf8, None), (SIMPLIFIED.to_string(), UTF_8));
+
+        let gbk = GBK.encode(SIMPLIFIED).0.into_owned();
+        assert_eq!(decode(&gbk, Some("gb18030")).0, SIMPLIFIED);
+        assert_eq!(decode(SIMPLIFIED.as_bytes(), None).1, UTF_8);
+        assert_eq!(decode(SIMPLIFIED.as_bytes(), Some("not-a-label")).1, UTF_8);
+    }
+
+    #[test]
+    fn detects_bomless_utf16_by_zero_byte_parity() {
+        let text = SIMPLIFIED.repeat(3);
+        assert_eq!(decode(&utf16(&text, true), None), (text.clone(), UTF_16LE));
+        assert_eq!(decode(&utf16(&text, false), None), (text, UTF_16BE));
+
+        let english = "Chapter One\nIt was a bright cold day in April.\n".repeat(3);
+        assert_eq!(
+            decode(&utf16(&english, true), None),
+            (english.clone(), UTF_16LE)
+        );
+        assert_eq!(decode(english.as_bytes(), None), (english, UTF_8));
+    }
+
+    #[test]
+    fn treats_utf8_cut_at_the_sample_end_as_utf8() {
+        let mut data = "章".repeat(SAMPLE_BYTES / 3).into_bytes();
+        data.extend_from_slice("节".as_bytes());
+        assert_eq!(detect(&data), UTF_8);
+    }
+}
@@ -0,0 +1,685 @@
+//! EPUB books, flattened for the text reader.
+//!
+//! The reader works on plain lines, so every spine document is reduced to one
+//! line per block element, and each table-of-contents target (from the EPUB 3
+//! nav document, else the EPUB 2 NCX) becomes the `Chapter` on the line it
+//! lands on.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use percent_encoding::percent_decode_str;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesRef, BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use zip::ZipArchive;
+
+use crate::models::{BookContent, Chapter};
+
+use super::chapter::link_parents;
+
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// Elements whose start and end break the current line.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// Elements whose text never reaches the reader.
+const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "svg"];
+
+pub fn is_epub_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("epub"))
+}
+
+type Epub = ZipArchive<BufReader<File>>;
+
+/// `(title, target, level)` of one TOC entry; level 0 is the outermost list.
+type TocEntry = (String, String, u8);
+
+/// What the reader needs from the OPF package document. Paths are full
+/// archive entry names.
+#[derive(Default)]
+struct Package {
+    title: Option<String>,
+    spine: Vec<String>,
+    nav: Option<String>,
+    ncx: Option<String>,
+}
+
+/// The book title from the OPF `dc:title`, if the EPUB declares one.
+pub fn read_title(path: &Path) -> Option<String> {
+    let mut epub = open(path).ok()?;
+    read_package(&mut epub).ok()?.title
+}
+
+pub fn parse_epub(path: &Path) -> Result<BookContent, String> {
+    let mut epub = open(path)?;
+    let package = read_package(&mut epub)?;
+
+    let mut lines = Vec::new();
+    // Line index of every spine document and of every `id` inside one, keyed
+    // the way TOC targets are written: `path` and `path#id`.
+    let mut targets = HashMap::new();
+    for doc in &package.spine {
+        let Ok(xhtml) = read_entry(&mut epub, doc) else {
+            continue;
+        };
+        targets.insert(doc.clone(), lines.len());
+        let (doc_lines, anchors) = extract_lines(&xhtml);
+        for (id, offset) in anchors {
+            targets.insert(format!("{doc}#{id}"), lines.len() + offset);
+        }
+        lines.extend(doc_lines);
+    }
+
+    let toc = package
+        .nav
+        .as_deref()
+        .and_then(|nav| {
+            read_entry(&mut epub, nav)
+                .ok()
+                .map(|xml| parse_nav(&xml, nav))
+        })
+        .filter(|toc| !toc.is_empty())
+        .or_else(|| {
+            package.ncx.as_deref().and_then(|ncx| {
+                read_entry(&mut epub, ncx)
+                    .ok()
+                    .map(|xml| parse_ncx(&xml, ncx))
+            })
+        })
+        .unwrap_or_default();
+
+    let mut chapters: Vec<Chapter> = toc
+        .into_iter()
+        .filter_map(|(title, target, level)| {
+            let line_index = targets.get(&target).or_else(|| {
+                let doc = target
+                    .split_once('#')
+                    .map_or(target.as_str(), |(doc, _)| doc);
+                targets.get(doc)
+            })?;
+            (*line_index < lines.len()).then_some(Chapter {
+                title,
+                line_index: *line_index,
+                confidence: None,
+                level,
+                parent: None,
+            })
+        })
+        .collect();
+    link_parents(&mut chapters);
+
+    Ok(BookContent {
+        lines,
+        chapters,
+        // EPUB content documents are XML and read as UTF-8.
+        encoding: "UTF-8".to_string(),
+    })
+}
+
+fn open(path: &Path) -> Result<Epub, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open file: {e}"))?;
+    ZipArchive::new(BufReader::new(file)).map_err(|e| format!("Failed to read EPUB: {e}"))
+}
+
+fn read_entry(epub: &mut Epub, name: &str) -> Result<String, String> {
+    let mut data = Vec::new();
+    epub.by_name(name)
+        .map_err(|e| format!("Failed to read EPUB: {e}"))?
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read EPUB: {e}"))?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn read_package(epub: &mut Epub) -> Result<Package, String> {
+    let container = read_entry(epub, CONTAINER_PATH)?;
+    let opf_path = find_rootfile(&container).ok_or("Failed to read EPUB: no package document")?;
+    let opf = read_entry(epub, &opf_path)?;
+    Ok(parse_package(&opf, &opf_path))
+}
+
+fn find_rootfile(container: &str) -> Option<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e)) if e.local_name().as_ref() == "rootfile" => {
+                if let Some(full_path) = attribute(&e, "full-path") {
+                    return Some(full_path);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+fn parse_package(opf: &str, opf_path: &str) -> Package {
+    let mut package = Package::default();
+    // Manifest item id -> full entry path.
+    let mut manifest = HashMap::new();
+    let mut spine_ids = Vec::new();
+    let mut ncx_id = None;
+    let mut title: Option<String> = None;
+    let mut in_title = false;
+
+    let mut reader = Reader::from_str(opf);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e)) => match e.local_name().as_ref() {
+                "title" if package.title.is_none() => {
+                    in_title = true;
+                    title = Some(String::new());
+                }
+                "item" => {
+                    if let (Some(id), Some(href)) = (attribute(&e, "id"), attribute(&e, "href")) {
+                        let properties = attribute(&e, "properties").unwrap_or_default();
+                        let media_type = attribute(&e, "media-type").unwrap_or_default();
+                        let full_path = resolve_href(opf_path, &href);
+                        if properties.split_whitespace().any(|p| p == "nav") {
+                            package.nav = Some(full_path.clone());
+                        }
+                        if media_type == "application/x-dtbncx+xml" && ncx_id.is_none() {
+                            ncx_id = Some(id.clone());
+                        }
+                        manifest.insert(id, full_path);
+                    }
+                }
+                "spine" => {
+                    if let Some(toc) = attribute(&e, "toc") {
+                        ncx_id = Some(toc);
+                    }
+                }
+                "itemref" => {
+                    let linear = attribute(&e, "linear");
+                    if let Some(idref) = attribute(&e, "idref")
+                        && linear.as_deref() != Some("no")
+                    {
+                        spine_ids.push(idref);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Text(text)) if in_title => {
+                if let Some(title) = title.as_mut() {
+                    title.push_str(&text.xml10_content());
+                }
+            }
+            Ok(Event::GeneralRef(entity)) if in_title => {
+                if let Some(title) = title.as_mut()
+                    && let Some(resolved) = resolve_entity(&entity)
+                {
+                    title.push_str(&resolved);
+                }
+            }
+            Ok(Event::End(e)) if in_title && e.local_name().as_ref() == "title" => {
+                in_title = false;
+                package.title = title
+                    .take()
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty());
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    package.ncx = ncx_id.and_then(|id| manifest.get(&id).cloned());
+    package.spine = spine_ids
+        .iter()
+        .filter_map(|id| manifest.get(id).cloned())
+        .collect();
+    package
+}
+
+/// TOC entries from the `toc` nav of an EPUB 3 nav document, nested by `ol`.
+fn parse_nav(xhtml: &str, nav_path: &str) -> Vec<TocEntry> {
+    let mut toc = Vec::new();
+    let mut nav_depth = 0usize;
+    let mut list_depth = 0u8;
+    let mut in_toc = false;
+    let mut link: Option<(String, String)> = None;
+
+    let mut reader = Reader::from_str(xhtml);
+    reader.config_mut().check_end_names = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => match e.local_name().as_ref() {
+                "nav" => {
+                    nav_depth += 1;
+                    if nav_depth == 1 {
+                        in_toc = attribute(&e, "type")
+                            .is_some_and(|t| t.split_whitespace().any(|t| t == "toc"));
+                    }
+                }
+                "ol" if in_toc => list_depth = list_depth.saturating_add(1),
+                "a" if in_toc => {
+                    link = attribute(&e, "href")
+                        .map(|href| (String::new(), resolve_href(nav_path, &href)));
+                }
+                _ => {}
+            },
+            Ok(Event::Text(text)) => {
+                if let Some((title, _)) = link.as_mut() {
+                    title.push_str(&text.xml10_content());
+                }
+            }
+            Ok(Event::GeneralRef(entity)) => {
+                if let Some((title, _)) = link.as_mut()
+                    && let Some(resolved) = resolve_entity(&entity)
+                {
+                    title.push_str(&resolved);
+                }
+            }
+            Ok(Event::End(e)) => match e.local_name().as_ref() {
+                "nav" => {
+                    nav_depth = nav_depth.saturating_sub(1);
+                    if nav_depth == 0 {
+                        in_toc = false;
+                    }
+                }
+                "ol" if in_toc => list_depth = list_depth.saturating_sub(1),
+                "a" => {
+                    if let Some((title, target)) = link.take() {
+                        push_toc_entry(&mut toc, &title, target, list_depth.saturating_sub(1));
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    toc
+}
+
+/// TOC entries from the `navMap` of an EPUB 2 NCX, in document order and
+/// nested by `navPoint`.
+fn parse_ncx(xml: &str, ncx_path: &str) -> Vec<TocEntry> {
+    let mut toc = Vec::new();
+    let mut label = String::new();
+    let mut in_text = false;
+    let mut point_depth = 0u8;
+
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e)) => match e.local_name().as_ref() {
+                "navPoint" => {
+                    point_depth = point_depth.saturating_add(1);
+                    label.clear();
+                }
+                "text" => in_text = true,
+                "content" => {
+                    if let Some(src) = attribute(&e, "src") {
+                        let target = resolve_href(ncx_path, &src);
+                        push_toc_entry(&mut toc, &label, target, point_depth.saturating_sub(1));
+                        label.clear();
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Text(text)) if in_text => label.push_str(&text.xml10_content()),
+            Ok(Event::GeneralRef(entity)) if in_text => {
+                if let Some(resolved) = resolve_entity(&entity) {
+                    label.push_str(&resolved);
+                }
+            }
+            Ok(Event::End(e)) => match e.local_name().as_ref() {
+                "text" => in_text = false,
+                "navPoint" => point_depth = point_depth.saturating_sub(1),
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    toc
+}
+
+fn push_toc_entry(toc: &mut Vec<TocEntry>, title: &str, target: String, level: u8) {
+    let title = collapse_whitespace(title);
+    if !title.is_empty() {
+        toc.push((title, target, level));
+    }
+}
+
+/// Text lines of one XHTML document plus the line offset of each element
+/// `id`, so fragment targets can be located.
+fn extract_lines(xhtml: &str) -> (Vec<String>, Vec<(String, usize)>) {
+    let mut lines = Vec::new();
+    let mut anchors = Vec::new();
+    let mut current = String::new();
+    let mut skip_depth = 0usize;
+
+    let flush = |current: &mut String, lines: &mut Vec<String>| {
+        let line = collapse_whitespace(current);
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        current.clear();
+    };
+
+    let mut reader = Reader::from_str(xhtml);
+    reader.config_mut().check_end_names = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = e.local_name();
+                if skip_depth > 0 || SKIPPED_ELEMENTS.contains(&name.as_ref()) {
+                    skip_depth += 1;
+                    continue;
+                }
+                if BLOCK_ELEMENTS.contains(&name.as_ref()) {
+                    flush(&mut current, &mut lines);
+                }
+                if let Some(id) = attribute(&e, "id") {
+                    anchors.push((id, lines.len()));
+                }
+            }
+            Ok(Event::Empty(e)) if skip_depth == 0 => {
+                let name = e.local_name();
+                if name.as_ref() == "br" || BLOCK_ELEMENTS.contains(&name.as_ref()) {
+                    flush(&mut current, &mut lines);
+                }
+                if let Some(id) = attribute(&e, "id") {
+                    anchors.push((id, lines.len()));
+                }
+            }
+            Ok(Event::End(e)) => {
+                if skip_depth > 0 {
+                    skip_depth -= 1;
+                } else if BLOCK_ELEMENTS.contains(&e.local_name().as_ref()) {
+                    flush(&mut current, &mut lines);
+                }
+            }
+            Ok(Event::Text(text)) if skip_depth == 0 => current.push_str(&text.xml10_content()),
+            Ok(Event::CData(text)) if skip_depth == 0 => current.push_str(&text.xml10_content()),
+            Ok(Event::GeneralRef(entity)) if skip_depth == 0 => {
+                if let Some(resolved) = resolve_entity(&entity) {
+                    current.push_str(&resolved);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    flush(&mut current, &mut lines);
+    (lines, anchors)
+}
+
+/// Character references, XML's predefined entities and `&nbsp;`, which
+/// XHTML content uses freely without declaring it.
+fn resolve_entity(entity: &BytesRef) -> Option<String> {
+    if let Ok(Some(c)) = entity.resolve_char_ref() {
+        return Some(c.to_string());
+    }
+    let name = entity.xml10_content();
+    match resolve_predefined_entity(&name) {
+        Some(resolved) => Some(resolved.to_string()),
+        None => (name == "nbsp").then(|| " ".to_string()),
+    }
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.local_name().as_ref() == name)
+        .and_then(|attr| {
+            attr.normalized_value(XmlVersion::Implicit1_0)
+                .ok()
+                .map(|value| value.into_owned())
+        })
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Resolve an href relative to the archive entry that contains it. Hrefs are
+/// URLs, so they are percent-decoded; the fragment is kept.
+fn resolve_href(base_entry: &str, href: &str) -> String {
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+    let path = percent_decode_str(path).decode_utf8_lossy();
+
+    let mut parts: Vec<&str> = base_entry.split('/').collect();
+    parts.pop();
+    if path.is_empty() {
+        parts.push(base_entry.rsplit('/').next().unwrap_or(base_entry));
+    }
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    let resolved = parts.join("/");
+    match fragment {
+        Some(fragment) => format!("{resolved}#{fragment}"),
+        None => resolved,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::scanner::archive::tests::write_zip;
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+    /// Write a two-chapter EPUB whose second chapter nests under the first.
+    /// With `nav` the TOC comes from an EPUB 3 nav document, otherwise from an
+    /// EPUB 2 NCX.
+    pub(crate) fn write_epub(path: &Path, title: Option<&str>, nav: bool) {
+        let title = title
+            .map(|title| format!("<dc:title>{title}</dc:title>"))
+            .unwrap_or_default();
+        let toc_item = if nav {
+            r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#
+        } else {
+            r#"<item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#
+        };
+        let opf = format!(
+            r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">{title}</metadata>
+  <manifest>
+    {toc_item}
+    <item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c1" href="Text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="Text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="style" href="style.css" media-type="text/css"/>
+  </manifest>
+  <spine>
+    <itemref idref="cover" linear="no"/>
+    <itemref idref="c1"/>
+    <itemref idref="c2"/>
+    <itemref idref="missing"/>
+  </spine>
+</package>"#
+        );
+        let nav_doc = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type="landmarks"><ol><li><a href="Text/cover.xhtml">Cover</a></li></ol></nav>
+  <nav epub:type="toc"><ol>
+    <li><a href="Text/chapter%201.xhtml">第一章 开始</a>
+      <ol><li><a href="Text/chapter2.xhtml#part">第二章 &amp; 继续</a></li></ol>
+    </li>
+    <li><a href="Text/missing.xhtml">Missing</a></li>
+  </ol></nav>
+</body>
+</html>"#;
+        let ncx = r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/">
+  <navMap>
+    <navPoint id="p1"><navLabel><text>第一章 开始</text></navLabel><content src="Text/chapter%201.xhtml"/>
+      <navPoint id="p2"><navLabel><text>第二章 &amp; 继续</text></navLabel><content src="Text/chapter2.xhtml#part"/></navPoint>
+    </navPoint>
+  </navMap>
+</ncx>"#;
+        let chapter_1 = r#"<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>ignored</title><style>p { color: red; }</style></head>
+<body>
+  <h1>第一章 开始</h1>
+  <p>正文&nbsp;一<br/>换行</p>
+  <p>   </p>
+  <div><p>嵌套&#x4E00;</p></div>
+</body>
+</html>"#;
+        let chapter_2 = r#"<html xmlns="http://www.w3.org/1999/xhtml">
+<body>
+  <p>前言</p>
+  <h2 id="part">第二章 &amp; 继续</h2>
+  <p>正文<em>二</em></p>
+</body>
+</html>"#;
+
+        write_zip(
+            path,
+            &[
+                ("mimetype", b"application/epub+zip"),
+                (CONTAINER_PATH, CONTAINER.as_bytes()),
+                ("OEBPS/content.opf", opf.as_bytes()),
+                ("OEBPS/nav.xhtml", nav_doc.as_bytes()),
+                ("OEBPS/toc.ncx", ncx.as_bytes()),
+                (
+                    "OEBPS/Text/cover.xhtml",
+                    b"<html><body><p>Cover</p></body></html>",
+                ),
+                ("OEBPS/Text/chapter 1.xhtml", chapter_1.as_bytes()),
+                ("OEBPS/Text/chapter2.xhtml", chapter_2.as_bytes()),
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_epub_flattens_spine_and_maps_nested_nav_and_ncx_targets() {
+        let dir = tempfile::tempdir().expect("create epub dir");
+        for nav in [true, false] {
+            let path = dir.path().join(format!("book-{nav}.epub"));
+            write_epub(&path, Some("书名"), nav);
+
+            let content = parse_epub(&path).expect("parse epub");
+
+            assert_eq!(
+                content.lines,
+                vec![
+                    "第一章 开始",
+                    "正文 一",
+                    "换行",
+                    "嵌套一",
+                    "前言",
+                    "第二章 & 继续",
+                    "正文二",
+                ]
+            );
+            assert_eq!(
+                content
+                    .chapters
+                    .iter()
+                    .map(|chapter| (
+                        chapter.title.as_str(),
+                        chapter.line_index,
+                        chapter.level,
+                        chapter.parent
+                    ))
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("第一章 开始", 0, 0, None),
+                    ("第二章 & 继续", 5, 1, Some(0))
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn reads_title_from_opf_metadata() {
+        let dir = tempfile::tempdir().expect("create epub dir");
+        let titled = dir.path().join("titled.epub");
+        write_epub(&titled, Some(" 书名 &amp; 副题 "), true);
+        let untitled = dir.path().join("untitled.epub");
+        write_epub(&untitled, None, true);
+
+        assert_eq!(read_title(&titled).as_deref(), Some("书名 & 副题"));
+        assert_eq!(read_title(&untitled), None);
+        assert_eq!(read_title(&dir.path().join("missing.epub")), None);
+    }
+
+    #[test]
+    fn parse_epub_rejects_missing_and_malformed_files() {
+        let dir = tempfile::tempdir().expect("create epub dir");
+        let missing =
+            parse_epub(&dir.path().join("missing.epub")).expect_err("missing epub should fail");
+        assert!(missing.starts_with("Failed to open file:"));
+
+        let no_container = dir.path().join("no-container.epub");
+        write_zip(&no_container, &[("mimetype", b"application/epub+zip")]);
+        let error = parse_epub(&no_container).expect_err("epub without container should fail");
+        assert!(error.starts_with("Failed to read EPUB:"));
+    }
+
+    #[test]
+    fn resolves_hrefs_relative_to_their_document() {
+        assert_eq!(
+            resolve_href("OEBPS/content.opf", "Text/a.xhtml"),
+            "OEBPS/Text/a.xhtml"
+        );
+        assert_eq!(
+            resolve_href("OEBPS/Text/nav.xhtml", "../Text/b%20c.xhtml#x"),
+            "OEBPS/Text/b c.xhtml#x"
+        );
+        assert_eq!(resolve_href("content.opf", "./a.xhtml"), "a.xhtml");
+        assert_eq!(
+            resolve_href("OEBPS/toc.xhtml", "#top"),
+            "OEBPS/toc.xhtml#top"
+        );
+        assert!(is_epub_file(Path::new("Book.EPUB")));
+        assert!(!is_epub_file(Path::new("Book.txt")));
+    }
+}
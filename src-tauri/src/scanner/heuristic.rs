@@ -0,0 +1,373 @@
+//! Fallback chapter detection for text books without recognisable headings.
+//!
+//! When the library's chapter rules find nothing, or far too little for the
+//! book's length, every line is scored on how much it looks like a heading:
+//! short, set apart by blank lines, led by a numeral, centered, and part of a
+//! repeating numbered sequence. Lines scoring high enough become suggested
+//! chapters carrying that score as their confidence.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::models::Chapter;
+
+use super::paragraph::Paragraph;
+
+/// Rule chapters are "too little" when there are fewer than this many, or
+/// fewer than one per `LINES_PER_CHAPTER` lines.
+const MIN_RULE_CHAPTERS: usize = 2;
+const LINES_PER_CHAPTER: usize = 500;
+/// A single suggestion is not a table of contents.
+const MIN_SUGGESTIONS: usize = 2;
+/// Lowest score that is still suggested as a chapter.
+const MIN_CONFIDENCE: f32 = 0.6;
+/// Headings closer than this many lines are one heading; only the
+/// best-scoring line is kept. Numbered lines this close with the same
+/// numbering are a list, and none of them is kept.
+const MIN_GAP: usize = 3;
+/// Longer lines are prose, not headings.
+const MAX_HEADING_CHARS: usize = 40;
+/// Leading whitespace of a centered line, in columns. Wider than the usual
+/// two full-width spaces of paragraph indentation.
+const CENTERED_INDENT: usize = 6;
+
+/// A leading numeral, optionally after a short word ("Episode 3"), followed by
+/// punctuation, whitespace or the end of the line.
+static NUMBERED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:(?P<prefix>[A-Za-z]{2,12})\s*)?(?P<num>\d{1,4}|[一二三四五六七八九十百千零〇]{1,6}|[IVXLC]{1,7})(?P<sep>[.、．:：)）]|\s|$)",
+    )
+    .expect("Invalid numbered heading pattern")
+});
+
+/// Add suggested chapters when the rule pass found too few for a book of
+/// `lines.len()` lines. Rule chapters are kept as they are; suggestions fill
+/// in the other lines at chapter level, under any volume before them.
+pub fn with_suggestions(chapters: Vec<Chapter>, lines: &[Paragraph]) -> Vec<Chapter> {
+    if chapters.len() >= MIN_RULE_CHAPTERS && chapters.len() * LINES_PER_CHAPTER >= lines.len() {
+        return chapters;
+    }
+
+    let suggestions = suggest_chapters(lines);
+    if suggestions.len() < MIN_SUGGESTIONS {
+        return chapters;
+    }
+
+    let mut merged = chapters;
+    for suggestion in suggestions {
+        if !merged
+            .iter()
+            .any(|chapter| chapter.line_index.abs_diff(suggestion.line_index) < MIN_GAP)
+        {
+            merged.push(suggestion);
+        }
+    }
+    merged.sort_by_key(|chapter| chapter.line_index);
+    merged
+}
+
+struct Numeral {
+    shape: String,
+    value: Option<u32>,
+}
+
+/// Score every line and keep the likely headings.
+pub fn suggest_chapters(lines: &[Paragraph]) -> Vec<Chapter> {
+    let numerals: Vec<Option<Numeral>> = lines.iter().map(|line| numeral(&line.text)).collect();
+
+    let mut shape_counts: HashMap<&str, usize> = HashMap::new();
+    for numeral in numerals.iter().flatten() {
+        *shape_counts.entry(numeral.shape.as_str()).or_default() += 1;
+    }
+
+    let mut previous_value: HashMap<&str, u32> = HashMap::new();
+    let mut suggestions: Vec<Chapter> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if in_list(&numerals, index) {
+            continue;
+        }
+        let Some(mut score) = layout_score(line) else {
+            continue;
+        };
+
+        if let Some(numeral) = &numerals[index] {
+            score += 0.3;
+            if shape_counts[numeral.shape.as_str()] >= 3 {
+                score += 0.1;
+            }
+            if let Some(value) = numeral.value {
+                if previous_value
+                    .get(numeral.shape.as_str())
+                    .is_some_and(|previous| value == previous + 1)
+                {
+                    score += 0.15;
+                }
+                previous_value.insert(numeral.shape.as_str(), value);
+            }
+        }
+
+        if score < MIN_CONFIDENCE {
+            continue;
+        }
+
+        let confidence = (score.min(1.0) * 100.0).round() / 100.0;
+        let chapter = Chapter {
+            title: line.text.trim().to_string(),
+            line_index: index,
+            confidence: Some(confidence),
+            level: 1,
+            parent: None,
+        };
+        match suggestions.last_mut() {
+            Some(last) if index - last.line_index < MIN_GAP => {
+                if confidence > last.confidence.unwrap_or_default() {
+                    *last = chapter;
+                }
+            }
+            _ => suggestions.push(chapter),
+        }
+    }
+    suggestions
+}
+
+/// Whether the line at `index` is numbered like another line fewer than
+/// `MIN_GAP` lines away, making both items of a list.
+fn in_list(numerals: &[Option<Numeral>], index: usize) -> bool {
+    let Some(numeral) = &numerals[index] else {
+        return false;
+    };
+    let nearby = index.saturating_sub(MIN_GAP - 1)..numerals.len().min(index + MIN_GAP);
+    nearby.filter(|&other| other != index).any(|other| {
+        numerals[other]
+            .as_ref()
+            .is_some_and(|other| other.shape == numeral.shape)
+    })
+}
+
+/// Score from length, spacing, centering and ending punctuation, or `None`
+/// for lines that cannot be headings.
+fn layout_score(line: &Paragraph) -> Option<f32> {
+    let trimmed = line.text.trim();
+    let chars = trimmed.chars().count();
+    if chars == 0 || chars > MAX_HEADING_CHARS || trimmed.ends_with([',', '，', ';', '；', '、'])
+    {
+        return None;
+    }
+
+    let mut score = if chars <= 20 { 0.2 } else { 0.1 };
+    if line.blank_before {
+        score += 0.15;
+    }
+    if line.blank_after {
+        score += 0.15;
+    }
+    if indent_columns(&line.text) >= CENTERED_INDENT {
+        score += 0.15;
+    }
+    if trimmed
+        .trim_end_matches(['”', '’', '"', '」', '』'])
+        .ends_with(['。', '！', '？', '.', '!', '?', '…'])
+        && !NUMBERED.is_match(trimmed.trim_end_matches('.'))
+    {
+        score -= 0.2;
+    }
+    Some(score)
+}
+
+fn indent_columns(text: &str) -> usize {
+    text.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\u{3000}' { 2 } else { 1 })
+        .sum()
+}
+
+/// The numeral leading `text`, with a shape (prefix word, numeral script and
+/// separator) that repeats across a book's headings.
+fn numeral(text: &str) -> Option<Numeral> {
+    let captures = NUMBERED.captures(text.trim())?;
+    let num = captures.name("num")?.as_str();
+    let (script, value) = if num.starts_with(|c: char| c.is_ascii_digit()) {
+        ("arabic", num.parse().ok())
+    } else if num.starts_with(|c: char| c.is_ascii_uppercase()) {
+        ("roman", roman_value(num))
+    } else {
+        ("chinese", chinese_value(num))
+    };
+    let prefix = captures
+        .name("prefix")
+        .map_or(String::new(), |p| p.as_str().to_lowercase());
+    let sep = captures.name("sep").map_or("", |s| s.as_str().trim());
+    Some(Numeral {
+        shape: format!("{prefix}|{script}|{sep}"),
+        value,
+    })
+}
+
+fn roman_value(num: &str) -> Option<u32> {
+    let digits: Vec<u32> = num
+        .chars()
+        .map(|c| match c {
+            'I' => 1,
+            'V' => 5,
+            'X' => 10,
+            'L' => 50,
+            _ => 100,
+        })
+        .collect();
+    let value = digits
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| {
+            if digits.get(i + 1).is_some_and(|&next| next > d) {
+                -(d as i64)
+            } else {
+                d as i64
+            }
+        })
+        .sum::<i64>();
+    u32::try_from(value).ok().filter(|&v| v > 0)
+}
+
+fn chinese_value(num: &str) -> Option<u32> {
+    let mut total = 0;
+    let mut digit = 0;
+    for c in num.chars() {
+        let unit = match c {
+            '十' => 10,
+            '百' => 100,
+            '千' => 1000,
+            _ => {
+                digit = "零一二三四五六七八九"
+                    .chars()
+                    .position(|d| d == c)
+                    .unwrap_or(0) as u32;
+                continue;
+            }
+        };
+        total += digit.max(1) * unit;
+        digit = 0;
+    }
+    Some(total + digit).filter(|&v| v > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::paragraph::{ParagraphOptions, paragraphs};
+
+    fn source_lines(text: &str) -> Vec<Paragraph> {
+        paragraphs(text, &ParagraphOptions::default())
+    }
+
+    fn prose(sentences: usize) -> String {
+        "他们在这里说话，我们没有看见那个人，于是继续往前走。\n".repeat(sentences)
+    }
+
+    fn titles(chapters: &[Chapter]) -> Vec<(&str, usize)> {
+        chapters
+            .iter()
+            .map(|chapter| (chapter.title.as_str(), chapter.line_index))
+            .collect()
+    }
+
+    #[test]
+    fn suggests_numbered_headings_set_apart_by_blank_lines() {
+        let text = format!(
+            "1.\n\n{}\n2. 重逢\n\n{}\n3.\n\n{}",
+            prose(3),
+            prose(3),
+            prose(3)
+        );
+        let lines = source_lines(&text);
+        let chapters = suggest_chapters(&lines);
+
+        assert_eq!(
+            titles(&chapters),
+            vec![("1.", 0), ("2. 重逢", 4), ("3.", 8)]
+        );
+        let confidences: Vec<f32> = chapters.iter().filter_map(|c| c.confidence).collect();
+        assert!(confidences.iter().all(|&c| (0.6..=1.0).contains(&c)));
+        assert!(confidences[1] > confidences[0], "{confidences:?}");
+    }
+
+    #[test]
+    fn suggests_centered_lines_and_chinese_and_roman_sequences() {
+        let text = format!(
+            "        风起\n\n{}\n        云涌\n\n{}\n一、出发\n{}二、归来\n{}",
+            prose(4),
+            prose(4),
+            prose(4),
+            prose(4)
+        );
+        let lines = source_lines(&text);
+        assert_eq!(
+            titles(&suggest_chapters(&lines)),
+            vec![("风起", 0), ("云涌", 5), ("一、出发", 10), ("二、归来", 15)]
+        );
+
+        let roman = "Episode IV\nIt was dark.\nNobody came.\nEpisode V\nIt was light.\n\
+                     Everyone came.\nEpisode VI\n";
+        let chapters = suggest_chapters(&source_lines(roman));
+        assert_eq!(
+            titles(&chapters),
+            vec![("Episode IV", 0), ("Episode V", 3), ("Episode VI", 6)]
+        );
+    }
+
+    #[test]
+    fn skips_prose_lists_and_sentences() {
+        let text = format!(
+            "这是一行非常非常长的正文内容，远远超过了一个章节标题应有的长度，即使两边都有空行也不应被识别为标题。\n\n\
+             他笑了。\n\n好，\n\n1. 买菜\n2. 做饭\n3. 洗碗\n{}",
+            prose(2)
+        );
+        let lines = source_lines(&text);
+        assert!(suggest_chapters(&lines).is_empty());
+    }
+
+    #[test]
+    fn with_suggestions_only_fills_in_when_rules_find_too_little() {
+        let text = format!(
+            "序章\n\n{}\n1.\n\n{}\n2.\n\n{}",
+            prose(2),
+            prose(2),
+            prose(2)
+        );
+        let lines = source_lines(&text);
+        let rule_chapter = || Chapter {
+            title: "序章".to_string(),
+            line_index: 0,
+            confidence: None,
+            level: 0,
+            parent: None,
+        };
+
+        let merged = with_suggestions(vec![rule_chapter()], &lines);
+        assert_eq!(titles(&merged), vec![("序章", 0), ("1.", 3), ("2.", 6)]);
+        assert_eq!(merged[0].confidence, None);
+
+        let many: Vec<Chapter> = (0..lines.len())
+            .map(|line_index| Chapter {
+                line_index,
+                ..rule_chapter()
+            })
+            .collect();
+        assert_eq!(with_suggestions(many, &lines).len(), lines.len());
+
+        let single = source_lines("1.\n\n正文。\n");
+        assert!(with_suggestions(Vec::new(), &single).is_empty());
+        assert!(with_suggestions(Vec::new(), &[]).is_empty());
+    }
+
+    #[test]
+    fn reads_numeral_values() {
+        assert_eq!(roman_value("XIV"), Some(14));
+        assert_eq!(roman_value("CL"), Some(150));
+        assert_eq!(chinese_value("二十三"), Some(23));
+        assert_eq!(chinese_value("十"), Some(10));
+        assert_eq!(chinese_value("一百零五"), Some(105));
+        assert_eq!(chinese_value("零"), None);
+    }
+}
@@ -1,3 +1,68 @@
+pub mod archive;
 pub mod book;
+pub mod book_index;
+pub mod book_search;
+pub mod chapter;
 pub mod comic;
+pub mod encoding;
+pub mod epub;
+pub mod heuristic;
+pub mod paragraph;
 pub mod utils;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::models::{Author, Book, Comic};
+
+/// Error of a scan or index stopped through [`ScanProgress::cancel`].
+pub const CANCELLED: &str = "Cancelled";
+
+/// A scanned entry and the modification time (ms) of its file or directory
+/// when it was read. A rescan reuses the entry while that time is unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamped<T> {
+    pub entry: T,
+    pub mtime: u64,
+}
+
+/// Everything one library scan found. Authors carry no nested books; each
+/// book points at its author instead, as in the catalog.
+#[derive(Debug, Default)]
+pub struct LibraryScan {
+    pub comics: Vec<Stamped<Comic>>,
+    pub authors: Vec<Stamped<Author>>,
+    pub books: Vec<Stamped<Book>>,
+}
+
+/// Counters a running scan reports into and the flag that stops it, shared
+/// with whoever started the scan.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    /// Top-level comics and books looked at, reused or not.
+    pub scanned: AtomicU64,
+    /// Cover thumbnails written.
+    pub thumbnails: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl ScanProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Err(CANCELLED)` once cancelled, for `?` between steps of a job.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn count_scanned(&self) {
+        self.scanned.fetch_add(1, Ordering::Relaxed);
+    }
+}
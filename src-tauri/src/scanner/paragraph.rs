@@ -0,0 +1,292 @@
+//! Paragraph normalisation for text books.
+//!
+//! Physical lines of a TXT file are not always paragraphs: some books are
+//! hard-wrapped at a fixed width, some have no line breaks at all, and most
+//! Chinese ones indent paragraphs with full-width spaces. Each fix is opt-in
+//! per library; with every option off the reader gets the non-blank source
+//! lines unchanged.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParagraphOptions {
+    /// Join lines broken at the book's wrap width back into paragraphs.
+    pub rejoin_wrapped: bool,
+    /// Break over-long lines at sentence punctuation.
+    pub split_long_lines: bool,
+    /// Remove leading (full-width) indentation.
+    pub strip_indent: bool,
+    /// Keep one empty paragraph wherever the source has blank lines.
+    pub keep_blank_lines: bool,
+}
+
+/// One reader line and whether blank lines surround it in the source.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Paragraph {
+    pub text: String,
+    pub blank_before: bool,
+    pub blank_after: bool,
+}
+
+/// Lines shorter than this never set the wrap width.
+const MIN_WRAP_WIDTH: usize = 20;
+/// A line at least this percentage of the wrap width was broken by wrapping.
+const WRAPPED_PERCENT: usize = 85;
+/// Lines longer than this are split into sentences...
+const LONG_LINE_CHARS: usize = 1000;
+/// ...grouped into paragraphs of at least this many characters.
+const SPLIT_TARGET_CHARS: usize = 300;
+
+const SENTENCE_ENDS: &[char] = &['。', '！', '？', '!', '?', '…', '.'];
+const CLOSING_QUOTES: &[char] = &['”', '’', '"', '\'', '」', '』', '）', ')'];
+
+/// Normalise `text` into reader paragraphs.
+pub fn paragraphs(text: &str, options: &ParagraphOptions) -> Vec<Paragraph> {
+    let wrap_width = options.rejoin_wrapped.then(|| wrap_width(text)).flatten();
+
+    let mut out: Vec<Paragraph> = Vec::new();
+    let mut blank_before = true;
+    let mut joinable = false;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if let Some(last) = out.last_mut() {
+                last.blank_after = true;
+                if options.keep_blank_lines && !last.text.is_empty() {
+                    out.push(Paragraph {
+                        text: String::new(),
+                        blank_before: true,
+                        blank_after: true,
+                    });
+                }
+            }
+            blank_before = true;
+            joinable = false;
+            continue;
+        }
+
+        let content = if options.strip_indent {
+            line.trim_start()
+        } else {
+            line
+        };
+        if joinable
+            && !starts_indented(line)
+            && let Some(last) = out.last_mut()
+        {
+            join(&mut last.text, content.trim_end());
+        } else {
+            out.push(Paragraph {
+                text: content.to_string(),
+                blank_before,
+                blank_after: false,
+            });
+        }
+
+        joinable = wrap_width.is_some_and(|width| is_wrapped(line, width));
+        blank_before = false;
+    }
+
+    if out.last().is_some_and(|last| last.text.is_empty()) {
+        out.pop();
+        if let Some(last) = out.last_mut() {
+            last.blank_after = true;
+        }
+    }
+
+    if options.split_long_lines {
+        out = out.into_iter().flat_map(split_long).collect();
+    }
+    out
+}
+
+/// The most common length among longer lines: the column a hard-wrapped book
+/// was broken at.
+fn wrap_width(text: &str) -> Option<usize> {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for line in text.lines() {
+        let len = line.trim_end().chars().count();
+        if len >= MIN_WRAP_WIDTH {
+            *counts.entry(len).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(len, count)| (count, len))
+        .map(|(len, _)| len)
+}
+
+/// A line filled to the wrap width that does not end a sentence continues on
+/// the next line.
+fn is_wrapped(line: &str, width: usize) -> bool {
+    let line = line.trim_end();
+    line.chars().count() * 100 >= width * WRAPPED_PERCENT && !ends_sentence(line)
+}
+
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end_matches(CLOSING_QUOTES)
+        .ends_with(SENTENCE_ENDS)
+}
+
+fn starts_indented(line: &str) -> bool {
+    line.starts_with(char::is_whitespace)
+}
+
+/// Append a wrapped continuation, restoring the space a wrap between two
+/// words removed.
+fn join(paragraph: &mut String, next: &str) {
+    let next = next.trim_start();
+    if paragraph.ends_with(|c: char| c.is_ascii_alphanumeric() || c.is_ascii_punctuation())
+        && next.starts_with(|c: char| c.is_ascii_alphanumeric())
+    {
+        paragraph.push(' ');
+    }
+    paragraph.push_str(next);
+}
+
+/// Split a paragraph over `LONG_LINE_CHARS` after sentence ends, keeping
+/// pieces of at least `SPLIT_TARGET_CHARS`.
+fn split_long(paragraph: Paragraph) -> Vec<Paragraph> {
+    if paragraph.text.chars().count() <= LONG_LINE_CHARS {
+        return vec![paragraph];
+    }
+
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    let mut piece_chars = 0;
+    let mut chars = paragraph.text.chars().peekable();
+    while let Some(c) = chars.next() {
+        piece.push(c);
+        piece_chars += 1;
+        if !SENTENCE_ENDS.contains(&c) {
+            continue;
+        }
+        while let Some(&quote) = chars.peek().filter(|c| CLOSING_QUOTES.contains(c)) {
+            piece.push(quote);
+            chars.next();
+        }
+        // An ASCII full stop only ends a sentence before whitespace.
+        if c == '.' && chars.peek().is_some_and(|next| !next.is_whitespace()) {
+            continue;
+        }
+        if piece_chars >= SPLIT_TARGET_CHARS {
+            pieces.push(std::mem::take(&mut piece).trim().to_string());
+            piece_chars = 0;
+        }
+    }
+    if !piece.trim().is_empty() {
+        pieces.push(piece.trim().to_string());
+    }
+
+    let last = pieces.len() - 1;
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, text)| Paragraph {
+            text,
+            blank_before: index == 0 && paragraph.blank_before,
+            blank_after: index == last && paragraph.blank_after,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(paragraphs: &[Paragraph]) -> Vec<&str> {
+        paragraphs.iter().map(|p| p.text.as_str()).collect()
+    }
+
+    #[test]
+    fn default_options_keep_non_blank_lines_and_note_surrounding_blanks() {
+        let paragraphs = paragraphs(
+            "\n　　第一行\n第二行\n\n\n第三行\n",
+            &ParagraphOptions::default(),
+        );
+
+        assert_eq!(texts(&paragraphs), vec!["　　第一行", "第二行", "第三行"]);
+        assert_eq!(
+            paragraphs
+                .iter()
+                .map(|p| (p.blank_before, p.blank_after))
+                .collect::<Vec<_>>(),
+            vec![(true, false), (false, true), (true, false)]
+        );
+    }
+
+    #[test]
+    fn rejoins_hard_wrapped_lines_until_a_sentence_ends_or_indent() {
+        let text = [
+            "　　他们在这里说话我们没有看见",
+            "那个人于是继续往前走。",
+            "　　第二段落的内容一直很长长长",
+            "最后一行结束了",
+            "第一章",
+            "The quick brown fox jumps over",
+            "the lazy dog.",
+        ]
+        .join("\n");
+        let options = ParagraphOptions {
+            rejoin_wrapped: true,
+            strip_indent: true,
+            ..ParagraphOptions::default()
+        };
+
+        // The English line sets the wrap width, so the shorter lines before it
+        // are all paragraph ends.
+        assert_eq!(paragraphs(&text, &options).len(), 6);
+
+        let text = text
+            .replace("有看见", "有看见他们在这里说话")
+            .replace("内容一直很长长长", "内容一直很长长长他们在这里说话");
+        assert_eq!(
+            texts(&paragraphs(&text, &options)),
+            vec![
+                "他们在这里说话我们没有看见他们在这里说话那个人于是继续往前走。",
+                "第二段落的内容一直很长长长他们在这里说话最后一行结束了",
+                "第一章",
+                "The quick brown fox jumps over the lazy dog.",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_one_empty_paragraph_per_run_of_blank_lines() {
+        let options = ParagraphOptions {
+            keep_blank_lines: true,
+            ..ParagraphOptions::default()
+        };
+
+        let paragraphs = paragraphs("\n\n甲\n\n\n乙\n丙\n\n", &options);
+
+        assert_eq!(texts(&paragraphs), vec!["甲", "", "乙", "丙"]);
+        assert!(paragraphs[3].blank_after);
+    }
+
+    #[test]
+    fn splits_long_lines_after_sentences() {
+        let sentence = format!("{}。”", "字".repeat(149));
+        let english = format!("{}. ", "a.b".repeat(50));
+        let text = format!("{}{}\n短行\n", sentence.repeat(8), english.repeat(4));
+        let options = ParagraphOptions {
+            split_long_lines: true,
+            ..ParagraphOptions::default()
+        };
+
+        let paragraphs = paragraphs(&text, &options);
+
+        assert_eq!(
+            paragraphs
+                .iter()
+                .map(|p| p.text.chars().count())
+                .collect::<Vec<_>>(),
+            vec![302, 302, 302, 302, 303, 303, 2]
+        );
+        assert!(paragraphs[0].blank_before);
+        assert!(!paragraphs[1].blank_before);
+        assert_eq!(paragraphs[5].text, english.repeat(2).trim());
+    }
+}
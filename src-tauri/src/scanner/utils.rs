@@ -1,5 +1,5 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 use std::time::SystemTime;
 use tauri::AppHandle;
@@ -8,7 +8,7 @@ use tauri_plugin_opener::OpenerExt;
 use tracing::info;
 use uuid::Uuid;
 
-pub const BOOK_EXTENSIONS: &[&str] = &["txt"];
+pub const BOOK_EXTENSIONS: &[&str] = &["txt", "epub"];
 
 const NAMESPACE_STR: &str = "6ba7b810-9dad-11d1-80b4-00c04fd430c8";
 static NAMESPACE_UUID: LazyLock<Uuid> =
@@ -20,6 +20,17 @@ pub fn is_hidden(path: &Path) -> bool {
         .is_some_and(|name| name.starts_with('.'))
 }
 
+/// Paths of the visible entries directly under `path`: the comics of a comic
+/// library or the authors of a book library, before any type filtering.
+pub fn list_entries(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(path).map_err(|e| e.to_string())?;
+    Ok(entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| !is_hidden(path))
+        .collect())
+}
+
 pub fn is_book_file(path: &Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
@@ -61,6 +72,21 @@ pub fn get_created_time(metadata: &fs::Metadata) -> u64 {
         .unwrap_or_else(|_| current_time_millis())
 }
 
+/// Last modification time in ms, or now when unavailable so the entry is
+/// treated as changed.
+pub fn get_modified_time(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .map(|t| {
+            t.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+                .try_into()
+                .unwrap_or(u64::MAX)
+        })
+        .unwrap_or_else(|_| current_time_millis())
+}
+
 pub fn generate_uuid(input: &str) -> String {
     Uuid::new_v5(&NAMESPACE_UUID, input.as_bytes()).to_string()
 }
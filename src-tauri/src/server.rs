@@ -3,7 +3,8 @@
 //! Runs inside the Tauri process, sharing its `AppHandle`, cache dir, store and
 //! Rust scanning logic. Serves the built frontend and the API to any browser on
 //! the Wi-Fi at `http://<mac-lan-ip>:1430`. CPU-heavy scans run on the blocking
-//! pool so they don't stall the executor.
+//! pool so they don't stall the executor. Every client but the Mac itself
+//! pairs first, with the PIN shown in the tray (see `auth`).
 
 use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::path::{Path, PathBuf};
@@ -14,8 +15,9 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::Json;
 use axum::Router;
-use axum::extract::{ConnectInfo, Path as AxumPath, Query, Request, State};
+use axum::extract::{ConnectInfo, Extension, Path as AxumPath, Query, Request, State};
 use axum::http::{StatusCode, header};
+use axum::response::sse::{KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use serde::Deserialize;
@@ -25,10 +27,23 @@ use tower_http::compression::CompressionLayer;
 use tower_http::services::{ServeDir, ServeFile};
 use tracing::{error, info, warn};
 
+use crate::access::{self, Access};
+use crate::auth::{self, Caller, Device, Origin, PairOutcome};
+use crate::catalog::{self, CatalogPage, CatalogQuery};
 use crate::config;
-use crate::library::{self, Catalog};
-use crate::models::{ComicImage, FileTags};
-use crate::progress::{self, BookProgress, ComicProgress, ProgressDb, Snapshot};
+use crate::events::{self, Event};
+use crate::jobs::{self, Job, Task};
+use crate::library::{self, Catalog, CatalogChanges};
+use crate::models::{BookContent, BookLines, ComicImage, FileTags, LibrarySearchHit};
+use crate::policy::{self, Mode};
+use crate::profile::{self, Profile, ProfileId};
+use crate::progress::{
+    self, BookProgress, ComicProgress, ProgressChanges, ProgressDb, RemovedProgress, Snapshot,
+};
+use crate::scanner::book_index::{self, BookIndex};
+use crate::scanner::book_search;
+use crate::scanner::chapter::{self, ChapterRule};
+use crate::scanner::paragraph::ParagraphOptions;
 
 const PORT: u16 = 1430;
 
@@ -72,7 +87,40 @@ fn build_router(app: AppHandle, activity: Activity) -> Router {
     let index = format!("{DIST_DIR}/index.html");
     let static_files = ServeDir::new(DIST_DIR).not_found_service(ServeFile::new(index));
 
-    Router::new()
+    let router = Router::new()
+        .route("/api/auth/status", get(auth_status))
+        .route("/api/auth/pair", post(pair_device))
+        .route("/api/auth/logout", post(logout))
+        // Any device may list the profiles to pick one; only the Mac adds them.
+        .route(
+            "/api/profiles",
+            post(create_profile)
+                .route_layer(axum::middleware::from_fn(require_owner))
+                .get(list_profiles),
+        )
+        .route(
+            "/api/profiles/{id}",
+            axum::routing::put(rename_profile)
+                .delete(remove_profile)
+                .route_layer(axum::middleware::from_fn(require_owner)),
+        )
+        .route("/api/profiles/{id}/select", post(select_profile))
+        .route(
+            "/api/access-key",
+            post(enter_access_key).delete(clear_access_key),
+        )
+        .route("/api/devices", get(list_devices))
+        .route(
+            "/api/devices/{id}",
+            axum::routing::put(rename_device)
+                .delete(forget_device)
+                .route_layer(axum::middleware::from_fn(require_owner)),
+        )
+        .route(
+            "/api/devices/{id}/guest",
+            axum::routing::put(set_device_guest)
+                .route_layer(axum::middleware::from_fn(require_owner)),
+        )
         .route("/api/scan-comic-images", get(scan_comic_images))
         .route("/api/parse-book", get(parse_book))
         .route("/api/tag", post(set_tag))
@@ -81,16 +129,36 @@ fn build_router(app: AppHandle, activity: Activity) -> Router {
             get(store_get).put(store_put).delete(store_delete),
         )
         .route("/api/libraries", get(get_catalog))
+        .route("/api/libraries/changes", get(get_catalog_changes))
+        .route("/api/search", get(search_libraries))
+        .route("/api/catalog", get(query_catalog))
         .route(
             "/api/libraries/order",
             axum::routing::put(reorder_libraries),
         )
         .route("/api/library/{id}/refresh", post(refresh_library))
+        .route("/api/events", get(event_stream))
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/{id}", get(get_job))
+        .route("/api/jobs/{id}/cancel", post(cancel_job))
         .route("/api/library/{id}", axum::routing::delete(remove_library))
+        .route(
+            "/api/library/{id}/chapter-rules",
+            get(get_chapter_rules).put(put_chapter_rules),
+        )
+        .route(
+            "/api/library/{id}/paragraph-options",
+            get(get_paragraph_options).put(put_paragraph_options),
+        )
         .route("/api/comic/{id}/tags", post(set_comic_tags))
         .route("/api/book/{id}/tags", post(set_book_tags))
+        .route("/api/book/{id}/toc", get(get_book_toc))
+        .route("/api/book/{id}/lines", get(get_book_lines))
+        .route("/api/book/{id}/chapters/{index}", get(get_book_chapter))
+        .route("/api/book/{id}/search", get(search_book))
         .route("/api/reveal", post(reveal_path))
         .route("/api/progress", get(get_progress))
+        .route("/api/progress/changes", get(get_progress_changes))
         .route(
             "/api/progress/comic/{id}",
             axum::routing::put(put_comic_progress).delete(delete_comic_progress),
@@ -104,7 +172,10 @@ fn build_router(app: AppHandle, activity: Activity) -> Router {
             axum::routing::put(put_book_favorites).delete(delete_book_favorites),
         )
         .route("/file", get(serve_file))
-        .fallback_service(static_files)
+        .route("/archive", get(serve_archive_entry))
+        .fallback_service(static_files);
+
+    guard(router, &app)
         // Stamp every request as activity so the idle-sleep manager keeps the
         // Mac awake while clients are using it, and lets it sleep once they stop.
         .layer(axum::middleware::from_fn_with_state(
@@ -119,6 +190,106 @@ fn build_router(app: AppHandle, activity: Activity) -> Router {
         .with_state(app)
 }
 
+/// The access checks in front of every route, outermost first: the allowlist,
+/// then authorization, then the read-only rule.
+fn guard<R: Runtime>(router: Router<AppHandle<R>>, app: &AppHandle<R>) -> Router<AppHandle<R>> {
+    router
+        .layer(axum::middleware::from_fn_with_state(
+            app.clone(),
+            enforce_read_only,
+        ))
+        .layer(axum::middleware::from_fn_with_state(app.clone(), authorize))
+        .layer(axum::middleware::from_fn_with_state(
+            app.clone(),
+            reject_unlisted,
+        ))
+}
+
+/// Resolve who a request comes from and turn away unpaired clients, except on
+/// the paths a browser needs to load the app and pair. A paired device is
+/// marked as seen from the request's address and user agent. Authorized
+/// requests are scoped to a reader profile and to the libraries the client may
+/// see.
+async fn authorize<R: Runtime>(
+    State(app): State<AppHandle<R>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    match auth::caller(&app, addr.ip(), req.headers()) {
+        Some(caller) => {
+            if let Caller::Device(id) = &caller {
+                auth::seen(&app, id, &Origin::new(addr.ip(), req.headers()));
+            }
+            let Some(profile) = profile::resolve(&app, req.headers()) else {
+                return (StatusCode::BAD_REQUEST, "Unknown profile").into_response();
+            };
+            let access = access::resolve(&app, &caller, addr.ip(), req.headers());
+            req.extensions_mut().insert(caller);
+            req.extensions_mut().insert(ProfileId(profile));
+            req.extensions_mut().insert(access);
+        }
+        None if !is_public(req.uri().path()) => {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        None => {}
+    }
+    next.run(req).await
+}
+
+/// Turn away connections from outside the allowlist before anything else,
+/// pairing included, sees them.
+async fn reject_unlisted<R: Runtime>(
+    State(app): State<AppHandle<R>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    if !policy::admits(&app, addr.ip()) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    next.run(req).await
+}
+
+/// Refuse writes from read-only callers: guest devices, and devices outside
+/// the writer ranges.
+async fn enforce_read_only<R: Runtime>(
+    State(app): State<AppHandle<R>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let refused = policy::classify(req.method(), req.uri().path()) == Mode::Write
+        && req
+            .extensions()
+            .get::<Caller>()
+            .is_some_and(|caller| !policy::may_write(&app, caller, addr.ip()));
+    if refused {
+        return (StatusCode::FORBIDDEN, "Read-only client").into_response();
+    }
+    next.run(req).await
+}
+
+/// Leave changing devices and profiles to the owner, so a paired device
+/// cannot revoke the others or drop someone's progress.
+async fn require_owner(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    caller: Option<Extension<Caller>>,
+    req: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    if !caller.is_some_and(|Extension(caller)| caller.is_owner(addr.ip())) {
+        return (StatusCode::FORBIDDEN, "Only the Mac can manage this").into_response();
+    }
+    next.run(req).await
+}
+
+/// Paths served without pairing: the app shell and its assets, and pairing.
+fn is_public(path: &str) -> bool {
+    let protected = path.starts_with("/api/") || path == "/file" || path == "/archive";
+    !protected || matches!(path, "/api/auth/status" | "/api/auth/pair")
+}
+
 /// Strip Accept-Encoding for loopback clients so the CompressionLayer above
 /// sends raw bytes: on localhost transfer is free, so gzip is pure CPU overhead
 /// (compress + decompress) that makes large books slower. LAN clients keep it.
@@ -170,6 +341,36 @@ struct PathQuery {
     path: String,
 }
 
+#[derive(Deserialize)]
+struct LineRange {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+/// A revision the client already holds.
+#[derive(Deserialize)]
+struct SinceQuery {
+    since: i64,
+}
+
+#[derive(Deserialize)]
+struct PairBody {
+    pin: String,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// A new name for a device or profile.
+#[derive(Deserialize)]
+struct RenameBody {
+    name: String,
+}
+
 #[derive(Deserialize)]
 struct TagBody {
     path: String,
@@ -177,20 +378,274 @@ struct TagBody {
     deleted: Option<bool>,
 }
 
+// --- Pairing ---
+
+async fn auth_status(caller: Option<Extension<Caller>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "authorized": caller.is_some() }))
+}
+
+/// Trade the tray's PIN for a device token, also set as the browser's cookie.
+async fn pair_device(
+    State(app): State<AppHandle>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<PairBody>,
+) -> Result<Response, ApiError> {
+    let origin = Origin::new(addr.ip(), &headers);
+    let name = body
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "未命名设备".to_string());
+    let outcome = blocking(move || auth::pair(&app, &body.pin, &name, &origin))
+        .await?
+        .map_err(ApiError)?;
+    Ok(match outcome {
+        PairOutcome::Paired(paired) => (
+            [(header::SET_COOKIE, auth::session_cookie(&paired.token))],
+            Json(paired),
+        )
+            .into_response(),
+        PairOutcome::WrongPin => (StatusCode::UNAUTHORIZED, "Wrong PIN").into_response(),
+        PairOutcome::Throttled(delay) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(
+                header::RETRY_AFTER,
+                (delay.as_secs_f64().ceil() as u64).to_string(),
+            )],
+            "Too many wrong PINs",
+        )
+            .into_response(),
+    })
+}
+
+/// Revoke the calling device and clear its cookie.
+async fn logout(
+    State(app): State<AppHandle>,
+    caller: Option<Extension<Caller>>,
+) -> Result<Response, ApiError> {
+    if let Some(Extension(Caller::Device(id))) = caller {
+        blocking(move || auth::revoke(&app, &id))
+            .await?
+            .map_err(ApiError)?;
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, auth::cleared_cookie())],
+    )
+        .into_response())
+}
+
+// --- Profiles ---
+
+/// Reader profiles, with the caller's own marked `current`.
+async fn list_profiles(
+    State(app): State<AppHandle>,
+    Extension(ProfileId(current)): Extension<ProfileId>,
+) -> Result<Json<Vec<Profile>>, ApiError> {
+    let mut profiles = blocking(move || profile::list(&app))
+        .await?
+        .map_err(ApiError)?;
+    for profile in &mut profiles {
+        profile.current = profile.id == current;
+    }
+    Ok(Json(profiles))
+}
+
+async fn create_profile(
+    State(app): State<AppHandle>,
+    Json(body): Json<RenameBody>,
+) -> Result<Response, ApiError> {
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, "Profile name is empty").into_response());
+    }
+    let profile = blocking(move || profile::create(&app, &name))
+        .await?
+        .map_err(ApiError)?;
+    Ok(Json(profile).into_response())
+}
+
+async fn rename_profile(
+    State(app): State<AppHandle>,
+    AxumPath(id): AxumPath<String>,
+    Json(body): Json<RenameBody>,
+) -> Result<Response, ApiError> {
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, "Profile name is empty").into_response());
+    }
+    let renamed = blocking(move || profile::rename(&app, &id, &name))
+        .await?
+        .map_err(ApiError)?;
+    Ok(found_status(renamed).into_response())
+}
+
+/// Remove a profile with its progress and store blobs.
+async fn remove_profile(
+    State(app): State<AppHandle>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    if id == profile::DEFAULT_PROFILE {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            "The default profile cannot be removed",
+        )
+            .into_response());
+    }
+    let removed = blocking(move || profile::remove(&app, &id))
+        .await?
+        .map_err(ApiError)?;
+    Ok(found_status(removed).into_response())
+}
+
+/// Make `id` the browser's profile, remembered in a cookie.
+async fn select_profile(
+    State(app): State<AppHandle>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    let profiles = blocking(move || profile::list(&app))
+        .await?
+        .map_err(ApiError)?;
+    if !profiles.iter().any(|profile| profile.id == id) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, profile::selection_cookie(&id))],
+    )
+        .into_response())
+}
+
+// --- Library access ---
+
+#[derive(Deserialize)]
+struct AccessKeyBody {
+    key: String,
+}
+
+/// Keep an access key in the browser, unlocking the libraries it admits.
+async fn enter_access_key(
+    State(app): State<AppHandle>,
+    Json(body): Json<AccessKeyBody>,
+) -> Response {
+    let key = body.key.trim();
+    if !access::is_known_key(&app, key) {
+        return (StatusCode::NOT_FOUND, "Unknown access key").into_response();
+    }
+    (
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, access::key_cookie(key))],
+    )
+        .into_response()
+}
+
+async fn clear_access_key() -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, access::cleared_key_cookie())],
+    )
+        .into_response()
+}
+
+// --- Devices ---
+
+/// Paired devices, with the caller's own marked `current`.
+async fn list_devices(
+    State(app): State<AppHandle>,
+    caller: Option<Extension<Caller>>,
+) -> Result<Json<Vec<Device>>, ApiError> {
+    let current = caller_device(caller);
+    let mut devices = blocking(move || auth::devices(&app)).await?;
+    for device in &mut devices {
+        device.current = current.as_deref() == Some(device.id.as_str());
+    }
+    Ok(Json(devices))
+}
+
+async fn rename_device(
+    State(app): State<AppHandle>,
+    AxumPath(id): AxumPath<String>,
+    Json(body): Json<RenameBody>,
+) -> Result<Response, ApiError> {
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, "Device name is empty").into_response());
+    }
+    let renamed = blocking(move || auth::rename(&app, &id, &name))
+        .await?
+        .map_err(ApiError)?;
+    Ok(found_status(renamed).into_response())
+}
+
+#[derive(Deserialize)]
+struct GuestBody {
+    guest: bool,
+}
+
+/// Make a device a guest, which may only read, or give it write access back.
+async fn set_device_guest(
+    State(app): State<AppHandle>,
+    AxumPath(id): AxumPath<String>,
+    Json(body): Json<GuestBody>,
+) -> Result<StatusCode, ApiError> {
+    let updated = blocking(move || auth::set_guest(&app, &id, body.guest))
+        .await?
+        .map_err(ApiError)?;
+    Ok(found_status(updated))
+}
+
+/// Forget a device, revoking its token; it must pair again to reconnect.
+async fn forget_device(
+    State(app): State<AppHandle>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let removed = blocking(move || auth::revoke(&app, &id))
+        .await?
+        .map_err(ApiError)?;
+    Ok(found_status(removed))
+}
+
+/// 204 when a change or removal found its target, 404 otherwise.
+fn found_status(found: bool) -> StatusCode {
+    if found {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// The paired device behind a request; loopback callers have none.
+fn caller_device(caller: Option<Extension<Caller>>) -> Option<String> {
+    caller.and_then(|Extension(caller)| caller.device_id().map(str::to_string))
+}
+
 // --- API handlers ---
 
 async fn scan_comic_images(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     Query(q): Query<PathQuery>,
-) -> Result<Json<Vec<ComicImage>>, ApiError> {
-    blocking(move || crate::scanner::comic::scan_comic_images(app, &q.path))
-        .await?
-        .map(Json)
-        .map_err(ApiError)
+) -> Result<Response, ApiError> {
+    if !in_visible_library(&app, &q.path, &access) {
+        return Ok((StatusCode::FORBIDDEN, "forbidden").into_response());
+    }
+    let images: Vec<ComicImage> =
+        blocking(move || crate::scanner::comic::scan_comic_images(app, &q.path))
+            .await?
+            .map_err(ApiError)?;
+    Ok(Json(images).into_response())
 }
 
-async fn parse_book(Query(q): Query<PathQuery>) -> Result<Response, ApiError> {
-    let content = blocking(move || crate::scanner::book::parse_book(&q.path))
+async fn parse_book(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    Query(q): Query<PathQuery>,
+) -> Result<Response, ApiError> {
+    if !in_visible_library(&app, &q.path, &access) {
+        return Ok((StatusCode::FORBIDDEN, "forbidden").into_response());
+    }
+    let content = blocking(move || parse_book_impl(&app, &q.path))
         .await?
         .map_err(ApiError)?;
 
@@ -209,7 +664,119 @@ async fn parse_book(Query(q): Query<PathQuery>) -> Result<Response, ApiError> {
     Ok(res)
 }
 
-async fn set_tag(Json(b): Json<TagBody>) -> Result<StatusCode, ApiError> {
+async fn get_book_toc(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    let toc = blocking(move || book_index_impl(&app, &access, &id))
+        .await?
+        .map_err(ApiError)?
+        .map(BookIndex::into_toc);
+    Ok(found(toc))
+}
+
+async fn get_book_lines(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+    Query(q): Query<LineRange>,
+) -> Result<Response, ApiError> {
+    let lines = blocking(move || {
+        book_index_impl(&app, &access, &id)?
+            .map(|index| index.lines(q.start, q.end))
+            .transpose()
+    })
+    .await?
+    .map_err(ApiError)?;
+    Ok(found(lines))
+}
+
+async fn get_book_chapter(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath((id, index)): AxumPath<(String, usize)>,
+) -> Result<Response, ApiError> {
+    let lines: Option<BookLines> = blocking(move || {
+        book_index_impl(&app, &access, &id)?
+            .map(|book| book.chapter(index))
+            .transpose()
+            .map(Option::flatten)
+    })
+    .await?
+    .map_err(ApiError)?;
+    Ok(found(lines))
+}
+
+async fn search_book(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+    Query(q): Query<SearchQuery>,
+) -> Result<Response, ApiError> {
+    if let Err(e) = book_search::validate_query(&q.q) {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    let result = blocking(move || {
+        book_index_impl(&app, &access, &id)?
+            .map(|index| book_search::search(&index, &q.q))
+            .transpose()
+    })
+    .await?
+    .map_err(ApiError)?;
+    Ok(found(result))
+}
+
+async fn search_libraries(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    Query(q): Query<SearchQuery>,
+) -> Result<Response, ApiError> {
+    if let Err(e) = book_search::validate_query(&q.q) {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    let hits: Vec<LibrarySearchHit> = blocking(move || {
+        let mut hits = crate::fulltext::search(&app, &q.q, &access)?;
+        let mut visible = Ok(());
+        hits.retain(|hit| {
+            item_visible(&app, &access, |c| library::book_path(c, &hit.book_id)).unwrap_or_else(
+                |e| {
+                    visible = Err(e);
+                    false
+                },
+            )
+        });
+        visible.map(|()| hits)
+    })
+    .await?
+    .map_err(ApiError)?;
+    Ok(Json(hits).into_response())
+}
+
+async fn query_catalog(
+    State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
+    Query(q): Query<CatalogQuery>,
+) -> Result<Response, ApiError> {
+    if let Some(cursor) = &q.cursor
+        && let Err(e) = catalog::validate_cursor(cursor)
+    {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    let page: CatalogPage = blocking(move || catalog::query(&app, &profile, &access, &q))
+        .await?
+        .map_err(ApiError)?;
+    Ok(Json(page).into_response())
+}
+
+async fn set_tag(
+    Extension(access): Extension<Access>,
+    Json(b): Json<TagBody>,
+) -> Result<StatusCode, ApiError> {
+    if !access.sees_path(Path::new(&b.path)) {
+        return Ok(StatusCode::NOT_FOUND);
+    }
     blocking(move || {
         let tags = FileTags {
             starred: b.starred,
@@ -222,16 +789,112 @@ async fn set_tag(Json(b): Json<TagBody>) -> Result<StatusCode, ApiError> {
     .map_err(ApiError)
 }
 
+/// Parse with the encoding stored for the book and its library's chapter
+/// rules, answering from the parsed-book cache when the file is unchanged.
+fn parse_book_impl(app: &AppHandle, path: &str) -> Result<BookContent, String> {
+    open_book_index(app, path)?.content()
+}
+
+/// Open the line index of the book with `id`, or `None` for an unknown book
+/// or one hidden from the client.
+fn book_index_impl(
+    app: &AppHandle,
+    access: &Access,
+    id: &str,
+) -> Result<Option<BookIndex>, String> {
+    let path = {
+        let state = app.state::<library::LibraryDb>();
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        library::book_path(&conn, id).map_err(|e| e.to_string())?
+    };
+    path.filter(|path| access.sees_path(Path::new(path)))
+        .map(|path| open_book_index(app, &path))
+        .transpose()
+}
+
+/// Whether the comic or book at the catalog path `lookup` returns exists and
+/// is visible to the client.
+fn item_visible(
+    app: &AppHandle,
+    access: &Access,
+    lookup: impl FnOnce(&rusqlite::Connection) -> rusqlite::Result<Option<String>>,
+) -> Result<bool, String> {
+    if access.sees_everything() {
+        return Ok(true);
+    }
+    let state = app.state::<library::LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    let path = lookup(&conn).map_err(|e| e.to_string())?;
+    Ok(path.is_some_and(|path| access.sees_path(Path::new(&path))))
+}
+
+/// Open the cached index of the book at `path`, remembering a newly detected
+/// encoding for the next open.
+fn open_book_index(app: &AppHandle, path: &str) -> Result<BookIndex, String> {
+    let state = app.state::<library::LibraryDb>();
+    let options = {
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        library::book_parse_options(&conn, path).map_err(|e| e.to_string())?
+    };
+
+    let index = book_index::open(&book_index::cache_dir(app), path, &options)?;
+
+    let encoding = &index.toc().encoding;
+    if options.encoding.as_ref() != Some(encoding) {
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        library::set_book_encoding(&conn, path, encoding).map_err(|e| e.to_string())?;
+    }
+    Ok(index)
+}
+
+fn chapter_rules_impl(app: &AppHandle, id: &str) -> Result<Vec<ChapterRule>, String> {
+    let state = app.state::<library::LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    library::chapter_rules(&conn, id).map_err(|e| e.to_string())
+}
+
+fn set_chapter_rules_impl(app: &AppHandle, id: &str, rules: &[ChapterRule]) -> Result<(), String> {
+    let state = app.state::<library::LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    library::set_chapter_rules(&conn, id, rules).map_err(|e| e.to_string())
+}
+
+fn paragraph_options_impl(app: &AppHandle, id: &str) -> Result<ParagraphOptions, String> {
+    let state = app.state::<library::LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    library::paragraph_options(&conn, id).map_err(|e| e.to_string())
+}
+
+fn set_paragraph_options_impl(
+    app: &AppHandle,
+    id: &str,
+    options: &ParagraphOptions,
+) -> Result<(), String> {
+    let state = app.state::<library::LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    library::set_paragraph_options(&conn, id, options).map_err(|e| e.to_string())
+}
+
 fn remove_library_impl(app: &AppHandle, id: &str) -> Result<(), String> {
     let state = app.state::<library::LibraryDb>();
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     library::remove(&conn, id).map_err(|e| e.to_string())
 }
 
-fn reorder_libraries_impl(app: &AppHandle, ordered_ids: &[String]) -> Result<(), String> {
+/// Reorder the libraries. A client with hidden libraries only moves the ones
+/// it sees, among the positions they hold, so the hidden ones stay put.
+fn reorder_libraries_impl(
+    app: &AppHandle,
+    access: &Access,
+    mut ordered_ids: Vec<String>,
+) -> Result<(), String> {
     let state = app.state::<library::LibraryDb>();
     let conn = state.0.lock().map_err(|e| e.to_string())?;
-    library::reorder(&conn, ordered_ids).map_err(|e| e.to_string())
+    if access.sees_everything() {
+        return library::reorder(&conn, &ordered_ids).map_err(|e| e.to_string());
+    }
+    ordered_ids.retain(|id| access.sees_library(id));
+    library::reorder_within(&conn, &ordered_ids).map_err(|e| e.to_string())
 }
 
 fn with_progress<T>(
@@ -243,21 +906,96 @@ fn with_progress<T>(
     f(&conn).map_err(|e| e.to_string())
 }
 
+/// Drop the progress of comics and books the client may not see from
+/// `snapshot` and `removed`.
+fn filter_progress(
+    app: &AppHandle,
+    access: &Access,
+    snapshot: &mut Snapshot,
+    removed: Option<&mut RemovedProgress>,
+) -> Result<(), String> {
+    if access.sees_everything() {
+        return Ok(());
+    }
+    let state = app.state::<library::LibraryDb>();
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    retain_visible_progress(&conn, access, snapshot, removed);
+    Ok(())
+}
+
+/// Keep the progress of the comics and books in `conn`'s catalog that lie in
+/// libraries visible to `access`.
+fn retain_visible_progress(
+    conn: &rusqlite::Connection,
+    access: &Access,
+    snapshot: &mut Snapshot,
+    removed: Option<&mut RemovedProgress>,
+) {
+    let sees_comic = |id: &str| {
+        library::comic_library(conn, id)
+            .is_ok_and(|library_id| access.sees_item(library_id.as_deref()))
+    };
+    let sees_book = |id: &str| {
+        library::book_library(conn, id)
+            .is_ok_and(|library_id| access.sees_item(library_id.as_deref()))
+    };
+    snapshot.comics.retain(|id, _| sees_comic(id));
+    snapshot.books.retain(|id, _| sees_book(id));
+    snapshot.favorite_chapters.retain(|id, _| sees_book(id));
+    if let Some(removed) = removed {
+        removed.comics.retain(|id| sees_comic(id));
+        removed.books.retain(|id| sees_book(id));
+        removed.favorite_chapters.retain(|id| sees_book(id));
+    }
+}
+
+/// Apply the progress `write` for the comic or book in the library
+/// `library_of` finds and publish the `event` built from that library, or
+/// 404 when the item is hidden from the client.
+fn write_progress(
+    app: &AppHandle,
+    access: &Access,
+    library_of: impl FnOnce(&rusqlite::Connection) -> rusqlite::Result<Option<String>>,
+    write: impl FnOnce(&rusqlite::Connection) -> rusqlite::Result<()>,
+    event: impl FnOnce(Option<String>) -> Event,
+) -> Result<StatusCode, String> {
+    let library_id = {
+        let state = app.state::<library::LibraryDb>();
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        library_of(&conn).map_err(|e| e.to_string())?
+    };
+    if !access.sees_item(library_id.as_deref()) {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+    with_progress(app, write)?;
+    events::publish(app, event(library_id));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// JSON for a found resource, 404 otherwise.
+fn found<T: serde::Serialize>(value: Option<T>) -> Response {
+    match value {
+        Some(value) => Json(value).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 fn no_content(result: Result<(), String>) -> Result<StatusCode, ApiError> {
     result.map(|()| StatusCode::NO_CONTENT).map_err(ApiError)
 }
 
-// --- Server store: the single owner of each persisted zustand blob ---
+// --- Server store: the single owner of each persisted zustand blob, per profile ---
 
 async fn store_get<R: Runtime>(
     State(app): State<AppHandle<R>>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
     AxumPath(key): AxumPath<String>,
 ) -> Response {
     if let Err(e) = config::validate_store_key(&key) {
         return (StatusCode::BAD_REQUEST, e).into_response();
     }
 
-    match config::read_store_data(app, key) {
+    match config::read_store_file(&profile::store_dir(&app, &profile), &key) {
         Some(data) => ([(header::CONTENT_TYPE, "application/json")], data).into_response(),
         None => StatusCode::NOT_FOUND.into_response(),
     }
@@ -265,6 +1003,7 @@ async fn store_get<R: Runtime>(
 
 async fn store_put<R: Runtime>(
     State(app): State<AppHandle<R>>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
     AxumPath(key): AxumPath<String>,
     body: String,
 ) -> Response {
@@ -272,7 +1011,7 @@ async fn store_put<R: Runtime>(
         return (StatusCode::BAD_REQUEST, e).into_response();
     }
 
-    match config::write_store_data(app, key, body) {
+    match config::write_store_file(&profile::store_dir(&app, &profile), &key, &body) {
         Ok(()) => StatusCode::NO_CONTENT.into_response(),
         Err(e) => ApiError(e).into_response(),
     }
@@ -280,13 +1019,14 @@ async fn store_put<R: Runtime>(
 
 async fn store_delete<R: Runtime>(
     State(app): State<AppHandle<R>>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
     AxumPath(key): AxumPath<String>,
 ) -> Response {
     if let Err(e) = config::validate_store_key(&key) {
         return (StatusCode::BAD_REQUEST, e).into_response();
     }
 
-    match config::remove_store_data(app, key) {
+    match config::remove_store_file(&profile::store_dir(&app, &profile), &key) {
         Ok(()) => StatusCode::NO_CONTENT.into_response(),
         Err(e) => ApiError(e).into_response(),
     }
@@ -299,8 +1039,12 @@ struct RevealBody {
 
 async fn reveal_path(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     Json(body): Json<RevealBody>,
 ) -> Result<StatusCode, ApiError> {
+    if !access.sees_path(Path::new(&body.path)) {
+        return Ok(StatusCode::NOT_FOUND);
+    }
     crate::scanner::utils::open_path_native(app, body.path)
         .map(|()| StatusCode::NO_CONTENT)
         .map_err(ApiError)
@@ -308,122 +1052,426 @@ async fn reveal_path(
 
 // --- Library catalog (single source of truth) ---
 
-async fn get_catalog(State(app): State<AppHandle>) -> Result<Json<Catalog>, ApiError> {
+async fn get_catalog(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+) -> Result<Json<Catalog>, ApiError> {
     blocking(move || {
         let state = app.state::<library::LibraryDb>();
         let conn = state.0.lock().map_err(|e| e.to_string())?;
-        library::get_catalog(&conn).map_err(|e| e.to_string())
+        let mut catalog = library::get_catalog(&conn).map_err(|e| e.to_string())?;
+        access.filter_catalog(&mut catalog);
+        Ok(catalog)
     })
     .await?
     .map(Json)
     .map_err(ApiError)
 }
 
+async fn get_catalog_changes(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    Query(q): Query<SinceQuery>,
+) -> Result<Json<CatalogChanges>, ApiError> {
+    blocking(move || {
+        let state = app.state::<library::LibraryDb>();
+        let conn = state.0.lock().map_err(|e| e.to_string())?;
+        library::catalog_changes(&conn, q.since, &access).map_err(|e| e.to_string())
+    })
+    .await?
+    .map(Json)
+    .map_err(ApiError)
+}
+
+/// Queue a rescan of the library and answer with its job at once.
 async fn refresh_library(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
-) -> Result<StatusCode, ApiError> {
-    blocking(move || library::refresh(&app, &id))
-        .await?
-        .map(|()| StatusCode::NO_CONTENT)
-        .map_err(ApiError)
+) -> Result<Response, ApiError> {
+    let job = blocking(move || {
+        if !access.sees_library(&id) || library::library_path(&app, &id).is_none() {
+            return Ok(None);
+        }
+        jobs::submit(&app, Task::Refresh(id)).map(Some)
+    })
+    .await?
+    .map_err(ApiError)?;
+    Ok(match job {
+        Some(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
 }
 
 async fn remove_library(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
 ) -> Result<StatusCode, ApiError> {
+    if !access.sees_library(&id) {
+        return Ok(StatusCode::NOT_FOUND);
+    }
     let roots_app = app.clone();
-    blocking(move || remove_library_impl(&app, &id))
-        .await?
-        .map_err(ApiError)?;
+    let library_id = id.clone();
+    blocking(move || {
+        jobs::cancel_library(&app, &library_id)?;
+        remove_library_impl(&app, &library_id)
+    })
+    .await?
+    .map_err(ApiError)?;
     rebuild_allowed_roots(&roots_app);
+    crate::watcher::rebuild(&roots_app);
+    events::publish(&roots_app, Event::LibraryRemoved { library_id: id });
     Ok(StatusCode::NO_CONTENT)
 }
 
+// --- Live change events ---
+
+async fn event_stream(
+    State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
+) -> Response {
+    Sse::new(events::stream(&app, profile, access))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// --- Background jobs ---
+
+async fn list_jobs(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+) -> Result<Json<Vec<Job>>, ApiError> {
+    let mut jobs = jobs::list(&app).map_err(ApiError)?;
+    jobs.retain(|job| job_visible(&access, job));
+    Ok(Json(jobs))
+}
+
+async fn get_job(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<u64>,
+) -> Result<Response, ApiError> {
+    let job = jobs::get(&app, id).map_err(ApiError)?;
+    Ok(found(job.filter(|job| job_visible(&access, job))))
+}
+
+async fn cancel_job(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<u64>,
+) -> Result<Response, ApiError> {
+    let visible = jobs::get(&app, id)
+        .map_err(ApiError)?
+        .is_some_and(|job| job_visible(&access, &job));
+    if !visible {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    jobs::cancel(&app, id).map(found).map_err(ApiError)
+}
+
+/// Whether a job works on a library the client may see; cache cleaning works
+/// on none.
+fn job_visible(access: &Access, job: &Job) -> bool {
+    job.library_id
+        .as_deref()
+        .is_none_or(|id| access.sees_library(id))
+}
+
+async fn get_chapter_rules(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    if !access.sees_library(&id) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let rules = blocking(move || chapter_rules_impl(&app, &id))
+        .await?
+        .map_err(ApiError)?;
+    Ok(Json(rules).into_response())
+}
+
+async fn put_chapter_rules(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+    Json(rules): Json<Vec<ChapterRule>>,
+) -> Result<Response, ApiError> {
+    if !access.sees_library(&id) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    if let Err(e) = chapter::validate_rules(&rules) {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    no_content(blocking(move || set_chapter_rules_impl(&app, &id, &rules)).await?)
+        .map(IntoResponse::into_response)
+}
+
+async fn get_paragraph_options(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    if !access.sees_library(&id) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let options = blocking(move || paragraph_options_impl(&app, &id))
+        .await?
+        .map_err(ApiError)?;
+    Ok(Json(options).into_response())
+}
+
+async fn put_paragraph_options(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    AxumPath(id): AxumPath<String>,
+    Json(options): Json<ParagraphOptions>,
+) -> Result<StatusCode, ApiError> {
+    if !access.sees_library(&id) {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+    no_content(blocking(move || set_paragraph_options_impl(&app, &id, &options)).await?)
+}
+
 async fn reorder_libraries(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     Json(ordered_ids): Json<Vec<String>>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || reorder_libraries_impl(&app, &ordered_ids)).await?)
+    no_content(blocking(move || reorder_libraries_impl(&app, &access, ordered_ids)).await?)
 }
 
 async fn set_comic_tags(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
     Json(tags): Json<FileTags>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || library::set_comic_tags(&app, &id, &tags)).await?)
+    blocking(move || {
+        if !item_visible(&app, &access, |c| library::comic_path(c, &id))? {
+            return Ok(StatusCode::NOT_FOUND);
+        }
+        library::set_comic_tags(&app, &id, &tags).map(|()| StatusCode::NO_CONTENT)
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 async fn set_book_tags(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
     Json(tags): Json<FileTags>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || library::set_book_tags(&app, &id, &tags)).await?)
+    blocking(move || {
+        if !item_visible(&app, &access, |c| library::book_path(c, &id))? {
+            return Ok(StatusCode::NOT_FOUND);
+        }
+        library::set_book_tags(&app, &id, &tags).map(|()| StatusCode::NO_CONTENT)
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 // --- Reading progress (single source of truth, field-level) ---
 
-async fn get_progress(State(app): State<AppHandle>) -> Result<Json<Snapshot>, ApiError> {
-    blocking(move || with_progress(&app, progress::get_snapshot))
-        .await?
-        .map(Json)
-        .map_err(ApiError)
+async fn get_progress(
+    State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
+) -> Result<Json<Snapshot>, ApiError> {
+    blocking(move || {
+        let mut snapshot = with_progress(&app, |c| progress::get_snapshot(c, &profile))?;
+        filter_progress(&app, &access, &mut snapshot, None)?;
+        Ok(snapshot)
+    })
+    .await?
+    .map(Json)
+    .map_err(ApiError)
+}
+
+async fn get_progress_changes(
+    State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
+    Query(q): Query<SinceQuery>,
+) -> Result<Json<ProgressChanges>, ApiError> {
+    blocking(move || {
+        let mut changes =
+            with_progress(&app, |c| progress::progress_changes(c, &profile, q.since))?;
+        filter_progress(
+            &app,
+            &access,
+            &mut changes.snapshot,
+            Some(&mut changes.removed),
+        )?;
+        Ok(changes)
+    })
+    .await?
+    .map(Json)
+    .map_err(ApiError)
 }
 
 async fn put_comic_progress(
     State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
-    Json(p): Json<ComicProgress>,
+    caller: Option<Extension<Caller>>,
+    Json(mut p): Json<ComicProgress>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || with_progress(&app, |c| progress::upsert_comic(c, &id, &p))).await?)
+    // Attribute the write to the device that made it.
+    p.device_id = caller_device(caller);
+    blocking(move || {
+        write_progress(
+            &app,
+            &access,
+            |c| library::comic_library(c, &id),
+            |c| progress::upsert_comic(c, &profile, &id, &p),
+            |library_id| Event::ComicProgressUpdated {
+                library_id,
+                profile_id: profile.clone(),
+                comic_id: id.clone(),
+                progress: Some(p.clone()),
+            },
+        )
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 async fn delete_comic_progress(
     State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || with_progress(&app, |c| progress::delete_comic(c, &id))).await?)
+    blocking(move || {
+        write_progress(
+            &app,
+            &access,
+            |c| library::comic_library(c, &id),
+            |c| progress::delete_comic(c, &profile, &id),
+            |library_id| Event::ComicProgressUpdated {
+                library_id,
+                profile_id: profile.clone(),
+                comic_id: id.clone(),
+                progress: None,
+            },
+        )
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 async fn put_book_progress(
     State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
-    Json(p): Json<BookProgress>,
+    caller: Option<Extension<Caller>>,
+    Json(mut p): Json<BookProgress>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || with_progress(&app, |c| progress::upsert_book(c, &id, &p))).await?)
+    // Attribute the write to the device that made it.
+    p.device_id = caller_device(caller);
+    blocking(move || {
+        write_progress(
+            &app,
+            &access,
+            |c| library::book_library(c, &id),
+            |c| progress::upsert_book(c, &profile, &id, &p),
+            |library_id| Event::BookProgressUpdated {
+                library_id,
+                profile_id: profile.clone(),
+                book_id: id.clone(),
+                progress: Some(p.clone()),
+            },
+        )
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 async fn delete_book_progress(
     State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || with_progress(&app, |c| progress::delete_book(c, &id))).await?)
+    blocking(move || {
+        write_progress(
+            &app,
+            &access,
+            |c| library::book_library(c, &id),
+            |c| progress::delete_book(c, &profile, &id),
+            |library_id| Event::BookProgressUpdated {
+                library_id,
+                profile_id: profile.clone(),
+                book_id: id.clone(),
+                progress: None,
+            },
+        )
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 async fn put_book_favorites(
     State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
     Json(lines): Json<Vec<i64>>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(
-        blocking(move || with_progress(&app, |c| progress::set_favorites(c, &id, &lines))).await?,
-    )
+    blocking(move || {
+        write_progress(
+            &app,
+            &access,
+            |c| library::book_library(c, &id),
+            |c| progress::set_favorites(c, &profile, &id, &lines),
+            |library_id| Event::FavoritesUpdated {
+                library_id,
+                profile_id: profile.clone(),
+                book_id: id.clone(),
+                lines: lines.clone(),
+            },
+        )
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 async fn delete_book_favorites(
     State(app): State<AppHandle>,
+    Extension(ProfileId(profile)): Extension<ProfileId>,
+    Extension(access): Extension<Access>,
     AxumPath(id): AxumPath<String>,
 ) -> Result<StatusCode, ApiError> {
-    no_content(blocking(move || with_progress(&app, |c| progress::delete_favorites(c, &id))).await?)
+    blocking(move || {
+        write_progress(
+            &app,
+            &access,
+            |c| library::book_library(c, &id),
+            |c| progress::delete_favorites(c, &profile, &id),
+            |library_id| Event::FavoritesUpdated {
+                library_id,
+                profile_id: profile.clone(),
+                book_id: id.clone(),
+                lines: Vec::new(),
+            },
+        )
+    })
+    .await?
+    .map_err(ApiError)
 }
 
 // --- File streaming (covers, thumbnails, full-size images) ---
 
 async fn serve_file(
     State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
     Query(q): Query<PathQuery>,
     req: Request,
 ) -> Response {
@@ -432,7 +1480,7 @@ async fn serve_file(
         return (StatusCode::FORBIDDEN, "forbidden").into_response();
     };
     let roots = app.state::<AllowedRoots>();
-    let Some(is_thumbnail) = roots.classify(&canon) else {
+    let Some(is_thumbnail) = roots.classify(&canon, &access) else {
         return (StatusCode::FORBIDDEN, "forbidden").into_response();
     };
 
@@ -455,6 +1503,41 @@ async fn serve_file(
     }
 }
 
+#[derive(Deserialize)]
+struct ArchiveEntryQuery {
+    path: String,
+    entry: String,
+}
+
+/// Stream one page out of a comic archive. The archive itself goes through
+/// the same allowlist as `/file`; the page is read in memory, never extracted.
+async fn serve_archive_entry(
+    State(app): State<AppHandle>,
+    Extension(access): Extension<Access>,
+    Query(q): Query<ArchiveEntryQuery>,
+) -> Response {
+    let Ok(canon) = PathBuf::from(&q.path).canonicalize() else {
+        return (StatusCode::FORBIDDEN, "forbidden").into_response();
+    };
+    if app
+        .state::<AllowedRoots>()
+        .classify(&canon, &access)
+        .is_none()
+    {
+        return (StatusCode::FORBIDDEN, "forbidden").into_response();
+    }
+
+    let content_type = crate::scanner::archive::page_content_type(&q.entry);
+    match blocking(move || crate::scanner::archive::read_page(&canon, &q.entry)).await {
+        Ok(Ok(data)) => ([(header::CONTENT_TYPE, content_type)], data).into_response(),
+        Ok(Err(e)) => {
+            warn!(error = %e, path = %q.path, "Failed to serve archive entry");
+            (StatusCode::NOT_FOUND, e).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
 // --- File allowlist ---
 
 struct AllowedRoots(RwLock<RootsSnapshot>);
@@ -466,17 +1549,28 @@ struct RootsSnapshot {
 }
 
 impl AllowedRoots {
-    fn classify(&self, canon: &Path) -> Option<bool> {
+    /// Whether the client with `access` may be served `canon`, and if so
+    /// whether it is a thumbnail. Hidden libraries are outside the allowlist.
+    fn classify(&self, canon: &Path, access: &Access) -> Option<bool> {
         let Ok(s) = self.0.read() else {
             return None;
         };
-        if !s.roots.iter().any(|root| canon.starts_with(root)) {
+        if !s.roots.iter().any(|root| canon.starts_with(root)) || !access.sees_path(canon) {
             return None;
         }
         Some(s.thumb_dir.as_ref().is_some_and(|t| canon.starts_with(t)))
     }
 }
 
+/// Whether `path` is inside a library the client may see. Scanning and parsing
+/// read arbitrary files and write caches, so they are held to the file
+/// allowlist, minus the thumbnail cache.
+fn in_visible_library(app: &AppHandle, path: &str, access: &Access) -> bool {
+    Path::new(path)
+        .canonicalize()
+        .is_ok_and(|canon| app.state::<AllowedRoots>().classify(&canon, access) == Some(false))
+}
+
 pub(crate) fn init_allowed_roots(app: &AppHandle) {
     app.manage(AllowedRoots(RwLock::new(RootsSnapshot::default())));
     rebuild_allowed_roots(app);
@@ -767,6 +1861,7 @@ mod tests {
     use serde_json::Value;
     use std::sync::Mutex;
 
+    use crate::auth::Auth;
     use crate::config::ConfigState;
     use crate::models::Config;
 
@@ -774,11 +1869,12 @@ mod tests {
         let app = tauri::test::mock_app();
         app.manage(ConfigState(Mutex::new(Config {
             cache_dir: Some(cache_dir.to_string_lossy().into_owned()),
+            ..Config::default()
         })));
         app
     }
 
-    fn store_test_router(app: AppHandle<tauri::test::MockRuntime>) -> Router {
+    fn store_test_router(app: AppHandle<tauri::test::MockRuntime>, profile: &str) -> Router {
         Router::new()
             .route(
                 "/api/store/{key}",
@@ -786,6 +1882,7 @@ mod tests {
                     .put(store_put::<tauri::test::MockRuntime>)
                     .delete(store_delete::<tauri::test::MockRuntime>),
             )
+            .layer(Extension(ProfileId(profile.to_string())))
             .layer(axum::middleware::from_fn(no_store_dynamic))
             .with_state(app)
     }
@@ -818,12 +1915,246 @@ mod tests {
         )
     }
 
+    #[test]
+    fn only_the_app_shell_and_pairing_are_served_without_a_device() {
+        for path in [
+            "/",
+            "/index.html",
+            "/assets/index.js",
+            "/api/auth/status",
+            "/api/auth/pair",
+        ] {
+            assert!(is_public(path), "{path} should be public");
+        }
+        for path in [
+            "/api/libraries",
+            "/api/auth/logout",
+            "/api/devices",
+            "/api/profiles",
+            "/api/store/reader",
+            "/api/reveal",
+            "/file",
+            "/archive",
+        ] {
+            assert!(!is_public(path), "{path} should need a device");
+        }
+    }
+
+    #[test]
+    fn only_the_mac_may_manage_devices_and_profiles() {
+        tauri::async_runtime::block_on(async {
+            let router = |caller: Caller| {
+                Router::new()
+                    .route(
+                        "/api/devices/{id}",
+                        axum::routing::delete(|| async { StatusCode::NO_CONTENT })
+                            .route_layer(axum::middleware::from_fn(require_owner)),
+                    )
+                    .route(
+                        "/api/profiles",
+                        post(|| async { StatusCode::NO_CONTENT })
+                            .route_layer(axum::middleware::from_fn(require_owner))
+                            .get(|| async { StatusCode::OK }),
+                    )
+                    .layer(Extension(caller))
+            };
+            let request = |method: Method, uri: &str, ip: [u8; 4]| {
+                let mut req = api_request(method, uri, Body::empty());
+                req.extensions_mut()
+                    .insert(ConnectInfo(SocketAddr::from((ip, 50000))));
+                req
+            };
+            let from = |ip| request(Method::DELETE, "/api/devices/other", ip);
+            let device = || Caller::Device("device-1".to_string());
+
+            let (status, _, _) = send(&router(device()), from([192, 168, 1, 20])).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            let (status, _, _) = send(&router(Caller::Loopback), from([127, 0, 0, 1])).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+            // The Mac's own browser, paired because loopback auth is on.
+            let (status, _, _) = send(&router(device()), from([127, 0, 0, 1])).await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+
+            // Any device lists the profiles, but only the Mac creates one.
+            let lan = [192, 168, 1, 20];
+            let (status, _, _) = send(
+                &router(device()),
+                request(Method::GET, "/api/profiles", lan),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            let (status, _, _) = send(
+                &router(device()),
+                request(Method::POST, "/api/profiles", lan),
+            )
+            .await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            let (status, _, _) = send(
+                &router(Caller::Loopback),
+                request(Method::POST, "/api/profiles", [127, 0, 0, 1]),
+            )
+            .await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+        });
+    }
+
+    #[test]
+    fn the_access_checks_turn_away_unlisted_unpaired_revoked_and_read_only_clients() {
+        async fn ok() -> StatusCode {
+            StatusCode::OK
+        }
+
+        tauri::async_runtime::block_on(async {
+            let dir = tempfile::tempdir().expect("create temp dir");
+            let app = app_with_cache_dir(dir.path());
+            app.state::<ConfigState>()
+                .0
+                .lock()
+                .expect("lock config")
+                .allowed_ranges = vec!["192.168.1.0/24".to_string()];
+            app.manage(Auth::in_memory());
+            let auth = app.state::<Auth>();
+            let writer = auth.add_device("iPad", false);
+            let guest = auth.add_device("Kid's tablet", true);
+            let revoked = auth.add_device("Old phone", false);
+            auth.revoke_token(&revoked);
+
+            let routes = Router::new()
+                .route("/api/auth/status", get(ok))
+                .route("/api/libraries", get(ok))
+                .route("/api/tag", post(ok));
+            let router = guard(routes, app.handle()).with_state(app.handle().clone());
+            let request = |method: Method, uri: &str, ip: [u8; 4], token: Option<&str>| {
+                let mut req = api_request(method, uri, Body::empty());
+                req.extensions_mut()
+                    .insert(ConnectInfo(SocketAddr::from((ip, 50000))));
+                if let Some(token) = token {
+                    let bearer = format!("Bearer {token}").parse().expect("bearer header");
+                    req.headers_mut().insert(header::AUTHORIZATION, bearer);
+                }
+                req
+            };
+            let status = |req| {
+                let router = router.clone();
+                async move { send(&router, req).await.0 }
+            };
+            let (lan, outside) = ([192, 168, 1, 20], [203, 0, 113, 9]);
+
+            // Outside the allowlist nothing gets through, pairing included.
+            let req = request(Method::GET, "/api/auth/status", outside, None);
+            assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+            let req = request(Method::GET, "/api/libraries", outside, Some(&writer));
+            assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+
+            // An unpaired LAN client only reaches the public paths.
+            let req = request(Method::GET, "/api/libraries", lan, None);
+            assert_eq!(status(req).await, StatusCode::UNAUTHORIZED);
+            let req = request(Method::GET, "/api/auth/status", lan, None);
+            assert_eq!(status(req).await, StatusCode::OK);
+
+            // A token is accepted as a bearer header or as the cookie, until
+            // its device is revoked.
+            let req = request(Method::GET, "/api/libraries", lan, Some(&writer));
+            assert_eq!(status(req).await, StatusCode::OK);
+            let mut req = request(Method::GET, "/api/libraries", lan, None);
+            let cookie = format!("{}={writer}", auth::COOKIE);
+            req.headers_mut()
+                .insert(header::COOKIE, cookie.parse().expect("cookie header"));
+            assert_eq!(status(req).await, StatusCode::OK);
+            let req = request(Method::GET, "/api/libraries", lan, Some(&revoked));
+            assert_eq!(status(req).await, StatusCode::UNAUTHORIZED);
+
+            // A guest reads but does not write; other devices and the Mac do.
+            let req = request(Method::GET, "/api/libraries", lan, Some(&guest));
+            assert_eq!(status(req).await, StatusCode::OK);
+            let req = request(Method::POST, "/api/tag", lan, Some(&guest));
+            assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+            let req = request(Method::POST, "/api/tag", lan, Some(&writer));
+            assert_eq!(status(req).await, StatusCode::OK);
+            let req = request(Method::POST, "/api/tag", [127, 0, 0, 1], None);
+            assert_eq!(status(req).await, StatusCode::OK);
+        });
+    }
+
+    #[test]
+    fn the_allowlist_tells_library_files_from_thumbnails_and_hidden_libraries() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let canon = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).expect("create dir");
+            path.canonicalize().expect("resolve dir")
+        };
+        let (library, thumbs, kids) = (canon("library"), canon("thumbs"), canon("kids"));
+        let outside = canon("outside");
+        let roots = AllowedRoots(RwLock::new(RootsSnapshot {
+            thumb_dir: Some(thumbs.clone()),
+            roots: vec![library.clone(), thumbs.clone(), kids.clone()],
+        }));
+        let everything = Access::default();
+
+        assert_eq!(roots.classify(&library, &everything), Some(false));
+        assert_eq!(roots.classify(&thumbs, &everything), Some(true));
+        assert_eq!(roots.classify(&outside, &everything), None);
+        assert_eq!(roots.classify(&kids, &Access::hiding_roots(&[&kids])), None);
+    }
+
+    #[test]
+    fn restricted_clients_only_get_the_progress_of_visible_libraries() {
+        let library_conn = rusqlite::Connection::open_in_memory().expect("open library db");
+        library::create_schema(&library_conn).expect("create library schema");
+        for (id, library_id) in [("comic-kids", "kids"), ("comic-family", "family")] {
+            library_conn
+                .execute(
+                    "INSERT INTO comics (id, title, path, cover, library_id, created_at, starred, deleted)
+                     VALUES (?1, ?1, '/p', '', ?2, 0, 0, 0)",
+                    rusqlite::params![id, library_id],
+                )
+                .expect("insert comic");
+        }
+        library_conn
+            .execute(
+                "INSERT INTO books (id, title, path, author_id, library_id, size, created_at, starred, deleted)
+                 VALUES ('book-kids', 'Book', '/p', 'a', 'kids', 0, 0, 0, 0)",
+                [],
+            )
+            .expect("insert book");
+        let progress_conn = rusqlite::Connection::open_in_memory().expect("open progress db");
+        progress::create_schema(&progress_conn).expect("create progress schema");
+        let profile = profile::DEFAULT_PROFILE;
+        for id in ["comic-kids", "comic-family", "comic-gone"] {
+            let read = ComicProgress {
+                current: 1,
+                total: 2,
+                percent: 50.0,
+                last_read: 1,
+                device_id: None,
+            };
+            progress::upsert_comic(&progress_conn, profile, id, &read).expect("write progress");
+        }
+        progress::set_favorites(&progress_conn, profile, "book-kids", &[3]).expect("favorite");
+        progress::delete_comic(&progress_conn, profile, "comic-kids").expect("clear progress");
+
+        let mut changes =
+            progress::progress_changes(&progress_conn, profile, 0).expect("read changes");
+        retain_visible_progress(
+            &library_conn,
+            &Access::hiding(&["kids"]),
+            &mut changes.snapshot,
+            Some(&mut changes.removed),
+        );
+
+        let comics: Vec<_> = changes.snapshot.comics.keys().collect();
+        assert_eq!(comics, ["comic-family"]);
+        assert!(changes.snapshot.favorite_chapters.is_empty());
+        assert!(changes.removed.comics.is_empty());
+    }
+
     #[test]
     fn store_api_round_trips_persisted_json() {
         tauri::async_runtime::block_on(async {
             let cache_dir = tempfile::tempdir().expect("create cache dir");
             let app = app_with_cache_dir(cache_dir.path());
-            let router = store_test_router(app.handle().clone());
+            let router = store_test_router(app.handle().clone(), profile::DEFAULT_PROFILE);
 
             let (status, body, cache_control) = send(
                 &router,
@@ -875,12 +2206,42 @@ mod tests {
         });
     }
 
+    #[test]
+    fn store_api_keeps_each_profiles_blobs_apart() {
+        tauri::async_runtime::block_on(async {
+            let cache_dir = tempfile::tempdir().expect("create cache dir");
+            let app = app_with_cache_dir(cache_dir.path());
+            let default = store_test_router(app.handle().clone(), profile::DEFAULT_PROFILE);
+            let kid = store_test_router(app.handle().clone(), "kid");
+
+            let (status, _, _) = send(
+                &kid,
+                api_request(Method::PUT, "/api/store/reader", r#"{"page":7}"#),
+            )
+            .await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+
+            let (status, _, _) = send(
+                &default,
+                api_request(Method::GET, "/api/store/reader", Body::empty()),
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert!(
+                cache_dir
+                    .path()
+                    .join("store/profiles/kid/reader.json")
+                    .is_file()
+            );
+        });
+    }
+
     #[test]
     fn store_api_rejects_invalid_keys_at_http_boundary() {
         tauri::async_runtime::block_on(async {
             let cache_dir = tempfile::tempdir().expect("create cache dir");
             let app = app_with_cache_dir(cache_dir.path());
-            let router = store_test_router(app.handle().clone());
+            let router = store_test_router(app.handle().clone(), profile::DEFAULT_PROFILE);
 
             for uri in ["/api/store/library.json", "/api/store/with%20space"] {
                 for method in [Method::GET, Method::PUT, Method::DELETE] {
@@ -5,12 +5,12 @@ use memmap2::Mmap;
 use percent_encoding::utf8_percent_encode;
 use sha2::{Digest, Sha256};
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Seek, Write};
 use std::num::NonZeroU32;
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
 use std::time::SystemTime;
 use tauri::{AppHandle, Manager};
 use tracing::info;
@@ -21,6 +21,7 @@ use crate::models::ThumbnailStats;
 
 const STATS_KEY: &str = "stats";
 static THUMB_TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+static JXL_HOOK: Once = Once::new();
 
 pub struct ThumbnailStatsState(pub Mutex<ThumbnailStats>);
 
@@ -84,7 +85,9 @@ const ENCODE_SET: percent_encoding::AsciiSet = percent_encoding::NON_ALPHANUMERI
     .remove(b'(')
     .remove(b')');
 
-pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "webp", "gif", "bmp", "tif", "tiff", "jxl",
+];
 
 pub fn get_thumbnail_dir(app: &AppHandle) -> PathBuf {
     if let Some(base_path) = config::get_configured_cache_dir(app) {
@@ -125,10 +128,39 @@ pub fn is_image_file(path: &Path) -> bool {
         })
 }
 
+/// Thumbnail cache key for one entry inside an archive: the archive's file
+/// identity (as in `get_thumbnail_hash`) plus the entry name.
+pub fn get_archive_entry_hash(archive_metadata: &fs::Metadata, entry: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(archive_metadata.ino().to_le_bytes());
+    hasher.update(archive_metadata.len().to_le_bytes());
+    hasher.update(archive_metadata.mtime().to_le_bytes());
+    hasher.update(entry.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// `ImageReader` with the format sniffed from content. The `image` crate has
+/// no JPEG XL codec of its own, so jxl-oxide is registered as a hook first.
+fn image_reader<R: BufRead + Seek>(reader: R) -> std::io::Result<ImageReader<R>> {
+    JXL_HOOK.call_once(|| {
+        jxl_oxide::integration::register_image_decoding_hook();
+    });
+    ImageReader::new(reader).with_guessed_format()
+}
+
 pub fn get_image_dimensions_fast(path: &Path) -> Result<(u32, u32), Box<dyn std::error::Error>> {
     let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let reader = ImageReader::new(reader).with_guessed_format()?;
+    let reader = image_reader(BufReader::new(file))?;
+    let (width, height) = reader.into_dimensions()?;
+    Ok((width, height))
+}
+
+/// Read dimensions from an in-memory image. The data may be only a prefix of
+/// the file, as long as it covers the format header.
+pub fn get_image_dimensions_from_bytes(
+    data: &[u8],
+) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    let reader = image_reader(std::io::Cursor::new(data))?;
     let (width, height) = reader.into_dimensions()?;
     Ok((width, height))
 }
@@ -151,10 +183,35 @@ pub fn process_and_get_dimensions(
     let file = File::open(source_path)?;
     // SAFETY: the mapping is read-only and lives no longer than the open file handle.
     let mmap = unsafe { Mmap::map(&file)? };
+    generate_thumbnail(&mmap, thumb_path, decompressor, resizer)
+}
+
+/// `process_and_get_dimensions` for an image that is already in memory, such
+/// as a page read out of a comic archive.
+pub fn process_bytes_and_get_dimensions(
+    data: &[u8],
+    thumb_path: &Path,
+    decompressor: &mut Decompressor,
+    resizer: &mut fr::Resizer,
+) -> Result<(u32, u32, u64), Box<dyn std::error::Error>> {
+    if thumb_path.exists()
+        && let Ok((w, h)) = get_image_dimensions_from_bytes(data)
+    {
+        return Ok((w, h, 0));
+    }
+
+    generate_thumbnail(data, thumb_path, decompressor, resizer)
+}
 
-    if is_jpeg(&mmap) {
+fn generate_thumbnail(
+    data: &[u8],
+    thumb_path: &Path,
+    decompressor: &mut Decompressor,
+    resizer: &mut fr::Resizer,
+) -> Result<(u32, u32, u64), Box<dyn std::error::Error>> {
+    if is_jpeg(data) {
         // Fast JPEG decoding with turbojpeg
-        let header = decompressor.read_header(&mmap)?;
+        let header = decompressor.read_header(data)?;
         let (orig_width, orig_height) = (header.width as u32, header.height as u32);
 
         let scale_ratio = orig_width / THUMB_WIDTH;
@@ -182,7 +239,7 @@ pub fn process_and_get_dimensions(
         };
 
         decompressor.set_scaling_factor(scaling_factor)?;
-        decompressor.decompress(&mmap, image)?;
+        decompressor.decompress(data, image)?;
         resize_and_save(
             pixels,
             scaled_width as u32,
@@ -193,8 +250,9 @@ pub fn process_and_get_dimensions(
             resizer,
         )
     } else {
-        // Standard image decoding for PNG and other formats
-        let reader = ImageReader::new(std::io::Cursor::new(&mmap[..])).with_guessed_format()?;
+        // Standard image decoding for PNG and other formats. Animated GIF and
+        // WebP decode to their first frame.
+        let reader = image_reader(std::io::Cursor::new(data))?;
         let img = reader.decode()?;
         let (width, height) = (img.width(), img.height());
 
@@ -348,12 +406,42 @@ pub fn find_cover_image(folder_path: &Path) -> Option<PathBuf> {
     fallback_first_path
 }
 
+/// Pick the cover among archive entry names with the same precedence as
+/// `find_cover_image`: an explicit `1001.*`, then a `_p0` page, then the first
+/// page in natural order. Rules apply to the entry's file name, so pages
+/// nested in a folder inside the archive are handled alike.
+pub fn find_cover_entry<'a>(entries: &[&'a str]) -> Option<&'a str> {
+    fn stem(entry: &str) -> &str {
+        let name = entry.rsplit('/').next().unwrap_or(entry);
+        name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+    }
+
+    let first_natural = |matches: &dyn Fn(&str) -> bool| {
+        entries
+            .iter()
+            .copied()
+            .filter(|entry| matches(entry))
+            .min_by(|a, b| natord::compare(a, b))
+    };
+
+    first_natural(&|entry| stem(entry) == "1001")
+        .or_else(|| first_natural(&|entry| stem(entry).ends_with("_p0")))
+        .or_else(|| first_natural(&|_| true))
+}
+
 /// Build the server URL that streams the file at `path`.
 pub fn file_url(path: &str) -> String {
     let encoded_path = utf8_percent_encode(path, &ENCODE_SET).to_string();
     format!("/file?path={encoded_path}")
 }
 
+/// Build the server URL that streams one entry out of the archive at `path`.
+pub fn archive_entry_url(path: &str, entry: &str) -> String {
+    let encoded_path = utf8_percent_encode(path, &ENCODE_SET);
+    let encoded_entry = utf8_percent_encode(entry, &ENCODE_SET);
+    format!("/archive?path={encoded_path}&entry={encoded_entry}")
+}
+
 fn scan_thumbnail_stats(thumb_dir: &Path) -> ThumbnailStats {
     let mut stats = ThumbnailStats::default();
 
@@ -521,7 +609,10 @@ mod tests {
         assert!(is_image_file(Path::new("page.JPG")));
         assert!(is_image_file(Path::new("page.jpeg")));
         assert!(is_image_file(Path::new("page.png")));
-        assert!(!is_image_file(Path::new("page.webp")));
+        assert!(is_image_file(Path::new("page.webp")));
+        assert!(is_image_file(Path::new("page.TIF")));
+        assert!(is_image_file(Path::new("page.jxl")));
+        assert!(!is_image_file(Path::new("page.psd")));
         assert!(!is_image_file(Path::new("page")));
 
         assert!(is_jpeg(&[0xff, 0xd8, 0xff, 0x00]));
@@ -566,8 +657,8 @@ mod tests {
     }
 
     #[test]
-    fn generates_and_reuses_png_and_jpeg_thumbnails() {
-        for extension in ["png", "jpg"] {
+    fn generates_and_reuses_thumbnails_for_every_encodable_format() {
+        for extension in ["png", "jpg", "webp", "gif", "bmp", "tiff"] {
             let dir = tempfile::tempdir().expect("create thumbnail dir");
             let source = dir.path().join(format!("source.{extension}"));
             let thumbnail = dir.path().join("thumbnail.jpg");
@@ -597,6 +688,129 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chooses_archive_cover_entry_with_directory_cover_precedence() {
+        assert_eq!(find_cover_entry(&[]), None);
+        assert_eq!(find_cover_entry(&["10.jpg", "2.jpg"]), Some("2.jpg"));
+        assert_eq!(
+            find_cover_entry(&["1.jpg", "vol/series_p0.png", "series_p1.png"]),
+            Some("vol/series_p0.png")
+        );
+        assert_eq!(
+            find_cover_entry(&["series_p0.png", "vol/1001.JPG"]),
+            Some("vol/1001.JPG")
+        );
+    }
+
+    #[test]
+    fn generates_thumbnails_from_in_memory_images() {
+        let dir = tempfile::tempdir().expect("create thumbnail dir");
+        let thumbnail = dir.path().join("thumbnail.jpg");
+        let mut data = std::io::Cursor::new(Vec::new());
+        RgbImage::from_pixel(32, 16, Rgb([120, 80, 40]))
+            .write_to(&mut data, image::ImageFormat::Png)
+            .expect("encode test image");
+        let data = data.into_inner();
+
+        assert_eq!(
+            get_image_dimensions_from_bytes(&data).expect("read dimensions"),
+            (32, 16)
+        );
+
+        let mut decompressor = Decompressor::new().expect("create JPEG decompressor");
+        let mut resizer = fr::Resizer::new();
+        let (width, height, created_size) =
+            process_bytes_and_get_dimensions(&data, &thumbnail, &mut decompressor, &mut resizer)
+                .expect("generate thumbnail");
+        assert_eq!((width, height), (32, 16));
+        assert!(created_size > 0);
+
+        let (_, _, reused_size) =
+            process_bytes_and_get_dimensions(&data, &thumbnail, &mut decompressor, &mut resizer)
+                .expect("reuse thumbnail");
+        assert_eq!(reused_size, 0);
+    }
+
+    #[test]
+    fn generates_thumbnails_from_jpeg_xl_pages() {
+        // A black 64x48 codestream spelled out by hand, as no encoder ships
+        // with the crate: one modular frame whose MA tree is a single
+        // zero-predictor leaf, with every residual zero.
+        const BLACK_JXL: &[u8] = &[
+            0xff, 0x0a, 0x0b, 0xce, 0x08, 0x81, 0x04, 0x0c, 0x00, 0x89, 0x7c, 0x3e,
+        ];
+        let dir = tempfile::tempdir().expect("create thumbnail dir");
+        let thumbnail = dir.path().join("thumbnail.jpg");
+
+        assert_eq!(
+            get_image_dimensions_from_bytes(BLACK_JXL).expect("read JXL dimensions"),
+            (64, 48)
+        );
+        let mut decompressor = Decompressor::new().expect("create JPEG decompressor");
+        let mut resizer = fr::Resizer::new();
+        let (width, height, created_size) = process_bytes_and_get_dimensions(
+            BLACK_JXL,
+            &thumbnail,
+            &mut decompressor,
+            &mut resizer,
+        )
+        .expect("generate JXL thumbnail");
+        assert_eq!((width, height), (64, 48));
+        assert!(created_size > 0);
+        image::open(&thumbnail).expect("thumbnail is a readable image");
+    }
+
+    #[test]
+    fn animated_gif_thumbnails_use_the_first_frame() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Frame, Rgba, RgbaImage};
+
+        let dir = tempfile::tempdir().expect("create thumbnail dir");
+        let thumbnail = dir.path().join("thumbnail.jpg");
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            encoder
+                .encode_frames([
+                    Frame::new(RgbaImage::from_pixel(16, 32, Rgba([255, 0, 0, 255]))),
+                    Frame::new(RgbaImage::from_pixel(16, 32, Rgba([0, 0, 255, 255]))),
+                ])
+                .expect("encode animated gif");
+        }
+
+        let mut decompressor = Decompressor::new().expect("create JPEG decompressor");
+        let mut resizer = fr::Resizer::new();
+        let (width, height, _) =
+            process_bytes_and_get_dimensions(&data, &thumbnail, &mut decompressor, &mut resizer)
+                .expect("generate gif thumbnail");
+        assert_eq!((width, height), (16, 32));
+
+        let pixel = *image::open(&thumbnail)
+            .expect("open gif thumbnail")
+            .into_rgb8()
+            .get_pixel(0, 0);
+        assert!(pixel[0] > 200 && pixel[2] < 60, "{pixel:?}");
+    }
+
+    #[test]
+    fn archive_entry_urls_and_hashes_identify_the_entry() {
+        assert_eq!(
+            archive_entry_url("/漫画/A&B.cbz", "ch 1/001.jpg"),
+            "/archive?path=%2F%E6%BC%AB%E7%94%BB%2FA%26B.cbz&entry=ch%201%2F001.jpg"
+        );
+
+        let file = tempfile::NamedTempFile::new().expect("create archive file");
+        let metadata = file.as_file().metadata().expect("read metadata");
+        assert_ne!(
+            get_archive_entry_hash(&metadata, "1.jpg"),
+            get_archive_entry_hash(&metadata, "2.jpg")
+        );
+        assert_ne!(
+            get_archive_entry_hash(&metadata, "1.jpg"),
+            get_thumbnail_hash(&metadata)
+        );
+    }
+
     #[test]
     fn computes_thumbnail_height_without_float_rounding_or_zero_height() {
         assert_eq!(thumbnail_height(32, 16).expect("scale 2:1"), 128);
@@ -1,9 +1,10 @@
 //! Menu-bar tray: the control center for library management.
 //!
 //! The menu is the only place to import libraries, set the cache directory, and
-//! clear the cache — the web UI is purely for reading. The menu is rebuilt
-//! after each mutating action so the library submenu and the cache-info footer
-//! stay current.
+//! clear the cache — the web UI is purely for reading. It also shows the PIN
+//! that pairs a new device, and revokes paired ones. The menu is rebuilt after
+//! each mutating action so the library submenu, the PIN and the cache-info
+//! footer stay current.
 
 use tauri::menu::{IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
 use tauri::tray::TrayIconBuilder;
@@ -83,6 +84,47 @@ fn build_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
 
     let sep2 = PredefinedMenuItem::separator(app)?;
 
+    // Pairing: the PIN a new browser enters (replaced once used), and one entry
+    // per paired device (click revokes it, after confirming).
+    let pin = MenuItem::with_id(
+        app,
+        "pin",
+        format!("配对码 {}", crate::auth::pin(app).unwrap_or_default()),
+        false,
+        None::<&str>,
+    )?;
+    let new_pin = MenuItem::with_id(app, "new-pin", "更换配对码", true, None::<&str>)?;
+    let devices = crate::auth::devices(app);
+    let device_items: Vec<MenuItem<Wry>> = if devices.is_empty() {
+        vec![MenuItem::with_id(
+            app,
+            "device:none",
+            pad_label("（暂无设备）"),
+            false,
+            None::<&str>,
+        )?]
+    } else {
+        devices
+            .iter()
+            .map(|device| {
+                MenuItem::with_id(
+                    app,
+                    format!("device:{}", device.id),
+                    pad_label(&device.name),
+                    true,
+                    None::<&str>,
+                )
+            })
+            .collect::<tauri::Result<Vec<_>>>()?
+    };
+    let device_refs: Vec<&dyn IsMenuItem<Wry>> = device_items
+        .iter()
+        .map(|i| i as &dyn IsMenuItem<Wry>)
+        .collect();
+    let paired = Submenu::with_items(app, "已配对设备", true, &device_refs)?;
+
+    let sep3 = PredefinedMenuItem::separator(app)?;
+
     // Read-only cache info, with a clickable (confirmed) clear action below it.
     // A macOS menu item can't both open a submenu and fire on click, so the
     // info is shown as an inline disabled line rather than 清除缓存's submenu.
@@ -96,13 +138,14 @@ fn build_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
     )?;
     let clean = MenuItem::with_id(app, "clean", "清除缓存", true, None::<&str>)?;
 
-    let sep3 = PredefinedMenuItem::separator(app)?;
+    let sep4 = PredefinedMenuItem::separator(app)?;
     let quit = MenuItem::with_id(app, "quit", "退出", true, None::<&str>)?;
 
     Menu::with_items(
         app,
         &[
-            &open, &sep1, &library, &import, &set_cache, &sep2, &info, &clean, &sep3, &quit,
+            &open, &sep1, &library, &import, &set_cache, &sep2, &pin, &new_pin, &paired, &sep3,
+            &info, &clean, &sep4, &quit,
         ],
     )
 }
@@ -129,6 +172,7 @@ fn handle_event(app: &AppHandle, id: &str) {
         "import" => spawn_import(app.clone()),
         "set-cache" => spawn_set_cache(app.clone()),
         "clean" => spawn_clean(app.clone()),
+        "new-pin" => crate::auth::rotate_pin(app),
         other if other.starts_with("lib:") => {
             let lib_id = other.trim_start_matches("lib:");
             if lib_id != "none"
@@ -137,6 +181,12 @@ fn handle_event(app: &AppHandle, id: &str) {
                 let _ = crate::scanner::utils::open_path_native(app.clone(), path);
             }
         }
+        other if other.starts_with("device:") => {
+            let device_id = other.trim_start_matches("device:");
+            if device_id != "none" {
+                spawn_revoke(app.clone(), device_id.to_string());
+            }
+        }
         _ => {}
     }
 }
@@ -146,26 +196,11 @@ fn spawn_import(app: AppHandle) {
         let Some(paths) = crate::scanner::utils::pick_directories_impl(&app).await else {
             return;
         };
-        let app2 = app.clone();
-        let created = tokio::task::spawn_blocking(move || {
-            let mut created = false;
-            for path in paths {
-                match crate::library::import(&app2, &path) {
-                    Ok(outcome) => created |= outcome.created,
-                    Err(e) => error!(path = %path, error = %e, "Import failed"),
-                }
+        // The import job rebuilds the menu once its library is in.
+        for path in paths {
+            if let Err(e) = crate::jobs::submit(&app, crate::jobs::Task::Import(path.clone())) {
+                error!(path = %path, error = %e, "Import failed");
             }
-            created
-        })
-        .await
-        .unwrap_or_else(|e| {
-            error!(error = %e, "Import task panicked");
-            false
-        });
-
-        if created {
-            crate::server::rebuild_allowed_roots(&app);
-            rebuild(&app);
         }
     });
 }
@@ -196,6 +231,7 @@ fn spawn_set_cache(app: AppHandle) {
 
         if updated {
             crate::server::rebuild_allowed_roots(&app);
+            crate::watcher::rebuild(&app);
             rebuild(&app);
         }
     });
@@ -207,16 +243,26 @@ fn spawn_clean(app: AppHandle) {
         {
             return;
         }
-        let app2 = app.clone();
-        // (0, 0) => no age/size threshold => clear everything.
-        match tokio::task::spawn_blocking(move || {
-            crate::thumbnail::clean_thumbnail_cache(app2, Some(0), Some(0))
-        })
-        .await
-        {
-            Ok(Ok(_)) => rebuild(&app),
-            Ok(Err(e)) => error!(error = %e, "Failed to clean thumbnail cache"),
-            Err(e) => error!(error = %e, "Clean cache task panicked"),
+        if let Err(e) = crate::jobs::submit(&app, crate::jobs::Task::CleanCache) {
+            error!(error = %e, "Failed to clean cache");
+        }
+    });
+}
+
+fn spawn_revoke(app: AppHandle, device_id: String) {
+    tauri::async_runtime::spawn(async move {
+        let Some(device) = crate::auth::devices(&app)
+            .into_iter()
+            .find(|device| device.id == device_id)
+        else {
+            return;
+        };
+        let message = format!("确认撤销「{}」？该设备需要重新配对。", device.name);
+        if !confirm(&app, "撤销设备", &message).await {
+            return;
+        }
+        if let Err(e) = crate::auth::revoke(&app, &device.id) {
+            error!(error = %e, "Failed to revoke device");
         }
     });
 }
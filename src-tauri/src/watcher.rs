@@ -0,0 +1,240 @@
+//! Filesystem watcher that keeps the catalog in step with library folders.
+//!
+//! One debounced watcher (FSEvents on macOS, inotify on Linux) covers every
+//! imported library root. Each batch of events is mapped to the entries
+//! directly under a root that it touched — a comic folder or archive, or an
+//! author directory — and only those are re-scanned, as an update job.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{DebounceEventResult, Debouncer, new_debouncer};
+use tauri::{AppHandle, Manager};
+use tracing::{error, info, warn};
+
+use crate::jobs::{self, Task};
+use crate::scanner::utils::is_hidden;
+
+/// Quiet time before a batch of events is handled, so a copy in progress is
+/// scanned as it settles rather than on every write.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+pub struct LibraryWatcher(Mutex<Watched>);
+
+struct Watched {
+    /// `None` when the platform watcher could not be started.
+    debouncer: Option<Debouncer<RecommendedWatcher>>,
+    roots: Vec<PathBuf>,
+}
+
+/// An imported library root. FSEvents reports resolved paths, so events are
+/// matched against the canonical form too.
+struct WatchedRoot {
+    id: String,
+    root: PathBuf,
+    canonical: PathBuf,
+}
+
+/// What one library needs re-scanned after a batch of events.
+#[derive(Debug, PartialEq)]
+enum Change {
+    /// The root itself changed.
+    Library,
+    /// Entries directly under the root.
+    Entries(BTreeSet<PathBuf>),
+}
+
+pub fn init(app: &AppHandle) {
+    let handler_app = app.clone();
+    let debouncer = new_debouncer(DEBOUNCE, move |result| handle(&handler_app, result))
+        .map_err(|e| error!(error = %e, "Failed to start library watcher"))
+        .ok();
+    app.manage(LibraryWatcher(Mutex::new(Watched {
+        debouncer,
+        roots: Vec::new(),
+    })));
+    rebuild(app);
+}
+
+/// Watch exactly the imported library roots. Called whenever libraries are
+/// imported or removed, or the library DB is re-opened; a root that could not
+/// be watched is retried here.
+pub fn rebuild(app: &AppHandle) {
+    let Some(state) = app.try_state::<LibraryWatcher>() else {
+        return;
+    };
+    let Ok(mut watched) = state.0.lock() else {
+        return;
+    };
+    let Watched { debouncer, roots } = &mut *watched;
+    let Some(debouncer) = debouncer.as_mut() else {
+        return;
+    };
+    let wanted: Vec<PathBuf> = library_roots(app)
+        .into_iter()
+        .map(|library| library.root)
+        .collect();
+
+    roots.retain(|root| {
+        if wanted.contains(root) {
+            return true;
+        }
+        if let Err(e) = debouncer.watcher().unwatch(root) {
+            warn!(error = %e, path = %root.display(), "Failed to unwatch library");
+        }
+        false
+    });
+    for root in wanted {
+        if roots.contains(&root) {
+            continue;
+        }
+        match debouncer.watcher().watch(&root, RecursiveMode::Recursive) {
+            Ok(()) => {
+                info!(path = %root.display(), "Watching library");
+                roots.push(root);
+            }
+            Err(e) => warn!(error = %e, path = %root.display(), "Failed to watch library"),
+        }
+    }
+}
+
+fn handle(app: &AppHandle, result: DebounceEventResult) {
+    let events = match result {
+        Ok(events) => events,
+        Err(e) => {
+            warn!(error = %e, "Library watcher error");
+            return;
+        }
+    };
+    let libraries = library_roots(app);
+    for (id, change) in changes(&libraries, events.iter().map(|event| event.path.as_path())) {
+        let task = match change {
+            Change::Library => Task::Refresh(id.clone()),
+            Change::Entries(entries) => Task::Update {
+                library_id: id.clone(),
+                entries,
+            },
+        };
+        if let Err(e) = jobs::submit(app, task) {
+            warn!(library_id = %id, error = %e, "Failed to update watched library");
+        }
+    }
+}
+
+fn library_roots(app: &AppHandle) -> Vec<WatchedRoot> {
+    crate::library::list_for_menu(app)
+        .into_iter()
+        .map(|(id, _, path)| {
+            let root = PathBuf::from(path);
+            let canonical = root.canonicalize().unwrap_or_else(|_| root.clone());
+            WatchedRoot {
+                id,
+                root,
+                canonical,
+            }
+        })
+        .collect()
+}
+
+/// Group event `paths` by library: each becomes the entry directly under the
+/// root it falls in, or a whole-library change for the root itself. Hidden
+/// entries, which scans skip, are dropped.
+fn changes<'a>(
+    libraries: &[WatchedRoot],
+    paths: impl IntoIterator<Item = &'a Path>,
+) -> HashMap<String, Change> {
+    let mut changes = HashMap::new();
+    for path in paths {
+        // The innermost root wins when libraries are nested.
+        let Some((library, rest)) = libraries
+            .iter()
+            .filter_map(|library| {
+                path.strip_prefix(&library.root)
+                    .or_else(|_| path.strip_prefix(&library.canonical))
+                    .ok()
+                    .map(|rest| (library, rest))
+            })
+            .min_by_key(|(_, rest)| rest.components().count())
+        else {
+            continue;
+        };
+
+        let Some(first) = rest.components().next() else {
+            changes.insert(library.id.clone(), Change::Library);
+            continue;
+        };
+        let entry = library.root.join(first);
+        if is_hidden(&entry) {
+            continue;
+        }
+        if let Change::Entries(entries) = changes
+            .entry(library.id.clone())
+            .or_insert_with(|| Change::Entries(BTreeSet::new()))
+        {
+            entries.insert(entry);
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(id: &str, root: &str, canonical: &str) -> WatchedRoot {
+        WatchedRoot {
+            id: id.to_string(),
+            root: PathBuf::from(root),
+            canonical: PathBuf::from(canonical),
+        }
+    }
+
+    fn entries(paths: &[&str]) -> Change {
+        Change::Entries(paths.iter().map(PathBuf::from).collect())
+    }
+
+    #[test]
+    fn maps_event_paths_to_top_level_entries_of_their_library() {
+        let libraries = [
+            root("comics", "/Volumes/Comics", "/Volumes/Comics"),
+            root("books", "/tmp/Books", "/private/tmp/Books"),
+            root("nested", "/Volumes/Comics/Nested", "/Volumes/Comics/Nested"),
+        ];
+        let paths = [
+            "/Volumes/Comics/One Piece/001.jpg",
+            "/Volumes/Comics/One Piece",
+            "/Volumes/Comics/New.cbz",
+            "/Volumes/Comics/.DS_Store",
+            "/private/tmp/Books/Author/Renamed.txt",
+            "/tmp/Books/Author/Old.txt",
+            "/Volumes/Comics/Nested/Inner/1.png",
+            "/elsewhere/file.txt",
+        ];
+
+        let changes = changes(&libraries, paths.iter().map(Path::new));
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes["comics"],
+            entries(&["/Volumes/Comics/New.cbz", "/Volumes/Comics/One Piece"])
+        );
+        assert_eq!(changes["books"], entries(&["/tmp/Books/Author"]));
+        assert_eq!(
+            changes["nested"],
+            entries(&["/Volumes/Comics/Nested/Inner"])
+        );
+    }
+
+    #[test]
+    fn a_change_to_the_root_rescans_the_whole_library() {
+        let libraries = [root("comics", "/Comics", "/Comics")];
+        let paths = ["/Comics/One Piece", "/Comics", "/Comics/Two"];
+
+        let changes = changes(&libraries, paths.iter().map(Path::new));
+
+        assert_eq!(changes["comics"], Change::Library);
+    }
+}
@@ -1,10 +1,16 @@
 import '@/styles/index.css'
-import { useEffect } from 'react'
+import { useEffect, useState } from 'react'
 import { AppLayout } from '@/components/layout/app-layout'
+import { PairingScreen } from '@/components/layout/pairing-screen'
+import { useServerEvents } from '@/hooks/use-server-events'
+import { fetchAuthorized } from '@/lib/auth-api'
+import { UNAUTHORIZED_EVENT } from '@/lib/http'
 import { useLibraryStore } from '@/store/library'
 import { useProgressStore } from '@/store/progress'
 
-function App() {
+function Reader() {
+  useServerEvents()
+
   useEffect(() => {
     void useLibraryStore.getState().hydrate()
     void useProgressStore.getState().hydrate()
@@ -35,4 +41,38 @@ function App() {
   return <AppLayout />
 }
 
+/** Ask an unpaired browser for the tray's PIN before loading the library. */
+function App() {
+  const [authorized, setAuthorized] = useState<boolean | null>(null)
+
+  useEffect(() => {
+    void fetchAuthorized().then(setAuthorized, (error: unknown) => {
+      // Unreachable backend: load as before and let each request fail.
+      console.error('Failed to check pairing:', error)
+      setAuthorized(true)
+    })
+
+    // A revoked device is sent back to pairing.
+    const requirePairing = () => {
+      setAuthorized(false)
+    }
+    window.addEventListener(UNAUTHORIZED_EVENT, requirePairing)
+    return () => {
+      window.removeEventListener(UNAUTHORIZED_EVENT, requirePairing)
+    }
+  }, [])
+
+  if (authorized === null) return null
+  if (!authorized) {
+    return (
+      <PairingScreen
+        onPaired={() => {
+          setAuthorized(true)
+        }}
+      />
+    )
+  }
+  return <Reader />
+}
+
 export default App
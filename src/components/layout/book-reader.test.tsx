@@ -65,9 +65,10 @@ const book = {
 const content = {
   lines: ['Chapter 1', 'Body', 'Chapter 2'],
   chapters: [
-    { title: 'Chapter 1', lineIndex: 0 },
-    { title: 'Chapter 2', lineIndex: 2 },
+    { title: 'Chapter 1', lineIndex: 0, level: 0 },
+    { title: 'Chapter 2', lineIndex: 2, level: 0 },
   ],
+  encoding: 'UTF-8',
 }
 
 describe('BookReader', () => {
@@ -146,6 +147,29 @@ describe('BookReader', () => {
     )
   })
 
+  it('collapses and expands the chapters of a volume', async () => {
+    vi.mocked(parseBook).mockResolvedValue({
+      ...content,
+      chapters: [
+        { title: 'Volume One', lineIndex: 0, level: 0 },
+        { title: 'Opening', lineIndex: 1, level: 1, parent: 0 },
+        { title: 'Closing', lineIndex: 2, level: 1, parent: 0 },
+      ],
+    })
+    render(<BookReader bookId="book-1" />)
+    await screen.findByText('Body')
+
+    fireEvent.click(screen.getByTitle('展开目录'))
+    expect(screen.getByText('Closing')).toBeInTheDocument()
+
+    fireEvent.click(screen.getByTitle('收起分卷'))
+    expect(screen.queryByText('Closing')).not.toBeInTheDocument()
+    expect(screen.getByText('Volume One')).toBeInTheDocument()
+
+    fireEvent.click(screen.getByTitle('展开分卷'))
+    expect(screen.getByText('Closing')).toBeInTheDocument()
+  })
+
   it('handles toolbar, chapter, progress and continue-reading actions', async () => {
     render(<BookReader bookId="book-1" showReading />)
     await screen.findByText('Body')
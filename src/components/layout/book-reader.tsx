@@ -1,4 +1,10 @@
-import { SquareMenu, Star, StepForward, Trash2 } from 'lucide-react'
+import {
+  ChevronRight,
+  SquareMenu,
+  Star,
+  StepForward,
+  Trash2,
+} from 'lucide-react'
 import {
   useEffect,
   useEffectEvent,
@@ -58,7 +64,26 @@ function TableOfContents({
   onClose,
 }: TableOfContentsProps) {
   const tocRef = useRef<HTMLDivElement>(null)
+  const [collapsedVolumes, setCollapsedVolumes] = useState<Set<number>>(
+    () => new Set(),
+  )
   const favoriteSet = new Set(favorites)
+  const parentSet = new Set(chapters.map((chapter) => chapter.parent))
+  // Parents always precede their children, so one pass resolves ancestry.
+  const hidden: boolean[] = []
+  chapters.forEach((chapter, index) => {
+    const parent = chapter.parent
+    hidden[index] =
+      parent !== undefined && (collapsedVolumes.has(parent) || hidden[parent])
+  })
+
+  const toggleVolume = (index: number) => {
+    setCollapsedVolumes((prev) => {
+      const next = new Set(prev)
+      if (!next.delete(index)) next.add(index)
+      return next
+    })
+  }
 
   useClickOutside(tocRef, onClose, !isCollapsed)
 
@@ -71,22 +96,47 @@ function TableOfContents({
       )}
     >
       <ScrollArea viewportClassName="h-full" className="pb-12">
-        {chapters.map((chapter) => {
+        {chapters.map((chapter, index) => {
+          if (hidden[index]) return null
           const isFavorite = favoriteSet.has(chapter.lineIndex)
+          const isVolume = parentSet.has(index)
+          const isExpanded = !collapsedVolumes.has(index)
           const isActiveLine = currentChapterTitle === chapter.title
+          const isSuggested = chapter.confidence !== undefined
           return (
             <div
               key={chapter.lineIndex}
               className="group hover:bg-overlay flex w-full cursor-pointer items-center gap-2 px-4 py-2 text-left text-sm"
+              style={{ paddingLeft: `${1 + chapter.level}rem` }}
               onClick={() => {
                 onSelect(chapter.lineIndex)
               }}
             >
+              {isVolume && (
+                <button
+                  type="button"
+                  className="shrink-0"
+                  title={isExpanded ? '收起分卷' : '展开分卷'}
+                  onClick={(e) => {
+                    e.stopPropagation()
+                    toggleVolume(index)
+                  }}
+                >
+                  <ChevronRight
+                    className={cn(
+                      'h-4 w-4 transition-transform',
+                      isExpanded && 'rotate-90',
+                    )}
+                  />
+                </button>
+              )}
               <span
                 className={cn(
                   'min-w-0 flex-1 truncate',
+                  isSuggested && 'text-subtle',
                   isActiveLine && 'text-love',
                 )}
+                title={isSuggested ? '推测章节' : undefined}
               >
                 {chapter.title}
               </span>
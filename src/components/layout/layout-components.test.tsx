@@ -1,15 +1,34 @@
-import { act, fireEvent, render, screen, within } from '@testing-library/react'
-import { beforeEach, describe, expect, it, vi } from 'vitest'
+import {
+  act,
+  fireEvent,
+  render,
+  screen,
+  waitFor,
+  within,
+} from '@testing-library/react'
+import { afterEach, beforeEach, describe, expect, it, vi } from 'vitest'
 import { LibraryArea } from '@/components/layout/library-area'
 import { Mask } from '@/components/layout/mask'
+import { PairingScreen } from '@/components/layout/pairing-screen'
+import { ProfileSwitcher } from '@/components/layout/profile-switcher'
 import { TabArea } from '@/components/layout/tab-area'
 import { TabNav } from '@/components/layout/tab-nav'
 import { ThemeSwitcher } from '@/components/layout/theme-switcher'
+import * as authApi from '@/lib/auth-api'
+import * as profileApi from '@/lib/profile-api'
 import { useLibraryStore } from '@/store/library'
 import { useTabsStore } from '@/store/tabs'
 import { useUIStore } from '@/store/ui'
 import { LibraryType } from '@/types/library'
 
+vi.mock('@/lib/auth-api', () => ({
+  pairDevice: vi.fn(),
+}))
+vi.mock('@/lib/profile-api', () => ({
+  createProfile: vi.fn(),
+  fetchProfiles: vi.fn(),
+  selectProfile: vi.fn(),
+}))
 vi.mock('@/components/layout/book-library', () => ({
   BookLibrary: ({ selectedLibrary }: { selectedLibrary: { id: string } }) => (
     <div>book-library:{selectedLibrary.id}</div>
@@ -38,6 +57,10 @@ describe('layout components', () => {
     useUIStore.setState(useUIStore.getInitialState(), true)
   })
 
+  afterEach(() => {
+    vi.restoreAllMocks()
+  })
+
   it('switches themes and marks the active choice', () => {
     render(<ThemeSwitcher />)
 
@@ -68,6 +91,7 @@ describe('layout components', () => {
           type: LibraryType.book,
           createdAt: 1,
           sortOrder: 0,
+          revision: 0,
         },
         comics: {
           id: 'comics',
@@ -76,6 +100,7 @@ describe('layout components', () => {
           type: LibraryType.comic,
           createdAt: 2,
           sortOrder: 1,
+          revision: 0,
         },
       },
     })
@@ -167,4 +192,51 @@ describe('layout components', () => {
     fireEvent.keyDown(window, { code: 'KeyD' })
     expect(useUIStore.getState().isMiddleCollapsed).toBe(true)
   })
+
+  it('pairs with the tray PIN and asks again after a wrong one', async () => {
+    const pairDevice = vi.mocked(authApi.pairDevice)
+    pairDevice.mockResolvedValueOnce(false).mockResolvedValueOnce(true)
+    const onPaired = vi.fn()
+    render(<PairingScreen onPaired={onPaired} />)
+
+    fireEvent.change(screen.getByLabelText('配对码'), {
+      target: { value: '12-34 56' },
+    })
+    fireEvent.change(screen.getByLabelText('设备名称'), {
+      target: { value: 'iPad' },
+    })
+    fireEvent.click(screen.getByText('配对'))
+    expect(await screen.findByRole('alert')).toHaveTextContent('配对码错误')
+    expect(pairDevice).toHaveBeenCalledWith('123456', 'iPad')
+    expect(screen.getByLabelText('配对码')).toHaveValue('')
+    expect(onPaired).not.toHaveBeenCalled()
+
+    fireEvent.change(screen.getByLabelText('配对码'), {
+      target: { value: '654321' },
+    })
+    fireEvent.click(screen.getByText('配对'))
+    await waitFor(() => {
+      expect(onPaired).toHaveBeenCalledTimes(1)
+    })
+  })
+
+  it('shows the current profile and switches to another', async () => {
+    // jsdom reports the reload that follows a switch as not implemented.
+    vi.spyOn(console, 'error').mockImplementation(() => undefined)
+    vi.mocked(profileApi.fetchProfiles).mockResolvedValue([
+      { id: 'default', name: '默认', createdAt: 0, current: false },
+      { id: 'kid', name: '小明', createdAt: 5, current: true },
+    ])
+    const selectProfile = vi
+      .mocked(profileApi.selectProfile)
+      .mockResolvedValue(undefined)
+    render(<ProfileSwitcher />)
+
+    const select = await screen.findByLabelText('阅读档案')
+    expect(select).toHaveValue('kid')
+    fireEvent.change(select, { target: { value: 'default' } })
+    await waitFor(() => {
+      expect(selectProfile).toHaveBeenCalledWith('default')
+    })
+  })
 })
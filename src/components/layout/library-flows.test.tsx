@@ -79,6 +79,10 @@ vi.mock('@/components/layout/book-reader', () => ({
   ),
 }))
 
+vi.mock('@/components/layout/profile-switcher', () => ({
+  ProfileSwitcher: () => <div data-testid="profile-switcher" />,
+}))
+
 vi.mock('@/components/layout/theme-switcher', () => ({
   ThemeSwitcher: () => <div data-testid="theme-switcher" />,
 }))
@@ -105,6 +109,7 @@ function library(overrides: Partial<Library>): Library {
     type: LibraryType.comic,
     createdAt: 1,
     sortOrder: 0,
+    revision: 0,
     ...overrides,
   }
 }
@@ -221,12 +226,14 @@ describe('library flow components', () => {
           name: 'Comics',
           type: LibraryType.comic,
           sortOrder: 1,
+          revision: 0,
         }),
         books: library({
           id: 'books',
           name: 'Books',
           type: LibraryType.book,
           sortOrder: 0,
+          revision: 0,
         }),
       },
       refreshLibrary,
@@ -0,0 +1,84 @@
+import { useState } from 'react'
+import { Button } from '@/components/ui/button'
+import { pairDevice } from '@/lib/auth-api'
+
+/** A guess at what this device is, to name it in the tray's device list. */
+function guessDeviceName() {
+  const agent = navigator.userAgent
+  if (agent.includes('iPad')) return 'iPad'
+  if (agent.includes('iPhone')) return 'iPhone'
+  if (agent.includes('Android')) return 'Android'
+  if (agent.includes('Macintosh')) return 'Mac'
+  if (agent.includes('Windows')) return 'Windows'
+  return ''
+}
+
+interface PairingScreenProps {
+  onPaired: () => void
+}
+
+export function PairingScreen({ onPaired }: PairingScreenProps) {
+  const [pin, setPin] = useState('')
+  const [name, setName] = useState(guessDeviceName)
+  const [message, setMessage] = useState<string | null>(null)
+  const [isPairing, setIsPairing] = useState(false)
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault()
+    setIsPairing(true)
+    try {
+      if (await pairDevice(pin, name)) {
+        onPaired()
+        return
+      }
+      setMessage('配对码错误，请核对菜单栏中的配对码')
+      setPin('')
+    } catch (error) {
+      setMessage('配对失败: ' + String(error))
+    } finally {
+      setIsPairing(false)
+    }
+  }
+
+  return (
+    <main className="bg-base flex h-dvh w-screen items-center justify-center">
+      <form
+        aria-label="配对设备"
+        className="flex w-64 flex-col gap-3"
+        onSubmit={(e) => {
+          void handleSubmit(e)
+        }}
+      >
+        <p className="text-sm">输入菜单栏中显示的配对码</p>
+        <input
+          aria-label="配对码"
+          inputMode="numeric"
+          autoComplete="one-time-code"
+          maxLength={6}
+          value={pin}
+          onChange={(e) => {
+            setPin(e.target.value.replace(/\D/g, ''))
+          }}
+          className="bg-overlay rounded-sm px-3 py-2 text-center text-lg tracking-widest outline-none"
+        />
+        <input
+          aria-label="设备名称"
+          placeholder="设备名称"
+          value={name}
+          onChange={(e) => {
+            setName(e.target.value)
+          }}
+          className="bg-overlay rounded-sm px-3 py-2 text-sm outline-none"
+        />
+        {message && (
+          <p role="alert" className="text-love text-sm">
+            {message}
+          </p>
+        )}
+        <Button type="submit" className="h-9" disabled={isPairing || !pin}>
+          配对
+        </Button>
+      </form>
+    </main>
+  )
+}
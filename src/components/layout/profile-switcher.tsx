@@ -0,0 +1,60 @@
+import { useEffect, useState } from 'react'
+import {
+  createProfile,
+  fetchProfiles,
+  selectProfile,
+  type Profile,
+} from '@/lib/profile-api'
+
+const NEW_PROFILE = '__new__'
+
+/**
+ * Pick the reader profile this browser reads under. Switching reloads the
+ * page, so every store hydrates from the new profile's progress.
+ */
+export function ProfileSwitcher() {
+  const [profiles, setProfiles] = useState<Profile[]>([])
+
+  useEffect(() => {
+    void fetchProfiles().then(setProfiles, (error: unknown) => {
+      console.error('Failed to load profiles:', error)
+    })
+  }, [])
+
+  const current = profiles.find((profile) => profile.current)
+
+  const handleChange = async (value: string) => {
+    try {
+      let id = value
+      if (value === NEW_PROFILE) {
+        const name = window.prompt('新档案名称')?.trim()
+        if (!name) return
+        id = (await createProfile(name)).id
+      }
+      await selectProfile(id)
+      window.location.reload()
+    } catch (error) {
+      console.error('Failed to switch profile:', error)
+    }
+  }
+
+  if (profiles.length === 0) return null
+
+  return (
+    <select
+      aria-label="阅读档案"
+      value={current?.id ?? ''}
+      onChange={(e) => {
+        void handleChange(e.target.value)
+      }}
+      className="bg-overlay h-8 w-full rounded-full px-3 text-sm outline-none"
+    >
+      {profiles.map((profile) => (
+        <option key={profile.id} value={profile.id}>
+          {profile.name}
+        </option>
+      ))}
+      <option value={NEW_PROFILE}>新建档案…</option>
+    </select>
+  )
+}
@@ -13,6 +13,7 @@ import {
   verticalListSortingStrategy,
 } from '@dnd-kit/sortable'
 import { BookImage, LibraryBig, RefreshCw, Trash2 } from 'lucide-react'
+import { ProfileSwitcher } from '@/components/layout/profile-switcher'
 import { ThemeSwitcher } from '@/components/layout/theme-switcher'
 import { Button } from '@/components/ui/button'
 import { ScrollArea } from '@/components/ui/scroll-area'
@@ -217,7 +218,8 @@ export function Sidebar() {
         </DndContext>
       </ScrollArea>
 
-      <div className="p-4">
+      <div className="flex flex-col gap-2 p-4">
+        <ProfileSwitcher />
         <ThemeSwitcher />
       </div>
     </aside>
@@ -0,0 +1,106 @@
+import { renderHook } from '@testing-library/react'
+import { beforeEach, describe, expect, it, vi } from 'vitest'
+import { useServerEvents } from '@/hooks/use-server-events'
+import { useLibraryStore } from '@/store/library'
+import { useProgressStore } from '@/store/progress'
+import type { Comic } from '@/types/library'
+
+class FakeEventSource {
+  static instances: FakeEventSource[] = []
+  onopen: (() => void) | null = null
+  onmessage: ((message: MessageEvent<string>) => void) | null = null
+  close = vi.fn()
+
+  constructor(readonly url: string) {
+    FakeEventSource.instances.push(this)
+  }
+
+  emit(data: unknown) {
+    this.onmessage?.({ data: JSON.stringify(data) } as MessageEvent<string>)
+  }
+}
+
+const comic: Comic = {
+  id: 'comic-1',
+  title: 'Comic',
+  path: '/comics/Comic',
+  cover: '',
+  libraryId: 'library-1',
+  createdAt: 1,
+  starred: false,
+  deleted: false,
+}
+
+describe('useServerEvents', () => {
+  beforeEach(() => {
+    FakeEventSource.instances = []
+    vi.stubGlobal('EventSource', FakeEventSource)
+    useLibraryStore.setState({
+      comics: { 'comic-1': comic },
+      hydrate: vi.fn().mockResolvedValue(undefined),
+    })
+    useProgressStore.setState({
+      comics: {},
+      books: { 'book-1': { current: 1, total: 9, percent: 11, lastRead: 1 } },
+      favoriteChapters: {},
+      hydrate: vi.fn().mockResolvedValue(undefined),
+    })
+  })
+
+  it('applies pushed tags and progress without writing them back', () => {
+    const fetchMock = vi.fn()
+    vi.stubGlobal('fetch', fetchMock)
+    const { unmount } = renderHook(() => {
+      useServerEvents()
+    })
+    const [source] = FakeEventSource.instances
+    expect(source?.url).toBe('/api/events')
+
+    source?.emit({
+      type: 'comicTagsChanged',
+      comicId: 'comic-1',
+      tags: { starred: true },
+    })
+    source?.emit({
+      type: 'comicProgressUpdated',
+      comicId: 'comic-1',
+      progress: { current: 3, total: 10, percent: 30, lastRead: 5 },
+    })
+    source?.emit({
+      type: 'bookProgressUpdated',
+      bookId: 'book-1',
+      progress: null,
+    })
+    source?.emit({ type: 'favoritesUpdated', bookId: 'book-1', lines: [4, 8] })
+
+    expect(useLibraryStore.getState().comics['comic-1']?.starred).toBe(true)
+    expect(useLibraryStore.getState().comics['comic-1']?.deleted).toBe(false)
+    expect(useProgressStore.getState().comics['comic-1']?.percent).toBe(30)
+    expect(useProgressStore.getState().books['book-1']).toBeUndefined()
+    expect(
+      useProgressStore.getState().favoriteChapters['book-1'],
+    ).toEqual([4, 8])
+    expect(fetchMock).not.toHaveBeenCalled()
+
+    unmount()
+    expect(source?.close).toHaveBeenCalled()
+  })
+
+  it('re-fetches the catalog on library events and everything on reconnect', () => {
+    renderHook(() => {
+      useServerEvents()
+    })
+    const [source] = FakeEventSource.instances
+    const hydrateLibrary = useLibraryStore.getState().hydrate
+    const hydrateProgress = useProgressStore.getState().hydrate
+
+    source?.onopen?.()
+    source?.emit({ type: 'libraryRefreshed', libraryId: 'library-1' })
+    expect(hydrateLibrary).toHaveBeenCalledTimes(1)
+    expect(hydrateProgress).not.toHaveBeenCalled()
+
+    source?.onopen?.()
+    expect(hydrateLibrary).toHaveBeenCalledTimes(2)
+    expect(hydrateProgress).toHaveBeenCalledTimes(1)
+  })
+})
@@ -0,0 +1,57 @@
+import { useEffect } from 'react'
+import { useLibraryStore } from '@/store/library'
+import { useProgressStore } from '@/store/progress'
+import type { ServerEvent } from '@/types/library'
+
+export function applyServerEvent(event: ServerEvent) {
+  const library = useLibraryStore.getState()
+  const progress = useProgressStore.getState()
+  switch (event.type) {
+    case 'libraryImported':
+    case 'libraryRefreshed':
+    case 'libraryRemoved':
+      void library.hydrate()
+      break
+    case 'comicTagsChanged':
+      library.applyComicTags(event.comicId, event.tags)
+      break
+    case 'bookTagsChanged':
+      library.applyBookTags(event.bookId, event.tags)
+      break
+    case 'comicProgressUpdated':
+      progress.applyComicProgress(event.comicId, event.progress)
+      break
+    case 'bookProgressUpdated':
+      progress.applyBookProgress(event.bookId, event.progress)
+      break
+    case 'favoritesUpdated':
+      progress.applyChapterFavorites(event.bookId, event.lines)
+      break
+    case 'lagged':
+      void library.hydrate()
+      void progress.hydrate()
+      break
+  }
+}
+
+/**
+ * Follow catalog, tag and progress changes made on other devices. The browser
+ * reconnects a dropped stream by itself; events sent meanwhile are lost, so a
+ * reconnect re-fetches everything.
+ */
+export function useServerEvents() {
+  useEffect(() => {
+    const source = new EventSource('/api/events')
+    let connected = false
+    source.onopen = () => {
+      if (connected) applyServerEvent({ type: 'lagged' })
+      connected = true
+    }
+    source.onmessage = (message: MessageEvent<string>) => {
+      applyServerEvent(JSON.parse(message.data) as ServerEvent)
+    }
+    return () => {
+      source.close()
+    }
+  }, [])
+}
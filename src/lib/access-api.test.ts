@@ -0,0 +1,35 @@
+import { describe, expect, it, vi } from 'vitest'
+import { lockLibraries, unlockLibraries } from '@/lib/access-api'
+
+describe('access API', () => {
+  it('enters and clears an access key', async () => {
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(new Response(null, { status: 204 }))
+      .mockResolvedValueOnce(new Response(null, { status: 204 }))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await unlockLibraries('secret')
+    expect(fetchMock).toHaveBeenCalledWith('/api/access-key', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ key: 'secret' }),
+    })
+    await lockLibraries()
+    expect(fetchMock).toHaveBeenLastCalledWith('/api/access-key', {
+      method: 'DELETE',
+      headers: undefined,
+      body: undefined,
+    })
+  })
+
+  it('rejects an unknown key', async () => {
+    vi.stubGlobal(
+      'fetch',
+      vi.fn().mockResolvedValue(new Response(null, { status: 404 })),
+    )
+    await expect(unlockLibraries('guess')).rejects.toThrow(
+      'HTTP 404 for POST access key',
+    )
+  })
+})
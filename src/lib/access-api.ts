@@ -0,0 +1,27 @@
+/**
+ * Library access keys. A library with an access rule is hidden from clients
+ * outside its IP ranges unless they hold one of its keys; the backend keeps
+ * an entered key in a cookie. Catalog deltas do not cover a change in what
+ * the client sees, so go through the library store, which re-fetches the
+ * whole catalog afterwards.
+ */
+async function request(method: string, body?: unknown): Promise<Response> {
+  const res = await fetch('/api/access-key', {
+    method,
+    headers:
+      body === undefined ? undefined : { 'Content-Type': 'application/json' },
+    body: body === undefined ? undefined : JSON.stringify(body),
+  })
+  if (!res.ok) throw new Error(`HTTP ${res.status} for ${method} access key`)
+  return res
+}
+
+/** Remember `key` in this browser; rejects when no library accepts it. */
+export async function unlockLibraries(key: string): Promise<void> {
+  await request('POST', { key })
+}
+
+/** Forget this browser's access key, hiding the libraries it unlocked. */
+export async function lockLibraries(): Promise<void> {
+  await request('DELETE')
+}
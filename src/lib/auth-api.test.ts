@@ -0,0 +1,90 @@
+import { describe, expect, it, vi } from 'vitest'
+import {
+  fetchAuthorized,
+  fetchDevices,
+  forgetDevice,
+  pairDevice,
+  renameDevice,
+  setDeviceGuest,
+} from '@/lib/auth-api'
+
+describe('auth API', () => {
+  it('reads whether this browser is authorized', async () => {
+    const fetchMock = vi.fn().mockResolvedValue(
+      new Response(JSON.stringify({ authorized: false }), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      }),
+    )
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(fetchAuthorized()).resolves.toBe(false)
+    expect(fetchMock).toHaveBeenCalledWith('/api/auth/status', {
+      cache: 'no-store',
+    })
+  })
+
+  it('pairs with a PIN and reports a wrong one', async () => {
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(
+        new Response(JSON.stringify({ deviceId: 'd', token: 't' }), {
+          status: 200,
+        }),
+      )
+      .mockResolvedValueOnce(new Response('Wrong PIN', { status: 401 }))
+      .mockResolvedValueOnce(new Response(null, { status: 429 }))
+      .mockResolvedValueOnce(new Response(null, { status: 500 }))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(pairDevice('123456', 'iPad')).resolves.toBe(true)
+    expect(fetchMock).toHaveBeenCalledWith('/api/auth/pair', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ pin: '123456', name: 'iPad' }),
+    })
+    await expect(pairDevice('000000', 'iPad')).resolves.toBe(false)
+    await expect(pairDevice('000001', 'iPad')).rejects.toThrow('尝试次数过多')
+    await expect(pairDevice('123456', 'iPad')).rejects.toThrow('HTTP 500')
+  })
+  it('lists, renames, restricts and forgets devices', async () => {
+    const devices = [
+      {
+        id: 'device-1',
+        name: 'iPad',
+        userAgent: 'Safari',
+        firstSeen: 1,
+        lastSeen: 2,
+        lastIp: '192.168.1.20',
+        guest: false,
+        current: true,
+      },
+    ]
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(
+        new Response(JSON.stringify(devices), { status: 200 }),
+      )
+      .mockResolvedValueOnce(new Response(null, { status: 204 }))
+      .mockResolvedValueOnce(new Response(null, { status: 204 }))
+      .mockResolvedValueOnce(new Response(null, { status: 404 }))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(fetchDevices()).resolves.toEqual(devices)
+    await renameDevice('device-1', 'Kids iPad')
+    expect(fetchMock).toHaveBeenCalledWith('/api/devices/device-1', {
+      method: 'PUT',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ name: 'Kids iPad' }),
+    })
+    await setDeviceGuest('device-1', true)
+    expect(fetchMock).toHaveBeenCalledWith('/api/devices/device-1/guest', {
+      method: 'PUT',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ guest: true }),
+    })
+    await expect(forgetDevice('device-2')).rejects.toThrow(
+      'HTTP 404 for DELETE /api/devices/device-2',
+    )
+  })
+})
@@ -0,0 +1,68 @@
+import { apiGet } from '@/lib/http'
+
+/** Whether this browser may use the API: paired, or the Mac itself. */
+export async function fetchAuthorized(): Promise<boolean> {
+  const status = await apiGet<{ authorized: boolean }>('/api/auth/status')
+  return status.authorized
+}
+
+/**
+ * Pair this browser using the PIN shown in the menu-bar tray. The backend
+ * keeps the device token in an HTTP-only cookie, so later requests (images and
+ * the event stream included) carry it without any client code. Resolves
+ * `false` for a wrong PIN; after too many wrong ones the backend refuses
+ * further tries for a while.
+ */
+export async function pairDevice(pin: string, name: string): Promise<boolean> {
+  const res = await fetch('/api/auth/pair', {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ pin, name }),
+  })
+  if (res.status === 401) return false
+  if (res.status === 429) throw new Error('尝试次数过多，请稍后再试')
+  if (!res.ok) throw new Error(`HTTP ${res.status} for POST /api/auth/pair`)
+  return true
+}
+
+/** A paired device, as listed by the backend. */
+export interface Device {
+  id: string
+  name: string
+  userAgent: string | null
+  /** When it paired (ms since epoch). */
+  firstSeen: number
+  lastSeen: number
+  lastIp: string | null
+  /** Whether it may only read, never change the library or progress. */
+  guest: boolean
+  /** Whether it is this browser. */
+  current: boolean
+}
+
+async function deviceRequest(
+  path: string,
+  method: string,
+  body?: unknown,
+): Promise<void> {
+  const res = await fetch(path, {
+    method,
+    headers:
+      body === undefined ? undefined : { 'Content-Type': 'application/json' },
+    body: body === undefined ? undefined : JSON.stringify(body),
+  })
+  if (!res.ok) throw new Error(`HTTP ${res.status} for ${method} ${path}`)
+}
+
+export const fetchDevices = (): Promise<Device[]> =>
+  apiGet<Device[]>('/api/devices')
+
+export const renameDevice = (id: string, name: string): Promise<void> =>
+  deviceRequest(`/api/devices/${id}`, 'PUT', { name })
+
+export const setDeviceGuest = (id: string, guest: boolean): Promise<void> =>
+  deviceRequest(`/api/devices/${id}/guest`, 'PUT', { guest })
+
+/** Forget a device: its token stops working and it must pair again. */
+export const forgetDevice = (id: string): Promise<void> =>
+  deviceRequest(`/api/devices/${id}`, 'DELETE')
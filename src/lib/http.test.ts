@@ -1,5 +1,5 @@
 import { describe, expect, it, vi } from 'vitest'
-import { apiGet } from '@/lib/http'
+import { apiGet, UNAUTHORIZED_EVENT } from '@/lib/http'
 
 describe('HTTP helper', () => {
   it('fetches JSON without using a cached response', async () => {
@@ -30,4 +30,18 @@ describe('HTTP helper', () => {
       'HTTP 503 for /api/example',
     )
   })
+
+  it('announces a 401 so the app can ask to pair', async () => {
+    const listener = vi.fn()
+    window.addEventListener(UNAUTHORIZED_EVENT, listener)
+    vi.stubGlobal(
+      'fetch',
+      vi.fn().mockResolvedValue(new Response(null, { status: 401 })),
+    )
+
+    await expect(apiGet('/api/example')).rejects.toThrow('HTTP 401')
+
+    expect(listener).toHaveBeenCalledTimes(1)
+    window.removeEventListener(UNAUTHORIZED_EVENT, listener)
+  })
 })
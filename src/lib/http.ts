@@ -3,9 +3,13 @@
  * and image URLs are relative; the scanner already emits `/file?path=…` URLs.
  */
 
+/** Dispatched on `window` when the backend turns this browser away. */
+export const UNAUTHORIZED_EVENT = 'eriri:unauthorized'
+
 /** GET a JSON endpoint, never cached — the backend is the source of truth. */
 export async function apiGet<T>(path: string): Promise<T> {
   const res = await fetch(path, { cache: 'no-store' })
+  if (res.status === 401) window.dispatchEvent(new Event(UNAUTHORIZED_EVENT))
   if (!res.ok) throw new Error(`HTTP ${res.status} for ${path}`)
   return (await res.json()) as T
 }
@@ -1,9 +1,14 @@
 import { describe, expect, it, vi } from 'vitest'
 import {
+  cancelJob,
   fetchCatalog,
+  fetchCatalogChanges,
+  fetchJobs,
+  queryCatalog,
   refreshLibrary,
   removeLibrary,
   reorderLibraries,
+  searchLibraries,
   setBookTags,
   setComicTags,
 } from '@/lib/library-api'
@@ -43,7 +48,13 @@ describe('library API', () => {
   })
 
   it('fetches the flat catalog without cache', async () => {
-    const catalog = { libraries: [], comics: [], authors: [], books: [] }
+    const catalog = {
+      revision: 4,
+      libraries: [],
+      comics: [],
+      authors: [],
+      books: [],
+    }
     const fetchMock = vi.fn().mockResolvedValue(
       new Response(JSON.stringify(catalog), {
         status: 200,
@@ -59,13 +70,102 @@ describe('library API', () => {
     })
   })
 
+  it('fetches the catalog changes since a revision', async () => {
+    const changes = {
+      revision: 6,
+      libraries: [],
+      comics: [],
+      authors: [],
+      books: [],
+      removed: { libraries: [], comics: ['comic-1'], authors: [], books: [] },
+    }
+    const fetchMock = vi.fn().mockResolvedValue(
+      new Response(JSON.stringify(changes), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      }),
+    )
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(fetchCatalogChanges(4)).resolves.toEqual(changes)
+
+    expect(fetchMock).toHaveBeenCalledWith('/api/libraries/changes?since=4', {
+      cache: 'no-store',
+    })
+  })
+
+  it('searches every library with an encoded query', async () => {
+    const hits = [
+      {
+        bookId: 'book-1',
+        bookTitle: '修仙传',
+        kind: 'line',
+        lineIndex: 3,
+        chapter: '第一章',
+        snippet: '他开始修仙。',
+        highlights: [[3, 5]],
+      },
+    ]
+    const fetchMock = vi.fn().mockResolvedValue(
+      new Response(JSON.stringify(hits), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      }),
+    )
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(searchLibraries('修仙 a&b')).resolves.toEqual(hits)
+    expect(fetchMock).toHaveBeenCalledWith(
+      '/api/search?q=%E4%BF%AE%E4%BB%99+a%26b',
+      { cache: 'no-store' },
+    )
+  })
+
+  it('queries the catalog with only the given parameters', async () => {
+    const page = { items: [], nextCursor: 'abc' }
+    const fetchMock = vi.fn().mockResolvedValue(
+      new Response(JSON.stringify(page), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      }),
+    )
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(
+      queryCatalog({
+        kind: 'book',
+        starred: true,
+        title: undefined,
+        sort: 'lastRead',
+        order: 'desc',
+        limit: 50,
+      }),
+    ).resolves.toEqual(page)
+    expect(fetchMock).toHaveBeenCalledWith(
+      '/api/catalog?kind=book&starred=true&sort=lastRead&order=desc&limit=50',
+      { cache: 'no-store' },
+    )
+  })
+
   it('refreshes and removes libraries through their backend endpoints', async () => {
+    const job = {
+      id: 7,
+      kind: 'refresh',
+      libraryId: 'library-1',
+      state: 'succeeded',
+      scanned: 3,
+      thumbnails: 1,
+      createdAt: 1,
+    }
     const fetchMock = vi
       .fn()
+      .mockResolvedValueOnce(
+        new Response(JSON.stringify(job), { status: 202 }),
+      )
       .mockResolvedValue(new Response(null, { status: 204 }))
     vi.stubGlobal('fetch', fetchMock)
 
-    await refreshLibrary('library-1')
+    await expect(refreshLibrary('library-1')).resolves.toEqual(job)
     await removeLibrary('library-1')
 
     expect(fetchMock).toHaveBeenNthCalledWith(
@@ -84,6 +184,74 @@ describe('library API', () => {
     })
   })
 
+  it('polls a refresh job until it ends and reports a failure', async () => {
+    vi.useFakeTimers()
+    const job = {
+      id: 7,
+      kind: 'refresh',
+      libraryId: 'library-1',
+      scanned: 0,
+      thumbnails: 0,
+      createdAt: 1,
+    }
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(
+        new Response(JSON.stringify({ ...job, state: 'queued' }), {
+          status: 202,
+        }),
+      )
+      .mockResolvedValueOnce(
+        new Response(JSON.stringify({ ...job, state: 'running' })),
+      )
+      .mockResolvedValueOnce(
+        new Response(
+          JSON.stringify({ ...job, state: 'failed', error: 'gone' }),
+        ),
+      )
+    vi.stubGlobal('fetch', fetchMock)
+
+    const refresh = expect(refreshLibrary('library-1')).rejects.toThrow('gone')
+    await vi.runAllTimersAsync()
+    await refresh
+
+    expect(fetchMock).toHaveBeenNthCalledWith(2, '/api/jobs/7', {
+      cache: 'no-store',
+    })
+    expect(fetchMock).toHaveBeenCalledTimes(3)
+    vi.useRealTimers()
+  })
+
+  it('lists and cancels background jobs', async () => {
+    const job = {
+      id: 3,
+      kind: 'import',
+      libraryId: 'library-1',
+      state: 'running',
+      scanned: 10,
+      thumbnails: 4,
+      createdAt: 1,
+      startedAt: 2,
+    }
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(new Response(JSON.stringify([job])))
+      .mockResolvedValueOnce(new Response(JSON.stringify(job)))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(fetchJobs()).resolves.toEqual([job])
+    await expect(cancelJob(3)).resolves.toEqual(job)
+
+    expect(fetchMock).toHaveBeenNthCalledWith(1, '/api/jobs', {
+      cache: 'no-store',
+    })
+    expect(fetchMock).toHaveBeenNthCalledWith(2, '/api/jobs/3/cancel', {
+      method: 'POST',
+      headers: undefined,
+      body: undefined,
+    })
+  })
+
   it('persists library order and logs failures without throwing', async () => {
     vi.spyOn(console, 'error').mockImplementation(() => undefined)
     const fetchMock = vi
@@ -1,19 +1,44 @@
 import { apiGet } from '@/lib/http'
-import type { Author, Book, Comic, FileTags, Library } from '@/types/library'
+import type {
+  Author,
+  Book,
+  CatalogPage,
+  CatalogQuery,
+  Comic,
+  FileTags,
+  Job,
+  Library,
+  LibrarySearchHit,
+} from '@/types/library'
 
 /** Flat catalog snapshot: authors carry no nested books (joined client-side). */
 export interface Catalog {
+  /** The catalog revision the rows are current at. */
+  revision: number
   libraries: Library[]
   comics: Comic[]
   authors: Author[]
   books: Book[]
 }
 
-async function send(
+/** Rows written after a revision, and the ids of rows deleted since. */
+export interface CatalogChanges extends Catalog {
+  removed: {
+    libraries: string[]
+    comics: string[]
+    authors: string[]
+    books: string[]
+  }
+}
+
+/** How often a running job is polled for its state. */
+const JOB_POLL_MS = 500
+
+async function request(
   path: string,
   method: string,
   body?: unknown,
-): Promise<void> {
+): Promise<Response> {
   const res = await fetch(path, {
     method,
     headers:
@@ -21,13 +46,78 @@ async function send(
     body: body === undefined ? undefined : JSON.stringify(body),
   })
   if (!res.ok) throw new Error(`HTTP ${res.status} for ${method} ${path}`)
+  return res
+}
+
+async function send(
+  path: string,
+  method: string,
+  body?: unknown,
+): Promise<void> {
+  await request(path, method, body)
 }
 
 export const fetchCatalog = (): Promise<Catalog> =>
   apiGet<Catalog>('/api/libraries')
 
-export const refreshLibrary = (id: string): Promise<void> =>
-  send(`/api/library/${id}/refresh`, 'POST')
+/** What changed in the catalog after revision `since`. */
+export const fetchCatalogChanges = (since: number): Promise<CatalogChanges> =>
+  apiGet<CatalogChanges>(`/api/libraries/changes?since=${since}`)
+
+/** Books, chapters and lines matching `query` across every library. */
+export const searchLibraries = (query: string): Promise<LibrarySearchHit[]> =>
+  apiGet<LibrarySearchHit[]>(
+    `/api/search?${new URLSearchParams({ q: query }).toString()}`,
+  )
+
+/** One page of comics or books matching `query`, sorted server-side. */
+export function queryCatalog(
+  query: CatalogQuery & { kind: 'book' },
+): Promise<CatalogPage<Book>>
+export function queryCatalog(
+  query?: CatalogQuery & { kind?: 'comic' },
+): Promise<CatalogPage<Comic>>
+export function queryCatalog(
+  query: CatalogQuery = {},
+): Promise<CatalogPage<Comic | Book>> {
+  const params = new URLSearchParams()
+  for (const [key, value] of Object.entries(query)) {
+    if (value !== undefined) params.set(key, String(value))
+  }
+  return apiGet<CatalogPage<Comic | Book>>(`/api/catalog?${params.toString()}`)
+}
+
+/** Rescan a library in the background and wait for the job to finish. */
+export async function refreshLibrary(id: string): Promise<Job> {
+  const res = await request(`/api/library/${id}/refresh`, 'POST')
+  const job = await waitForJob((await res.json()) as Job)
+  if (job.state === 'failed') {
+    throw new Error(job.error ?? `Refresh of library ${id} failed`)
+  }
+  return job
+}
+
+/** Recent background jobs, newest first. */
+export const fetchJobs = (): Promise<Job[]> => apiGet<Job[]>('/api/jobs')
+
+export const fetchJob = (id: number): Promise<Job> =>
+  apiGet<Job>(`/api/jobs/${id}`)
+
+/** Stop a job: a queued one at once, a running one at its next check. */
+export async function cancelJob(id: number): Promise<Job> {
+  const res = await request(`/api/jobs/${id}/cancel`, 'POST')
+  return (await res.json()) as Job
+}
+
+/** Poll `job` until it is no longer queued or running. */
+export async function waitForJob(job: Job): Promise<Job> {
+  let current = job
+  while (current.state === 'queued' || current.state === 'running') {
+    await new Promise((resolve) => setTimeout(resolve, JOB_POLL_MS))
+    current = await fetchJob(current.id)
+  }
+  return current
+}
 
 export const removeLibrary = (id: string): Promise<void> =>
   send(`/api/library/${id}`, 'DELETE')
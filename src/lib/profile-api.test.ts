@@ -0,0 +1,44 @@
+import { describe, expect, it, vi } from 'vitest'
+import {
+  createProfile,
+  fetchProfiles,
+  removeProfile,
+  selectProfile,
+} from '@/lib/profile-api'
+
+describe('profile API', () => {
+  it('lists and creates profiles', async () => {
+    const kid = { id: 'kid', name: '小明', createdAt: 5, current: false }
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(new Response(JSON.stringify([kid])))
+      .mockResolvedValueOnce(new Response(JSON.stringify(kid)))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(fetchProfiles()).resolves.toEqual([kid])
+    await expect(createProfile('小明')).resolves.toEqual(kid)
+    expect(fetchMock).toHaveBeenLastCalledWith('/api/profiles', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ name: '小明' }),
+    })
+  })
+
+  it('selects a profile and reports a failed removal', async () => {
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(new Response(null, { status: 204 }))
+      .mockResolvedValueOnce(new Response(null, { status: 400 }))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await selectProfile('kid')
+    expect(fetchMock).toHaveBeenCalledWith('/api/profiles/kid/select', {
+      method: 'POST',
+      headers: undefined,
+      body: undefined,
+    })
+    await expect(removeProfile('default')).rejects.toThrow(
+      'HTTP 400 for DELETE /api/profiles/default',
+    )
+  })
+})
@@ -0,0 +1,51 @@
+import { apiGet } from '@/lib/http'
+
+/**
+ * Reader profiles. Each keeps its own progress, favorite chapters and stored
+ * UI state; tags stay shared. The backend remembers this browser's profile
+ * in a cookie, so switching only needs a reload.
+ */
+export interface Profile {
+  id: string
+  name: string
+  createdAt: number
+  /** Whether this browser reads under it. */
+  current: boolean
+}
+
+async function request(
+  path: string,
+  method: string,
+  body?: unknown,
+): Promise<Response> {
+  const res = await fetch(path, {
+    method,
+    headers:
+      body === undefined ? undefined : { 'Content-Type': 'application/json' },
+    body: body === undefined ? undefined : JSON.stringify(body),
+  })
+  if (!res.ok) throw new Error(`HTTP ${res.status} for ${method} ${path}`)
+  return res
+}
+
+export const fetchProfiles = (): Promise<Profile[]> =>
+  apiGet<Profile[]>('/api/profiles')
+
+export async function createProfile(name: string): Promise<Profile> {
+  const res = await request('/api/profiles', 'POST', { name })
+  return (await res.json()) as Profile
+}
+
+export async function renameProfile(id: string, name: string): Promise<void> {
+  await request(`/api/profiles/${id}`, 'PUT', { name })
+}
+
+/** Remove a profile with its progress; the default one cannot be removed. */
+export async function removeProfile(id: string): Promise<void> {
+  await request(`/api/profiles/${id}`, 'DELETE')
+}
+
+/** Make `id` this browser's profile for every later request. */
+export async function selectProfile(id: string): Promise<void> {
+  await request(`/api/profiles/${id}/select`, 'POST')
+}
@@ -1,6 +1,7 @@
 import { describe, expect, it, vi } from 'vitest'
 import {
   fetchProgress,
+  fetchProgressChanges,
   removeBookFavorites,
   removeBookProgress,
   removeComicProgress,
@@ -12,6 +13,7 @@ import {
 describe('progress API', () => {
   it('fetches the reading progress snapshot from the backend', async () => {
     const snapshot = {
+      revision: 2,
       comics: {
         'comic-1': { current: 1, total: 3, percent: 50, lastRead: 100 },
       },
@@ -32,6 +34,28 @@ describe('progress API', () => {
     })
   })
 
+  it('fetches the progress changes since a revision', async () => {
+    const changes = {
+      revision: 5,
+      comics: {},
+      books: {},
+      favoriteChapters: { 'book-1': [3] },
+      removed: { comics: ['comic-1'], books: [], favoriteChapters: [] },
+    }
+    const fetchMock = vi.fn().mockResolvedValue(
+      new Response(JSON.stringify(changes), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      }),
+    )
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(fetchProgressChanges(2)).resolves.toEqual(changes)
+    expect(fetchMock).toHaveBeenCalledWith('/api/progress/changes?since=2', {
+      cache: 'no-store',
+    })
+  })
+
   it('writes comic and book progress as JSON', async () => {
     const fetchMock = vi
       .fn()
@@ -7,11 +7,22 @@ import type { BookProgress, ComicProgress } from '@/types/library'
  * logged, never thrown.
  */
 export interface ProgressSnapshot {
+  /** The progress revision the entries are current at. */
+  revision: number
   comics: Record<string, ComicProgress>
   books: Record<string, BookProgress>
   favoriteChapters: Record<string, number[]>
 }
 
+/** Entries written after a revision, and the ids cleared since. */
+export interface ProgressChanges extends ProgressSnapshot {
+  removed: {
+    comics: string[]
+    books: string[]
+    favoriteChapters: string[]
+  }
+}
+
 async function write(
   path: string,
   method: string,
@@ -33,6 +44,12 @@ async function write(
 export const fetchProgress = (): Promise<ProgressSnapshot> =>
   apiGet<ProgressSnapshot>('/api/progress')
 
+/** What changed in reading progress after revision `since`. */
+export const fetchProgressChanges = (
+  since: number,
+): Promise<ProgressChanges> =>
+  apiGet<ProgressChanges>(`/api/progress/changes?since=${since}`)
+
 export const saveComicProgress = (
   comicId: string,
   progress: ComicProgress,
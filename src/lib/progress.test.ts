@@ -18,9 +18,9 @@ describe('reading progress', () => {
 
     expect(
       createBookProgress(4, 11, [
-        { title: '序章', lineIndex: 0 },
-        { title: '第一章 开始', lineIndex: 3 },
-        { title: '第二章 继续', lineIndex: 8 },
+        { title: '序章', lineIndex: 0, level: 0 },
+        { title: '第一章 开始', lineIndex: 3, level: 0 },
+        { title: '第二章 继续', lineIndex: 8, level: 0 },
       ]),
     ).toEqual({
       current: 4,
@@ -31,6 +31,25 @@ describe('reading progress', () => {
     })
   })
 
+  it('names the chapter rather than its volume inside a nested table of contents', () => {
+    const chapters = [
+      { title: '第一卷', lineIndex: 0, level: 0 },
+      { title: '第一章', lineIndex: 1, level: 1, parent: 0 },
+      { title: '第二卷', lineIndex: 5, level: 0 },
+      { title: '第二章', lineIndex: 6, level: 1, parent: 2 },
+    ]
+
+    expect(createBookProgress(3, 10, chapters).currentChapterTitle).toBe(
+      '第一章',
+    )
+    expect(createBookProgress(5, 10, chapters).currentChapterTitle).toBe(
+      '第二卷',
+    )
+    expect(createBookProgress(7, 10, chapters).currentChapterTitle).toBe(
+      '第二章',
+    )
+  })
+
   it('treats single-page comics and single-line books as complete', () => {
     vi.setSystemTime(new Date('2026-01-02T03:04:05.000Z'))
 
@@ -1,14 +1,19 @@
 import { describe, expect, it, vi } from 'vitest'
 import {
+  getBookChapter,
+  getBookLines,
+  getBookToc,
   openPathNative,
   parseBook,
   scanComicImages,
+  searchBook,
   setFileTag,
 } from '@/lib/scanner'
 
 const book = {
   lines: ['Chapter 1', 'Body'],
-  chapters: [{ title: 'Chapter 1', lineIndex: 0 }],
+  chapters: [{ title: 'Chapter 1', lineIndex: 0, level: 0 }],
+  encoding: 'UTF-8',
 }
 
 describe('scanner API', () => {
@@ -35,6 +40,58 @@ describe('scanner API', () => {
     await expect(scanComicImages('/comic')).rejects.toThrow('offline')
   })
 
+  it('fetches a book table of contents, line ranges and chapters', async () => {
+    const json = (body: unknown) =>
+      new Response(JSON.stringify(body), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      })
+    const toc = { lineCount: 2, chapters: book.chapters, encoding: 'UTF-8' }
+    const lines = { start: 0, lines: book.lines }
+    const fetchMock = vi
+      .fn()
+      .mockResolvedValueOnce(json(toc))
+      .mockResolvedValueOnce(json(lines))
+      .mockResolvedValueOnce(json(lines))
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(getBookToc('book-1')).resolves.toEqual(toc)
+    await expect(getBookLines('book-1', 0, 2)).resolves.toEqual(lines)
+    await expect(getBookChapter('book-1', 0)).resolves.toEqual(lines)
+    expect(fetchMock.mock.calls.map(([url]) => url)).toEqual([
+      '/api/book/book-1/toc',
+      '/api/book/book-1/lines?start=0&end=2',
+      '/api/book/book-1/chapters/0',
+    ])
+  })
+
+  it('searches a book with an encoded query', async () => {
+    const result = {
+      hits: [
+        {
+          lineIndex: 0,
+          snippet: 'Chapter 1',
+          highlights: [[0, 7]],
+          chapter: 'Chapter 1',
+        },
+      ],
+      truncated: false,
+    }
+    const fetchMock = vi.fn().mockResolvedValue(
+      new Response(JSON.stringify(result), {
+        status: 200,
+        headers: { 'Content-Type': 'application/json' },
+      }),
+    )
+    vi.stubGlobal('fetch', fetchMock)
+
+    await expect(searchBook('book-1', 'Ｃｈａｐ ter')).resolves.toEqual(result)
+    expect(fetchMock).toHaveBeenCalledWith(
+      '/api/book/book-1/search?q=%EF%BC%A3%EF%BD%88%EF%BD%81%EF%BD%90+ter',
+      { cache: 'no-store' },
+    )
+  })
+
   it('parses a normal JSON response without progress reporting', async () => {
     vi.stubGlobal(
       'fetch',
@@ -132,10 +189,12 @@ describe('scanner API', () => {
     await expect(parseBook('/empty', vi.fn())).resolves.toEqual({
       lines: [],
       chapters: [],
+      encoding: '',
     })
     await expect(parseBook('/failed')).resolves.toEqual({
       lines: [],
       chapters: [],
+      encoding: '',
     })
     expect(console.error).toHaveBeenCalledTimes(2)
   })
@@ -1,5 +1,11 @@
 import { apiGet } from '@/lib/http'
-import type { BookContent, Image } from '@/types/library'
+import type {
+  BookContent,
+  BookLines,
+  BookSearch,
+  BookToc,
+  Image,
+} from '@/types/library'
 
 const qs = (params: Record<string, string>): string =>
   new URLSearchParams(params).toString()
@@ -42,10 +48,47 @@ export async function parseBook(
     return result
   } catch (error) {
     console.error('Failed to parse book:', error)
-    return { lines: [], chapters: [] }
+    return { lines: [], chapters: [], encoding: '' }
   }
 }
 
+export async function getBookToc(bookId: string): Promise<BookToc> {
+  return apiGet<BookToc>(`/api/book/${encodeURIComponent(bookId)}/toc`)
+}
+
+/** Lines `start..end` of a book; the server caps one range at 2000 lines. */
+export async function getBookLines(
+  bookId: string,
+  start: number,
+  end: number,
+): Promise<BookLines> {
+  return apiGet<BookLines>(
+    `/api/book/${encodeURIComponent(bookId)}/lines?${qs({
+      start: String(start),
+      end: String(end),
+    })}`,
+  )
+}
+
+export async function getBookChapter(
+  bookId: string,
+  index: number,
+): Promise<BookLines> {
+  return apiGet<BookLines>(
+    `/api/book/${encodeURIComponent(bookId)}/chapters/${index}`,
+  )
+}
+
+/** Lines of a book matching `query`, ignoring case and character width. */
+export async function searchBook(
+  bookId: string,
+  query: string,
+): Promise<BookSearch> {
+  return apiGet<BookSearch>(
+    `/api/book/${encodeURIComponent(bookId)}/search?${qs({ q: query })}`,
+  )
+}
+
 export async function setFileTag(
   path: string,
   tags: { starred?: boolean; deleted?: boolean },
@@ -1,4 +1,5 @@
 import { beforeEach, describe, expect, it, vi } from 'vitest'
+import * as access from '@/lib/access-api'
 import * as api from '@/lib/library-api'
 import * as scanner from '@/lib/scanner'
 import { useLibraryStore } from '@/store/library'
@@ -9,6 +10,7 @@ import { LibraryType } from '@/types/library'
 
 vi.mock('@/lib/library-api', () => ({
   fetchCatalog: vi.fn(),
+  fetchCatalogChanges: vi.fn(),
   refreshLibrary: vi.fn().mockResolvedValue(undefined),
   removeLibrary: vi.fn().mockResolvedValue(undefined),
   reorderLibraries: vi.fn().mockResolvedValue(undefined),
@@ -16,6 +18,11 @@ vi.mock('@/lib/library-api', () => ({
   setBookTags: vi.fn().mockResolvedValue(true),
 }))
 
+vi.mock('@/lib/access-api', () => ({
+  unlockLibraries: vi.fn().mockResolvedValue(undefined),
+  lockLibraries: vi.fn().mockResolvedValue(undefined),
+}))
+
 vi.mock('@/lib/scanner', () => ({
   scanComicImages: vi.fn().mockResolvedValue([]),
   setFileTag: vi.fn().mockResolvedValue(true),
@@ -25,6 +32,7 @@ const mockedApi = vi.mocked(api)
 const mockedScanner = vi.mocked(scanner)
 
 const emptyCatalog = {
+  revision: 0,
   libraries: [],
   comics: [],
   authors: [],
@@ -46,6 +54,7 @@ describe('library store', () => {
 
   it('hydrates a flat catalog into naturally sorted browsing maps', async () => {
     mockedApi.fetchCatalog.mockResolvedValueOnce({
+      revision: 3,
       libraries: [
         {
           id: 'library-1',
@@ -54,6 +63,7 @@ describe('library store', () => {
           type: LibraryType.comic,
           createdAt: 1,
           sortOrder: 0,
+          revision: 0,
         },
         {
           id: 'library-2',
@@ -62,6 +72,7 @@ describe('library store', () => {
           type: LibraryType.book,
           createdAt: 2,
           sortOrder: 1,
+          revision: 0,
         },
       ],
       comics: [
@@ -147,6 +158,71 @@ describe('library store', () => {
     ])
   })
 
+  it('merges the changes since the held revision into the catalog', async () => {
+    const comic = {
+      path: '/library/comics/Comic',
+      cover: '/file?path=cover',
+      libraryId: 'library-1',
+      starred: false,
+      deleted: false,
+      createdAt: 1,
+    }
+    mockedApi.fetchCatalog.mockResolvedValueOnce({
+      ...emptyCatalog,
+      revision: 5,
+      comics: [
+        { ...comic, id: 'comic-1', title: 'Comic 1' },
+        { ...comic, id: 'comic-3', title: 'Comic 3' },
+      ],
+    })
+    mockedApi.fetchCatalogChanges.mockResolvedValueOnce({
+      ...emptyCatalog,
+      revision: 7,
+      comics: [{ ...comic, id: 'comic-2', title: 'Comic 2' }],
+      removed: { libraries: [], comics: ['comic-3'], authors: [], books: [] },
+    })
+
+    await useLibraryStore.getState().hydrate()
+    await useLibraryStore.getState().hydrate()
+
+    expect(mockedApi.fetchCatalog).toHaveBeenCalledTimes(1)
+    expect(mockedApi.fetchCatalogChanges).toHaveBeenCalledWith(5)
+    expect(useLibraryStore.getState().revision).toBe(7)
+    expect(useLibraryStore.getState().libraryComics['library-1']).toEqual([
+      'comic-1',
+      'comic-2',
+    ])
+  })
+
+  it('re-fetches the whole catalog when the server revision went back', async () => {
+    useLibraryStore.setState({ revision: 9 })
+    mockedApi.fetchCatalogChanges.mockResolvedValueOnce({
+      ...emptyCatalog,
+      revision: 2,
+      removed: { libraries: [], comics: [], authors: [], books: [] },
+    })
+
+    await useLibraryStore.getState().hydrate()
+
+    expect(mockedApi.fetchCatalog).toHaveBeenCalledTimes(1)
+    expect(useLibraryStore.getState().revision).toBe(0)
+  })
+
+  it('re-fetches the whole catalog after an access key changes', async () => {
+    useLibraryStore.setState({ revision: 9 })
+
+    await useLibraryStore.getState().unlockLibraries('secret')
+    expect(access.unlockLibraries).toHaveBeenCalledWith('secret')
+    expect(mockedApi.fetchCatalogChanges).not.toHaveBeenCalled()
+    expect(mockedApi.fetchCatalog).toHaveBeenCalledTimes(1)
+
+    useLibraryStore.setState({ revision: 9 })
+    await useLibraryStore.getState().lockLibraries()
+    expect(access.lockLibraries).toHaveBeenCalled()
+    expect(mockedApi.fetchCatalogChanges).not.toHaveBeenCalled()
+    expect(mockedApi.fetchCatalog).toHaveBeenCalledTimes(2)
+  })
+
   it('keeps existing catalog state when hydration fails', async () => {
     vi.spyOn(console, 'error').mockImplementation(() => undefined)
     mockedApi.fetchCatalog.mockRejectedValueOnce(new Error('offline'))
@@ -159,6 +235,7 @@ describe('library store', () => {
           type: LibraryType.comic,
           createdAt: 1,
           sortOrder: 0,
+          revision: 0,
         },
       },
     })
@@ -280,6 +357,7 @@ describe('library store', () => {
           type: LibraryType.comic,
           createdAt: 1,
           sortOrder: 0,
+          revision: 0,
         },
         'library-2': {
           id: 'library-2',
@@ -288,6 +366,7 @@ describe('library store', () => {
           type: LibraryType.book,
           createdAt: 2,
           sortOrder: 1,
+          revision: 0,
         },
       },
     })
@@ -1,5 +1,6 @@
 import { create } from 'zustand'
 import { immer } from 'zustand/middleware/immer'
+import * as access from '@/lib/access-api'
 import * as api from '@/lib/library-api'
 import { scanComicImages, setFileTag } from '@/lib/scanner'
 import { useProgressStore } from '@/store/progress'
@@ -27,6 +28,8 @@ const collator = new Intl.Collator(undefined, {
 })
 
 interface LibraryState {
+  /** Catalog revision held, `null` before the first fetch. */
+  revision: number | null
   libraries: Record<string, Library>
   comics: Record<string, Comic>
   authors: Record<string, Author>
@@ -36,12 +39,21 @@ interface LibraryState {
   authorBooks: Record<string, string[]>
   comicImages: Record<string, ComicImage>
   hydrate: () => Promise<void>
+  /**
+   * Enter or forget an access key. The libraries it covers were written at
+   * revisions this client may already hold, so the whole catalog is fetched.
+   */
+  unlockLibraries: (key: string) => Promise<void>
+  lockLibraries: () => Promise<void>
   refreshLibrary: (libraryId: string) => Promise<void>
   removeLibrary: (id: string) => Promise<void>
   reorderLibrary: (orderedIds: string[]) => void
   getComicImages: (comicId: string) => Promise<Image[]>
   updateBookTags: (bookId: string, tags: FileTags) => Promise<void>
   updateComicTags: (comicId: string, tags: FileTags) => Promise<void>
+  /** Mirror tags already written elsewhere, without writing them back. */
+  applyBookTags: (bookId: string, tags: FileTags) => void
+  applyComicTags: (comicId: string, tags: FileTags) => void
   updateComicImageTags: (
     comicId: string,
     filename: string,
@@ -120,8 +132,39 @@ function buildMaps(catalog: api.Catalog): CatalogMaps {
   }
 }
 
+/** `rows` with `changed` written over them and `removed` dropped. */
+function mergeRows<T extends { id: string }>(
+  rows: Record<string, T>,
+  changed: T[],
+  removed: string[],
+): T[] {
+  const next = { ...rows }
+  for (const id of removed) delete next[id]
+  for (const row of changed) next[row.id] = row
+  return Object.values(next)
+}
+
+function mergeChanges(
+  maps: CatalogMaps,
+  changes: api.CatalogChanges,
+): api.Catalog {
+  const { removed } = changes
+  return {
+    revision: changes.revision,
+    libraries: mergeRows(
+      maps.libraries,
+      changes.libraries,
+      removed.libraries,
+    ).sort((a, b) => a.sortOrder - b.sortOrder),
+    comics: mergeRows(maps.comics, changes.comics, removed.comics),
+    authors: mergeRows(maps.authors, changes.authors, removed.authors),
+    books: mergeRows(maps.books, changes.books, removed.books),
+  }
+}
+
 export const useLibraryStore = create<LibraryState>()(
   immer((set, get) => ({
+    revision: null,
     libraries: {},
     comics: {},
     authors: {},
@@ -131,18 +174,43 @@ export const useLibraryStore = create<LibraryState>()(
     authorBooks: {},
     comicImages: {},
 
+    // Once a revision is held, only the changes since are fetched.
     hydrate: async () => {
       try {
-        const catalog = await api.fetchCatalog()
+        const since = get().revision
+        const changes =
+          since === null ? null : await api.fetchCatalogChanges(since)
+        // A revision behind ours means the library DB was swapped out.
+        const catalog =
+          changes && since !== null && changes.revision >= since
+            ? mergeChanges(get(), changes)
+            : await api.fetchCatalog()
         const maps = buildMaps(catalog)
         set((state) => {
           Object.assign(state, maps)
+          state.revision = catalog.revision
         })
       } catch (error) {
         console.error('Failed to fetch catalog:', error)
       }
     },
 
+    unlockLibraries: async (key) => {
+      await access.unlockLibraries(key)
+      set((state) => {
+        state.revision = null
+      })
+      await get().hydrate()
+    },
+
+    lockLibraries: async () => {
+      await access.lockLibraries()
+      set((state) => {
+        state.revision = null
+      })
+      await get().hydrate()
+    },
+
     refreshLibrary: async (id) => {
       const setScanning = useUIStore.getState().setIsScanning
       setScanning(true)
@@ -207,15 +275,7 @@ export const useLibraryStore = create<LibraryState>()(
       if (!book) return
 
       const isSuccess = await api.setBookTags(bookId, tags)
-      if (isSuccess) {
-        set((state) => {
-          const b = state.books[bookId]
-          if (b) {
-            if (tags.starred !== undefined) b.starred = tags.starred
-            if (tags.deleted !== undefined) b.deleted = tags.deleted
-          }
-        })
-      }
+      if (isSuccess) get().applyBookTags(bookId, tags)
     },
 
     updateComicTags: async (comicId, tags) => {
@@ -223,15 +283,27 @@ export const useLibraryStore = create<LibraryState>()(
       if (!comic) return
 
       const isSuccess = await api.setComicTags(comicId, tags)
-      if (isSuccess) {
-        set((state) => {
-          const c = state.comics[comicId]
-          if (c) {
-            if (tags.starred !== undefined) c.starred = tags.starred
-            if (tags.deleted !== undefined) c.deleted = tags.deleted
-          }
-        })
-      }
+      if (isSuccess) get().applyComicTags(comicId, tags)
+    },
+
+    applyBookTags: (bookId, tags) => {
+      set((state) => {
+        const b = state.books[bookId]
+        if (b) {
+          if (tags.starred !== undefined) b.starred = tags.starred
+          if (tags.deleted !== undefined) b.deleted = tags.deleted
+        }
+      })
+    },
+
+    applyComicTags: (comicId, tags) => {
+      set((state) => {
+        const c = state.comics[comicId]
+        if (c) {
+          if (tags.starred !== undefined) c.starred = tags.starred
+          if (tags.deleted !== undefined) c.deleted = tags.deleted
+        }
+      })
     },
 
     updateComicImageTags: async (comicId, filename, tags) => {
@@ -4,6 +4,7 @@ import { useProgressStore } from '@/store/progress'
 
 vi.mock('@/lib/progress-api', () => ({
   fetchProgress: vi.fn(),
+  fetchProgressChanges: vi.fn(),
   saveComicProgress: vi.fn().mockResolvedValue(undefined),
   removeComicProgress: vi.fn().mockResolvedValue(undefined),
   saveBookProgress: vi.fn().mockResolvedValue(undefined),
@@ -21,6 +22,7 @@ describe('progress store', () => {
 
   it('hydrates reading progress from the backend snapshot', async () => {
     mockedApi.fetchProgress.mockResolvedValueOnce({
+      revision: 4,
       comics: {
         'comic-1': { current: 2, total: 5, percent: 50, lastRead: 123 },
       },
@@ -137,6 +139,33 @@ describe('progress store', () => {
     expect(mockedApi.removeBookFavorites).toHaveBeenCalledWith('book-1')
   })
 
+  it('applies the changes since the held revision', async () => {
+    const progress = { current: 1, total: 3, percent: 50, lastRead: 100 }
+    useProgressStore.setState({
+      revision: 4,
+      comics: { 'comic-1': progress, 'comic-2': progress },
+      favoriteChapters: { 'book-1': [0], 'book-2': [5] },
+    })
+    mockedApi.fetchProgressChanges.mockResolvedValueOnce({
+      revision: 6,
+      comics: { 'comic-2': { ...progress, current: 3, percent: 100 } },
+      books: {},
+      favoriteChapters: { 'book-1': [0, 8] },
+      removed: { comics: ['comic-1'], books: [], favoriteChapters: ['book-2'] },
+    })
+
+    await useProgressStore.getState().hydrate()
+
+    const state = useProgressStore.getState()
+    expect(mockedApi.fetchProgressChanges).toHaveBeenCalledWith(4)
+    expect(mockedApi.fetchProgress).not.toHaveBeenCalled()
+    expect(state.revision).toBe(6)
+    expect(state.comics).toEqual({
+      'comic-2': { ...progress, current: 3, percent: 100 },
+    })
+    expect(state.favoriteChapters).toEqual({ 'book-1': [0, 8] })
+  })
+
   it('keeps existing progress when backend hydration fails', async () => {
     vi.spyOn(console, 'error').mockImplementation(() => undefined)
     mockedApi.fetchProgress.mockRejectedValueOnce(new Error('offline'))
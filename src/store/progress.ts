@@ -4,6 +4,8 @@ import * as api from '@/lib/progress-api'
 import type { BookProgress, ComicProgress } from '@/types/library'
 
 interface ProgressState {
+  /** Progress revision held, `null` before the first fetch. */
+  revision: number | null
   comics: Record<string, ComicProgress>
   books: Record<string, BookProgress>
   favoriteChapters: Record<string, number[]>
@@ -14,21 +16,46 @@ interface ProgressState {
   removeBookProgress: (bookId: string) => void
   toggleChapterFavorite: (bookId: string, lineIndex: number) => void
   removeBookChapters: (bookId: string) => void
+  /** Mirror a change made on another device, without writing it back. */
+  applyComicProgress: (comicId: string, progress: ComicProgress | null) => void
+  applyBookProgress: (bookId: string, progress: BookProgress | null) => void
+  applyChapterFavorites: (bookId: string, lines: number[]) => void
 }
 
 export const useProgressStore = create<ProgressState>()(
-  immer((set) => ({
+  immer((set, get) => ({
+    revision: null,
     comics: {},
     books: {},
     favoriteChapters: {},
 
+    // Once a revision is held, only the changes since are fetched.
     hydrate: async () => {
       try {
+        const since = get().revision
+        const changes =
+          since === null ? null : await api.fetchProgressChanges(since)
+        // A fresh progress DB starts its revisions over.
+        if (changes && since !== null && changes.revision >= since) {
+          set((state) => {
+            const { removed } = changes
+            for (const id of removed.comics) delete state.comics[id]
+            for (const id of removed.books) delete state.books[id]
+            for (const id of removed.favoriteChapters)
+              delete state.favoriteChapters[id]
+            Object.assign(state.comics, changes.comics)
+            Object.assign(state.books, changes.books)
+            Object.assign(state.favoriteChapters, changes.favoriteChapters)
+            state.revision = changes.revision
+          })
+          return
+        }
         const snapshot = await api.fetchProgress()
         set((state) => {
           state.comics = snapshot.comics
           state.books = snapshot.books
           state.favoriteChapters = snapshot.favoriteChapters
+          state.revision = snapshot.revision
         })
       } catch (error) {
         console.error('Failed to fetch progress:', error)
@@ -91,5 +118,26 @@ export const useProgressStore = create<ProgressState>()(
       })
       void api.removeBookFavorites(bookId)
     },
+
+    applyComicProgress: (comicId, progress) => {
+      set((state) => {
+        if (progress) state.comics[comicId] = progress
+        else delete state.comics[comicId]
+      })
+    },
+
+    applyBookProgress: (bookId, progress) => {
+      set((state) => {
+        if (progress) state.books[bookId] = progress
+        else delete state.books[bookId]
+      })
+    },
+
+    applyChapterFavorites: (bookId, lines) => {
+      set((state) => {
+        if (lines.length) state.favoriteChapters[bookId] = lines
+        else delete state.favoriteChapters[bookId]
+      })
+    },
   })),
 )
@@ -12,6 +12,8 @@ export interface Library {
   type: LibraryType
   createdAt: number
   sortOrder: number
+  /** Revision of the library's last write; changes on every refresh. */
+  revision: number
 }
 
 /** Per-library navigation memory (device-local; lives in the UI store). */
@@ -57,13 +59,157 @@ export interface Book {
 export interface Chapter {
   title: string
   lineIndex: number
+  /** Set on suggested chapters from heuristic detection, from 0 to 1. */
+  confidence?: number
+  /** Depth in the table of contents; chapters inside a volume are 1. */
+  level: number
+  /** Index in `chapters` of the enclosing volume or entry. */
+  parent?: number
+}
+
+export type ChapterPreset = 'chinese' | 'english' | 'japanese'
+
+export type ChapterRule =
+  | { kind: 'preset'; preset: ChapterPreset; priority: number }
+  | { kind: 'regex'; pattern: string; priority: number; volume: boolean }
+
+export interface ParagraphOptions {
+  rejoinWrapped: boolean
+  splitLongLines: boolean
+  stripIndent: boolean
+  keepBlankLines: boolean
 }
 
 export interface BookContent {
   lines: string[]
   chapters: Chapter[]
+  encoding: string
 }
 
+/** A book without its lines, which are fetched in ranges. */
+export interface BookToc {
+  lineCount: number
+  chapters: Chapter[]
+  encoding: string
+}
+
+/** Lines `start..start + lines.length` of a book. */
+export interface BookLines {
+  start: number
+  lines: string[]
+}
+
+/** Lines of one book matching a search, in reading order. */
+export interface BookSearch {
+  hits: SearchHit[]
+  /** More lines matched than were returned. */
+  truncated: boolean
+}
+
+export interface SearchHit {
+  lineIndex: number
+  snippet: string
+  /** `[start, end)` of each match in `snippet`, in code points. */
+  highlights: [number, number][]
+  /** Title of the chapter the line belongs to. */
+  chapter?: string
+}
+
+export type LibraryHitKind = 'book' | 'chapter' | 'line'
+
+/** One match of a library-wide search; hits come best first. */
+export interface LibrarySearchHit {
+  bookId: string
+  bookTitle: string
+  kind: LibraryHitKind
+  lineIndex?: number
+  /** The matching chapter, or the chapter a matching line belongs to. */
+  chapter?: string
+  snippet: string
+  /** `[start, end)` of each match in `snippet`, in code points. */
+  highlights: [number, number][]
+}
+
+export type ReadState = 'unread' | 'reading' | 'finished'
+
+export type CatalogSortKey = 'title' | 'createdAt' | 'size' | 'lastRead'
+
+/** Filters, sort and page of a catalog query; every field is optional. */
+export interface CatalogQuery {
+  kind?: 'comic' | 'book'
+  libraryId?: string
+  /** Title substring, ignoring case and character width. */
+  title?: string
+  starred?: boolean
+  deleted?: boolean
+  read?: ReadState
+  /** `createdAt` range in ms, `[createdFrom, createdTo)`. */
+  createdFrom?: number
+  createdTo?: number
+  sort?: CatalogSortKey
+  order?: 'asc' | 'desc'
+  /** `nextCursor` of the previous page. */
+  cursor?: string
+  /** Page size, 100 by default and at most 500. */
+  limit?: number
+}
+
+export interface CatalogPage<T> {
+  items: T[]
+  /** Absent on the last page. */
+  nextCursor?: string
+}
+
+export type JobKind = 'import' | 'refresh' | 'update' | 'cleanCache'
+
+export type JobState =
+  | 'queued'
+  | 'running'
+  | 'succeeded'
+  | 'failed'
+  | 'cancelled'
+
+/** A background import, refresh or cache job; times are ms since the epoch. */
+export interface Job {
+  id: number
+  kind: JobKind
+  /** Absent for cache cleaning. */
+  libraryId?: string
+  state: JobState
+  /** Comics and books looked at so far. */
+  scanned: number
+  /** Cover thumbnails written so far. */
+  thumbnails: number
+  /** Why a failed job failed. */
+  error?: string
+  createdAt: number
+  startedAt?: number
+  finishedAt?: number
+}
+
+/** A change pushed by the backend over `/api/events`. */
+export type ServerEvent =
+  | {
+      type: 'libraryImported' | 'libraryRefreshed' | 'libraryRemoved'
+      libraryId: string
+    }
+  | { type: 'comicTagsChanged'; comicId: string; tags: FileTags }
+  | { type: 'bookTagsChanged'; bookId: string; tags: FileTags }
+  | {
+      type: 'comicProgressUpdated'
+      comicId: string
+      /** `null` when cleared. */
+      progress: ComicProgress | null
+    }
+  | {
+      type: 'bookProgressUpdated'
+      bookId: string
+      progress: BookProgress | null
+    }
+  | { type: 'favoritesUpdated'; bookId: string; lines: number[] }
+  /** Events were missed; re-fetch everything. */
+  | { type: 'lagged' }
+
 export interface Image {
   path: string
   url: string
@@ -96,6 +242,8 @@ export interface ComicProgress {
   total: number
   percent: number
   lastRead: number
+  /** The paired device that wrote it; absent for the Mac itself. */
+  deviceId?: string
 }
 
 export interface BookProgress {
@@ -104,4 +252,6 @@ export interface BookProgress {
   percent: number
   lastRead: number
   currentChapterTitle?: string
+  /** The paired device that wrote it; absent for the Mac itself. */
+  deviceId?: string
 }